@@ -146,7 +146,15 @@ enum NetworkAction {
     },
 
     /// Show network status
-    Status,
+    Status {
+        /// Coordinator address (e.g., 127.0.0.1:7000)
+        #[arg(short, long)]
+        coordinator: String,
+
+        /// Bind address (default: 0.0.0.0:0 for random port)
+        #[arg(short, long, default_value = "0.0.0.0:0")]
+        bind: String,
+    },
 
     /// List known peers
     Peers,
@@ -395,6 +403,46 @@ async fn handle_identity(action: IdentityAction, config_dir: &std::path::Path) -
     Ok(())
 }
 
+/// Answer hole punch offers forwarded by the coordinator we joined through
+fn answer_punch_offers(
+    rpc: &std::sync::Arc<
+        saorsa_gossip_transport::RpcTransport<saorsa_gossip_transport::AntQuicTransport>,
+    >,
+    coordinator_peer: saorsa_gossip_types::PeerId,
+    coordinator_addr: std::net::SocketAddr,
+) {
+    use saorsa_gossip_coordinator::{
+        Bootstrap, CoordinatorHandler, HolePuncher, NatClass, PeerCache, PeerCacheEntry, PeerRoles,
+    };
+
+    let local_peer = rpc.inner().peer_id();
+    let cache = PeerCache::new();
+    cache.insert(PeerCacheEntry::new(
+        coordinator_peer,
+        vec![coordinator_addr],
+        NatClass::Unknown,
+        PeerRoles {
+            coordinator: true,
+            reflector: false,
+            rendezvous: true,
+            relay: false,
+        },
+    ));
+    let bootstrap = Bootstrap::new(local_peer, cache, CoordinatorHandler::new(local_peer));
+
+    let puncher = HolePuncher::new(rpc.clone(), std::sync::Arc::new(bootstrap));
+    if let Some(addr) = rpc.inner().get_external_address() {
+        puncher.set_local_addrs(vec![addr]);
+    }
+    rpc.register_handler(
+        saorsa_gossip_transport::StreamType::Membership,
+        move |peer_id, data| {
+            let puncher = puncher.clone();
+            async move { puncher.handle_request(peer_id, data).await }
+        },
+    );
+}
+
 /// Handle network commands
 async fn handle_network(action: NetworkAction, config_dir: &std::path::Path) -> Result<()> {
     use saorsa_gossip_identity::Identity;
@@ -435,50 +483,63 @@ async fn handle_network(action: NetworkAction, config_dir: &std::path::Path) ->
             );
             println!("  Ant PeerId: {:?}", transport.ant_peer_id());
 
-            // Send a PING to coordinator to test message exchange
-            println!("\n📡 Sending PING to coordinator...");
-            use saorsa_gossip_transport::GossipTransport;
+            // Send a PING request to the coordinator to test message exchange
+            use saorsa_gossip_transport::{GossipTransport, RpcTransport, StreamType};
             use std::time::Instant;
 
-            let ping_start = Instant::now();
+            let coordinator_peer = match transport.get_bootstrap_peer_id(coordinator_addr).await {
+                Some(peer_id) => Ok(peer_id),
+                None => transport.dial_bootstrap(coordinator_addr).await,
+            };
+            let rpc = std::sync::Arc::new(RpcTransport::new(transport));
+
+            // Membership reads the Membership stream; no other protocol runs
+            // yet, so frames of other streams are discarded
+            use saorsa_gossip_membership::{
+                HyParViewMembership, Membership, DEFAULT_ACTIVE_DEGREE, DEFAULT_PASSIVE_DEGREE,
+            };
+            let membership = HyParViewMembership::new(
+                rpc.inner().peer_id(),
+                DEFAULT_ACTIVE_DEGREE,
+                DEFAULT_PASSIVE_DEGREE,
+                rpc.clone(),
+            );
+            let (_membership_task, other_streams) = membership.spawn_receive_task();
+            drop(other_streams);
 
-            // Get coordinator's peer ID (we need to discover this from bootstrap)
-            // For now, we'll send to the transport and handle it on coordinator side
-            // TODO: Get actual coordinator peer ID from discovery
+            match coordinator_peer {
+                Ok(coordinator_peer) => {
+                    answer_punch_offers(&rpc, coordinator_peer, coordinator_addr);
 
-            // Try to receive a message with timeout to test connectivity
-            println!("⏳ Waiting for coordinator response (5s timeout)...");
+                    match membership.join(vec![coordinator.clone()]).await {
+                        Ok(()) => println!("✓ Joined the overlay through the coordinator"),
+                        Err(e) => println!("❌ Overlay join failed: {}", e),
+                    }
 
-            let receive_task = tokio::spawn({
-                let transport = std::sync::Arc::new(transport);
-                async move {
-                    match tokio::time::timeout(
-                        std::time::Duration::from_secs(5),
-                        transport.receive_message(),
-                    )
-                    .await
+                    println!("\n📡 Sending PING to coordinator (5s timeout)...");
+                    let ping_start = Instant::now();
+
+                    match rpc
+                        .request(
+                            coordinator_peer,
+                            StreamType::Membership,
+                            bytes::Bytes::from_static(b"PING"),
+                            std::time::Duration::from_secs(5),
+                        )
+                        .await
                     {
-                        Ok(Ok((peer_id, stream_type, data))) => Some((peer_id, stream_type, data)),
-                        Ok(Err(e)) => {
-                            println!("❌ Error receiving: {}", e);
-                            None
-                        }
-                        Err(_) => {
-                            println!("⏱️  Timeout waiting for response");
-                            None
+                        Ok(data) => {
+                            println!(
+                                "✓ Received response from peer {}",
+                                hex::encode(coordinator_peer.as_bytes())
+                            );
+                            println!("  RTT: {:?}", ping_start.elapsed());
+                            println!("  Data: {}", String::from_utf8_lossy(&data));
                         }
+                        Err(e) => println!("❌ PING failed: {}", e),
                     }
                 }
-            });
-
-            if let Ok(Some((peer_id, _stream_type, data))) = receive_task.await {
-                let rtt = ping_start.elapsed();
-                println!(
-                    "✓ Received response from peer {}",
-                    hex::encode(peer_id.as_bytes())
-                );
-                println!("  RTT: {:?}", rtt);
-                println!("  Data: {}", String::from_utf8_lossy(&data));
+                Err(e) => println!("❌ Could not reach coordinator: {}", e),
             }
 
             println!("\n⚠️  Full network integration in progress!");
@@ -490,15 +551,76 @@ async fn handle_network(action: NetworkAction, config_dir: &std::path::Path) ->
             // Keep connection alive
             tokio::signal::ctrl_c().await?;
             println!("\n👋 Disconnecting...");
+            rpc.close().await?;
         }
 
-        NetworkAction::Status => {
-            println!("Network status - Coming soon!");
-            println!("This will show:");
-            println!("  - Connection state");
-            println!("  - Observed IPv4/IPv6 addresses");
-            println!("  - NAT type");
-            println!("  - Active peer count");
+        NetworkAction::Status { coordinator, bind } => {
+            use saorsa_gossip_transport::{GossipTransport, RpcTransport, StreamType};
+
+            let bind_addr: std::net::SocketAddr = bind.parse()?;
+            let coordinator_addr: std::net::SocketAddr = coordinator.parse()?;
+
+            let transport = AntQuicTransport::new(bind_addr, vec![coordinator_addr]).await?;
+            let coordinator_peer = match transport.get_bootstrap_peer_id(coordinator_addr).await {
+                Some(peer_id) => Ok(peer_id),
+                None => transport.dial_bootstrap(coordinator_addr).await,
+            };
+            let rpc = RpcTransport::new(transport);
+
+            // Exchange a PING so the connection has a fresh RTT sample
+            let connected = match coordinator_peer {
+                Ok(peer_id) => rpc
+                    .request(
+                        peer_id,
+                        StreamType::Membership,
+                        bytes::Bytes::from_static(b"PING"),
+                        std::time::Duration::from_secs(5),
+                    )
+                    .await
+                    .is_ok(),
+                Err(_) => false,
+            };
+
+            let stats = rpc.inner().stats().await;
+            println!("Network status");
+            println!(
+                "  Connection: {}",
+                if connected {
+                    "connected"
+                } else {
+                    "unreachable"
+                }
+            );
+            match rpc.inner().get_external_address() {
+                Some(addr) => println!("  Observed address: {}", addr),
+                None => println!("  Observed address: unknown"),
+            }
+            println!("  Active peers: {}", stats.peers.len());
+
+            for peer in &stats.peers {
+                println!("\n  Peer {}", hex::encode(peer.peer.as_bytes()));
+                if let Some(addr) = peer.addr {
+                    println!("    Address: {}", addr);
+                }
+                if let Some(rtt) = peer.rtt {
+                    println!("    RTT: {:?}", rtt);
+                }
+                println!("    Queue depth: {}", peer.queue_depth);
+            }
+
+            println!("\n  Traffic by stream:");
+            for stream_type in StreamType::ALL {
+                let stream = stats.stream(stream_type);
+                println!(
+                    "    {:<10} sent {} msgs / {} bytes, received {} msgs / {} bytes, {} errors",
+                    format!("{:?}", stream_type),
+                    stream.messages_sent,
+                    stream.bytes_sent,
+                    stream.messages_received,
+                    stream.bytes_received,
+                    stream.errors
+                );
+            }
         }
 
         NetworkAction::Peers => {
@@ -5,7 +5,7 @@
 //! - Bootstrap discovery
 //! - Address reflection for NAT traversal
 //! - Optional relay services
-//! - Optional rendezvous services
+//! - Optional rendezvous for coordinated hole punching
 //!
 //! # Usage
 //!
@@ -44,6 +44,10 @@ struct Args {
     /// Publish interval in seconds (coordinator adverts)
     #[arg(long, default_value = "300")]
     publish_interval: u64,
+
+    /// Reflector addresses (comma-separated) used to detect our NAT class
+    #[arg(long, value_delimiter = ',')]
+    reflectors: Vec<SocketAddr>,
 }
 
 #[tokio::main]
@@ -68,19 +72,7 @@ async fn main() -> Result<()> {
         hex::encode(identity.peer_id.as_bytes())
     );
 
-    // 2. Start coordinator services based on roles
-    if coordinator_roles.coordinator {
-        tracing::info!("Starting coordinator advertisement service...");
-        start_coordinator_service(
-            &identity,
-            &coordinator_roles,
-            args.bind,
-            args.publish_interval,
-        )
-        .await?;
-    }
-
-    // 3. Start transport and message handling
+    // 2. Start transport and message handling
     tracing::info!("Initializing transport on {}...", args.bind);
     let transport = saorsa_gossip_transport::AntQuicTransport::new(
         args.bind,
@@ -95,36 +87,102 @@ async fn main() -> Result<()> {
 
     if coordinator_roles.reflector {
         tracing::info!("Reflector role enabled (address observation)");
-        // Address reflection is handled by responding to PING messages
     }
 
     if coordinator_roles.relay {
         tracing::info!("Relay role enabled (message forwarding)");
-        // TODO: Implement relay service with rate limiting
     }
+    let transport = saorsa_gossip_transport::RelayTransport::new(
+        transport,
+        saorsa_gossip_transport::RelayConfig::default().with_serve(coordinator_roles.relay),
+    )?;
 
     if coordinator_roles.rendezvous {
         tracing::info!("Rendezvous role enabled (connection coordination)");
-        // TODO: Implement rendezvous coordination
+    }
+
+    // 3. Answer PING, observed-address and hole punch requests
+    let local_peer_id = transport.inner().peer_id();
+    let transport = std::sync::Arc::new(saorsa_gossip_transport::RpcTransport::new(transport));
+    let reflector = coordinator_roles
+        .reflector
+        .then(|| std::sync::Arc::downgrade(&transport));
+    let rendezvous = coordinator_roles.rendezvous.then(|| {
+        (
+            std::sync::Arc::downgrade(&transport),
+            saorsa_gossip_coordinator::Rendezvous::new(local_peer_id),
+        )
+    });
+    transport.register_handler(
+        saorsa_gossip_transport::StreamType::Membership,
+        move |peer_id, data| {
+            let reflector = reflector.clone();
+            let rendezvous = rendezvous.clone();
+            async move {
+                if data.as_ref() == b"PING" {
+                    tracing::info!(
+                        "📡 PING request from peer {}",
+                        hex::encode(peer_id.as_bytes())
+                    );
+                    return Ok(bytes::Bytes::from_static(b"PONG"));
+                }
+                if saorsa_gossip_coordinator::ObservedAddress::is_request(&data) {
+                    return observe_address(reflector, peer_id).await;
+                }
+                if let Ok(request) = saorsa_gossip_coordinator::PunchMessage::from_bytes(&data) {
+                    return coordinate_punch(rendezvous, peer_id, request).await;
+                }
+                Err(anyhow::anyhow!("Unsupported membership request"))
+            }
+        },
+    );
+
+    // 4. Detect our NAT class and start coordinator services based on roles
+    if coordinator_roles.coordinator {
+        let nat_class = if args.reflectors.is_empty() {
+            // Coordinators are expected to be publicly reachable
+            saorsa_gossip_coordinator::NatClass::Eim
+        } else {
+            detect_nat_class(&transport, args.bind, &args.reflectors).await
+        };
+
+        tracing::info!("Starting coordinator advertisement service...");
+        start_coordinator_service(
+            &identity,
+            &coordinator_roles,
+            args.bind,
+            nat_class,
+            args.publish_interval,
+        )
+        .await?;
     }
 
     tracing::info!("Coordinator node running. Press Ctrl+C to stop.");
 
-    // 4. Start background update checker
+    // 5. Start background update checker
     tracing::info!("Starting background update checker (checks every 6 hours)...");
     updater::start_background_checker();
 
-    // 5. Start message handling loop
-    let transport = std::sync::Arc::new(transport);
+    // 6. Start message handling loop
     let transport_clone = transport.clone();
 
     tokio::spawn(async move {
         handle_messages(transport_clone).await;
     });
 
-    // 6. Wait for shutdown signal
+    // 7. Periodically log transport statistics
+    let stats_transport = transport.clone();
+    tokio::spawn(async move {
+        log_stats(stats_transport).await;
+    });
+
+    // 8. Wait for shutdown signal
     tokio::signal::ctrl_c().await?;
     tracing::info!("Shutting down coordinator...");
+    {
+        use saorsa_gossip_transport::GossipTransport;
+        transport.close().await?;
+    }
 
     Ok(())
 }
@@ -167,16 +225,17 @@ async fn start_coordinator_service(
     identity: &CoordinatorIdentity,
     roles: &CoordinatorRoles,
     bind_addr: SocketAddr,
+    nat_class: saorsa_gossip_coordinator::NatClass,
     publish_interval_secs: u64,
 ) -> Result<()> {
-    use saorsa_gossip_coordinator::{CoordinatorPublisher, NatClass, PeriodicPublisher};
+    use saorsa_gossip_coordinator::{CoordinatorPublisher, PeriodicPublisher};
 
     // Create publisher
     let publisher = CoordinatorPublisher::new(
         identity.peer_id,
         roles.clone().into(),
         vec![bind_addr],
-        NatClass::Eim, // Default to EIM, can be detected via STUN
+        nat_class,
     );
 
     // Set signing key
@@ -256,8 +315,108 @@ impl From<CoordinatorRoles> for saorsa_gossip_coordinator::CoordinatorRoles {
     }
 }
 
+/// Time to wait for a reflector to answer an observed-address request
+const OBSERVE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Answer an observed-address request with the address `peer_id` connects from
+async fn observe_address(
+    reflector: Option<std::sync::Weak<CoordinatorTransport>>,
+    peer_id: saorsa_gossip_types::PeerId,
+) -> Result<bytes::Bytes> {
+    use saorsa_gossip_transport::GossipTransport;
+
+    let transport = reflector
+        .and_then(|transport| transport.upgrade())
+        .ok_or_else(|| anyhow::anyhow!("Reflector role is not enabled"))?;
+    let addr = transport
+        .peer_addr(&peer_id)
+        .await
+        .ok_or_else(|| anyhow::anyhow!("No address known for peer"))?;
+
+    tracing::debug!(
+        "Reflected address {} to peer {}",
+        addr,
+        hex::encode(peer_id.as_bytes())
+    );
+    saorsa_gossip_coordinator::ObservedAddress::new(addr).to_bytes()
+}
+
+/// Ask each reflector for our observed address and classify our NAT
+async fn detect_nat_class(
+    transport: &CoordinatorTransport,
+    bind_addr: SocketAddr,
+    reflectors: &[SocketAddr],
+) -> saorsa_gossip_coordinator::NatClass {
+    use saorsa_gossip_transport::GossipTransport;
+
+    let mut detector = saorsa_gossip_coordinator::NatDetector::new().with_local_addr(bind_addr);
+    for &addr in reflectors {
+        let observed = async {
+            let peer_id = transport.dial_bootstrap(addr).await?;
+            let response = transport
+                .request(
+                    peer_id,
+                    saorsa_gossip_transport::StreamType::Membership,
+                    bytes::Bytes::from_static(saorsa_gossip_coordinator::OBSERVE_REQUEST),
+                    OBSERVE_TIMEOUT,
+                )
+                .await?;
+            let observed = saorsa_gossip_coordinator::ObservedAddress::from_bytes(&response)?;
+            anyhow::Ok((peer_id, observed.addr))
+        };
+
+        match observed.await {
+            Ok((peer_id, observed)) => {
+                tracing::info!("Reflector {} observed us at {}", addr, observed);
+                detector.record(peer_id, observed);
+            }
+            Err(e) => tracing::warn!("Reflector {} did not answer: {}", addr, e),
+        }
+    }
+
+    let nat_class = detector.classify();
+    tracing::info!(
+        "Detected NAT class {:?} from {} reflectors",
+        nat_class,
+        detector.len()
+    );
+    nat_class
+}
+
+/// Forward a hole punch request to its target and answer with the target's addresses
+async fn coordinate_punch(
+    rendezvous: Option<(
+        std::sync::Weak<CoordinatorTransport>,
+        saorsa_gossip_coordinator::Rendezvous,
+    )>,
+    initiator: saorsa_gossip_types::PeerId,
+    request: saorsa_gossip_coordinator::PunchMessage,
+) -> Result<bytes::Bytes> {
+    let (transport, rendezvous) = rendezvous
+        .and_then(|(transport, rendezvous)| Some((transport.upgrade()?, rendezvous)))
+        .ok_or_else(|| anyhow::anyhow!("Rendezvous role is not enabled"))?;
+
+    let sync = rendezvous
+        .coordinate(&transport, initiator, request)
+        .await?;
+
+    if let Some(plan) = saorsa_gossip_coordinator::PunchPlan::from_message(&sync) {
+        tracing::info!(
+            "Coordinated hole punch from peer {} to peer {}",
+            hex::encode(initiator.as_bytes()),
+            hex::encode(plan.peer.as_bytes())
+        );
+    }
+    sync.to_bytes()
+}
+
+/// Transport stack of a coordinator: RPC over relay circuits over QUIC
+type CoordinatorTransport = saorsa_gossip_transport::RpcTransport<
+    saorsa_gossip_transport::RelayTransport<saorsa_gossip_transport::AntQuicTransport>,
+>;
+
 /// Handle incoming messages from peers
-async fn handle_messages(transport: std::sync::Arc<saorsa_gossip_transport::AntQuicTransport>) {
+async fn handle_messages(transport: std::sync::Arc<CoordinatorTransport>) {
     use saorsa_gossip_transport::GossipTransport;
 
     tracing::info!("Message handler started - listening for PING messages...");
@@ -315,6 +474,54 @@ async fn handle_messages(transport: std::sync::Arc<saorsa_gossip_transport::AntQ
     }
 }
 
+/// Interval between transport statistics log lines
+const STATS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Log transport statistics every [`STATS_INTERVAL`]
+async fn log_stats(transport: std::sync::Arc<CoordinatorTransport>) {
+    let mut interval = tokio::time::interval(STATS_INTERVAL);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let stats = transport.inner().inner().stats().await;
+        let totals = stats.totals();
+        tracing::info!(
+            "Transport stats: {} peers, {} msgs / {} bytes sent, {} msgs / {} bytes received, {} errors",
+            stats.peers.len(),
+            totals.messages_sent,
+            totals.bytes_sent,
+            totals.messages_received,
+            totals.bytes_received,
+            totals.errors
+        );
+
+        for peer in &stats.peers {
+            let peer_totals = peer.totals();
+            tracing::debug!(
+                "  peer {} rtt={:?} queue={} sent={} received={} errors={}",
+                hex::encode(peer.peer.as_bytes()),
+                peer.rtt,
+                peer.queue_depth,
+                peer_totals.messages_sent,
+                peer_totals.messages_received,
+                peer_totals.errors
+            );
+        }
+
+        let relay = transport.inner().relay_stats();
+        if relay.circuits > 0 || relay.frames_relayed > 0 {
+            tracing::info!(
+                "Relay stats: {} circuits, {} frames / {} bytes relayed, {} frames over quota",
+                relay.circuits,
+                relay.frames_relayed,
+                relay.bytes_relayed,
+                relay.frames_dropped
+            );
+        }
+    }
+}
+
 /// Initialize logging based on verbosity
 fn init_logging(verbose: bool) -> Result<()> {
     use tracing_subscriber::EnvFilter;
@@ -2,7 +2,7 @@
 //!
 //! Implements SPEC2 §7.4 bootstrap flow: cache → FOAF → connect
 
-use crate::{CoordinatorHandler, FindCoordinatorQuery, PeerCache, PeerCacheEntry};
+use crate::{CoordinatorHandler, FindCoordinatorQuery, PeerCache, PeerCacheEntry, PunchMessage};
 use saorsa_gossip_types::PeerId;
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -65,6 +65,11 @@ impl Bootstrap {
         }
     }
 
+    /// The peer cache bootstrap draws on
+    pub fn peer_cache(&self) -> &PeerCache {
+        &self.peer_cache
+    }
+
     fn pending_queries_guard(&self) -> Option<MutexGuard<'_, HashMap<[u8; 32], Instant>>> {
         self.pending_queries.lock().ok()
     }
@@ -130,7 +135,8 @@ impl Bootstrap {
     ///
     /// Traversal preference order:
     /// 1. Direct: Use public_addrs (best performance, lowest cost)
-    /// 2. Reflexive: Use reflexive_addrs from hole punching (moderate cost)
+    /// 2. Reflexive: Use reflexive_addrs from hole punching (moderate cost),
+    ///    and the only option besides relay once a punch to the peer succeeded
     /// 3. Relay: Lookup relay peer's public address (last resort, highest cost)
     fn get_addr_for_method(
         &self,
@@ -139,6 +145,11 @@ impl Bootstrap {
     ) -> Option<SocketAddr> {
         match method {
             TraversalMethod::Direct => {
+                // A punched peer is behind NAT, so its public address is not
+                // reachable unsolicited
+                if entry.is_punched() {
+                    return None;
+                }
                 // Direct connection via public address
                 entry.public_addrs.first().copied()
             }
@@ -162,6 +173,43 @@ impl Bootstrap {
         }
     }
 
+    /// Plan a coordinated hole punch to `target`
+    ///
+    /// Selects the best cached rendezvous coordinator by traversal preference
+    /// and builds the request to send it, carrying our reflexive addresses.
+    /// Returns `None` if no rendezvous coordinator is cached.
+    pub fn punch_request(
+        &self,
+        target: PeerId,
+        local_addrs: Vec<SocketAddr>,
+    ) -> Option<(BootstrapResult, PunchMessage)> {
+        let mut rendezvous = self.peer_cache.get_by_role(|entry| {
+            entry.roles.rendezvous && entry.peer_id != target && entry.peer_id != self.peer_id
+        });
+        rendezvous.sort_by_key(|entry| std::cmp::Reverse(entry.last_success));
+
+        let result = self.select_best_coordinator(&rendezvous)?;
+        Some((result, PunchMessage::request(target, local_addrs)))
+    }
+
+    /// Check if `peer_id` is a cached rendezvous coordinator
+    ///
+    /// Punch offers are only accepted from these, so other peers cannot make
+    /// us dial arbitrary addresses.
+    pub fn is_rendezvous(&self, peer_id: &PeerId) -> bool {
+        self.peer_cache
+            .get(peer_id)
+            .is_some_and(|entry| entry.roles.rendezvous)
+    }
+
+    /// Record that a hole punch to `peer_id` succeeded at `addr`
+    ///
+    /// Later bootstraps reach the peer over [`TraversalMethod::Reflexive`].
+    /// Returns `false` if the peer is not cached.
+    pub fn record_punch(&self, peer_id: &PeerId, addr: SocketAddr) -> bool {
+        self.peer_cache.record_punch(peer_id, addr)
+    }
+
     /// Handle a FOAF FIND_COORDINATOR response
     ///
     /// Processes coordinator adverts from response, updates cache, and returns connect action.
@@ -801,6 +849,92 @@ mod tests {
         }
     }
 
+    /// Test punch request goes to a rendezvous coordinator
+    #[test]
+    fn test_punch_request_selects_rendezvous() {
+        let peer_id = PeerId::new([40u8; 32]);
+        let peer_cache = PeerCache::new();
+        let handler = CoordinatorHandler::new(peer_id);
+
+        let target = PeerId::new([41u8; 32]);
+        let rendezvous_peer = PeerId::new([42u8; 32]);
+        let rendezvous_addr = "203.0.113.20:7000".parse().expect("valid");
+        let local_addr: SocketAddr = "198.51.100.5:4433".parse().expect("valid");
+
+        // Plain coordinator without the rendezvous role is not used
+        peer_cache.insert(PeerCacheEntry::new(
+            PeerId::new([43u8; 32]),
+            vec!["203.0.113.21:7000".parse().expect("valid")],
+            NatClass::Eim,
+            PeerRoles {
+                coordinator: true,
+                reflector: false,
+                rendezvous: false,
+                relay: false,
+            },
+        ));
+        peer_cache.insert(PeerCacheEntry::new(
+            rendezvous_peer,
+            vec![rendezvous_addr],
+            NatClass::Eim,
+            PeerRoles {
+                coordinator: true,
+                reflector: false,
+                rendezvous: true,
+                relay: false,
+            },
+        ));
+
+        let bootstrap = Bootstrap::new(peer_id, peer_cache, handler);
+        let (result, request) = bootstrap
+            .punch_request(target, vec![local_addr])
+            .expect("rendezvous available");
+
+        assert_eq!(result.peer_id, rendezvous_peer);
+        assert_eq!(result.addr, rendezvous_addr);
+        assert_eq!(request, PunchMessage::request(target, vec![local_addr]));
+
+        // The target itself is never its own rendezvous
+        assert!(bootstrap
+            .punch_request(rendezvous_peer, vec![local_addr])
+            .is_none());
+    }
+
+    /// Test a recorded punch makes reconnects prefer Reflexive
+    #[test]
+    fn test_recorded_punch_prefers_reflexive() {
+        let peer_id = PeerId::new([44u8; 32]);
+        let peer_cache = PeerCache::new();
+        let handler = CoordinatorHandler::new(peer_id);
+
+        let coord_peer = PeerId::new([45u8; 32]);
+        let public_addr = "10.0.0.5:8080".parse().expect("valid");
+        let punched_addr = "203.0.113.30:41000".parse().expect("valid");
+
+        peer_cache.insert(PeerCacheEntry::new(
+            coord_peer,
+            vec![public_addr],
+            NatClass::Edm,
+            PeerRoles {
+                coordinator: true,
+                reflector: false,
+                rendezvous: false,
+                relay: false,
+            },
+        ));
+
+        let bootstrap = Bootstrap::new(peer_id, peer_cache, handler);
+        assert!(bootstrap.record_punch(&coord_peer, punched_addr));
+
+        match bootstrap.find_coordinator() {
+            BootstrapAction::Connect(result) => {
+                assert_eq!(result.method, TraversalMethod::Reflexive);
+                assert_eq!(result.addr, punched_addr);
+            }
+            _ => panic!("Expected Connect action"),
+        }
+    }
+
     /// Test builder pattern for PeerCacheEntry
     #[test]
     fn test_peer_cache_entry_builder() {
@@ -50,6 +50,8 @@ mod foaf;
 mod handler;
 mod peer_cache;
 mod publisher;
+mod punch;
+mod reflection;
 mod topic;
 
 pub use bootstrap::{Bootstrap, BootstrapAction, BootstrapResult, TraversalMethod};
@@ -61,6 +63,13 @@ pub use foaf::{
 pub use handler::CoordinatorHandler;
 pub use peer_cache::{PeerCache, PeerCacheEntry, PeerRoles};
 pub use publisher::{CoordinatorPublisher, PeriodicPublisher};
+pub use punch::{
+    HolePuncher, PunchMessage, PunchPlan, Rendezvous, MAX_PUNCH_ADDRS, PUNCH_REQUEST_TIMEOUT,
+    PUNCH_START_DELAY,
+};
+pub use reflection::{
+    NatDetector, ObservedAddress, MIN_REFLECTORS, OBSERVE_REQUEST, PORT_PREDICTION_WINDOW,
+};
 pub use topic::coordinator_topic;
 
 fn current_unix_time_millis() -> u64 {
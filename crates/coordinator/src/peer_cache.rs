@@ -39,6 +39,9 @@ pub struct PeerCacheEntry {
     pub nat_class: NatClass,
     /// Roles this peer provides
     pub roles: PeerRoles,
+    /// Last successful hole punch timestamp (unix ms)
+    #[serde(default)]
+    pub last_punch: Option<u64>,
 }
 
 /// Roles a peer can provide
@@ -71,6 +74,7 @@ impl PeerCacheEntry {
             last_success: now,
             nat_class,
             roles,
+            last_punch: None,
         }
     }
 
@@ -91,6 +95,23 @@ impl PeerCacheEntry {
         self.last_success = unix_time_millis();
     }
 
+    /// Record a successful hole punch to `addr`
+    ///
+    /// Moves `addr` to the front of the reflexive addresses so the next
+    /// reconnect tries it first.
+    pub fn mark_punched(&mut self, addr: SocketAddr) {
+        self.reflexive_addrs.retain(|existing| *existing != addr);
+        self.reflexive_addrs.insert(0, addr);
+        let now = unix_time_millis();
+        self.last_success = now;
+        self.last_punch = Some(now);
+    }
+
+    /// Check if a hole punch to this peer has succeeded
+    pub fn is_punched(&self) -> bool {
+        self.last_punch.is_some() && !self.reflexive_addrs.is_empty()
+    }
+
     /// Check if entry is recent (within last 24 hours)
     pub fn is_recent(&self) -> bool {
         let now = unix_time_millis();
@@ -129,6 +150,24 @@ impl PeerCache {
             .and_then(|entries| entries.get(peer_id).cloned())
     }
 
+    /// Record a successful hole punch to `peer_id` at `addr`
+    ///
+    /// Returns `false` if the peer is not cached.
+    pub fn record_punch(&self, peer_id: &PeerId, addr: SocketAddr) -> bool {
+        let mut entries = match self.lock_entries() {
+            Some(guard) => guard,
+            None => return false,
+        };
+
+        match entries.get_mut(peer_id) {
+            Some(entry) => {
+                entry.mark_punched(addr);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Get all coordinators, sorted by recency
     pub fn get_coordinators(&self) -> Vec<PeerCacheEntry> {
         let mut coordinators = self
@@ -143,7 +182,7 @@ impl PeerCache {
             .unwrap_or_default();
 
         // Sort by last_success descending (most recent first)
-        coordinators.sort_by(|a, b| b.last_success.cmp(&a.last_success));
+        coordinators.sort_by_key(|c| std::cmp::Reverse(c.last_success));
         coordinators
     }
 
@@ -554,6 +593,37 @@ mod tests {
         assert!(cache.is_empty());
     }
 
+    #[test]
+    fn test_record_punch() {
+        let cache = PeerCache::new();
+        let peer_id = PeerId::new([1u8; 32]);
+        let old_addr = "203.0.113.7:4433".parse().expect("valid");
+        let punched_addr = "203.0.113.7:4434".parse().expect("valid");
+
+        cache.insert(
+            PeerCacheEntry::new(
+                peer_id,
+                vec![],
+                NatClass::Edm,
+                PeerRoles {
+                    coordinator: false,
+                    reflector: false,
+                    rendezvous: false,
+                    relay: false,
+                },
+            )
+            .with_reflexive_addrs(vec![old_addr, punched_addr]),
+        );
+        assert!(!cache.get(&peer_id).expect("entry").is_punched());
+
+        assert!(cache.record_punch(&peer_id, punched_addr));
+        let entry = cache.get(&peer_id).expect("entry");
+        assert!(entry.is_punched());
+        assert_eq!(entry.reflexive_addrs, vec![punched_addr, old_addr]);
+
+        assert!(!cache.record_punch(&PeerId::new([2u8; 32]), punched_addr));
+    }
+
     /// Test saving cache to disk
     #[test]
     fn test_save_cache_to_disk() {
@@ -0,0 +1,680 @@
+//! Coordinated hole punching through rendezvous coordinators
+//!
+//! Two peers behind NAT cannot dial each other until each NAT holds a
+//! mapping towards the other side. A coordinator with the `rendezvous` role,
+//! reachable by both peers, swaps their reflexive addresses and fixes a
+//! common start time so both sides dial simultaneously:
+//!
+//! 1. The initiator sends [`PunchMessage::Request`] naming the target and
+//!    its own reflexive addresses to the rendezvous coordinator
+//! 2. The coordinator forwards [`PunchMessage::Offer`] to the target with
+//!    the initiator's addresses and a delay until the start
+//! 3. The target answers with [`PunchMessage::Answer`] carrying its own
+//!    reflexive addresses and starts dialing once the delay has passed
+//! 4. The coordinator replies to the initiator with [`PunchMessage::Sync`]
+//!    carrying the target's addresses and what remains of the delay
+//!
+//! The coordinator only passes on addresses at the IP it observes each peer
+//! connecting from, so a peer cannot make the other dial a third party.
+//! Delays count from when each side receives its message, so the peers'
+//! clocks need not agree.
+//!
+//! Every message is a CBOR encoded [`PunchMessage`] sent as an RPC request
+//! or response. [`Rendezvous`] runs the coordinator side and [`HolePuncher`]
+//! the peer side. Once a dial succeeds the address is recorded with
+//! [`crate::Bootstrap::record_punch`] so later reconnects use the
+//! [`crate::TraversalMethod::Reflexive`] path.
+
+use crate::Bootstrap;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use saorsa_gossip_transport::{GossipTransport, RpcTransport, StreamType};
+use saorsa_gossip_types::PeerId;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Delay between the coordinator forwarding an offer and both peers dialing
+///
+/// Covers the time for the offer and the sync to reach both peers.
+pub const PUNCH_START_DELAY: Duration = Duration::from_millis(500);
+
+/// Largest number of reflexive addresses accepted per peer
+pub const MAX_PUNCH_ADDRS: usize = 8;
+
+/// Time to wait for the other side of a punch exchange to answer
+///
+/// Covers the rendezvous forwarding the offer and the target answering it.
+pub const PUNCH_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Hole punch coordination message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PunchMessage {
+    /// Initiator → rendezvous: connect me to `target`
+    Request {
+        /// Peer the initiator wants to reach
+        target: PeerId,
+        /// Initiator's reflexive addresses
+        addrs: Vec<SocketAddr>,
+    },
+    /// Rendezvous → target: `initiator` wants to connect
+    Offer {
+        /// Peer asking for the connection
+        initiator: PeerId,
+        /// Initiator's reflexive addresses
+        addrs: Vec<SocketAddr>,
+        /// Milliseconds from receipt until both peers start dialing
+        start_in: u64,
+    },
+    /// Target → rendezvous: the target's reflexive addresses
+    Answer {
+        /// Target's reflexive addresses
+        addrs: Vec<SocketAddr>,
+    },
+    /// Rendezvous → initiator: dial `peer` after `start_in`
+    Sync {
+        /// Peer to dial
+        peer: PeerId,
+        /// Target's reflexive addresses
+        addrs: Vec<SocketAddr>,
+        /// Milliseconds from receipt until both peers start dialing
+        start_in: u64,
+    },
+}
+
+impl PunchMessage {
+    /// Create a request to reach `target` from the given reflexive addresses
+    pub fn request(target: PeerId, addrs: Vec<SocketAddr>) -> Self {
+        Self::Request {
+            target,
+            addrs: truncate_addrs(addrs),
+        }
+    }
+
+    /// Create an answer carrying the target's reflexive addresses
+    pub fn answer(addrs: Vec<SocketAddr>) -> Self {
+        Self::Answer {
+            addrs: truncate_addrs(addrs),
+        }
+    }
+
+    /// Convert to bytes for transport
+    pub fn to_bytes(&self) -> anyhow::Result<bytes::Bytes> {
+        let mut buffer = Vec::new();
+        ciborium::into_writer(self, &mut buffer)?;
+        Ok(bytes::Bytes::from(buffer))
+    }
+
+    /// Parse from bytes received over transport
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        Ok(ciborium::from_reader(data)?)
+    }
+}
+
+fn truncate_addrs(mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    addrs.truncate(MAX_PUNCH_ADDRS);
+    addrs
+}
+
+/// The addresses passed on for a peer seen connecting from `observed`
+///
+/// The observed address comes first, followed by the claimed addresses at
+/// the same IP; claims of other hosts are dropped.
+fn vouched_addrs(observed: SocketAddr, claimed: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let mut addrs = vec![observed];
+    for addr in claimed {
+        if addr.ip() == observed.ip() && !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    truncate_addrs(addrs)
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Rendezvous side of the punch protocol
+///
+/// Holds no per-session state: the offer and the sync are built from the
+/// request and the answer, which the RPC layer already correlates.
+#[derive(Debug, Clone)]
+pub struct Rendezvous {
+    /// Local peer ID
+    peer_id: PeerId,
+    /// Delay between the offer and the start time
+    start_delay: Duration,
+}
+
+impl Rendezvous {
+    /// Create a rendezvous using [`PUNCH_START_DELAY`]
+    pub fn new(peer_id: PeerId) -> Self {
+        Self {
+            peer_id,
+            start_delay: PUNCH_START_DELAY,
+        }
+    }
+
+    /// Set the delay between the offer and the start time
+    pub fn with_start_delay(mut self, start_delay: Duration) -> Self {
+        self.start_delay = start_delay;
+        self
+    }
+
+    /// Turn a request from `initiator`, seen at `observed`, into an offer for the target
+    ///
+    /// Returns the target and the offer to forward to it, or an error if
+    /// the message is not a request or names the initiator or us.
+    pub fn offer(
+        &self,
+        initiator: PeerId,
+        observed: SocketAddr,
+        request: PunchMessage,
+    ) -> anyhow::Result<(PeerId, PunchMessage)> {
+        let PunchMessage::Request { target, addrs } = request else {
+            return Err(anyhow::anyhow!("Expected a punch request"));
+        };
+        if target == initiator || target == self.peer_id {
+            return Err(anyhow::anyhow!("Invalid punch target"));
+        }
+
+        let offer = PunchMessage::Offer {
+            initiator,
+            addrs: vouched_addrs(observed, addrs),
+            start_in: millis(self.start_delay),
+        };
+        Ok((target, offer))
+    }
+
+    /// Turn the answer of `target`, seen at `observed`, into a sync for the initiator
+    ///
+    /// `elapsed` is the time since the offer was sent; the sync carries
+    /// what remains of the offer's delay.
+    pub fn sync(
+        &self,
+        target: PeerId,
+        observed: SocketAddr,
+        offer: &PunchMessage,
+        answer: PunchMessage,
+        elapsed: Duration,
+    ) -> anyhow::Result<PunchMessage> {
+        let PunchMessage::Offer { start_in, .. } = offer else {
+            return Err(anyhow::anyhow!("Expected a punch offer"));
+        };
+        let PunchMessage::Answer { addrs } = answer else {
+            return Err(anyhow::anyhow!("Expected a punch answer"));
+        };
+
+        Ok(PunchMessage::Sync {
+            peer: target,
+            addrs: vouched_addrs(observed, addrs),
+            start_in: start_in.saturating_sub(millis(elapsed)),
+        })
+    }
+
+    /// Forward a request from `initiator` to its target and build the sync
+    ///
+    /// The offer goes out as an RPC request on the membership stream; both
+    /// peers must be connected to `transport`.
+    pub async fn coordinate<T: GossipTransport + 'static>(
+        &self,
+        transport: &RpcTransport<T>,
+        initiator: PeerId,
+        request: PunchMessage,
+    ) -> Result<PunchMessage> {
+        let observed = transport
+            .peer_addr(&initiator)
+            .await
+            .ok_or_else(|| anyhow!("No observed address for the initiator"))?;
+        let (target, offer) = self.offer(initiator, observed, request)?;
+        let observed = transport
+            .peer_addr(&target)
+            .await
+            .ok_or_else(|| anyhow!("Punch target is not connected"))?;
+
+        let sent = Instant::now();
+        let answer = transport
+            .request(
+                target,
+                StreamType::Membership,
+                offer.to_bytes()?,
+                PUNCH_REQUEST_TIMEOUT,
+            )
+            .await?;
+        self.sync(
+            target,
+            observed,
+            &offer,
+            PunchMessage::from_bytes(&answer)?,
+            sent.elapsed(),
+        )
+    }
+}
+
+/// What one side of a punch dials, and when
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PunchPlan {
+    /// Peer to dial
+    pub peer: PeerId,
+    /// Candidate addresses, tried in order
+    pub addrs: Vec<SocketAddr>,
+    /// When to start dialing
+    pub start_at: Instant,
+}
+
+impl PunchPlan {
+    /// Build the plan carried by an offer (target side) or a sync (initiator side)
+    ///
+    /// Call it as the message arrives: the start time counts from now.
+    /// Returns `None` for other messages.
+    pub fn from_message(message: &PunchMessage) -> Option<Self> {
+        let (peer, addrs, start_in) = match message {
+            PunchMessage::Offer {
+                initiator,
+                addrs,
+                start_in,
+            } => (*initiator, addrs, *start_in),
+            PunchMessage::Sync {
+                peer,
+                addrs,
+                start_in,
+            } => (*peer, addrs, *start_in),
+            PunchMessage::Request { .. } | PunchMessage::Answer { .. } => return None,
+        };
+        Some(Self {
+            peer,
+            addrs: addrs.clone(),
+            start_at: Instant::now() + Duration::from_millis(start_in),
+        })
+    }
+
+    /// Time left until dialing should start
+    pub fn delay(&self) -> Duration {
+        self.start_at.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Peer side of the punch protocol
+///
+/// Asks rendezvous coordinators to connect us to other peers and answers
+/// the offers they forward. Either way both sides dial the other's addresses
+/// at the agreed start time, and the first address that connects is recorded
+/// in the bootstrap peer cache.
+pub struct HolePuncher<T: GossipTransport + 'static> {
+    /// RPC transport reaching the rendezvous and the punched peers
+    transport: Arc<RpcTransport<T>>,
+    /// Bootstrap holding the peer cache
+    bootstrap: Arc<Bootstrap>,
+    /// Our reflexive addresses, sent to the other side
+    local_addrs: Arc<RwLock<Vec<SocketAddr>>>,
+    /// Time to wait for the rendezvous to answer a request
+    request_timeout: Duration,
+}
+
+impl<T: GossipTransport + 'static> Clone for HolePuncher<T> {
+    fn clone(&self) -> Self {
+        Self {
+            transport: self.transport.clone(),
+            bootstrap: self.bootstrap.clone(),
+            local_addrs: self.local_addrs.clone(),
+            request_timeout: self.request_timeout,
+        }
+    }
+}
+
+impl<T: GossipTransport + 'static> HolePuncher<T> {
+    /// Create a puncher using [`PUNCH_REQUEST_TIMEOUT`]
+    pub fn new(transport: Arc<RpcTransport<T>>, bootstrap: Arc<Bootstrap>) -> Self {
+        Self {
+            transport,
+            bootstrap,
+            local_addrs: Arc::new(RwLock::new(Vec::new())),
+            request_timeout: PUNCH_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Set the time to wait for the rendezvous to answer a request
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Set our reflexive addresses, as observed by reflectors
+    pub fn set_local_addrs(&self, addrs: Vec<SocketAddr>) {
+        *self.local_addrs.write().unwrap_or_else(|e| e.into_inner()) = truncate_addrs(addrs);
+    }
+
+    fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.local_addrs
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Punch a connection to `target` through a cached rendezvous coordinator
+    ///
+    /// Sends the request, waits for the sync, then dials the target's
+    /// addresses at the start time. Returns the address that connected.
+    pub async fn punch(&self, target: PeerId) -> Result<SocketAddr> {
+        let (rendezvous, request) = self
+            .bootstrap
+            .punch_request(target, self.local_addrs())
+            .ok_or_else(|| anyhow!("No rendezvous coordinator cached"))?;
+        self.transport
+            .dial(rendezvous.peer_id, rendezvous.addr)
+            .await?;
+
+        let response = self
+            .transport
+            .request(
+                rendezvous.peer_id,
+                StreamType::Membership,
+                request.to_bytes()?,
+                self.request_timeout,
+            )
+            .await?;
+        let plan = PunchPlan::from_message(&PunchMessage::from_bytes(&response)?)
+            .filter(|plan| plan.peer == target)
+            .ok_or_else(|| anyhow!("Rendezvous did not sync the punch to the target"))?;
+        self.execute(plan).await
+    }
+
+    /// Answer an offer forwarded by `rendezvous`
+    ///
+    /// Replies with our addresses and dials the initiator in the background
+    /// at the start time. Offers from peers not cached as rendezvous
+    /// coordinators are refused.
+    pub fn answer(&self, rendezvous: PeerId, offer: &PunchMessage) -> Result<PunchMessage> {
+        if !self.bootstrap.is_rendezvous(&rendezvous) {
+            return Err(anyhow!("Punch offer from unknown rendezvous"));
+        }
+        let plan = match offer {
+            PunchMessage::Offer { .. } => PunchPlan::from_message(offer),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("Expected a punch offer"))?;
+
+        let puncher = self.clone();
+        tokio::spawn(async move {
+            let peer = plan.peer;
+            if let Err(e) = puncher.execute(plan).await {
+                debug!(peer_id = %peer, "Hole punch to initiator failed: {}", e);
+            }
+        });
+        Ok(PunchMessage::answer(self.local_addrs()))
+    }
+
+    /// Handle a punch message received as an RPC request from `peer`
+    ///
+    /// Suitable as the membership stream handler, or as part of one.
+    pub async fn handle_request(&self, peer: PeerId, data: Bytes) -> Result<Bytes> {
+        let offer = PunchMessage::from_bytes(&data)?;
+        self.answer(peer, &offer)?.to_bytes()
+    }
+
+    /// Wait for the start time, then dial the plan's addresses in order
+    ///
+    /// Records the first address that connects and returns it.
+    pub async fn execute(&self, plan: PunchPlan) -> Result<SocketAddr> {
+        tokio::time::sleep(plan.delay()).await;
+
+        let mut last_error = anyhow!("No addresses to punch");
+        for addr in plan.addrs {
+            match self.transport.dial(plan.peer, addr).await {
+                Ok(()) => {
+                    debug!(peer_id = %plan.peer, %addr, "Hole punch succeeded");
+                    self.bootstrap.record_punch(&plan.peer, addr);
+                    return Ok(addr);
+                }
+                Err(e) => {
+                    warn!(peer_id = %plan.peer, %addr, "Hole punch dial failed: {}", e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CoordinatorHandler, NatClass, PeerCache, PeerCacheEntry, PeerRoles};
+    use saorsa_gossip_transport::{MemoryNetwork, MemoryTransport};
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().expect("valid address")
+    }
+
+    #[test]
+    fn test_punch_message_roundtrip() {
+        let request = PunchMessage::request(PeerId::new([2u8; 32]), vec![addr("203.0.113.7:4433")]);
+        let bytes = request.to_bytes().expect("encode");
+        assert_eq!(PunchMessage::from_bytes(&bytes).expect("decode"), request);
+
+        assert!(PunchMessage::from_bytes(b"PING").is_err());
+    }
+
+    #[test]
+    fn test_rendezvous_exchanges_addresses() {
+        let initiator = PeerId::new([1u8; 32]);
+        let target = PeerId::new([2u8; 32]);
+        let rendezvous = Rendezvous::new(PeerId::new([3u8; 32]));
+
+        let request = PunchMessage::request(target, vec![addr("203.0.113.7:4433")]);
+        let (to, offer) = rendezvous
+            .offer(initiator, addr("203.0.113.7:4433"), request)
+            .expect("offer");
+        assert_eq!(to, target);
+
+        let target_plan = PunchPlan::from_message(&offer).expect("offer plan");
+        assert_eq!(target_plan.peer, initiator);
+        assert_eq!(target_plan.addrs, vec![addr("203.0.113.7:4433")]);
+        assert!(target_plan.delay() <= PUNCH_START_DELAY);
+
+        let answer = PunchMessage::answer(vec![addr("198.51.100.2:5000")]);
+        let elapsed = Duration::from_millis(100);
+        let sync = rendezvous
+            .sync(target, addr("198.51.100.2:5000"), &offer, answer, elapsed)
+            .expect("sync");
+
+        let initiator_plan = PunchPlan::from_message(&sync).expect("sync plan");
+        assert_eq!(initiator_plan.peer, target);
+        assert_eq!(initiator_plan.addrs, vec![addr("198.51.100.2:5000")]);
+        let PunchMessage::Sync { start_in, .. } = sync else {
+            panic!("Expected a sync");
+        };
+        assert_eq!(Duration::from_millis(start_in), PUNCH_START_DELAY - elapsed);
+    }
+
+    #[test]
+    fn test_rendezvous_passes_on_only_observed_hosts() {
+        let initiator = PeerId::new([1u8; 32]);
+        let target = PeerId::new([2u8; 32]);
+        let rendezvous = Rendezvous::new(PeerId::new([3u8; 32]));
+        let observed = addr("203.0.113.7:61000");
+
+        // A claim of another host is dropped; other ports of the same host stay
+        let request = PunchMessage::request(
+            target,
+            vec![addr("192.0.2.80:80"), addr("203.0.113.7:4433")],
+        );
+        let (_, offer) = rendezvous
+            .offer(initiator, observed, request)
+            .expect("offer");
+        let plan = PunchPlan::from_message(&offer).expect("offer plan");
+        assert_eq!(plan.addrs, vec![observed, addr("203.0.113.7:4433")]);
+    }
+
+    #[test]
+    fn test_rendezvous_rejects_invalid_requests() {
+        let initiator = PeerId::new([1u8; 32]);
+        let local = PeerId::new([3u8; 32]);
+        let rendezvous = Rendezvous::new(local);
+
+        let observed = addr("203.0.113.7:4433");
+        assert!(rendezvous
+            .offer(
+                initiator,
+                observed,
+                PunchMessage::request(initiator, vec![])
+            )
+            .is_err());
+        assert!(rendezvous
+            .offer(initiator, observed, PunchMessage::request(local, vec![]))
+            .is_err());
+        assert!(rendezvous
+            .offer(initiator, observed, PunchMessage::answer(vec![]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_addresses_are_capped() {
+        let addrs = (0..20)
+            .map(|port| addr(&format!("203.0.113.7:{}", 4000 + port)))
+            .collect();
+        let PunchMessage::Answer { addrs } = PunchMessage::answer(addrs) else {
+            panic!("Expected an answer");
+        };
+        assert_eq!(addrs.len(), MAX_PUNCH_ADDRS);
+    }
+
+    fn roles(rendezvous: bool) -> PeerRoles {
+        PeerRoles {
+            coordinator: rendezvous,
+            reflector: false,
+            rendezvous,
+            relay: false,
+        }
+    }
+
+    /// A peer whose cache knows the rendezvous and the other peer
+    async fn punch_node(
+        network: &MemoryNetwork,
+        id: u8,
+        addr: &str,
+        known: &[(PeerId, SocketAddr, bool)],
+    ) -> HolePuncher<MemoryTransport> {
+        let peer = PeerId::new([id; 32]);
+        let transport = network
+            .add_node_at(peer, self::addr(addr))
+            .await
+            .expect("node");
+        let cache = PeerCache::new();
+        for &(known, addr, rendezvous) in known {
+            let public_addrs = if rendezvous { vec![addr] } else { Vec::new() };
+            cache.insert(PeerCacheEntry::new(
+                known,
+                public_addrs,
+                NatClass::Eim,
+                roles(rendezvous),
+            ));
+        }
+        let bootstrap = Bootstrap::new(peer, cache, CoordinatorHandler::new(peer));
+
+        let puncher = HolePuncher::new(Arc::new(RpcTransport::new(transport)), Arc::new(bootstrap));
+        puncher.set_local_addrs(vec![self::addr(addr)]);
+        let handler = puncher.clone();
+        puncher
+            .transport
+            .register_handler(StreamType::Membership, move |peer, data| {
+                let handler = handler.clone();
+                async move { handler.handle_request(peer, data).await }
+            });
+        puncher
+    }
+
+    #[tokio::test]
+    async fn test_peers_punch_through_rendezvous() {
+        let network = MemoryNetwork::new();
+        let rendezvous_id = PeerId::new([3u8; 32]);
+        let rendezvous_addr = addr("203.0.113.3:4433");
+        let rendezvous_transport = Arc::new(RpcTransport::new(
+            network
+                .add_node_at(rendezvous_id, rendezvous_addr)
+                .await
+                .expect("rendezvous"),
+        ));
+        let rendezvous = Rendezvous::new(rendezvous_id).with_start_delay(Duration::from_millis(50));
+        let weak = Arc::downgrade(&rendezvous_transport);
+        rendezvous_transport.register_handler(StreamType::Membership, move |peer, data| {
+            let weak = weak.clone();
+            let rendezvous = rendezvous.clone();
+            async move {
+                let transport = weak.upgrade().ok_or_else(|| anyhow!("Closed"))?;
+                let request = PunchMessage::from_bytes(&data)?;
+                rendezvous
+                    .coordinate(&transport, peer, request)
+                    .await?
+                    .to_bytes()
+            }
+        });
+
+        let initiator_id = PeerId::new([1u8; 32]);
+        let target_id = PeerId::new([2u8; 32]);
+        let initiator = punch_node(
+            &network,
+            1,
+            "198.51.100.1:5000",
+            &[
+                (rendezvous_id, rendezvous_addr, true),
+                (target_id, addr("198.51.100.2:5000"), false),
+            ],
+        )
+        .await;
+        let target = punch_node(
+            &network,
+            2,
+            "198.51.100.2:5000",
+            &[
+                (rendezvous_id, rendezvous_addr, true),
+                (initiator_id, addr("198.51.100.1:5000"), false),
+            ],
+        )
+        .await;
+
+        // The target keeps a connection to its rendezvous
+        target
+            .transport
+            .dial(rendezvous_id, rendezvous_addr)
+            .await
+            .expect("dial rendezvous");
+
+        let punched = initiator.punch(target_id).await.expect("punch");
+        assert_eq!(punched, addr("198.51.100.2:5000"));
+        let entry = initiator
+            .bootstrap
+            .peer_cache()
+            .get(&target_id)
+            .expect("target cached");
+        assert!(entry.is_punched());
+        assert_eq!(entry.reflexive_addrs, vec![punched]);
+
+        // The target dials back at the same start time
+        let mut punched_back = false;
+        for _ in 0..50 {
+            let entry = target.bootstrap.peer_cache().get(&initiator_id);
+            if entry.is_some_and(|entry| entry.is_punched()) {
+                punched_back = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(punched_back);
+    }
+
+    #[tokio::test]
+    async fn test_offers_from_unknown_peers_are_refused() {
+        let network = MemoryNetwork::new();
+        let target = punch_node(&network, 2, "198.51.100.2:5000", &[]).await;
+        let offer = PunchMessage::Offer {
+            initiator: PeerId::new([1u8; 32]),
+            addrs: vec![addr("198.51.100.1:5000")],
+            start_in: 0,
+        };
+
+        assert!(target.answer(PeerId::new([9u8; 32]), &offer).is_err());
+    }
+}
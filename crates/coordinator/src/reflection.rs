@@ -0,0 +1,223 @@
+//! Address reflection and NAT class detection
+//!
+//! Reflectors answer an observed-address request with the address they see
+//! the requester connecting from. A [`NatDetector`] collects those answers
+//! from several reflectors and classifies the local NAT:
+//!
+//! - **Eim**: every reflector sees the same address, so the mapping does not
+//!   depend on the destination
+//! - **Edm**: reflectors see the same IP with nearby ports, so the mapping
+//!   changes per destination but the next port can be predicted
+//! - **Symmetric**: reflectors see different IPs or unrelated ports
+//!
+//! The request is the plain bytes [`OBSERVE_REQUEST`]; the answer is a CBOR
+//! encoded [`ObservedAddress`].
+
+use crate::{NatClass, PeerCacheEntry};
+use saorsa_gossip_types::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+/// Request asking a reflector for the requester's observed address
+pub const OBSERVE_REQUEST: &[u8] = b"OBSERVE";
+
+/// Reflectors that must answer before the NAT class is known
+pub const MIN_REFLECTORS: usize = 2;
+
+/// Largest port spread still treated as a predictable mapping
+pub const PORT_PREDICTION_WINDOW: u16 = 16;
+
+/// A reflector's answer: the address it observed the requester at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObservedAddress {
+    /// The requester's address as seen by the reflector
+    pub addr: SocketAddr,
+}
+
+impl ObservedAddress {
+    /// Create an answer for a requester observed at `addr`
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr }
+    }
+
+    /// Check whether `data` is an observed-address request
+    pub fn is_request(data: &[u8]) -> bool {
+        data == OBSERVE_REQUEST
+    }
+
+    /// Convert to bytes for transport
+    pub fn to_bytes(&self) -> anyhow::Result<bytes::Bytes> {
+        let mut buffer = Vec::new();
+        ciborium::into_writer(self, &mut buffer)?;
+        Ok(bytes::Bytes::from(buffer))
+    }
+
+    /// Parse from bytes received over transport
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        Ok(ciborium::from_reader(data)?)
+    }
+}
+
+/// Classifies the local NAT from reflector observations
+#[derive(Debug, Clone, Default)]
+pub struct NatDetector {
+    /// Local bound address, if known
+    local_addr: Option<SocketAddr>,
+    /// Observed address per reflector
+    observations: HashMap<PeerId, SocketAddr>,
+}
+
+impl NatDetector {
+    /// Create a detector with no observations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the local bound address, to recognise hosts that are not behind a NAT
+    pub fn with_local_addr(mut self, addr: SocketAddr) -> Self {
+        self.local_addr = Some(addr);
+        self
+    }
+
+    /// Record the address `reflector` observed us at
+    ///
+    /// A later answer from the same reflector replaces the earlier one.
+    pub fn record(&mut self, reflector: PeerId, observed: SocketAddr) {
+        self.observations.insert(reflector, observed);
+    }
+
+    /// Get the number of reflectors that answered
+    pub fn len(&self) -> usize {
+        self.observations.len()
+    }
+
+    /// Check if no reflector has answered yet
+    pub fn is_empty(&self) -> bool {
+        self.observations.is_empty()
+    }
+
+    /// Get the distinct observed (reflexive) addresses
+    pub fn reflexive_addrs(&self) -> Vec<SocketAddr> {
+        let mut addrs: Vec<SocketAddr> = self.observations.values().copied().collect();
+        addrs.sort();
+        addrs.dedup();
+        addrs
+    }
+
+    /// Classify the local NAT
+    ///
+    /// Returns [`NatClass::Unknown`] until [`MIN_REFLECTORS`] reflectors have
+    /// answered, unless a reflector saw our own bound address.
+    pub fn classify(&self) -> NatClass {
+        let addrs = self.reflexive_addrs();
+        if let Some(local) = self.local_addr {
+            if addrs == [local] {
+                return NatClass::Eim;
+            }
+        }
+        if self.observations.len() < MIN_REFLECTORS {
+            return NatClass::Unknown;
+        }
+        if addrs.len() == 1 {
+            return NatClass::Eim;
+        }
+
+        let ip = addrs[0].ip();
+        if addrs.iter().any(|addr| addr.ip() != ip) {
+            return NatClass::Symmetric;
+        }
+        let (min, max) = addrs.iter().fold((u16::MAX, 0), |(min, max), addr| {
+            (min.min(addr.port()), max.max(addr.port()))
+        });
+        if max - min <= PORT_PREDICTION_WINDOW {
+            NatClass::Edm
+        } else {
+            NatClass::Symmetric
+        }
+    }
+
+    /// Store the classification and reflexive addresses in a peer cache entry
+    pub fn apply(&self, entry: &mut PeerCacheEntry) {
+        entry.nat_class = self.classify();
+        entry.reflexive_addrs = self.reflexive_addrs();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PeerRoles;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().expect("valid address")
+    }
+
+    fn detector(observed: &[&str]) -> NatDetector {
+        let mut detector = NatDetector::new();
+        for (i, observed) in observed.iter().enumerate() {
+            detector.record(PeerId::new([i as u8; 32]), addr(observed));
+        }
+        detector
+    }
+
+    #[test]
+    fn test_observed_address_roundtrip() {
+        let observed = ObservedAddress::new(addr("203.0.113.7:4433"));
+        let bytes = observed.to_bytes().expect("encode");
+        assert_eq!(
+            ObservedAddress::from_bytes(&bytes).expect("decode"),
+            observed
+        );
+
+        assert!(ObservedAddress::is_request(OBSERVE_REQUEST));
+        assert!(!ObservedAddress::is_request(b"PING"));
+    }
+
+    #[test]
+    fn test_classify_needs_enough_reflectors() {
+        assert_eq!(detector(&[]).classify(), NatClass::Unknown);
+        assert_eq!(
+            detector(&["203.0.113.7:4433"]).classify(),
+            NatClass::Unknown
+        );
+
+        // Unless the reflector saw our own address
+        let detector = detector(&["203.0.113.7:4433"]).with_local_addr(addr("203.0.113.7:4433"));
+        assert_eq!(detector.classify(), NatClass::Eim);
+    }
+
+    #[test]
+    fn test_classify_nat_classes() {
+        let eim = detector(&["203.0.113.7:4433", "203.0.113.7:4433", "203.0.113.7:4433"]);
+        assert_eq!(eim.classify(), NatClass::Eim);
+
+        let edm = detector(&["203.0.113.7:4433", "203.0.113.7:4434", "203.0.113.7:4437"]);
+        assert_eq!(edm.classify(), NatClass::Edm);
+
+        let scattered = detector(&["203.0.113.7:4433", "203.0.113.7:51200"]);
+        assert_eq!(scattered.classify(), NatClass::Symmetric);
+
+        let multi_ip = detector(&["203.0.113.7:4433", "198.51.100.2:4433"]);
+        assert_eq!(multi_ip.classify(), NatClass::Symmetric);
+    }
+
+    #[test]
+    fn test_apply_updates_cache_entry() {
+        let roles = PeerRoles {
+            coordinator: false,
+            reflector: false,
+            rendezvous: false,
+            relay: false,
+        };
+        let mut entry =
+            PeerCacheEntry::new(PeerId::new([9u8; 32]), vec![], NatClass::Unknown, roles);
+
+        detector(&["203.0.113.7:4433", "203.0.113.7:4434"]).apply(&mut entry);
+        assert_eq!(entry.nat_class, NatClass::Edm);
+        assert_eq!(
+            entry.reflexive_addrs,
+            vec![addr("203.0.113.7:4433"), addr("203.0.113.7:4434")]
+        );
+    }
+}
@@ -2,20 +2,37 @@
 //!
 //! Provides:
 //! - HyParView for partial views (active + passive)
-//! - SWIM for failure detection
+//! - SWIM for failure detection, with indirect probes, incarnation numbers
+//!   and piggybacked dissemination
 //! - Periodic shuffling and anti-entropy
+//! - Event subscription for neighbour and liveness changes
+//! - Passive view snapshots in the coordinator peer cache format
+//! - Topology-aware promotion of passive peers by RTT, diversity and score
 
 use anyhow::{anyhow, Result};
-use saorsa_gossip_transport::{GossipTransport, StreamType};
+use bytes::Bytes;
+use rand::seq::IteratorRandom;
+use saorsa_gossip_transport::{GossipTransport, StreamType, TransportEvent};
 use saorsa_gossip_types::PeerId;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio::task::JoinHandle;
 use tokio::time;
 use tracing::{debug, trace, warn};
 
+mod selector;
+mod snapshot;
+mod swim;
+
+pub use selector::{
+    AsnLookup, NeighborCandidate, NeighborSelector, RandomSelector, TopologySelector,
+};
+pub use swim::{MemberUpdate, PeerState, SwimConfig, SwimDetector, SwimMessage};
+
 /// Default active view degree (8-12 peers)
 pub const DEFAULT_ACTIVE_DEGREE: usize = 8;
 /// Maximum active view degree
@@ -30,27 +47,134 @@ pub const SHUFFLE_PERIOD_SECS: u64 = 30;
 pub const SWIM_PROBE_INTERVAL_SECS: u64 = 1;
 /// SWIM suspect timeout (per SPEC.md)
 pub const SWIM_SUSPECT_TIMEOUT_SECS: u64 = 3;
+/// Active random walk length (ARWL): hops a FORWARDJOIN travels before the
+/// joining node must be taken into an active view
+pub const ACTIVE_RANDOM_WALK_LENGTH: usize = 6;
+/// Passive random walk length (PRWL): the remaining hop count at which a
+/// FORWARDJOIN adds the joining node to a passive view
+pub const PASSIVE_RANDOM_WALK_LENGTH: usize = 3;
+/// Number of active view peers offered in a shuffle (ka)
+pub const SHUFFLE_ACTIVE_SAMPLE: usize = 3;
+/// Number of passive view peers offered in a shuffle (kp)
+pub const SHUFFLE_PASSIVE_SAMPLE: usize = 4;
+/// Hops a SHUFFLE travels before the receiving node replies
+pub const SHUFFLE_WALK_LENGTH: usize = 3;
+
+/// Capacity of the membership event broadcast channel
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+/// Capacity of the channel handing on frames of other streams from a receive task
+pub const PASSTHROUGH_CHANNEL_CAPACITY: usize = 256;
+
+/// Time to wait for a NEIGHBOR reply before asking another peer
+const NEIGHBOR_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time after sending a JOIN during which FORWARDJOIN replies are accepted
+const FORWARD_JOIN_REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
 
-/// SWIM protocol messages
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub enum SwimMessage {
-    /// Ping message to probe peer
-    Ping,
-    /// Ack response to ping
-    Ack,
+/// Priority of a HyParView NEIGHBOR request
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NeighborPriority {
+    /// The sender has no active neighbours left; always accepted
+    High,
+    /// The sender is topping up its active view; accepted only if there is room
+    Low,
+}
+
+/// A peer and the address it was last reached at
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerEntry {
+    /// Peer ID
+    pub peer: PeerId,
+    /// Last-known address, if any
+    pub addr: Option<SocketAddr>,
 }
 
 /// HyParView protocol messages
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HyParViewMessage {
-    /// Join request
+    /// Join request from a new node to its contact
     Join(PeerId),
-    /// Shuffle request with peer list
-    Shuffle(Vec<PeerId>),
-    /// ForwardJoin request
-    ForwardJoin(PeerId, usize),
+    /// Random walk carrying a sample of the origin's views
+    Shuffle {
+        /// Node that started the shuffle; the reply goes straight to it
+        origin: PeerId,
+        /// Remaining hops
+        ttl: usize,
+        /// The origin followed by a sample of its active and passive views
+        peers: Vec<PeerEntry>,
+    },
+    /// Random walk announcing a joining node
+    ForwardJoin {
+        /// The joining node
+        joiner: PeerId,
+        /// Address the joining node was reached at, if known
+        addr: Option<SocketAddr>,
+        /// Remaining hops
+        ttl: usize,
+    },
     /// Disconnect notification
     Disconnect,
+    /// The sender ended a FORWARDJOIN walk and took the joining node as neighbour
+    ForwardJoinReply,
+    /// Request to become an active neighbour of the receiver
+    Neighbor(NeighborPriority),
+    /// Answer to a NEIGHBOR request
+    NeighborReply {
+        /// Whether the sender took the requester into its active view
+        accepted: bool,
+    },
+    /// Answer to a SHUFFLE from the node where its walk ended
+    ShuffleReply {
+        /// A sample of the sender's passive view
+        peers: Vec<PeerEntry>,
+    },
+}
+
+/// Envelope for every frame on [`StreamType::Membership`]
+///
+/// HyParView and SWIM share the stream; the envelope tells
+/// [`HyParViewMembership::handle_message`] which protocol a frame is for.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MembershipMessage {
+    /// View maintenance
+    HyParView(HyParViewMessage),
+    /// Failure detection
+    Swim(SwimMessage),
+    /// A protocol added by a newer version, skipped by older nodes
+    #[serde(other)]
+    Unknown,
+}
+
+impl MembershipMessage {
+    /// Convert to bytes for transport
+    pub fn to_bytes(&self) -> Result<Bytes> {
+        Ok(Bytes::from(bincode::serialize(self)?))
+    }
+
+    /// Parse from bytes received over transport
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(data)?)
+    }
+}
+
+/// A change in membership, delivered to [`Membership::subscribe`] subscribers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MembershipEvent {
+    /// A peer joined the active view
+    NeighborUp(PeerId),
+    /// A peer left the active view
+    NeighborDown(PeerId),
+    /// A peer stopped answering probes, or was reported to
+    PeerSuspected(PeerId),
+    /// A peer failed to refute a suspicion in time, or was reported dead
+    PeerDead(PeerId),
+    /// A suspected or dead peer turned out to be alive
+    PeerRefuted(PeerId),
 }
 
 /// Membership management trait
@@ -69,367 +193,990 @@ pub trait Membership: Send + Sync {
     async fn add_active(&self, peer: PeerId) -> Result<()>;
 
     /// Remove a peer from the active view
+    ///
+    /// Only the local view changes; the peer is not reported as failed.
     async fn remove_active(&self, peer: PeerId) -> Result<()>;
 
     /// Promote a peer from passive to active view
     async fn promote(&self, peer: PeerId) -> Result<()>;
+
+    /// Subscribe to neighbour and liveness changes
+    ///
+    /// Only events after the call are delivered; read [`Self::active_view`]
+    /// first for the current state.
+    fn subscribe(&self) -> broadcast::Receiver<MembershipEvent>;
 }
 
-/// Peer state for SWIM failure detection
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum PeerState {
-    /// Peer is alive and responding
-    Alive,
-    /// Peer is suspected of failure
-    Suspect,
-    /// Peer is confirmed dead
-    Dead,
+/// HyParView protocol parameters
+#[derive(Debug, Clone)]
+pub struct HyParViewConfig {
+    /// Target size of the active view (default: 8)
+    pub active_degree: usize,
+    /// Maximum size of the passive view (default: 64)
+    pub passive_degree: usize,
+    /// Active random walk length, ARWL (default: 6)
+    pub active_walk_length: usize,
+    /// Passive random walk length, PRWL (default: 3)
+    pub passive_walk_length: usize,
+    /// Active view peers offered in each shuffle (default: 3)
+    pub shuffle_active_sample: usize,
+    /// Passive view peers offered in each shuffle (default: 4)
+    pub shuffle_passive_sample: usize,
+    /// Hops a shuffle travels (default: 3)
+    pub shuffle_walk_length: usize,
+    /// Chooses the passive peers to promote (default: [`TopologySelector`])
+    pub selector: Arc<dyn NeighborSelector>,
 }
 
-/// SWIM peer entry with timestamp
-#[derive(Clone, Debug)]
-struct SwimPeerEntry {
-    state: PeerState,
-    last_update: Instant,
+impl HyParViewConfig {
+    /// Create a configuration with the given view sizes
+    pub fn new(active_degree: usize, passive_degree: usize) -> Self {
+        Self {
+            active_degree,
+            passive_degree,
+            active_walk_length: ACTIVE_RANDOM_WALK_LENGTH,
+            passive_walk_length: PASSIVE_RANDOM_WALK_LENGTH,
+            shuffle_active_sample: SHUFFLE_ACTIVE_SAMPLE,
+            shuffle_passive_sample: SHUFFLE_PASSIVE_SAMPLE,
+            shuffle_walk_length: SHUFFLE_WALK_LENGTH,
+            selector: Arc::new(TopologySelector::new()),
+        }
+    }
+
+    /// Set the active and passive random walk lengths
+    ///
+    /// The passive walk length should not exceed the active one, or joining
+    /// nodes never reach passive views.
+    pub fn with_walk_lengths(mut self, active: usize, passive: usize) -> Self {
+        self.active_walk_length = active;
+        self.passive_walk_length = passive;
+        self
+    }
+
+    /// Set how many active and passive peers each shuffle offers, and how
+    /// far it travels
+    pub fn with_shuffle(mut self, active_sample: usize, passive_sample: usize, ttl: usize) -> Self {
+        self.shuffle_active_sample = active_sample;
+        self.shuffle_passive_sample = passive_sample;
+        self.shuffle_walk_length = ttl;
+        self
+    }
+
+    /// Set how passive peers are chosen for promotion
+    pub fn with_selector(mut self, selector: Arc<dyn NeighborSelector>) -> Self {
+        self.selector = selector;
+        self
+    }
 }
 
-/// SWIM failure detector
-pub struct SwimDetector<T: GossipTransport + 'static> {
-    /// Peer states with timestamps
-    states: Arc<RwLock<HashMap<PeerId, SwimPeerEntry>>>,
-    /// Probe period in seconds
-    probe_period: u64,
-    /// Suspect timeout in seconds
-    suspect_timeout: u64,
-    /// Transport layer for sending probes
+impl Default for HyParViewConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_ACTIVE_DEGREE, DEFAULT_PASSIVE_DEGREE)
+    }
+}
+
+/// HyParView membership implementation
+pub struct HyParViewMembership<T: GossipTransport + 'static> {
+    /// Local peer ID
+    local_peer: PeerId,
+    /// Active view (for routing)
+    active: Arc<RwLock<HashSet<PeerId>>>,
+    /// Passive view (for healing)
+    passive: Arc<RwLock<HashSet<PeerId>>>,
+    /// Last-known addresses of peers in either view
+    addrs: Arc<RwLock<HashMap<PeerId, SocketAddr>>>,
+    /// When peers in either view were last heard from (unix ms)
+    seen: Arc<RwLock<HashMap<PeerId, u64>>>,
+    /// Scores handed to the neighbour selector
+    scores: Arc<RwLock<HashMap<PeerId, f64>>>,
+    /// Peers sent a NEIGHBOR request, with the time it was sent
+    pending_neighbors: Arc<RwLock<HashMap<PeerId, Instant>>>,
+    /// When our last JOIN was sent, and how many FORWARDJOIN replies it may
+    /// still bring
+    pending_join: Arc<RwLock<Option<(Instant, usize)>>>,
+    /// Peers offered in our outstanding shuffle, evicted first when merging
+    /// its reply; `None` once it was answered
+    shuffle_sent: Arc<RwLock<Option<Vec<PeerId>>>>,
+    /// SWIM failure detector
+    swim: SwimDetector<T>,
+    /// Protocol parameters
+    config: HyParViewConfig,
+    /// Transport layer for sending messages
     transport: Arc<T>,
+    /// Membership events, shared with the SWIM detector
+    events: broadcast::Sender<MembershipEvent>,
+}
+
+impl<T: GossipTransport + 'static> Clone for HyParViewMembership<T> {
+    /// Get another handle to the same membership
+    ///
+    /// Clones share views; background tasks are not duplicated.
+    fn clone(&self) -> Self {
+        Self {
+            local_peer: self.local_peer,
+            active: self.active.clone(),
+            passive: self.passive.clone(),
+            addrs: self.addrs.clone(),
+            seen: self.seen.clone(),
+            scores: self.scores.clone(),
+            pending_neighbors: self.pending_neighbors.clone(),
+            pending_join: self.pending_join.clone(),
+            shuffle_sent: self.shuffle_sent.clone(),
+            swim: self.swim.clone(),
+            config: self.config.clone(),
+            transport: self.transport.clone(),
+            events: self.events.clone(),
+        }
+    }
 }
 
-impl<T: GossipTransport + 'static> SwimDetector<T> {
-    /// Create a new SWIM detector
-    pub fn new(probe_period: u64, suspect_timeout: u64, transport: Arc<T>) -> Self {
-        let detector = Self {
-            states: Arc::new(RwLock::new(HashMap::new())),
-            probe_period,
-            suspect_timeout,
+impl<T: GossipTransport + 'static> HyParViewMembership<T> {
+    /// Create a new HyParView membership manager
+    pub fn new(
+        local_peer: PeerId,
+        active_degree: usize,
+        passive_degree: usize,
+        transport: Arc<T>,
+    ) -> Self {
+        Self::with_config(
+            local_peer,
+            HyParViewConfig::new(active_degree, passive_degree),
+            transport,
+        )
+    }
+
+    /// Create a new HyParView membership manager with custom parameters
+    pub fn with_config(local_peer: PeerId, config: HyParViewConfig, transport: Arc<T>) -> Self {
+        let swim = SwimDetector::new(
+            local_peer,
+            SWIM_PROBE_INTERVAL_SECS,
+            SWIM_SUSPECT_TIMEOUT_SECS,
+            transport.clone(),
+        );
+        let membership = Self {
+            local_peer,
+            active: Arc::new(RwLock::new(HashSet::new())),
+            passive: Arc::new(RwLock::new(HashSet::new())),
+            addrs: Arc::new(RwLock::new(HashMap::new())),
+            seen: Arc::new(RwLock::new(HashMap::new())),
+            scores: Arc::new(RwLock::new(HashMap::new())),
+            pending_neighbors: Arc::new(RwLock::new(HashMap::new())),
+            pending_join: Arc::new(RwLock::new(None)),
+            shuffle_sent: Arc::new(RwLock::new(None)),
+            events: swim.event_sender(),
+            swim,
+            config,
             transport,
         };
 
-        // Start background probing task
-        detector.spawn_probe_task();
-        detector.spawn_suspect_timeout_task();
+        // Start background shuffle task
+        membership.spawn_shuffle_task();
+        membership.spawn_degree_maintenance_task();
+        membership.spawn_transport_event_task();
 
-        detector
+        membership
     }
 
-    /// Mark a peer as alive
-    pub async fn mark_alive(&self, peer: PeerId) {
-        let mut states = self.states.write().await;
-        states.insert(
-            peer,
-            SwimPeerEntry {
-                state: PeerState::Alive,
-                last_update: Instant::now(),
-            },
-        );
-        trace!(peer_id = %peer, "SWIM: Marked peer as alive");
+    /// Get the local peer ID
+    pub fn local_peer(&self) -> PeerId {
+        self.local_peer
     }
 
-    /// Mark a peer as suspect
-    pub async fn mark_suspect(&self, peer: PeerId) {
-        let mut states = self.states.write().await;
-        if let Some(entry) = states.get_mut(&peer) {
-            if entry.state == PeerState::Alive {
-                entry.state = PeerState::Suspect;
-                entry.last_update = Instant::now();
-                debug!(peer_id = %peer, "SWIM: Marked peer as suspect");
+    /// Get the SWIM detector
+    pub fn swim(&self) -> &SwimDetector<T> {
+        &self.swim
+    }
+
+    /// Set the score the neighbour selector sees for `peer`
+    ///
+    /// Higher is better; unscored peers count as 0. Only peers in either
+    /// view are scored, and scores are dropped with the peer once it leaves
+    /// both views. Returns whether the score was recorded.
+    pub async fn set_peer_score(&self, peer: PeerId, score: f64) -> bool {
+        // Held across the check so a concurrent eviction cannot leave the score behind
+        let mut scores = self.scores.write().await;
+        let known =
+            self.active.read().await.contains(&peer) || self.passive.read().await.contains(&peer);
+        if known {
+            scores.insert(peer, score);
+        }
+        known
+    }
+
+    /// Handle a frame received from `from` on [`StreamType::Membership`]
+    ///
+    /// This drives both protocols: feed it every frame received on the
+    /// stream, and it routes each to HyParView or SWIM. Frames of protocols
+    /// this version does not know are skipped.
+    pub async fn handle_message(&self, from: PeerId, data: &[u8]) -> Result<()> {
+        let message = MembershipMessage::from_bytes(data)
+            .map_err(|e| anyhow!("Malformed membership message from {}: {}", from, e))?;
+        if let Some(seen) = self.seen.write().await.get_mut(&from) {
+            *seen = unix_time_millis();
+        }
+
+        match message {
+            MembershipMessage::HyParView(message) => self.handle_hyparview(from, message).await,
+            MembershipMessage::Swim(message) => self.swim.handle_message(from, message).await,
+            MembershipMessage::Unknown => {
+                debug!(peer_id = %from, "Skipping membership message of unknown kind");
+                Ok(())
             }
         }
     }
 
-    /// Mark a peer as dead
-    pub async fn mark_dead(&self, peer: PeerId) {
-        let mut states = self.states.write().await;
-        states.insert(
-            peer,
-            SwimPeerEntry {
-                state: PeerState::Dead,
-                last_update: Instant::now(),
-            },
-        );
-        warn!(peer_id = %peer, "SWIM: Marked peer as dead");
+    /// Spawn a task feeding frames the transport receives to [`Self::handle_message`]
+    ///
+    /// The task owns the transport's receive loop. Frames on other streams
+    /// are handed on through the returned receiver; while it is full the
+    /// task waits, and once it is dropped they are discarded. The task stops
+    /// once the transport closes.
+    pub fn spawn_receive_task(
+        &self,
+    ) -> (JoinHandle<()>, mpsc::Receiver<(PeerId, StreamType, Bytes)>) {
+        let membership = self.clone();
+        let (passthrough, receiver) = mpsc::channel(PASSTHROUGH_CHANNEL_CAPACITY);
+
+        let task = tokio::spawn(async move {
+            while let Ok((from, stream_type, data)) = membership.transport.receive_message().await {
+                if stream_type != StreamType::Membership {
+                    if passthrough.send((from, stream_type, data)).await.is_err() {
+                        trace!(peer_id = %from, ?stream_type, "Dropping non-membership frame");
+                    }
+                    continue;
+                }
+                if let Err(e) = membership.handle_message(from, &data).await {
+                    debug!(peer_id = %from, "Failed to handle membership message: {}", e);
+                }
+            }
+            debug!("Membership receive task stopped: transport closed");
+        });
+        (task, receiver)
     }
 
-    /// Get the state of a peer
-    pub async fn get_state(&self, peer: &PeerId) -> Option<PeerState> {
-        let states = self.states.read().await;
-        states.get(peer).map(|entry| entry.state)
+    /// Handle a HyParView message received from `from`
+    async fn handle_hyparview(&self, from: PeerId, message: HyParViewMessage) -> Result<()> {
+        trace!(peer_id = %from, ?message, "HyParView: Received message");
+
+        match message {
+            HyParViewMessage::Join(joiner) => self.handle_join(from, joiner).await,
+            HyParViewMessage::ForwardJoin { joiner, addr, ttl } => {
+                self.handle_forward_join(from, joiner, addr, ttl).await
+            }
+            HyParViewMessage::ForwardJoinReply => {
+                if !self.expect_join_reply().await {
+                    debug!(peer_id = %from, "HyParView: Ignoring unsolicited FORWARDJOIN reply");
+                    return Ok(());
+                }
+                self.learn_addr(from).await;
+                self.add_node_active(from).await;
+                Ok(())
+            }
+            HyParViewMessage::Neighbor(priority) => self.handle_neighbor(from, priority).await,
+            HyParViewMessage::NeighborReply { accepted } => {
+                self.handle_neighbor_reply(from, accepted).await;
+                Ok(())
+            }
+            HyParViewMessage::Shuffle { origin, ttl, peers } => {
+                self.handle_shuffle(from, origin, ttl, peers).await
+            }
+            HyParViewMessage::ShuffleReply { mut peers } => {
+                // The walk may end anywhere, so any one reply answers our shuffle
+                let Some(mut sent) = self.shuffle_sent.write().await.take() else {
+                    debug!(peer_id = %from, "HyParView: Ignoring unsolicited SHUFFLE reply");
+                    return Ok(());
+                };
+                peers.truncate(self.shuffle_size());
+                self.merge_passive(peers, &mut sent).await;
+                Ok(())
+            }
+            HyParViewMessage::Disconnect => {
+                if self.demote(from).await {
+                    debug!(peer_id = %from, "HyParView: Neighbour disconnected us");
+                    self.request_neighbor().await;
+                }
+                Ok(())
+            }
+        }
     }
 
-    /// Get all peers in a specific state
-    pub async fn get_peers_in_state(&self, state: PeerState) -> Vec<PeerId> {
-        let states = self.states.read().await;
-        states
+    /// Take a joining node as neighbour and announce it to the overlay
+    async fn handle_join(&self, from: PeerId, joiner: PeerId) -> Result<()> {
+        if joiner != from {
+            return Err(anyhow!("JOIN from {} on behalf of {}", from, joiner));
+        }
+
+        let addr = self.learn_addr(from).await;
+        self.add_node_active(from).await;
+
+        let forward = HyParViewMessage::ForwardJoin {
+            joiner,
+            addr,
+            ttl: self.config.active_walk_length,
+        };
+        let neighbours: Vec<PeerId> = self
+            .active
+            .read()
+            .await
             .iter()
-            .filter(|(_, entry)| entry.state == state)
-            .map(|(peer, _)| *peer)
-            .collect()
-    }
+            .filter(|&&peer| peer != joiner)
+            .copied()
+            .collect();
+        for peer in neighbours {
+            if let Err(e) = self.send(peer, &forward).await {
+                debug!(peer_id = %peer, "HyParView: Failed to forward JOIN: {}", e);
+            }
+        }
 
-    /// Remove a peer from tracking
-    pub async fn remove_peer(&self, peer: &PeerId) {
-        let mut states = self.states.write().await;
-        states.remove(peer);
+        debug!(peer_id = %joiner, "HyParView: Node joined through us");
+        Ok(())
     }
 
-    /// Get the probe period
-    pub fn probe_period(&self) -> u64 {
-        self.probe_period
-    }
+    /// Continue a FORWARDJOIN random walk, or end it here
+    async fn handle_forward_join(
+        &self,
+        from: PeerId,
+        joiner: PeerId,
+        addr: Option<SocketAddr>,
+        ttl: usize,
+    ) -> Result<()> {
+        if joiner == self.local_peer {
+            return Ok(());
+        }
+        if let Some(addr) = addr {
+            self.addrs.write().await.entry(joiner).or_insert(addr);
+        }
 
-    /// Get the suspect timeout
-    pub fn suspect_timeout(&self) -> u64 {
-        self.suspect_timeout
+        let next = {
+            let active = self.active.read().await;
+            if ttl == 0 || active.len() <= 1 {
+                None
+            } else {
+                active
+                    .iter()
+                    .filter(|&&peer| peer != from && peer != joiner)
+                    .copied()
+                    .choose(&mut rand::thread_rng())
+            }
+        };
+        let Some(next) = next else {
+            return self.accept_joiner(joiner).await;
+        };
+
+        if ttl == self.config.passive_walk_length {
+            self.add_node_passive(joiner).await;
+        }
+        let forward = HyParViewMessage::ForwardJoin {
+            joiner,
+            addr,
+            ttl: ttl - 1,
+        };
+        self.send(next, &forward).await
     }
 
-    /// Spawn background task to probe random peers
-    fn spawn_probe_task(&self) {
-        let states = self.states.clone();
-        let probe_period = self.probe_period;
-        let transport = self.transport.clone();
+    /// End a FORWARDJOIN walk by taking the joining node as neighbour
+    async fn accept_joiner(&self, joiner: PeerId) -> Result<()> {
+        if !self.add_node_active(joiner).await {
+            return Ok(());
+        }
 
-        tokio::spawn(async move {
-            let mut interval = time::interval(Duration::from_secs(probe_period));
+        if let Err(e) = self
+            .dial_and_send(joiner, &HyParViewMessage::ForwardJoinReply)
+            .await
+        {
+            self.remove_node_active(joiner).await;
+            self.forget_if_unknown(joiner).await;
+            return Err(anyhow!("Failed to reach joining node {}: {}", joiner, e));
+        }
+        debug!(peer_id = %joiner, "HyParView: Accepted forwarded JOIN");
+        Ok(())
+    }
 
-            loop {
-                interval.tick().await;
+    /// Count a FORWARDJOIN reply against our last JOIN
+    ///
+    /// Each JOIN starts one walk per neighbour of the contact, so at most
+    /// the active degree of replies are accepted, and only for a while.
+    /// Returns whether the reply was expected.
+    async fn expect_join_reply(&self) -> bool {
+        let mut pending = self.pending_join.write().await;
+        match pending.as_mut() {
+            Some((sent, remaining))
+                if *remaining > 0 && sent.elapsed() < FORWARD_JOIN_REPLY_TIMEOUT =>
+            {
+                *remaining -= 1;
+                true
+            }
+            _ => {
+                *pending = None;
+                false
+            }
+        }
+    }
 
-                let states_guard = states.read().await;
-                let alive_peers: Vec<PeerId> = states_guard
-                    .iter()
-                    .filter(|(_, entry)| entry.state == PeerState::Alive)
-                    .map(|(peer, _)| *peer)
-                    .collect();
-                drop(states_guard);
-
-                if let Some(&peer) = alive_peers.first() {
-                    // Send PING to peer via transport
-                    trace!(peer_id = %peer, "SWIM: Probing peer");
-                    let ping_msg = SwimMessage::Ping;
-                    if let Ok(bytes) = bincode::serialize(&ping_msg) {
-                        let _ = transport
-                            .send_to_peer(peer, StreamType::Membership, bytes.into())
-                            .await;
-                    }
-                    // Note: Response handling would mark peer alive/suspect
-                    // For now, we'll rely on manual state updates
+    /// Answer a NEIGHBOR request according to its priority
+    async fn handle_neighbor(&self, from: PeerId, priority: NeighborPriority) -> Result<()> {
+        self.learn_addr(from).await;
+
+        let accepted = {
+            let active = self.active.read().await;
+            match priority {
+                NeighborPriority::High => true,
+                NeighborPriority::Low => {
+                    active.contains(&from) || active.len() < self.config.active_degree
                 }
             }
-        });
+        };
+        if accepted {
+            self.add_node_active(from).await;
+        }
+
+        debug!(peer_id = %from, ?priority, accepted, "HyParView: NEIGHBOR request");
+        self.send(from, &HyParViewMessage::NeighborReply { accepted })
+            .await
     }
 
-    /// Spawn background task to check suspect timeouts
-    fn spawn_suspect_timeout_task(&self) {
-        let states = self.states.clone();
-        let suspect_timeout = self.suspect_timeout;
+    /// Promote a peer that accepted our NEIGHBOR request
+    async fn handle_neighbor_reply(&self, from: PeerId, accepted: bool) {
+        if self.pending_neighbors.write().await.remove(&from).is_none() {
+            debug!(peer_id = %from, "HyParView: Ignoring unsolicited NEIGHBOR reply");
+            return;
+        }
 
-        tokio::spawn(async move {
-            let mut interval = time::interval(Duration::from_secs(1));
+        if accepted {
+            self.add_node_active(from).await;
+        } else {
+            debug!(peer_id = %from, "HyParView: NEIGHBOR request declined");
+        }
+    }
 
-            loop {
-                interval.tick().await;
+    /// Ask a random passive peer to become an active neighbour
+    ///
+    /// The request has high priority when the active view is empty, so a
+    /// node is never left isolated. Passive peers that cannot be reached are
+    /// dropped. Returns the peer asked, if any.
+    async fn request_neighbor(&self) -> Option<PeerId> {
+        let priority = if self.active.read().await.is_empty() {
+            NeighborPriority::High
+        } else {
+            NeighborPriority::Low
+        };
 
-                let mut states_guard = states.write().await;
-                let now = Instant::now();
-
-                // Find suspects that have timed out
-                let mut to_mark_dead = Vec::new();
-                for (peer, entry) in states_guard.iter() {
-                    if entry.state == PeerState::Suspect {
-                        let elapsed = now.duration_since(entry.last_update);
-                        if elapsed > Duration::from_secs(suspect_timeout) {
-                            to_mark_dead.push(*peer);
-                        }
-                    }
+        loop {
+            let peer = self.select_neighbor().await?;
+
+            self.pending_neighbors
+                .write()
+                .await
+                .insert(peer, Instant::now());
+            match self
+                .dial_and_send(peer, &HyParViewMessage::Neighbor(priority))
+                .await
+            {
+                Ok(()) => {
+                    debug!(peer_id = %peer, ?priority, "HyParView: Sent NEIGHBOR request");
+                    return Some(peer);
                 }
-
-                // Mark timed-out suspects as dead
-                for peer in to_mark_dead {
-                    states_guard.insert(
-                        peer,
-                        SwimPeerEntry {
-                            state: PeerState::Dead,
-                            last_update: now,
-                        },
-                    );
-                    warn!(peer_id = %peer, "SWIM: Suspect timeout → marked dead");
+                Err(e) => {
+                    debug!(peer_id = %peer, "HyParView: Passive peer unreachable: {}", e);
+                    self.pending_neighbors.write().await.remove(&peer);
+                    self.passive.write().await.remove(&peer);
+                    self.forget_if_unknown(peer).await;
                 }
             }
-        });
+        }
     }
-}
 
-/// HyParView membership implementation
-pub struct HyParViewMembership<T: GossipTransport + 'static> {
-    /// Active view (for routing)
-    active: Arc<RwLock<HashSet<PeerId>>>,
-    /// Passive view (for healing)
-    passive: Arc<RwLock<HashSet<PeerId>>>,
-    /// SWIM failure detector
-    swim: SwimDetector<T>,
-    /// Active view degree
-    active_degree: usize,
-    /// Passive view degree
-    passive_degree: usize,
-    /// Transport layer for sending messages
-    transport: Arc<T>,
-}
+    /// Let the selector pick a passive peer not already asked
+    async fn select_neighbor(&self) -> Option<PeerId> {
+        let passive: Vec<PeerId> = {
+            let passive = self.passive.read().await;
+            let pending = self.pending_neighbors.read().await;
+            passive
+                .iter()
+                .filter(|peer| !pending.contains_key(peer))
+                .copied()
+                .collect()
+        };
+        if passive.is_empty() {
+            return None;
+        }
+        let active: Vec<PeerId> = self.active.read().await.iter().copied().collect();
+
+        let candidates = self.candidates(&passive).await;
+        let active = self.candidates(&active).await;
+        self.config
+            .selector
+            .select(&candidates, &active)
+            .filter(|peer| passive.contains(peer))
+    }
 
-impl<T: GossipTransport + 'static> HyParViewMembership<T> {
-    /// Create a new HyParView membership manager
-    pub fn new(active_degree: usize, passive_degree: usize, transport: Arc<T>) -> Self {
-        let membership = Self {
-            active: Arc::new(RwLock::new(HashSet::new())),
-            passive: Arc::new(RwLock::new(HashSet::new())),
-            swim: SwimDetector::new(
-                SWIM_PROBE_INTERVAL_SECS,
-                SWIM_SUSPECT_TIMEOUT_SECS,
-                transport.clone(),
-            ),
-            active_degree,
-            passive_degree,
-            transport,
+    /// Gather what the neighbour selector knows about `peers`
+    async fn candidates(&self, peers: &[PeerId]) -> Vec<NeighborCandidate> {
+        let mut candidates = Vec::with_capacity(peers.len());
+        for &peer in peers {
+            let rtt = self.transport.peer_rtt(&peer).await;
+            candidates.push(NeighborCandidate {
+                peer,
+                addr: None,
+                rtt,
+                score: 0.0,
+            });
+        }
+
+        let addrs = self.addrs.read().await;
+        let scores = self.scores.read().await;
+        for candidate in &mut candidates {
+            candidate.addr = addrs.get(&candidate.peer).copied();
+            candidate.score = scores.get(&candidate.peer).copied().unwrap_or_default();
+        }
+        candidates
+    }
+
+    /// Add `peer` to the active view, dropping a random neighbour if full
+    ///
+    /// The dropped neighbour is sent a DISCONNECT and kept in the passive
+    /// view. Returns whether `peer` was newly added.
+    async fn add_node_active(&self, peer: PeerId) -> bool {
+        if peer == self.local_peer {
+            return false;
+        }
+
+        let dropped = {
+            let mut active = self.active.write().await;
+            if active.contains(&peer) {
+                return false;
+            }
+            let dropped = if active.len() >= self.config.active_degree {
+                active.iter().copied().choose(&mut rand::thread_rng())
+            } else {
+                None
+            };
+            if let Some(dropped) = dropped {
+                active.remove(&dropped);
+            }
+            active.insert(peer);
+            dropped
         };
+        self.passive.write().await.remove(&peer);
+        self.pending_neighbors.write().await.remove(&peer);
+        self.seen.write().await.insert(peer, unix_time_millis());
 
-        // Start background shuffle task
-        membership.spawn_shuffle_task();
-        membership.spawn_degree_maintenance_task();
+        if let Some(dropped) = dropped {
+            self.evicted_from_active(dropped).await;
+        }
 
-        membership
+        self.swim.mark_alive(peer).await;
+        self.emit(MembershipEvent::NeighborUp(peer));
+        debug!(peer_id = %peer, "Added to active view");
+        true
     }
 
-    /// Get the SWIM detector
-    pub fn swim(&self) -> &SwimDetector<T> {
-        &self.swim
+    /// Finish evicting `peer`, already removed from the active view
+    ///
+    /// It is sent a DISCONNECT and kept in the passive view.
+    async fn evicted_from_active(&self, peer: PeerId) {
+        self.emit(MembershipEvent::NeighborDown(peer));
+        self.add_node_passive(peer).await;
+        debug!(peer_id = %peer, "Demoted to passive (active view full)");
+        if let Err(e) = self.send(peer, &HyParViewMessage::Disconnect).await {
+            debug!(peer_id = %peer, "HyParView: Failed to send DISCONNECT: {}", e);
+        }
     }
 
-    /// Shuffle the passive view with a random peer
-    pub async fn shuffle(&self) -> Result<()> {
-        let active = self.active.read().await;
-        let passive = self.passive.read().await;
+    /// Remove `peer` from the active view
+    ///
+    /// Returns whether it was active.
+    async fn remove_node_active(&self, peer: PeerId) -> bool {
+        if !self.active.write().await.remove(&peer) {
+            return false;
+        }
+        self.emit(MembershipEvent::NeighborDown(peer));
+        true
+    }
 
-        if active.is_empty() {
-            return Ok(());
+    /// Notify subscribers, if any
+    fn emit(&self, event: MembershipEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Add `peer` to the passive view, evicting a random entry if full
+    async fn add_node_passive(&self, peer: PeerId) -> bool {
+        self.insert_passive(peer, &mut Vec::new()).await
+    }
+
+    /// Add `peer` to the passive view
+    ///
+    /// When the view is full, an entry of `evict_first` still in the view is
+    /// evicted, or a random one once those run out.
+    async fn insert_passive(&self, peer: PeerId, evict_first: &mut Vec<PeerId>) -> bool {
+        if peer == self.local_peer || self.active.read().await.contains(&peer) {
+            return false;
         }
 
-        // Select random active peer for shuffle
-        let target = *active
-            .iter()
-            .next()
-            .ok_or_else(|| anyhow!("No active peers"))?;
+        let evicted = {
+            let mut passive = self.passive.write().await;
+            if passive.contains(&peer) || self.config.passive_degree == 0 {
+                return false;
+            }
+            let evicted = if passive.len() >= self.config.passive_degree {
+                evict_first.retain(|candidate| passive.contains(candidate));
+                evict_first
+                    .pop()
+                    .or_else(|| passive.iter().copied().choose(&mut rand::thread_rng()))
+            } else {
+                None
+            };
+            if let Some(evicted) = evicted {
+                passive.remove(&evicted);
+            }
+            passive.insert(peer);
+            evicted
+        };
+        self.seen
+            .write()
+            .await
+            .entry(peer)
+            .or_insert_with(unix_time_millis);
+
+        if let Some(evicted) = evicted {
+            self.forget_if_unknown(evicted).await;
+            trace!(peer_id = %evicted, "Removed from passive view (over capacity)");
+        }
+        true
+    }
+
+    /// Merge peers learned from a shuffle into the passive view
+    ///
+    /// Peers in `evict_first`, the ones just offered to the other side, make
+    /// room before any others.
+    async fn merge_passive(&self, entries: Vec<PeerEntry>, evict_first: &mut Vec<PeerId>) {
+        for entry in entries {
+            if !self.insert_passive(entry.peer, evict_first).await {
+                continue;
+            }
+            if let Some(addr) = entry.addr {
+                self.addrs.write().await.entry(entry.peer).or_insert(addr);
+            }
+        }
+    }
 
-        // Select random subset of passive view to exchange
-        let exchange_size = (self.passive_degree / 4).max(1);
-        let to_exchange: Vec<PeerId> = passive.iter().take(exchange_size).copied().collect();
+    /// Move `peer` from the active to the passive view
+    ///
+    /// Returns whether it was active.
+    async fn demote(&self, peer: PeerId) -> bool {
+        if !self.remove_node_active(peer).await {
+            return false;
+        }
+        self.seen.write().await.insert(peer, unix_time_millis());
+        self.add_node_passive(peer).await;
+        true
+    }
 
-        drop(active);
-        drop(passive);
+    /// Record the transport's address for `peer`
+    async fn learn_addr(&self, peer: PeerId) -> Option<SocketAddr> {
+        let addr = self.transport.peer_addr(&peer).await?;
+        self.addrs.write().await.insert(peer, addr);
+        Some(addr)
+    }
+
+    /// Drop the address and liveness of `peer` unless it is in either view
+    async fn forget_if_unknown(&self, peer: PeerId) {
+        if self.active.read().await.contains(&peer) || self.passive.read().await.contains(&peer) {
+            return;
+        }
+        self.addrs.write().await.remove(&peer);
+        self.seen.write().await.remove(&peer);
+        self.scores.write().await.remove(&peer);
+    }
+
+    /// Serialize and send a HyParView message
+    async fn send(&self, peer: PeerId, message: &HyParViewMessage) -> Result<()> {
+        let bytes = MembershipMessage::HyParView(message.clone()).to_bytes()?;
+        self.transport
+            .send_to_peer(peer, StreamType::Membership, bytes)
+            .await
+    }
+
+    /// Connect to `peer` at its last-known address, then send a message
+    async fn dial_and_send(&self, peer: PeerId, message: &HyParViewMessage) -> Result<()> {
+        let addr = self.addrs.read().await.get(&peer).copied();
+        if let Some(addr) = addr {
+            self.transport.dial(peer, addr).await?;
+        }
+        self.send(peer, message).await
+    }
+
+    /// Contact a seed, take it as neighbour and send it a JOIN
+    async fn join_via(&self, addr: SocketAddr) -> Result<PeerId> {
+        let contact = self.transport.dial_bootstrap(addr).await?;
+        if contact == self.local_peer {
+            return Err(anyhow!("Seed {} is this node", addr));
+        }
+        self.addrs.write().await.insert(contact, addr);
+        self.add_node_active(contact).await;
+
+        // Replies may arrive before the send returns
+        *self.pending_join.write().await = Some((Instant::now(), self.config.active_degree));
+        if let Err(e) = self
+            .send(contact, &HyParViewMessage::Join(self.local_peer))
+            .await
+        {
+            self.remove_node_active(contact).await;
+            self.forget_if_unknown(contact).await;
+            return Err(e);
+        }
+        Ok(contact)
+    }
+
+    /// Shuffle the passive view with a random peer
+    ///
+    /// Sends this node and a random sample of its active and passive views on
+    /// a random walk starting at a random active peer. The node where the walk
+    /// ends replies with a sample of its own passive view.
+    pub async fn shuffle(&self) -> Result<()> {
+        let (target, sample) = {
+            let active = self.active.read().await;
+            let passive = self.passive.read().await;
+            let mut rng = rand::thread_rng();
+
+            let Some(target) = active.iter().copied().choose(&mut rng) else {
+                return Ok(());
+            };
+            let mut sample = active
+                .iter()
+                .copied()
+                .filter(|&peer| peer != target)
+                .choose_multiple(&mut rng, self.config.shuffle_active_sample);
+            sample.extend(
+                passive
+                    .iter()
+                    .copied()
+                    .choose_multiple(&mut rng, self.config.shuffle_passive_sample),
+            );
+            (target, sample)
+        };
 
         debug!(
             peer_id = %target,
-            exchange_count = to_exchange.len(),
+            exchange_count = sample.len(),
             "HyParView: Shuffling passive view"
         );
+        *self.shuffle_sent.write().await = Some(sample.clone());
+
+        // The first hop fills in the address we are reached at
+        let mut peers = vec![PeerEntry {
+            peer: self.local_peer,
+            addr: None,
+        }];
+        peers.extend(self.entries(&sample).await);
+        let message = HyParViewMessage::Shuffle {
+            origin: self.local_peer,
+            ttl: self.config.shuffle_walk_length,
+            peers,
+        };
+        self.send(target, &message).await
+    }
 
-        // Send SHUFFLE message to target peer via transport
-        let shuffle_msg = HyParViewMessage::Shuffle(to_exchange);
-        if let Ok(bytes) = bincode::serialize(&shuffle_msg) {
-            self.transport
-                .send_to_peer(target, StreamType::Membership, bytes.into())
-                .await?;
+    /// The most peers a shuffle or its reply carries: the samples and the origin
+    fn shuffle_size(&self) -> usize {
+        self.config.shuffle_active_sample + self.config.shuffle_passive_sample + 1
+    }
+
+    /// Continue a SHUFFLE walk, or end it here and reply to its origin
+    async fn handle_shuffle(
+        &self,
+        from: PeerId,
+        origin: PeerId,
+        ttl: usize,
+        mut peers: Vec<PeerEntry>,
+    ) -> Result<()> {
+        if origin == self.local_peer {
+            return Ok(());
+        }
+        peers.truncate(self.shuffle_size());
+        if from == origin {
+            let addr = self.transport.peer_addr(&origin).await;
+            for entry in peers.iter_mut().filter(|entry| entry.peer == origin) {
+                entry.addr = entry.addr.or(addr);
+            }
         }
-        // Note: Peer will respond with their own passive view subset
-        // We'll merge responses into our passive view via handle_shuffle_response()
 
-        Ok(())
+        let next = {
+            let active = self.active.read().await;
+            if ttl == 0 || active.len() <= 1 {
+                None
+            } else {
+                active
+                    .iter()
+                    .filter(|&&peer| peer != from && peer != origin)
+                    .copied()
+                    .choose(&mut rand::thread_rng())
+            }
+        };
+        if let Some(next) = next {
+            let forward = HyParViewMessage::Shuffle {
+                origin,
+                ttl: ttl - 1,
+                peers,
+            };
+            return self.send(next, &forward).await;
+        }
+
+        // The walk ends here: answer with as many passive peers as offered
+        let mut reply = {
+            let passive = self.passive.read().await;
+            passive
+                .iter()
+                .copied()
+                .filter(|&peer| peer != origin)
+                .choose_multiple(&mut rand::thread_rng(), peers.len())
+        };
+        let reply_entries = self.entries(&reply).await;
+        let origin_addr = peers
+            .iter()
+            .find(|entry| entry.peer == origin)
+            .and_then(|entry| entry.addr);
+        self.merge_passive(peers, &mut reply).await;
+
+        trace!(peer_id = %origin, "HyParView: Replying to shuffle");
+        if let Some(addr) = origin_addr {
+            self.transport.dial(origin, addr).await?;
+        }
+        self.send(
+            origin,
+            &HyParViewMessage::ShuffleReply {
+                peers: reply_entries,
+            },
+        )
+        .await
     }
 
-    /// Maintain active and passive view degrees
-    #[cfg(test)]
-    async fn maintain_degrees(&self) {
-        let mut active = self.active.write().await;
-        let mut passive = self.passive.write().await;
+    /// Attach last-known addresses to peers
+    async fn entries(&self, peers: &[PeerId]) -> Vec<PeerEntry> {
+        let addrs = self.addrs.read().await;
+        peers
+            .iter()
+            .map(|&peer| PeerEntry {
+                peer,
+                addr: addrs.get(&peer).copied(),
+            })
+            .collect()
+    }
 
-        // Enforce active degree limits (8-12)
-        if active.len() < DEFAULT_ACTIVE_DEGREE && !passive.is_empty() {
-            // Promote from passive
-            let to_promote = DEFAULT_ACTIVE_DEGREE - active.len();
-            let peers: Vec<PeerId> = passive.iter().take(to_promote).copied().collect();
-
-            for peer in peers {
-                passive.remove(&peer);
-                active.insert(peer);
-                debug!(peer_id = %peer, "Promoted from passive to active");
-            }
-        } else if active.len() > MAX_ACTIVE_DEGREE {
-            // Demote to passive
-            let to_demote = active.len() - MAX_ACTIVE_DEGREE;
-            let peers: Vec<PeerId> = active.iter().take(to_demote).copied().collect();
-
-            for peer in peers {
-                active.remove(&peer);
-                if passive.len() < MAX_PASSIVE_DEGREE {
-                    passive.insert(peer);
-                    debug!(peer_id = %peer, "Demoted from active to passive");
-                }
+    /// Shrink views that grew past their configured degrees
+    ///
+    /// Excess neighbours are chosen at random and evicted as when the active
+    /// view is full, so they are sent a DISCONNECT and kept in the passive
+    /// view; excess passive peers are dropped with their address and score.
+    async fn trim_views(&self) {
+        let demoted: Vec<PeerId> = {
+            let mut active = self.active.write().await;
+            let excess = active.len().saturating_sub(self.config.active_degree);
+            let demoted: Vec<PeerId> = active
+                .iter()
+                .copied()
+                .choose_multiple(&mut rand::thread_rng(), excess);
+            for peer in &demoted {
+                active.remove(peer);
             }
+            demoted
+        };
+        for peer in demoted {
+            self.evicted_from_active(peer).await;
         }
 
-        // Enforce passive degree limit (max 128)
-        if passive.len() > MAX_PASSIVE_DEGREE {
-            let to_remove = passive.len() - MAX_PASSIVE_DEGREE;
-            let peers: Vec<PeerId> = passive.iter().take(to_remove).copied().collect();
-
-            for peer in peers {
-                passive.remove(&peer);
-                trace!(peer_id = %peer, "Removed from passive view (over capacity)");
+        let removed: Vec<PeerId> = {
+            let mut passive = self.passive.write().await;
+            let excess = passive.len().saturating_sub(self.config.passive_degree);
+            let removed: Vec<PeerId> = passive
+                .iter()
+                .copied()
+                .choose_multiple(&mut rand::thread_rng(), excess);
+            for peer in &removed {
+                passive.remove(peer);
             }
+            removed
+        };
+        for peer in removed {
+            self.forget_if_unknown(peer).await;
+            trace!(peer_id = %peer, "Removed from passive view (over capacity)");
         }
     }
 
     /// Spawn background task for periodic shuffling
     fn spawn_shuffle_task(&self) {
-        let active = self.active.clone();
-        let passive = self.passive.clone();
+        let membership = self.clone();
 
         tokio::spawn(async move {
-            let mut interval = time::interval(Duration::from_secs(SHUFFLE_PERIOD_SECS));
+            // Views start empty, so the first shuffle waits a full period
+            let period = Duration::from_secs(SHUFFLE_PERIOD_SECS);
+            let mut interval = time::interval_at(time::Instant::now() + period, period);
 
             loop {
                 interval.tick().await;
 
-                let active_guard = active.read().await;
-                let passive_guard = passive.read().await;
-
-                if !active_guard.is_empty() {
-                    debug!(
-                        active_count = active_guard.len(),
-                        passive_count = passive_guard.len(),
-                        "HyParView: Periodic shuffle tick"
-                    );
+                if let Err(e) = membership.shuffle().await {
+                    debug!("HyParView: Periodic shuffle failed: {}", e);
                 }
+            }
+        });
+    }
+
+    /// Spawn background task reacting to transport connection events
+    ///
+    /// A peer whose connection drops leaves the active view immediately and is
+    /// kept in the passive view as a healing candidate, while a replacement is
+    /// requested from the passive view.
+    fn spawn_transport_event_task(&self) {
+        let membership = self.clone();
+        let mut events = self.transport.subscribe_events();
 
-                // TODO: Actual shuffle implementation requires transport
-                drop(active_guard);
-                drop(passive_guard);
+        tokio::spawn(async move {
+            loop {
+                let peer = match events.recv().await {
+                    Ok(TransportEvent::PeerDisconnected { peer, .. }) => peer,
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                if !membership.demote(peer).await {
+                    continue;
+                }
+                debug!(peer_id = %peer, "Peer disconnected: moved from active to passive view");
+                membership.request_neighbor().await;
             }
         });
     }
 
+    /// Ask passive peers to fill the free slots of the active view
+    async fn fill_active_view(&self) {
+        // Requests that were never answered free their slot
+        let pending = {
+            let mut pending = self.pending_neighbors.write().await;
+            pending.retain(|_, sent| sent.elapsed() < NEIGHBOR_REQUEST_TIMEOUT);
+            pending.len()
+        };
+
+        let active_count = self.active.read().await.len();
+        let missing = self
+            .config
+            .active_degree
+            .saturating_sub(active_count + pending);
+        for _ in 0..missing {
+            if self.request_neighbor().await.is_none() {
+                break;
+            }
+        }
+    }
+
     /// Spawn background task for degree maintenance
+    ///
+    /// Missing active neighbours are requested from the passive view; they
+    /// join the active view once they accept. Views past their configured
+    /// degrees are trimmed.
     fn spawn_degree_maintenance_task(&self) {
-        let active = self.active.clone();
-        let passive = self.passive.clone();
+        let membership = self.clone();
 
         tokio::spawn(async move {
             let mut interval = time::interval(Duration::from_secs(10));
@@ -437,50 +1184,8 @@ impl<T: GossipTransport + 'static> HyParViewMembership<T> {
             loop {
                 interval.tick().await;
 
-                let mut active_guard = active.write().await;
-                let mut passive_guard = passive.write().await;
-
-                let active_count = active_guard.len();
-                let passive_count = passive_guard.len();
-
-                // Promote from passive if active is low
-                if active_count < DEFAULT_ACTIVE_DEGREE && !passive_guard.is_empty() {
-                    let to_promote = DEFAULT_ACTIVE_DEGREE - active_count;
-                    let peers: Vec<PeerId> =
-                        passive_guard.iter().take(to_promote).copied().collect();
-
-                    for peer in peers {
-                        passive_guard.remove(&peer);
-                        active_guard.insert(peer);
-                        debug!(peer_id = %peer, "Degree maintenance: promoted to active");
-                    }
-                }
-
-                // Demote to passive if active is high
-                if active_count > MAX_ACTIVE_DEGREE {
-                    let to_demote = active_count - MAX_ACTIVE_DEGREE;
-                    let peers: Vec<PeerId> = active_guard.iter().take(to_demote).copied().collect();
-
-                    for peer in peers {
-                        active_guard.remove(&peer);
-                        if passive_guard.len() < MAX_PASSIVE_DEGREE {
-                            passive_guard.insert(peer);
-                            debug!(peer_id = %peer, "Degree maintenance: demoted to passive");
-                        }
-                    }
-                }
-
-                // Trim passive if over capacity
-                if passive_count > MAX_PASSIVE_DEGREE {
-                    let to_remove = passive_count - MAX_PASSIVE_DEGREE;
-                    let peers: Vec<PeerId> =
-                        passive_guard.iter().take(to_remove).copied().collect();
-
-                    for peer in peers {
-                        passive_guard.remove(&peer);
-                        trace!(peer_id = %peer, "Degree maintenance: removed from passive");
-                    }
-                }
+                membership.fill_active_view().await;
+                membership.trim_views().await;
             }
         });
     }
@@ -489,18 +1194,35 @@ impl<T: GossipTransport + 'static> HyParViewMembership<T> {
 #[async_trait::async_trait]
 impl<T: GossipTransport + 'static> Membership for HyParViewMembership<T> {
     async fn join(&self, seeds: Vec<String>) -> Result<()> {
-        // Parse seed addresses and add to active view
+        // Join through the first seed that answers; the FORWARDJOIN walks it
+        // starts fill the rest of the active view
+        let mut last_err = None;
         for seed in seeds {
-            // In a real implementation, we would:
-            // 1. Parse the seed address (SocketAddr)
-            // 2. Connect via transport
-            // 3. Send JOIN message
-            // 4. Receive FORWARDJOIN response with peer list
-            // 5. Add peers to active/passive views
+            let addr: SocketAddr = match seed.parse() {
+                Ok(addr) => addr,
+                Err(e) => {
+                    warn!(seed = %seed, "JOIN: Invalid seed address: {}", e);
+                    last_err = Some(anyhow!("Invalid seed address {}: {}", seed, e));
+                    continue;
+                }
+            };
 
-            debug!(seed = %seed, "JOIN: Attempting to join via seed (TODO: transport)");
+            match self.join_via(addr).await {
+                Ok(contact) => {
+                    debug!(seed = %seed, peer_id = %contact, "JOIN: Joined via seed");
+                    return Ok(());
+                }
+                Err(e) => {
+                    debug!(seed = %seed, "JOIN: Seed unreachable: {}", e);
+                    last_err = Some(e);
+                }
+            }
+        }
+
+        match last_err {
+            Some(e) => Err(anyhow!("Failed to join through any seed: {}", e)),
+            None => Ok(()),
         }
-        Ok(())
     }
 
     fn active_view(&self) -> Vec<PeerId> {
@@ -520,37 +1242,15 @@ impl<T: GossipTransport + 'static> Membership for HyParViewMembership<T> {
     }
 
     async fn add_active(&self, peer: PeerId) -> Result<()> {
-        let mut active = self.active.write().await;
-
-        // If active view is full, demote one peer to passive
-        if active.len() >= self.active_degree {
-            if let Some(&to_demote) = active.iter().next() {
-                active.remove(&to_demote);
-                // Move to passive view
-                let mut passive = self.passive.write().await;
-                if passive.len() < self.passive_degree {
-                    passive.insert(to_demote);
-                    debug!(peer_id = %to_demote, "Demoted to passive (active view full)");
-                }
-            }
-        }
-
-        active.insert(peer);
-        drop(active); // Release lock before async call
-
-        self.swim.mark_alive(peer).await;
-        debug!(peer_id = %peer, "Added to active view");
-
+        self.add_node_active(peer).await;
         Ok(())
     }
 
     async fn remove_active(&self, peer: PeerId) -> Result<()> {
-        let mut active = self.active.write().await;
-        let removed = active.remove(&peer);
-        drop(active);
-
-        if removed {
-            self.swim.mark_dead(peer).await;
+        if self.remove_node_active(peer).await {
+            // Removing a neighbour says nothing about its health, so
+            // nothing is gossiped; we just stop probing it
+            self.swim.remove_peer(&peer).await;
             debug!(peer_id = %peer, "Removed from active view");
         }
 
@@ -569,19 +1269,28 @@ impl<T: GossipTransport + 'static> Membership for HyParViewMembership<T> {
 
         Ok(())
     }
+
+    fn subscribe(&self) -> broadcast::Receiver<MembershipEvent> {
+        self.events.subscribe()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use saorsa_gossip_transport::{QuicTransport, TransportConfig};
+    use saorsa_gossip_transport::{MemoryNetwork, MemoryTransport, QuicTransport, TransportConfig};
 
     fn test_transport() -> Arc<QuicTransport> {
         Arc::new(QuicTransport::new(TransportConfig::default()))
     }
 
+    fn local_peer() -> PeerId {
+        PeerId::new([0xAA; 32])
+    }
+
     fn test_membership() -> HyParViewMembership<QuicTransport> {
         HyParViewMembership::new(
+            local_peer(),
             DEFAULT_ACTIVE_DEGREE,
             DEFAULT_PASSIVE_DEGREE,
             test_transport(),
@@ -616,12 +1325,36 @@ mod tests {
 
         let active = membership.active_view();
         assert_eq!(active.len(), 0);
+
+        // SWIM stops tracking the peer rather than declaring it dead
+        assert_eq!(membership.swim().get_state(&peer).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_reports_neighbour_changes() {
+        let membership = test_membership();
+        let mut events = membership.subscribe();
+        let peer = PeerId::new([1u8; 32]);
+
+        membership.add_active(peer).await.expect("add");
+        membership.add_active(peer).await.expect("add again");
+        membership.remove_active(peer).await.expect("remove");
+
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(MembershipEvent::NeighborUp(peer))
+        );
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(MembershipEvent::NeighborDown(peer))
+        );
+        assert!(events.try_recv().is_err());
     }
 
     #[tokio::test]
     async fn test_active_view_capacity() {
         let transport = test_transport();
-        let membership = HyParViewMembership::new(3, 10, transport);
+        let membership = HyParViewMembership::new(local_peer(), 3, 10, transport);
 
         // Add 5 peers (more than capacity)
         for i in 0..5 {
@@ -638,38 +1371,6 @@ mod tests {
         assert_eq!(passive.len(), 2);
     }
 
-    #[tokio::test]
-    async fn test_swim_states() {
-        let transport = test_transport();
-        let swim = SwimDetector::new(1, 3, transport);
-        let peer = PeerId::new([1u8; 32]);
-
-        swim.mark_alive(peer).await;
-        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Alive));
-
-        swim.mark_suspect(peer).await;
-        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Suspect));
-
-        swim.mark_dead(peer).await;
-        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Dead));
-    }
-
-    #[tokio::test]
-    async fn test_swim_suspect_timeout() {
-        let transport = test_transport();
-        let swim = SwimDetector::new(1, 1, transport); // 1s timeout
-        let peer = PeerId::new([1u8; 32]);
-
-        swim.mark_alive(peer).await;
-        swim.mark_suspect(peer).await;
-
-        // Wait for timeout
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-
-        // Should be marked dead automatically
-        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Dead));
-    }
-
     #[tokio::test]
     async fn test_promote_from_passive() {
         let membership = test_membership();
@@ -693,49 +1394,514 @@ mod tests {
 
     #[tokio::test]
     async fn test_degree_maintenance() {
-        let transport = test_transport();
-        let membership = HyParViewMembership::new(5, 20, transport);
+        let network = MemoryNetwork::new();
+        let (node, _) = memory_node(&network, 1, HyParViewConfig::new(5, 20)).await;
+        let mut peers = Vec::new();
+        for id in 10..25 {
+            let (peer, _) = memory_node(&network, id, HyParViewConfig::default()).await;
+            node.add_node_passive(peer.local_peer()).await;
+            peers.push(peer);
+        }
 
-        // Add many peers to passive
-        for i in 0..15 {
-            let peer = PeerId::new([i; 32]);
-            let mut passive = membership.passive.write().await;
-            passive.insert(peer);
+        // Free slots are requested from the passive view and filled as
+        // peers accept
+        node.fill_active_view().await;
+        eventually(|| node.active_view().len() == 5).await;
+        assert_eq!(node.passive_view().len(), 10);
+
+        // A view pushed past its degree is trimmed into the passive view
+        for id in 30..33 {
+            node.active.write().await.insert(PeerId::new([id; 32]));
+        }
+        node.trim_views().await;
+        assert_eq!(node.active_view().len(), 5);
+        assert_eq!(node.passive_view().len(), 13);
+    }
+
+    /// A node on a memory network whose inbound messages drive its membership
+    async fn memory_node(
+        network: &MemoryNetwork,
+        id: u8,
+        config: HyParViewConfig,
+    ) -> (HyParViewMembership<MemoryTransport>, Arc<MemoryTransport>) {
+        let peer = PeerId::new([id; 32]);
+        let transport = Arc::new(network.add_node(peer).await.expect("node"));
+        let membership = HyParViewMembership::with_config(peer, config, transport.clone());
+        membership.spawn_receive_task();
+        (membership, transport)
+    }
+
+    async fn eventually(condition: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
         }
+        panic!("condition not reached in time");
+    }
 
-        // Run maintenance
-        membership.maintain_degrees().await;
+    /// The next HyParView message, skipping SWIM probes
+    async fn next_message(transport: &MemoryTransport) -> HyParViewMessage {
+        loop {
+            let (_, _, data) = transport.receive_message().await.expect("message");
+            if let MembershipMessage::HyParView(message) =
+                MembershipMessage::from_bytes(&data).expect("decode")
+            {
+                return message;
+            }
+        }
+    }
 
-        // Should have promoted some to active
-        let active = membership.active_view();
-        assert!(active.len() >= 5);
-        assert!(active.len() <= 12);
+    fn frame(message: HyParViewMessage) -> Bytes {
+        MembershipMessage::HyParView(message)
+            .to_bytes()
+            .expect("serialize")
+    }
+
+    fn set(peers: Vec<PeerId>) -> HashSet<PeerId> {
+        peers.into_iter().collect()
     }
 
     #[tokio::test]
-    async fn test_get_peers_in_state() {
-        let transport = test_transport();
-        let swim = SwimDetector::new(1, 100, transport); // Long timeout so background task doesn't interfere
+    async fn test_join_walk_ends_at_isolated_neighbour() {
+        let network = MemoryNetwork::new();
+        let (a, transport_a) = memory_node(&network, 1, HyParViewConfig::default()).await;
+        let (b, _) = memory_node(&network, 2, HyParViewConfig::default()).await;
+        let (c, _) = memory_node(&network, 3, HyParViewConfig::default()).await;
+        let seed = vec![transport_a.local_addr().to_string()];
+
+        b.join(seed.clone()).await.expect("b joins");
+        eventually(|| a.active_view() == vec![b.local_peer()]).await;
+        assert_eq!(b.active_view(), vec![a.local_peer()]);
+
+        // a forwards c's JOIN to b, whose only neighbour is a, so the walk
+        // ends there and b takes c as neighbour too
+        c.join(seed).await.expect("c joins");
+        let all_but = |node: &HyParViewMembership<MemoryTransport>| {
+            set([a.local_peer(), b.local_peer(), c.local_peer()]
+                .into_iter()
+                .filter(|&peer| peer != node.local_peer())
+                .collect())
+        };
+        eventually(|| {
+            [&a, &b, &c]
+                .iter()
+                .all(|node| set(node.active_view()) == all_but(node))
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_unsolicited_forward_join_reply_is_ignored() {
+        let network = MemoryNetwork::new();
+        let (node, _) = memory_node(&network, 1, HyParViewConfig::new(1, 8)).await;
+        let (seed, seed_transport) = memory_node(&network, 2, HyParViewConfig::default()).await;
+        let stranger = PeerId::new([3u8; 32]);
+
+        node.handle_message(stranger, &frame(HyParViewMessage::ForwardJoinReply))
+            .await
+            .expect("handle");
+        assert!(node.active_view().is_empty());
+
+        // After a JOIN, replies are accepted up to the active degree
+        node.join(vec![seed_transport.local_addr().to_string()])
+            .await
+            .expect("join");
+        assert_eq!(node.active_view(), vec![seed.local_peer()]);
+        node.handle_message(stranger, &frame(HyParViewMessage::ForwardJoinReply))
+            .await
+            .expect("handle");
+        assert_eq!(node.active_view(), vec![stranger]);
+
+        let late = PeerId::new([4u8; 32]);
+        node.handle_message(late, &frame(HyParViewMessage::ForwardJoinReply))
+            .await
+            .expect("handle");
+        assert_eq!(node.active_view(), vec![stranger]);
+    }
+
+    #[tokio::test]
+    async fn test_forward_join_enters_passive_view_and_continues() {
+        let network = MemoryNetwork::new();
+        let (node, _) = memory_node(&network, 1, HyParViewConfig::default()).await;
+        let (_, transport_2) = memory_node(&network, 2, HyParViewConfig::default()).await;
+        let neighbour = PeerId::new([3u8; 32]);
+        let neighbour_transport = network.add_node(neighbour).await.expect("neighbour");
+        let joiner = PeerId::new([4u8; 32]);
+        node.add_active(transport_2.peer_id()).await.expect("add");
+        node.add_active(neighbour).await.expect("add");
+
+        let forward = HyParViewMessage::ForwardJoin {
+            joiner,
+            addr: None,
+            ttl: PASSIVE_RANDOM_WALK_LENGTH,
+        };
+        node.handle_message(transport_2.peer_id(), &frame(forward))
+            .await
+            .expect("handle");
+
+        assert_eq!(node.passive_view(), vec![joiner]);
+        assert!(!node.active_view().contains(&joiner));
+
+        // The walk continues to the only other neighbour with one hop less
+        let (from, stream_type, data) = neighbour_transport.receive_message().await.expect("walk");
+        assert_eq!(
+            (from, stream_type),
+            (node.local_peer(), StreamType::Membership)
+        );
+        assert_eq!(
+            MembershipMessage::from_bytes(&data).expect("decode"),
+            MembershipMessage::HyParView(HyParViewMessage::ForwardJoin {
+                joiner,
+                addr: None,
+                ttl: PASSIVE_RANDOM_WALK_LENGTH - 1,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_neighbor_priority() {
+        let network = MemoryNetwork::new();
+        let config = HyParViewConfig::new(1, 8);
+        let (node, _) = memory_node(&network, 1, config).await;
+        let existing = PeerId::new([2u8; 32]);
+        let existing_transport = network.add_node(existing).await.expect("existing");
+        let requester = PeerId::new([3u8; 32]);
+        let requester_transport = network.add_node(requester).await.expect("requester");
+        node.add_active(existing).await.expect("add");
+
+        // A full active view turns low-priority requests away
+        let low = frame(HyParViewMessage::Neighbor(NeighborPriority::Low));
+        node.handle_message(requester, &low).await.expect("handle");
+        assert_eq!(
+            next_message(&requester_transport).await,
+            HyParViewMessage::NeighborReply { accepted: false }
+        );
+        assert_eq!(node.active_view(), vec![existing]);
+
+        // High priority displaces a neighbour, which is told to go passive
+        let high = frame(HyParViewMessage::Neighbor(NeighborPriority::High));
+        node.handle_message(requester, &high).await.expect("handle");
+        assert_eq!(
+            next_message(&requester_transport).await,
+            HyParViewMessage::NeighborReply { accepted: true }
+        );
+        assert_eq!(node.active_view(), vec![requester]);
+        assert_eq!(node.passive_view(), vec![existing]);
+        assert_eq!(
+            next_message(&existing_transport).await,
+            HyParViewMessage::Disconnect
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_heals_from_passive_view() {
+        let network = MemoryNetwork::new();
+        let (node, _) = memory_node(&network, 1, HyParViewConfig::new(1, 8)).await;
+        let (neighbour, _) = memory_node(&network, 2, HyParViewConfig::default()).await;
+        let (spare, _) = memory_node(&network, 3, HyParViewConfig::default()).await;
+        node.add_active(neighbour.local_peer()).await.expect("add");
+        node.add_node_passive(spare.local_peer()).await;
+
+        // Losing the last neighbour sends a high-priority NEIGHBOR request,
+        // which the spare has to accept
+        node.handle_message(neighbour.local_peer(), &frame(HyParViewMessage::Disconnect))
+            .await
+            .expect("handle");
+        eventually(|| node.active_view().len() == 1).await;
+
+        let healed = node.active_view()[0];
+        assert!(healed == neighbour.local_peer() || healed == spare.local_peer());
+        assert_eq!(node.passive_view().len(), 1);
+        assert!(node.pending_neighbors.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_join_fails_without_reachable_seed() {
+        let membership = memory_node(&MemoryNetwork::new(), 1, HyParViewConfig::default())
+            .await
+            .0;
+        assert!(membership.join(Vec::new()).await.is_ok());
+        assert!(membership
+            .join(vec![
+                "not an address".to_string(),
+                "127.0.0.1:9".to_string()
+            ])
+            .await
+            .is_err());
+        assert!(membership.active_view().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shuffle_walk_exchanges_passive_peers() {
+        let network = MemoryNetwork::new();
+        let config = HyParViewConfig::default().with_shuffle(3, 4, 1);
+        let (a, _) = memory_node(&network, 1, config.clone()).await;
+        let (b, _) = memory_node(&network, 2, config.clone()).await;
+        let (c, _) = memory_node(&network, 3, config).await;
+        let p = [PeerId::new([10u8; 32]), PeerId::new([11u8; 32])];
+        let q = [PeerId::new([20u8; 32]), PeerId::new([21u8; 32])];
+        // Passive peers must be reachable, or degree maintenance drops them
+        let mut others = Vec::new();
+        for peer in [p, q].concat() {
+            others.push(network.add_node(peer).await.expect("passive peer"));
+        }
 
-        let peer1 = PeerId::new([1u8; 32]);
-        let peer2 = PeerId::new([2u8; 32]);
-        let peer3 = PeerId::new([3u8; 32]);
+        // A line a - b - c, with a passive view at each end
+        a.add_active(b.local_peer()).await.expect("add");
+        b.add_active(a.local_peer()).await.expect("add");
+        b.add_active(c.local_peer()).await.expect("add");
+        c.add_active(b.local_peer()).await.expect("add");
+        for peer in p {
+            a.add_node_passive(peer).await;
+        }
+        for peer in q {
+            c.add_node_passive(peer).await;
+        }
+
+        // The walk passes b and ends at c, which answers a directly. Degree
+        // maintenance may promote the new peers, so check both views.
+        let known = |node: &HyParViewMembership<MemoryTransport>| {
+            set([node.active_view(), node.passive_view()].concat())
+        };
+        a.shuffle().await.expect("shuffle");
+        eventually(|| known(&a).is_superset(&set(q.to_vec()))).await;
+        assert!(known(&c).is_superset(&set(vec![a.local_peer(), p[0], p[1]])));
+        assert!(b.passive_view().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shuffle_reply_evicts_sent_peers_first() {
+        let network = MemoryNetwork::new();
+        let config = HyParViewConfig::new(8, 4).with_shuffle(0, 2, 0);
+        let (node, _) = memory_node(&network, 1, config).await;
+        let target = PeerId::new([2u8; 32]);
+        let target_transport = network.add_node(target).await.expect("target");
+        node.add_active(target).await.expect("add");
+        let kept: Vec<PeerId> = (10..14).map(|i| PeerId::new([i; 32])).collect();
+        for &peer in &kept {
+            node.add_node_passive(peer).await;
+        }
+
+        node.shuffle().await.expect("shuffle");
+        let HyParViewMessage::Shuffle { origin, ttl, peers } =
+            next_message(&target_transport).await
+        else {
+            panic!("expected a shuffle");
+        };
+        assert_eq!((origin, ttl), (node.local_peer(), 0));
+        assert_eq!(peers.len(), 3);
+        assert_eq!(peers[0].peer, node.local_peer());
+        let sent: HashSet<PeerId> = peers[1..].iter().map(|entry| entry.peer).collect();
+
+        let received = [PeerId::new([20u8; 32]), PeerId::new([21u8; 32])];
+        let reply = HyParViewMessage::ShuffleReply {
+            peers: received
+                .iter()
+                .map(|&peer| PeerEntry { peer, addr: None })
+                .collect(),
+        };
+        node.handle_message(target, &frame(reply))
+            .await
+            .expect("handle");
+
+        let mut expected: HashSet<PeerId> = kept
+            .into_iter()
+            .filter(|peer| !sent.contains(peer))
+            .collect();
+        expected.extend(received);
+        assert_eq!(set(node.passive_view()), expected);
+    }
+
+    #[tokio::test]
+    async fn test_shuffle_reply_needs_outstanding_shuffle() {
+        let network = MemoryNetwork::new();
+        let config = HyParViewConfig::new(8, 16).with_shuffle(0, 1, 0);
+        let (node, _) = memory_node(&network, 1, config).await;
+        let target = PeerId::new([2u8; 32]);
+        let _target_transport = network.add_node(target).await.expect("target");
+        node.add_active(target).await.expect("add");
+        let reply = HyParViewMessage::ShuffleReply {
+            peers: (10..15)
+                .map(|i| PeerEntry {
+                    peer: PeerId::new([i; 32]),
+                    addr: None,
+                })
+                .collect(),
+        };
+
+        node.handle_message(target, &frame(reply.clone()))
+            .await
+            .expect("handle");
+        assert!(node.passive_view().is_empty());
+
+        // One reply per shuffle, cut to the size of a shuffle
+        node.shuffle().await.expect("shuffle");
+        node.handle_message(target, &frame(reply.clone()))
+            .await
+            .expect("handle");
+        assert_eq!(node.passive_view().len(), 2);
+        node.handle_message(target, &frame(reply))
+            .await
+            .expect("handle");
+        assert_eq!(node.passive_view().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_routes_swim_frames() {
+        let network = MemoryNetwork::new();
+        let (node, _) = memory_node(&network, 1, HyParViewConfig::default()).await;
+        let prober = PeerId::new([2u8; 32]);
+        let prober_transport = network.add_node(prober).await.expect("prober");
+
+        let ping = MembershipMessage::Swim(SwimMessage::Ping {
+            seq: 7,
+            updates: Vec::new(),
+        });
+        node.handle_message(prober, &ping.to_bytes().expect("serialize"))
+            .await
+            .expect("handle");
+
+        let (_, stream_type, data) = prober_transport.receive_message().await.expect("ack");
+        assert_eq!(stream_type, StreamType::Membership);
+        assert!(matches!(
+            MembershipMessage::from_bytes(&data).expect("decode"),
+            MembershipMessage::Swim(SwimMessage::Ack { seq: 7, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_receive_task_hands_on_other_streams() {
+        let network = MemoryNetwork::new();
+        let peer = PeerId::new([1u8; 32]);
+        let transport = Arc::new(network.add_node(peer).await.expect("node"));
+        let membership =
+            HyParViewMembership::with_config(peer, HyParViewConfig::default(), transport.clone());
+        let (_task, mut passthrough) = membership.spawn_receive_task();
+        let sender = network
+            .add_node(PeerId::new([2u8; 32]))
+            .await
+            .expect("sender");
+
+        sender
+            .send_to_peer(peer, StreamType::PubSub, Bytes::from_static(b"publish"))
+            .await
+            .expect("send");
+        let (from, stream_type, data) =
+            tokio::time::timeout(Duration::from_secs(5), passthrough.recv())
+                .await
+                .expect("frame in time")
+                .expect("frame");
+        assert_eq!((from, stream_type), (sender.peer_id(), StreamType::PubSub));
+        assert_eq!(data, Bytes::from_static(b"publish"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_skips_unknown_kinds() {
+        let membership = test_membership();
+        let peer = PeerId::new([1u8; 32]);
+
+        // A kind from a newer version, followed by its payload
+        let mut future = 99u32.to_le_bytes().to_vec();
+        future.extend_from_slice(b"payload");
+        assert_eq!(
+            MembershipMessage::from_bytes(&future).expect("decode"),
+            MembershipMessage::Unknown
+        );
+        membership
+            .handle_message(peer, &future)
+            .await
+            .expect("unknown kinds are skipped");
+
+        // Truncated frames are still rejected
+        assert!(membership.handle_message(peer, &[1, 0]).await.is_err());
+    }
+
+    /// Picks the best-scored candidate
+    #[derive(Debug)]
+    struct BestScore;
+
+    impl NeighborSelector for BestScore {
+        fn select(
+            &self,
+            candidates: &[NeighborCandidate],
+            _active: &[NeighborCandidate],
+        ) -> Option<PeerId> {
+            candidates
+                .iter()
+                .max_by(|a, b| a.score.total_cmp(&b.score))
+                .map(|candidate| candidate.peer)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_neighbor_requests_follow_selector() {
+        let network = MemoryNetwork::new();
+        let config = HyParViewConfig::default().with_selector(Arc::new(BestScore));
+        let (node, _) = memory_node(&network, 1, config).await;
+        let peers = [2u8, 3, 4].map(|id| PeerId::new([id; 32]));
+        let mut transports = Vec::new();
+        for peer in peers {
+            transports.push(network.add_node(peer).await.expect("peer"));
+            node.add_node_passive(peer).await;
+        }
 
-        swim.mark_alive(peer1).await;
-        swim.mark_alive(peer2).await; // Start as alive
-        swim.mark_suspect(peer2).await; // Then mark suspect
-        swim.mark_dead(peer3).await;
+        node.set_peer_score(peers[1], 5.0).await;
+        assert_eq!(node.request_neighbor().await, Some(peers[1]));
 
-        let alive = swim.get_peers_in_state(PeerState::Alive).await;
-        let suspects = swim.get_peers_in_state(PeerState::Suspect).await;
-        let dead = swim.get_peers_in_state(PeerState::Dead).await;
+        // A peer already asked is not offered again
+        node.set_peer_score(peers[2], 1.0).await;
+        assert_eq!(node.request_neighbor().await, Some(peers[2]));
+    }
 
-        assert_eq!(alive.len(), 1);
-        assert_eq!(suspects.len(), 1);
-        assert_eq!(dead.len(), 1);
+    #[tokio::test]
+    async fn test_scores_are_kept_only_for_known_peers() {
+        let membership = HyParViewMembership::new(local_peer(), 2, 2, test_transport());
+        let known = PeerId::new([1u8; 32]);
+        let unknown = PeerId::new([2u8; 32]);
+        membership.add_node_passive(known).await;
+
+        assert!(membership.set_peer_score(known, 1.0).await);
+        assert!(!membership.set_peer_score(unknown, 1.0).await);
+        assert!(!membership.scores.read().await.contains_key(&unknown));
+
+        // Evicting the peer from the passive view drops its score
+        for id in 3..6 {
+            membership.add_node_passive(PeerId::new([id; 32])).await;
+        }
+        let scores = membership.scores.read().await;
+        assert_eq!(
+            scores.contains_key(&known),
+            membership.passive_view().contains(&known)
+        );
+    }
 
-        assert!(alive.contains(&peer1));
-        assert!(suspects.contains(&peer2));
-        assert!(dead.contains(&peer3));
+    #[tokio::test]
+    async fn test_trim_views_uses_configured_degrees() {
+        let network = MemoryNetwork::new();
+        let (node, _) = memory_node(&network, 1, HyParViewConfig::new(2, 3)).await;
+        let mut transports = HashMap::new();
+        for id in 2..6 {
+            let peer = PeerId::new([id; 32]);
+            transports.insert(peer, network.add_node(peer).await.expect("peer"));
+            node.active.write().await.insert(peer);
+        }
+        for id in 10..16 {
+            node.passive.write().await.insert(PeerId::new([id; 32]));
+        }
+
+        node.trim_views().await;
+        let active = node.active_view();
+        assert_eq!(active.len(), 2);
+        assert!(node.passive_view().len() <= 3);
+
+        // Demoted neighbours were told
+        for (peer, transport) in &transports {
+            if active.contains(peer) {
+                continue;
+            }
+            assert_eq!(next_message(transport).await, HyParViewMessage::Disconnect);
+        }
     }
 }
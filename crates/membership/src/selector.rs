@@ -0,0 +1,319 @@
+//! Choosing which passive peers to promote
+//!
+//! Plain HyParView promotes random passive peers. A [`NeighborSelector`]
+//! sees each candidate's address, round-trip time and score next to the
+//! current active view, so it can build a low-latency overlay without letting
+//! one network, which an attacker may control, take over the active view.
+
+use rand::seq::{IteratorRandom, SliceRandom};
+use saorsa_gossip_types::PeerId;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What is known about a peer when choosing neighbours
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NeighborCandidate {
+    /// Peer ID
+    pub peer: PeerId,
+    /// Last-known address, if any
+    pub addr: Option<SocketAddr>,
+    /// Round-trip time measured by the transport, if connected
+    pub rtt: Option<Duration>,
+    /// Score set through [`crate::HyParViewMembership::set_peer_score`] (default: 0)
+    pub score: f64,
+}
+
+/// Chooses the passive peer to ask to become an active neighbour
+pub trait NeighborSelector: fmt::Debug + Send + Sync {
+    /// Pick one of `candidates`, given the current active view
+    ///
+    /// Returning `None`, or a peer that is not a candidate, asks nobody.
+    fn select(
+        &self,
+        candidates: &[NeighborCandidate],
+        active: &[NeighborCandidate],
+    ) -> Option<PeerId>;
+}
+
+/// Promotes a random passive peer, as in plain HyParView
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomSelector;
+
+impl NeighborSelector for RandomSelector {
+    fn select(
+        &self,
+        candidates: &[NeighborCandidate],
+        _active: &[NeighborCandidate],
+    ) -> Option<PeerId> {
+        candidates
+            .choose(&mut rand::thread_rng())
+            .map(|candidate| candidate.peer)
+    }
+}
+
+/// Maps an IP address to its autonomous system number
+pub type AsnLookup = Arc<dyn Fn(IpAddr) -> Option<u32> + Send + Sync>;
+
+/// Network a peer is grouped into for diversity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Network {
+    Asn(u32),
+    Prefix(IpAddr),
+}
+
+/// Prefers diverse, then nearby and well-scored peers
+///
+/// Candidates in networks with the fewest active neighbours come first; an
+/// ASN is used when a lookup is configured and knows the address, otherwise
+/// an IP prefix. Among those, the cost is the RTT minus the score times
+/// `score_weight`, and the peer is picked at random from the `sample`
+/// cheapest so the choice stays hard to predict.
+#[derive(Clone)]
+pub struct TopologySelector {
+    /// IPv4 prefix length grouping addresses (default: 24)
+    pub ipv4_prefix: u8,
+    /// IPv6 prefix length grouping addresses (default: 48)
+    pub ipv6_prefix: u8,
+    /// RTT assumed for peers without a measurement (default: 250 ms)
+    pub unknown_rtt: Duration,
+    /// RTT one point of score makes up for (default: 50 ms)
+    pub score_weight: Duration,
+    /// Number of best candidates to pick from at random (default: 3)
+    pub sample: usize,
+    /// ASN lookup, if available
+    asn_lookup: Option<AsnLookup>,
+}
+
+impl TopologySelector {
+    /// Create a selector with default parameters
+    pub fn new() -> Self {
+        Self {
+            ipv4_prefix: 24,
+            ipv6_prefix: 48,
+            unknown_rtt: Duration::from_millis(250),
+            score_weight: Duration::from_millis(50),
+            sample: 3,
+            asn_lookup: None,
+        }
+    }
+
+    /// Set the prefix lengths grouping IPv4 and IPv6 addresses
+    pub fn with_prefix_lengths(mut self, ipv4: u8, ipv6: u8) -> Self {
+        self.ipv4_prefix = ipv4.min(32);
+        self.ipv6_prefix = ipv6.min(128);
+        self
+    }
+
+    /// Group addresses by autonomous system where `lookup` knows them
+    pub fn with_asn_lookup(mut self, lookup: AsnLookup) -> Self {
+        self.asn_lookup = Some(lookup);
+        self
+    }
+
+    /// Set the RTT one point of score makes up for
+    pub fn with_score_weight(mut self, score_weight: Duration) -> Self {
+        self.score_weight = score_weight;
+        self
+    }
+
+    /// Set how many of the best candidates to pick from at random
+    pub fn with_sample(mut self, sample: usize) -> Self {
+        self.sample = sample.max(1);
+        self
+    }
+
+    fn network(&self, addr: SocketAddr) -> Network {
+        let ip = addr.ip();
+        if let Some(asn) = self.asn_lookup.as_ref().and_then(|lookup| lookup(ip)) {
+            return Network::Asn(asn);
+        }
+        let prefix = match ip {
+            IpAddr::V4(ip) => {
+                let mask = u32::MAX
+                    .checked_shl(32u32.saturating_sub(self.ipv4_prefix.into()))
+                    .unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(ip) & mask))
+            }
+            IpAddr::V6(ip) => {
+                let mask = u128::MAX
+                    .checked_shl(128u32.saturating_sub(self.ipv6_prefix.into()))
+                    .unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask))
+            }
+        };
+        Network::Prefix(prefix)
+    }
+
+    fn cost(&self, candidate: &NeighborCandidate) -> f64 {
+        let rtt = candidate.rtt.unwrap_or(self.unknown_rtt);
+        rtt.as_secs_f64() - candidate.score * self.score_weight.as_secs_f64()
+    }
+}
+
+impl Default for TopologySelector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for TopologySelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TopologySelector")
+            .field("ipv4_prefix", &self.ipv4_prefix)
+            .field("ipv6_prefix", &self.ipv6_prefix)
+            .field("unknown_rtt", &self.unknown_rtt)
+            .field("score_weight", &self.score_weight)
+            .field("sample", &self.sample)
+            .field("asn_lookup", &self.asn_lookup.is_some())
+            .finish()
+    }
+}
+
+impl NeighborSelector for TopologySelector {
+    fn select(
+        &self,
+        candidates: &[NeighborCandidate],
+        active: &[NeighborCandidate],
+    ) -> Option<PeerId> {
+        let mut neighbours: HashMap<Network, usize> = HashMap::new();
+        for addr in active.iter().filter_map(|neighbour| neighbour.addr) {
+            *neighbours.entry(self.network(addr)).or_default() += 1;
+        }
+        let crowding = |candidate: &NeighborCandidate| {
+            candidate.addr.map_or(0, |addr| {
+                neighbours
+                    .get(&self.network(addr))
+                    .copied()
+                    .unwrap_or_default()
+            })
+        };
+
+        let mut ranked: Vec<(usize, f64, PeerId)> = candidates
+            .iter()
+            .map(|candidate| (crowding(candidate), self.cost(candidate), candidate.peer))
+            .collect();
+        ranked.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+        let least_crowded = ranked.first()?.0;
+        ranked
+            .iter()
+            .take_while(|(crowding, _, _)| *crowding == least_crowded)
+            .take(self.sample.max(1))
+            .choose(&mut rand::thread_rng())
+            .map(|(_, _, peer)| *peer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: u8, addr: &str, rtt_ms: u64) -> NeighborCandidate {
+        NeighborCandidate {
+            peer: PeerId::new([id; 32]),
+            addr: Some(addr.parse().expect("valid address")),
+            rtt: Some(Duration::from_millis(rtt_ms)),
+            score: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_diversity_comes_before_latency() {
+        let selector = TopologySelector::new();
+        let active = [candidate(1, "10.0.0.1:9000", 10)];
+        let candidates = [
+            candidate(2, "10.0.0.2:9000", 5),
+            candidate(3, "10.0.0.3:9000", 6),
+            candidate(4, "192.0.2.1:9000", 80),
+        ];
+
+        for _ in 0..20 {
+            assert_eq!(
+                selector.select(&candidates, &active),
+                Some(PeerId::new([4u8; 32]))
+            );
+        }
+    }
+
+    #[test]
+    fn test_lowest_cost_wins_among_equally_diverse() {
+        let selector = TopologySelector::new().with_sample(1);
+        let mut scored = candidate(3, "198.51.100.1:9000", 100);
+        scored.score = 2.0;
+        let candidates = [
+            candidate(1, "192.0.2.1:9000", 50),
+            candidate(2, "203.0.113.1:9000", 20),
+        ];
+        assert_eq!(
+            selector.select(&candidates, &[]),
+            Some(PeerId::new([2u8; 32]))
+        );
+
+        // Two points of score make up for 100 ms
+        let candidates = [candidates[1], scored];
+        assert_eq!(
+            selector.select(&candidates, &[]),
+            Some(PeerId::new([3u8; 32]))
+        );
+    }
+
+    #[test]
+    fn test_sample_keeps_choice_random() {
+        let selector = TopologySelector::new();
+        let candidates: Vec<NeighborCandidate> = (1..=5)
+            .map(|id| candidate(id, &format!("192.0.{}.1:9000", id), u64::from(id)))
+            .collect();
+
+        let mut picked = std::collections::HashSet::new();
+        for _ in 0..200 {
+            picked.insert(selector.select(&candidates, &[]).expect("candidate"));
+        }
+        let best: std::collections::HashSet<PeerId> =
+            (1..=3).map(|id| PeerId::new([id; 32])).collect();
+        assert_eq!(picked, best);
+    }
+
+    #[test]
+    fn test_asn_groups_across_prefixes() {
+        // Both 10/8 and 172.16/12 belong to the same operator
+        let lookup: AsnLookup = Arc::new(|ip| match ip {
+            IpAddr::V4(ip) if ip.octets()[0] == 10 || ip.octets()[0] == 172 => Some(64500),
+            _ => None,
+        });
+        let selector = TopologySelector::new().with_asn_lookup(lookup);
+        let active = [candidate(1, "10.0.0.1:9000", 10)];
+        let candidates = [
+            candidate(2, "172.16.0.1:9000", 5),
+            candidate(3, "192.0.2.1:9000", 90),
+        ];
+
+        assert_eq!(
+            selector.select(&candidates, &active),
+            Some(PeerId::new([3u8; 32]))
+        );
+    }
+
+    #[test]
+    fn test_ipv6_prefix_grouping() {
+        let selector = TopologySelector::new();
+        let active = [candidate(1, "[2001:db8:1::1]:9000", 10)];
+        let candidates = [
+            candidate(2, "[2001:db8:1:ff::1]:9000", 5),
+            candidate(3, "[2001:db8:2::1]:9000", 90),
+        ];
+
+        assert_eq!(
+            selector.select(&candidates, &active),
+            Some(PeerId::new([3u8; 32]))
+        );
+    }
+
+    #[test]
+    fn test_no_candidates() {
+        assert_eq!(TopologySelector::new().select(&[], &[]), None);
+        assert_eq!(RandomSelector.select(&[], &[]), None);
+    }
+}
@@ -0,0 +1,257 @@
+//! Passive view snapshots
+//!
+//! A node that restarts with empty views has to bootstrap through its seeds
+//! again. Snapshotting the passive view into a coordinator [`PeerCache`]
+//! (the same CBOR file the bootstrap cache uses) lets it restore the peers
+//! it last knew, with their addresses and when they were last heard from,
+//! and ask them to become neighbours straight away.
+
+use crate::{unix_time_millis, HyParViewMembership};
+use anyhow::Result;
+use saorsa_gossip_coordinator::{NatClass, PeerCache, PeerCacheEntry, PeerRoles};
+use saorsa_gossip_transport::GossipTransport;
+use saorsa_gossip_types::PeerId;
+use std::net::SocketAddr;
+use std::path::Path;
+use tracing::debug;
+
+/// Roles of a peer known only through membership
+fn no_roles() -> PeerRoles {
+    PeerRoles {
+        coordinator: false,
+        reflector: false,
+        rendezvous: false,
+        relay: false,
+    }
+}
+
+/// The address to reach a cached peer at
+///
+/// A punched address is preferred, then a public one.
+fn dial_addr(entry: &PeerCacheEntry) -> Option<SocketAddr> {
+    if entry.is_punched() {
+        return entry.reflexive_addrs.first().copied();
+    }
+    entry
+        .public_addrs
+        .first()
+        .or(entry.reflexive_addrs.first())
+        .copied()
+}
+
+impl<T: GossipTransport + 'static> HyParViewMembership<T> {
+    /// Record the passive view in `cache`
+    ///
+    /// Current neighbours are recorded too, as heard from now, since they are
+    /// the best candidates after a restart. Entries already in the cache keep
+    /// their roles and NAT class; their address and last-success time are
+    /// refreshed.
+    pub async fn snapshot_passive_view(&self, cache: &PeerCache) {
+        let now = unix_time_millis();
+        let mut peers: Vec<(PeerId, u64)> = {
+            let seen = self.seen.read().await;
+            self.passive
+                .read()
+                .await
+                .iter()
+                .map(|peer| (*peer, seen.get(peer).copied().unwrap_or(now)))
+                .collect()
+        };
+        peers.extend(self.active.read().await.iter().map(|peer| (*peer, now)));
+
+        let addrs = self.addrs.read().await;
+        for (peer, last_seen) in peers {
+            let addr = addrs.get(&peer).copied();
+            let mut entry = cache.get(&peer).unwrap_or_else(|| {
+                PeerCacheEntry::new(peer, Vec::new(), NatClass::Unknown, no_roles())
+            });
+            if let Some(addr) = addr {
+                if !entry.reflexive_addrs.contains(&addr) {
+                    entry.public_addrs.retain(|existing| *existing != addr);
+                    entry.public_addrs.insert(0, addr);
+                }
+            }
+            entry.last_success = entry.last_success.max(last_seen);
+            cache.insert(entry);
+        }
+    }
+
+    /// Refill the passive view from `cache` and ask for neighbours
+    ///
+    /// The most recently seen peers of the last 24 hours are restored, up to
+    /// the passive degree, and NEIGHBOR requests go out at once for any free
+    /// active slots. Returns the number of peers restored.
+    pub async fn restore_passive_view(&self, cache: &PeerCache) -> usize {
+        let mut entries =
+            cache.get_by_role(|entry| entry.peer_id != self.local_peer && entry.is_recent());
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_success));
+        entries.truncate(self.config.passive_degree);
+
+        let mut restored = 0;
+        for entry in entries {
+            if !self.add_node_passive(entry.peer_id).await {
+                continue;
+            }
+            if let Some(addr) = dial_addr(&entry) {
+                self.addrs
+                    .write()
+                    .await
+                    .entry(entry.peer_id)
+                    .or_insert(addr);
+            }
+            self.seen
+                .write()
+                .await
+                .insert(entry.peer_id, entry.last_success);
+            restored += 1;
+        }
+
+        debug!(restored, "HyParView: Restored passive view from peer cache");
+        self.fill_active_view().await;
+        restored
+    }
+
+    /// Snapshot the passive view into the peer cache file at `path`
+    ///
+    /// Other entries of the file, such as cached coordinators, are kept.
+    pub async fn save_passive_view(&self, path: &Path) -> Result<()> {
+        let path = path.to_path_buf();
+        let load_path = path.clone();
+        let cache = tokio::task::spawn_blocking(move || PeerCache::load(&load_path)).await??;
+        self.snapshot_passive_view(&cache).await;
+        tokio::task::spawn_blocking(move || cache.save(&path)).await?
+    }
+
+    /// Restore the passive view from the peer cache file at `path`
+    ///
+    /// A missing file restores nothing. Returns the number of peers restored.
+    pub async fn load_passive_view(&self, path: &Path) -> Result<usize> {
+        let path = path.to_path_buf();
+        let cache = tokio::task::spawn_blocking(move || PeerCache::load(&path)).await??;
+        Ok(self.restore_passive_view(&cache).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HyParViewConfig, Membership};
+    use saorsa_gossip_transport::{MemoryNetwork, MemoryTransport, QuicTransport, TransportConfig};
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn test_membership(id: u8) -> HyParViewMembership<QuicTransport> {
+        HyParViewMembership::new(
+            PeerId::new([id; 32]),
+            8,
+            64,
+            Arc::new(QuicTransport::new(TransportConfig::default())),
+        )
+    }
+
+    async fn memory_node(network: &MemoryNetwork, id: u8) -> HyParViewMembership<MemoryTransport> {
+        let peer = PeerId::new([id; 32]);
+        let transport = Arc::new(network.add_node(peer).await.expect("node"));
+        let membership =
+            HyParViewMembership::with_config(peer, HyParViewConfig::default(), transport);
+        membership.spawn_receive_task();
+        membership
+    }
+
+    #[tokio::test]
+    async fn test_passive_view_round_trip_keeps_other_entries() {
+        let dir = tempdir().expect("temp dir");
+        let path = dir.path().join("peer_cache.cbor");
+
+        // The file already holds a coordinator from bootstrap
+        let coordinator = PeerId::new([9u8; 32]);
+        let cache = PeerCache::new();
+        cache.insert(PeerCacheEntry::new(
+            coordinator,
+            vec!["203.0.113.9:4433".parse().expect("valid")],
+            NatClass::Eim,
+            PeerRoles {
+                coordinator: true,
+                ..no_roles()
+            },
+        ));
+        cache.save(&path).expect("save");
+
+        let node = test_membership(1);
+        let passive: Vec<PeerId> = (2..5).map(|id| PeerId::new([id; 32])).collect();
+        for peer in &passive {
+            node.add_node_passive(*peer).await;
+        }
+        let addr: SocketAddr = "198.51.100.2:9000".parse().expect("valid");
+        node.addrs.write().await.insert(passive[0], addr);
+        node.save_passive_view(&path).await.expect("save view");
+
+        let saved = PeerCache::load(&path).expect("load");
+        assert_eq!(saved.len(), 4);
+        assert!(
+            saved
+                .get(&coordinator)
+                .expect("coordinator")
+                .roles
+                .coordinator
+        );
+
+        let restarted = test_membership(1);
+        let restored = restarted.load_passive_view(&path).await.expect("load view");
+        assert_eq!(restored, 4);
+        let view: HashSet<PeerId> = restarted.passive_view().into_iter().collect();
+        assert!(passive.iter().all(|peer| view.contains(peer)));
+        assert_eq!(
+            restarted.addrs.read().await.get(&passive[0]).copied(),
+            Some(addr)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_skips_stale_and_local_entries() {
+        let node = test_membership(1);
+        let cache = PeerCache::new();
+        let fresh = PeerId::new([2u8; 32]);
+        let stale = PeerId::new([3u8; 32]);
+        for peer in [node.local_peer(), fresh, stale] {
+            cache.insert(PeerCacheEntry::new(
+                peer,
+                Vec::new(),
+                NatClass::Unknown,
+                no_roles(),
+            ));
+        }
+        let mut old = cache.get(&stale).expect("stale");
+        old.last_success -= 25 * 3600 * 1000;
+        cache.insert(old);
+
+        assert_eq!(node.restore_passive_view(&cache).await, 1);
+        assert_eq!(node.passive_view(), vec![fresh]);
+    }
+
+    #[tokio::test]
+    async fn test_restored_node_rejoins_without_seeds() {
+        let network = MemoryNetwork::new();
+        let dir = tempdir().expect("temp dir");
+        let path = dir.path().join("peer_cache.cbor");
+        let neighbour = memory_node(&network, 2).await;
+
+        // A previous run knew the neighbour
+        let before = test_membership(1);
+        before.add_node_passive(neighbour.local_peer()).await;
+        before.save_passive_view(&path).await.expect("save view");
+
+        let node = memory_node(&network, 1).await;
+        node.load_passive_view(&path).await.expect("load view");
+        for _ in 0..100 {
+            if !node.active_view().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(node.active_view(), vec![neighbour.local_peer()]);
+        assert!(neighbour.active_view().contains(&node.local_peer()));
+    }
+}
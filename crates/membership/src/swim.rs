@@ -0,0 +1,1474 @@
+//! SWIM failure detection
+//!
+//! Each protocol period a [`SwimDetector`] pings the next member of a
+//! randomized round-robin order. If no ack arrives within the ack timeout it
+//! asks `k` other members to ping the target on its behalf, and only when
+//! none of them gets an answer before the period ends is the target
+//! suspected. Suspicions, refutations and deaths are piggybacked on pings
+//! and acks; a suspected member refutes by raising its incarnation number.
+//!
+//! Timeouts adapt as in Lifeguard. A local health multiplier grows when our
+//! probes go unanswered or we have to refute a suspicion, and shrinks with
+//! every timely ack; probe periods, ack timeouts and new suspicions are
+//! stretched by it, so an overloaded node slows down instead of falsely
+//! suspecting healthy peers. A suspicion starts at its longest timeout and
+//! shrinks towards the minimum as independent members confirm it.
+
+use anyhow::Result;
+use bytes::Bytes;
+use rand::seq::{IteratorRandom, SliceRandom};
+use saorsa_gossip_transport::{GossipTransport, StreamType, TransportEvent};
+use saorsa_gossip_types::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{debug, trace, warn};
+
+use crate::{
+    MembershipEvent, MembershipMessage, EVENT_CHANNEL_CAPACITY, PASSTHROUGH_CHANNEL_CAPACITY,
+};
+
+/// Peer state for SWIM failure detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PeerState {
+    /// Peer is alive and responding
+    Alive,
+    /// Peer is suspected of failure
+    Suspect,
+    /// Peer is confirmed dead
+    Dead,
+}
+
+/// A membership change disseminated by piggybacking on SWIM messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberUpdate {
+    /// The member the update is about
+    pub peer: PeerId,
+    /// Its new state
+    pub state: PeerState,
+    /// The member's incarnation number the state applies to
+    pub incarnation: u64,
+    /// The member that raised the state, so suspicions can be confirmed
+    /// independently
+    pub source: PeerId,
+}
+
+/// SWIM protocol messages
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SwimMessage {
+    /// Ping message to probe peer
+    Ping {
+        /// Sequence number echoed by the ack
+        seq: u64,
+        /// Piggybacked membership updates
+        updates: Vec<MemberUpdate>,
+    },
+    /// Ack response to ping
+    Ack {
+        /// Sequence number of the ping being answered
+        seq: u64,
+        /// Piggybacked membership updates
+        updates: Vec<MemberUpdate>,
+    },
+    /// Request to ping `target` on the sender's behalf and relay its ack
+    PingReq {
+        /// Sequence number the relayed ack must carry
+        seq: u64,
+        /// Member to probe
+        target: PeerId,
+        /// Piggybacked membership updates
+        updates: Vec<MemberUpdate>,
+    },
+}
+
+/// SWIM protocol parameters
+#[derive(Debug, Clone)]
+pub struct SwimConfig {
+    /// Length of a protocol period, one probe each (default: 1 second)
+    pub probe_period: Duration,
+    /// Time to wait for a direct ack before probing indirectly (default: 500 ms)
+    pub ack_timeout: Duration,
+    /// Members asked to probe indirectly, `k` (default: 3)
+    pub indirect_probes: usize,
+    /// Time an unconfirmed suspect has to refute before it is declared dead
+    /// (default: 3 seconds)
+    pub suspect_timeout: Duration,
+    /// Time a suspect has once fully confirmed (default: 750 ms)
+    pub min_suspect_timeout: Duration,
+    /// Independent suspicions needed to reach the minimum timeout (default: 3)
+    pub suspicion_confirmations: usize,
+    /// Upper bound of the local health multiplier (default: 8)
+    pub max_local_health: u32,
+    /// Each update is piggybacked `retransmit_mult * log2(n + 1)` times (default: 4)
+    pub retransmit_mult: usize,
+    /// Maximum updates piggybacked on one message (default: 8)
+    pub max_piggyback: usize,
+    /// Time a dead member is remembered before it is forgotten (default: 60
+    /// times the suspect timeout)
+    pub dead_timeout: Duration,
+}
+
+impl SwimConfig {
+    /// Create a configuration with the given probe period and suspect timeout
+    pub fn new(probe_period: Duration, suspect_timeout: Duration) -> Self {
+        Self {
+            probe_period,
+            ack_timeout: probe_period / 2,
+            indirect_probes: 3,
+            suspect_timeout,
+            min_suspect_timeout: suspect_timeout / 4,
+            suspicion_confirmations: 3,
+            max_local_health: 8,
+            retransmit_mult: 4,
+            max_piggyback: 8,
+            dead_timeout: suspect_timeout * 60,
+        }
+    }
+
+    /// Set the time to wait for a direct ack
+    pub fn with_ack_timeout(mut self, ack_timeout: Duration) -> Self {
+        self.ack_timeout = ack_timeout;
+        self
+    }
+
+    /// Set how many members are asked to probe indirectly
+    pub fn with_indirect_probes(mut self, indirect_probes: usize) -> Self {
+        self.indirect_probes = indirect_probes;
+        self
+    }
+
+    /// Set the fully confirmed suspect timeout and the confirmations needed
+    pub fn with_suspicion(mut self, min_suspect_timeout: Duration, confirmations: usize) -> Self {
+        self.min_suspect_timeout = min_suspect_timeout;
+        self.suspicion_confirmations = confirmations;
+        self
+    }
+
+    /// Set how long dead members are remembered
+    pub fn with_dead_timeout(mut self, dead_timeout: Duration) -> Self {
+        self.dead_timeout = dead_timeout;
+        self
+    }
+
+    /// Set the upper bound of the local health multiplier; 0 disables it
+    pub fn with_max_local_health(mut self, max_local_health: u32) -> Self {
+        self.max_local_health = max_local_health;
+        self
+    }
+}
+
+impl Default for SwimConfig {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_secs(crate::SWIM_PROBE_INTERVAL_SECS),
+            Duration::from_secs(crate::SWIM_SUSPECT_TIMEOUT_SECS),
+        )
+    }
+}
+
+/// An ongoing suspicion and the members that raised it
+#[derive(Clone, Debug)]
+struct Suspicion {
+    started: Instant,
+    min: Duration,
+    max: Duration,
+    sources: HashSet<PeerId>,
+}
+
+impl Suspicion {
+    fn new(source: PeerId, min: Duration, max: Duration) -> Self {
+        Self {
+            started: Instant::now(),
+            min,
+            max: max.max(min),
+            sources: HashSet::from([source]),
+        }
+    }
+
+    /// The timeout after `confirmations` independent confirmations
+    ///
+    /// Lifeguard's `max - (max - min) * log(C + 1) / log(K + 1)`, reaching
+    /// the minimum once `expected` confirmations have arrived.
+    fn timeout(&self, expected: usize) -> Duration {
+        let confirmations = self.sources.len() - 1;
+        if expected == 0 || confirmations >= expected {
+            return self.min;
+        }
+        let fraction = ((confirmations + 1) as f64).ln() / ((expected + 1) as f64).ln();
+        self.max - (self.max - self.min).mul_f64(fraction)
+    }
+}
+
+/// SWIM peer entry with timestamp
+#[derive(Clone, Debug)]
+struct SwimPeerEntry {
+    state: PeerState,
+    incarnation: u64,
+    last_update: Instant,
+    /// Set while the peer is suspect
+    suspicion: Option<Suspicion>,
+}
+
+/// An update waiting to be piggybacked
+struct Broadcast {
+    update: MemberUpdate,
+    transmits: usize,
+}
+
+/// A probe of ours awaiting an ack
+struct PendingAck {
+    /// Members whose ack counts: the target, then any indirect probers
+    responders: HashSet<PeerId>,
+    ack: oneshot::Sender<()>,
+}
+
+/// A ping sent on another member's behalf
+struct Relay {
+    /// The member that asked, and the sequence number its ack must carry
+    requester: PeerId,
+    requester_seq: u64,
+    /// The member pinged, the only one whose ack is relayed
+    target: PeerId,
+    sent: Instant,
+}
+
+/// Protocol bookkeeping shared by all handles of a detector
+#[derive(Default)]
+struct SwimProtocol {
+    /// Our own incarnation number
+    incarnation: AtomicU64,
+    /// Lifeguard local health multiplier; 0 is healthy
+    local_health: AtomicU32,
+    /// Next ping sequence number
+    next_seq: AtomicU64,
+    /// Updates to piggyback, least transmitted first
+    broadcasts: Mutex<Vec<Broadcast>>,
+    /// Our probes awaiting an ack, by sequence number
+    acks: Mutex<HashMap<u64, PendingAck>>,
+    /// Pings sent for others, by our sequence number
+    relays: Mutex<HashMap<u64, Relay>>,
+    /// Remaining members of the current round-robin pass
+    probe_order: Mutex<VecDeque<PeerId>>,
+}
+
+impl SwimProtocol {
+    fn broadcasts(&self) -> MutexGuard<'_, Vec<Broadcast>> {
+        self.broadcasts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn acks(&self) -> MutexGuard<'_, HashMap<u64, PendingAck>> {
+        self.acks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn relays(&self) -> MutexGuard<'_, HashMap<u64, Relay>> {
+        self.relays.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn probe_order(&self) -> MutexGuard<'_, VecDeque<PeerId>> {
+        self.probe_order.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.next_seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Queue an update, replacing any older one about the same member
+    fn enqueue(&self, update: MemberUpdate) {
+        let mut broadcasts = self.broadcasts();
+        broadcasts.retain(|broadcast| broadcast.update.peer != update.peer);
+        broadcasts.push(Broadcast {
+            update,
+            transmits: 0,
+        });
+    }
+}
+
+/// How a probe was answered
+enum Probe {
+    /// The target acked within the ack timeout
+    Direct,
+    /// Only a relayed ack arrived
+    Indirect,
+    /// No ack before the period ended
+    Failed,
+}
+
+/// SWIM failure detector
+pub struct SwimDetector<T: GossipTransport + 'static> {
+    /// Local peer ID
+    local_peer: PeerId,
+    /// Peer states with timestamps
+    states: Arc<RwLock<HashMap<PeerId, SwimPeerEntry>>>,
+    /// Incarnation, probes in flight and pending updates
+    protocol: Arc<SwimProtocol>,
+    /// Protocol parameters
+    config: SwimConfig,
+    /// Transport layer for sending probes
+    transport: Arc<T>,
+    /// Suspicions, deaths and refutations, for subscribers
+    events: broadcast::Sender<MembershipEvent>,
+}
+
+impl<T: GossipTransport + 'static> Clone for SwimDetector<T> {
+    /// Get another handle to the same detector
+    ///
+    /// Clones share peer states; background tasks are not duplicated.
+    fn clone(&self) -> Self {
+        Self {
+            local_peer: self.local_peer,
+            states: self.states.clone(),
+            protocol: self.protocol.clone(),
+            config: self.config.clone(),
+            transport: self.transport.clone(),
+            events: self.events.clone(),
+        }
+    }
+}
+
+impl<T: GossipTransport + 'static> SwimDetector<T> {
+    /// Create a new SWIM detector
+    pub fn new(
+        local_peer: PeerId,
+        probe_period: u64,
+        suspect_timeout: u64,
+        transport: Arc<T>,
+    ) -> Self {
+        Self::with_config(
+            local_peer,
+            SwimConfig::new(
+                Duration::from_secs(probe_period),
+                Duration::from_secs(suspect_timeout),
+            ),
+            transport,
+        )
+    }
+
+    /// Create a new SWIM detector with custom parameters
+    pub fn with_config(local_peer: PeerId, config: SwimConfig, transport: Arc<T>) -> Self {
+        let detector = Self {
+            local_peer,
+            states: Arc::new(RwLock::new(HashMap::new())),
+            protocol: Arc::new(SwimProtocol::default()),
+            config,
+            transport,
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        };
+
+        // Start background probing task
+        detector.spawn_probe_task();
+        detector.spawn_suspect_timeout_task();
+        detector.spawn_transport_event_task();
+
+        detector
+    }
+
+    /// Mark a peer as alive
+    pub async fn mark_alive(&self, peer: PeerId) {
+        let mut states = self.states.write().await;
+        let incarnation = states.get(&peer).map_or(0, |entry| entry.incarnation);
+        let previous = states.insert(
+            peer,
+            SwimPeerEntry {
+                state: PeerState::Alive,
+                incarnation,
+                last_update: Instant::now(),
+                suspicion: None,
+            },
+        );
+        self.emit_transition(peer, previous.map(|entry| entry.state), PeerState::Alive);
+        trace!(peer_id = %peer, "SWIM: Marked peer as alive");
+    }
+
+    /// Mark a peer as suspect
+    ///
+    /// The suspicion is gossiped so the peer gets a chance to refute it. If
+    /// others already suspect the peer, our suspicion confirms theirs.
+    pub async fn mark_suspect(&self, peer: PeerId) {
+        let mut states = self.states.write().await;
+        let Some(entry) = states.get_mut(&peer) else {
+            return;
+        };
+        let update = MemberUpdate {
+            peer,
+            state: PeerState::Suspect,
+            incarnation: entry.incarnation,
+            source: self.local_peer,
+        };
+        match entry.state {
+            PeerState::Alive => {
+                entry.state = PeerState::Suspect;
+                entry.last_update = Instant::now();
+                entry.suspicion = Some(self.new_suspicion(self.local_peer));
+                self.protocol.enqueue(update);
+                self.emit(MembershipEvent::PeerSuspected(peer));
+                debug!(peer_id = %peer, "SWIM: Marked peer as suspect");
+            }
+            PeerState::Suspect => {
+                if self.confirm_suspicion(entry, self.local_peer) {
+                    self.protocol.enqueue(update);
+                }
+            }
+            PeerState::Dead => {}
+        }
+    }
+
+    /// Mark a peer as dead
+    pub async fn mark_dead(&self, peer: PeerId) {
+        let mut states = self.states.write().await;
+        let incarnation = states.get(&peer).map_or(0, |entry| entry.incarnation);
+        let previous = states.insert(
+            peer,
+            SwimPeerEntry {
+                state: PeerState::Dead,
+                incarnation,
+                last_update: Instant::now(),
+                suspicion: None,
+            },
+        );
+        self.protocol.enqueue(MemberUpdate {
+            peer,
+            state: PeerState::Dead,
+            incarnation,
+            source: self.local_peer,
+        });
+        self.emit_transition(peer, previous.map(|entry| entry.state), PeerState::Dead);
+        warn!(peer_id = %peer, "SWIM: Marked peer as dead");
+    }
+
+    /// Subscribe to suspicions, deaths and refutations of tracked peers
+    pub fn subscribe(&self) -> broadcast::Receiver<MembershipEvent> {
+        self.events.subscribe()
+    }
+
+    /// The sender behind [`Self::subscribe`], shared with HyParView
+    pub(crate) fn event_sender(&self) -> broadcast::Sender<MembershipEvent> {
+        self.events.clone()
+    }
+
+    /// Get the state of a peer
+    pub async fn get_state(&self, peer: &PeerId) -> Option<PeerState> {
+        let states = self.states.read().await;
+        states.get(peer).map(|entry| entry.state)
+    }
+
+    /// Get the last known incarnation number of a peer
+    pub async fn get_incarnation(&self, peer: &PeerId) -> Option<u64> {
+        let states = self.states.read().await;
+        states.get(peer).map(|entry| entry.incarnation)
+    }
+
+    /// Get our own incarnation number
+    ///
+    /// It grows each time we refute a suspicion about ourselves.
+    pub fn incarnation(&self) -> u64 {
+        self.protocol.incarnation.load(Ordering::Relaxed)
+    }
+
+    /// Get the Lifeguard local health multiplier
+    ///
+    /// 0 means healthy; each step stretches probe periods, ack timeouts and
+    /// new suspicions by another multiple of their configured length.
+    pub fn local_health(&self) -> u32 {
+        self.protocol.local_health.load(Ordering::Relaxed)
+    }
+
+    /// Get the probe period stretched by local health
+    pub fn effective_probe_period(&self) -> Duration {
+        self.scaled(self.config.probe_period)
+    }
+
+    /// Get the ack timeout stretched by local health
+    pub fn effective_ack_timeout(&self) -> Duration {
+        self.scaled(self.config.ack_timeout)
+    }
+
+    /// Get all peers in a specific state
+    pub async fn get_peers_in_state(&self, state: PeerState) -> Vec<PeerId> {
+        let states = self.states.read().await;
+        states
+            .iter()
+            .filter(|(_, entry)| entry.state == state)
+            .map(|(peer, _)| *peer)
+            .collect()
+    }
+
+    /// Remove a peer from tracking
+    pub async fn remove_peer(&self, peer: &PeerId) {
+        let mut states = self.states.write().await;
+        states.remove(peer);
+    }
+
+    /// Get the probe period in seconds
+    pub fn probe_period(&self) -> u64 {
+        self.config.probe_period.as_secs()
+    }
+
+    /// Get the suspect timeout in seconds
+    pub fn suspect_timeout(&self) -> u64 {
+        self.config.suspect_timeout.as_secs()
+    }
+
+    /// Spawn a task feeding SWIM frames the transport receives to [`Self::handle_message`]
+    ///
+    /// For a detector running without HyParView. The task owns the
+    /// transport's receive loop; frames on other streams are handed on as by
+    /// [`crate::HyParViewMembership::spawn_receive_task`]. It stops once the
+    /// transport closes.
+    pub fn spawn_receive_task(
+        &self,
+    ) -> (JoinHandle<()>, mpsc::Receiver<(PeerId, StreamType, Bytes)>) {
+        let detector = self.clone();
+        let (passthrough, receiver) = mpsc::channel(PASSTHROUGH_CHANNEL_CAPACITY);
+
+        let task = tokio::spawn(async move {
+            while let Ok((from, stream_type, data)) = detector.transport.receive_message().await {
+                if stream_type != StreamType::Membership {
+                    if passthrough.send((from, stream_type, data)).await.is_err() {
+                        trace!(peer_id = %from, ?stream_type, "SWIM: Dropping non-membership frame");
+                    }
+                    continue;
+                }
+                match MembershipMessage::from_bytes(&data) {
+                    Ok(MembershipMessage::Swim(message)) => {
+                        if let Err(e) = detector.handle_message(from, message).await {
+                            debug!(peer_id = %from, "SWIM: Failed to handle message: {}", e);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => debug!(peer_id = %from, "SWIM: Malformed message: {}", e),
+                }
+            }
+            debug!("SWIM: Receive task stopped: transport closed");
+        });
+        (task, receiver)
+    }
+
+    /// Handle a SWIM message received from `from`
+    ///
+    /// Piggybacked updates are applied before the message itself. Frames on
+    /// the membership stream are decoded and routed here by
+    /// [`crate::HyParViewMembership::handle_message`].
+    pub async fn handle_message(&self, from: PeerId, message: SwimMessage) -> Result<()> {
+        trace!(peer_id = %from, ?message, "SWIM: Received message");
+
+        match message {
+            SwimMessage::Ping { seq, updates } => {
+                self.apply_updates(updates).await;
+                let ack = SwimMessage::Ack {
+                    seq,
+                    updates: self.piggyback().await,
+                };
+                self.send(from, &ack).await
+            }
+            SwimMessage::PingReq {
+                seq,
+                target,
+                updates,
+            } => {
+                self.apply_updates(updates).await;
+                let relay_seq = self.protocol.next_seq();
+                self.protocol.relays().insert(
+                    relay_seq,
+                    Relay {
+                        requester: from,
+                        requester_seq: seq,
+                        target,
+                        sent: Instant::now(),
+                    },
+                );
+                let ping = SwimMessage::Ping {
+                    seq: relay_seq,
+                    updates: self.piggyback().await,
+                };
+                if let Err(e) = self.send(target, &ping).await {
+                    self.protocol.relays().remove(&relay_seq);
+                    return Err(e);
+                }
+                Ok(())
+            }
+            SwimMessage::Ack { seq, updates } => {
+                self.apply_updates(updates).await;
+
+                // Only the member probed, or one asked to probe it, may answer
+                let pending = {
+                    let mut acks = self.protocol.acks();
+                    match acks.get(&seq) {
+                        Some(pending) if pending.responders.contains(&from) => acks.remove(&seq),
+                        _ => None,
+                    }
+                };
+                if let Some(pending) = pending {
+                    let _ = pending.ack.send(());
+                    return Ok(());
+                }
+
+                // An ack for a ping we sent on someone else's behalf
+                let relay = {
+                    let mut relays = self.protocol.relays();
+                    match relays.get(&seq) {
+                        Some(relay) if relay.target == from => relays.remove(&seq),
+                        _ => None,
+                    }
+                };
+                if let Some(relay) = relay {
+                    let ack = SwimMessage::Ack {
+                        seq: relay.requester_seq,
+                        updates: self.piggyback().await,
+                    };
+                    self.send(relay.requester, &ack).await?;
+                } else {
+                    trace!(peer_id = %from, seq, "SWIM: Ignoring unsolicited ack");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Apply piggybacked updates
+    async fn apply_updates(&self, updates: Vec<MemberUpdate>) {
+        for update in updates {
+            self.apply_update(update).await;
+        }
+    }
+
+    /// Apply one update under SWIM's incarnation rules
+    ///
+    /// Updates about untracked peers are ignored; accepted ones are gossiped
+    /// on. Returns whether our view changed.
+    async fn apply_update(&self, update: MemberUpdate) -> bool {
+        if update.peer == self.local_peer {
+            self.refute(update);
+            return false;
+        }
+
+        let mut states = self.states.write().await;
+        let Some(entry) = states.get_mut(&update.peer) else {
+            return false;
+        };
+        if update.state == PeerState::Suspect
+            && entry.state == PeerState::Suspect
+            && update.incarnation == entry.incarnation
+        {
+            // Not a new suspicion, but possibly an independent confirmation
+            if self.confirm_suspicion(entry, update.source) {
+                drop(states);
+                self.protocol.enqueue(update);
+            }
+            return false;
+        }
+        let overrides = match (update.state, entry.state) {
+            (PeerState::Alive, _) => update.incarnation > entry.incarnation,
+            (_, PeerState::Dead) => false,
+            (PeerState::Dead, _) => true,
+            (PeerState::Suspect, PeerState::Alive) => update.incarnation >= entry.incarnation,
+            (PeerState::Suspect, PeerState::Suspect) => update.incarnation > entry.incarnation,
+        };
+        if !overrides {
+            return false;
+        }
+
+        if entry.state != update.state {
+            debug!(
+                peer_id = %update.peer,
+                state = ?update.state,
+                incarnation = update.incarnation,
+                "SWIM: Peer state changed by gossip"
+            );
+            self.emit_transition(update.peer, Some(entry.state), update.state);
+        }
+        entry.last_update = Instant::now();
+        entry.suspicion =
+            (update.state == PeerState::Suspect).then(|| self.new_suspicion(update.source));
+        entry.state = update.state;
+        entry.incarnation = update.incarnation;
+        drop(states);
+
+        self.protocol.enqueue(update);
+        true
+    }
+
+    /// Answer a suspicion or death claim about ourselves
+    fn refute(&self, update: MemberUpdate) {
+        if update.state == PeerState::Alive {
+            return;
+        }
+
+        let current = self.protocol.incarnation.load(Ordering::Relaxed);
+        if update.incarnation < current {
+            return;
+        }
+        // A claim at the largest incarnation cannot be outbid; it is forged
+        let Some(incarnation) = update.incarnation.checked_add(1) else {
+            debug!("SWIM: Ignoring claim about ourselves at the maximum incarnation");
+            return;
+        };
+        self.protocol
+            .incarnation
+            .store(incarnation, Ordering::Relaxed);
+        self.protocol.enqueue(MemberUpdate {
+            peer: self.local_peer,
+            state: PeerState::Alive,
+            incarnation,
+            source: self.local_peer,
+        });
+        debug!(incarnation, "SWIM: Refuting suspicion about ourselves");
+
+        // Being suspected suggests we are the slow one
+        self.adjust_health(1);
+    }
+
+    /// Notify subscribers, if any
+    fn emit(&self, event: MembershipEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Notify subscribers of a change of a peer's state
+    fn emit_transition(&self, peer: PeerId, from: Option<PeerState>, to: PeerState) {
+        let event = match (from, to) {
+            (Some(from), to) if from == to => return,
+            (_, PeerState::Suspect) => MembershipEvent::PeerSuspected(peer),
+            (_, PeerState::Dead) => MembershipEvent::PeerDead(peer),
+            (Some(_), PeerState::Alive) => MembershipEvent::PeerRefuted(peer),
+            (None, PeerState::Alive) => return,
+        };
+        self.emit(event);
+    }
+
+    /// Start a suspicion raised by `source`, stretched by local health
+    fn new_suspicion(&self, source: PeerId) -> Suspicion {
+        Suspicion::new(
+            source,
+            self.scaled(self.config.min_suspect_timeout),
+            self.scaled(self.config.suspect_timeout),
+        )
+    }
+
+    /// Count `source` as confirming the current suspicion of `entry`
+    ///
+    /// Returns whether it was a new confirmation worth gossiping on.
+    fn confirm_suspicion(&self, entry: &mut SwimPeerEntry, source: PeerId) -> bool {
+        let expected = self.config.suspicion_confirmations;
+        let Some(suspicion) = entry.suspicion.as_mut() else {
+            return false;
+        };
+        if suspicion.sources.len() > expected || !suspicion.sources.insert(source) {
+            return false;
+        }
+        trace!(
+            confirmations = suspicion.sources.len() - 1,
+            timeout = ?suspicion.timeout(expected),
+            "SWIM: Suspicion confirmed"
+        );
+        true
+    }
+
+    /// Stretch a configured duration by the local health multiplier
+    fn scaled(&self, duration: Duration) -> Duration {
+        duration * (self.local_health() + 1)
+    }
+
+    /// Raise or lower the local health multiplier within its bounds
+    fn adjust_health(&self, delta: i32) {
+        let max = self.config.max_local_health;
+        let _ = self.protocol.local_health.fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |health| Some(health.saturating_add_signed(delta).min(max)),
+        );
+    }
+
+    /// Take the updates to piggyback on the next message
+    ///
+    /// Least transmitted updates go first; each is dropped after
+    /// `retransmit_mult * log2(n + 1)` transmissions.
+    async fn piggyback(&self) -> Vec<MemberUpdate> {
+        let members = self.states.read().await.len();
+        let limit = self.config.retransmit_mult * (usize::BITS - members.leading_zeros()) as usize;
+
+        let mut broadcasts = self.protocol.broadcasts();
+        broadcasts.sort_by_key(|broadcast| broadcast.transmits);
+        let updates = broadcasts
+            .iter_mut()
+            .take(self.config.max_piggyback)
+            .map(|broadcast| {
+                broadcast.transmits += 1;
+                broadcast.update
+            })
+            .collect();
+        broadcasts.retain(|broadcast| broadcast.transmits < limit.max(1));
+        updates
+    }
+
+    /// Serialize and send a SWIM message
+    async fn send(&self, peer: PeerId, message: &SwimMessage) -> Result<()> {
+        let bytes = MembershipMessage::Swim(message.clone()).to_bytes()?;
+        self.transport
+            .send_to_peer(peer, StreamType::Membership, bytes)
+            .await
+    }
+
+    /// Pick the next member to probe
+    ///
+    /// Members are probed in a random order, each once per pass, so every
+    /// failure is detected within one pass.
+    async fn next_probe_target(&self) -> Option<PeerId> {
+        let states = self.states.read().await;
+        let probeable = |peer: &PeerId| {
+            states
+                .get(peer)
+                .is_some_and(|entry| entry.state != PeerState::Dead)
+        };
+
+        let mut order = self.protocol.probe_order();
+        while let Some(peer) = order.pop_front() {
+            if probeable(&peer) {
+                return Some(peer);
+            }
+        }
+
+        // Start a new pass
+        let mut members: Vec<PeerId> = states
+            .iter()
+            .filter(|(_, entry)| entry.state != PeerState::Dead)
+            .map(|(peer, _)| *peer)
+            .collect();
+        members.shuffle(&mut rand::thread_rng());
+        order.extend(members);
+        order.pop_front()
+    }
+
+    /// Run one protocol period: probe the next member, suspecting it if
+    /// neither it nor any indirect prober answers
+    async fn probe_round(&self) {
+        // Relays whose target never answered are stale by now
+        let probe_period = self.effective_probe_period();
+        self.protocol
+            .relays()
+            .retain(|_, relay| relay.sent.elapsed() < probe_period);
+
+        let Some(target) = self.next_probe_target().await else {
+            return;
+        };
+        trace!(peer_id = %target, "SWIM: Probing peer");
+
+        match self.probe(target).await {
+            Probe::Direct => self.adjust_health(-1),
+            Probe::Indirect => {}
+            Probe::Failed => {
+                debug!(peer_id = %target, "SWIM: No ack, direct or indirect");
+                self.mark_suspect(target).await;
+                self.adjust_health(1);
+            }
+        }
+    }
+
+    /// Ping `target`, then ask other members to ping it if it stays silent
+    ///
+    /// Reports how an ack arrived within the protocol period, if at all.
+    async fn probe(&self, target: PeerId) -> Probe {
+        let started = time::Instant::now();
+        let seq = self.protocol.next_seq();
+        let (ack_tx, mut ack_rx) = oneshot::channel();
+        self.protocol.acks().insert(
+            seq,
+            PendingAck {
+                responders: HashSet::from([target]),
+                ack: ack_tx,
+            },
+        );
+
+        let ping = SwimMessage::Ping {
+            seq,
+            updates: self.piggyback().await,
+        };
+        let acked = match self.send(target, &ping).await {
+            Ok(()) => time::timeout(self.effective_ack_timeout(), &mut ack_rx)
+                .await
+                .is_ok_and(|ack| ack.is_ok()),
+            Err(e) => {
+                trace!(peer_id = %target, "SWIM: Ping failed: {}", e);
+                false
+            }
+        };
+        if acked {
+            return Probe::Direct;
+        }
+
+        // Indirect probes through up to k other live members
+        let helpers: Vec<PeerId> = {
+            let states = self.states.read().await;
+            states
+                .iter()
+                .filter(|(peer, entry)| **peer != target && entry.state == PeerState::Alive)
+                .map(|(peer, _)| *peer)
+                .choose_multiple(&mut rand::thread_rng(), self.config.indirect_probes)
+        };
+        if let Some(pending) = self.protocol.acks().get_mut(&seq) {
+            pending.responders.extend(helpers.iter().copied());
+        }
+        for helper in helpers {
+            let request = SwimMessage::PingReq {
+                seq,
+                target,
+                updates: self.piggyback().await,
+            };
+            if let Err(e) = self.send(helper, &request).await {
+                trace!(peer_id = %helper, "SWIM: Ping request failed: {}", e);
+            }
+        }
+
+        let deadline = started + self.effective_probe_period();
+        let acked = time::timeout_at(deadline, ack_rx)
+            .await
+            .is_ok_and(|ack| ack.is_ok());
+        self.protocol.acks().remove(&seq);
+        if acked {
+            Probe::Indirect
+        } else {
+            Probe::Failed
+        }
+    }
+
+    /// Spawn background task running one probe per protocol period
+    fn spawn_probe_task(&self) {
+        let detector = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let started = time::Instant::now();
+                detector.probe_round().await;
+                // Health measured by this round sets the length of the period
+                time::sleep_until(started + detector.effective_probe_period()).await;
+            }
+        });
+    }
+
+    /// Spawn background task reacting to transport connection events
+    ///
+    /// A tracked peer whose connection drops becomes suspect straight away
+    /// instead of waiting for a missed probe; a reconnect marks it alive.
+    fn spawn_transport_event_task(&self) {
+        let detector = self.clone();
+        let mut events = self.transport.subscribe_events();
+
+        tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                match event {
+                    TransportEvent::PeerConnected { peer, .. } => {
+                        let mut states_guard = detector.states.write().await;
+                        if let Some(entry) = states_guard.get_mut(&peer) {
+                            detector.emit_transition(peer, Some(entry.state), PeerState::Alive);
+                            entry.state = PeerState::Alive;
+                            entry.last_update = Instant::now();
+                            entry.suspicion = None;
+                            debug!(peer_id = %peer, "SWIM: Peer reconnected → alive");
+                        }
+                    }
+                    TransportEvent::PeerDisconnected { peer, reason } => {
+                        debug!(peer_id = %peer, ?reason, "SWIM: Peer disconnected → suspect");
+                        detector.mark_suspect(peer).await;
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    /// Spawn background task to check suspect timeouts
+    ///
+    /// It also forgets members that have been dead for the dead timeout.
+    fn spawn_suspect_timeout_task(&self) {
+        let detector = self.clone();
+
+        tokio::spawn(async move {
+            let check_period = detector.config.probe_period.min(Duration::from_secs(1));
+            let mut interval = time::interval(check_period);
+
+            loop {
+                interval.tick().await;
+
+                let mut states_guard = detector.states.write().await;
+                let now = Instant::now();
+
+                // Mark suspects that failed to refute in time as dead
+                let expected = detector.config.suspicion_confirmations;
+                for (peer, entry) in states_guard.iter_mut() {
+                    let expired = entry.suspicion.as_ref().is_some_and(|suspicion| {
+                        now.duration_since(suspicion.started) > suspicion.timeout(expected)
+                    });
+                    if entry.state == PeerState::Suspect && expired {
+                        entry.state = PeerState::Dead;
+                        entry.last_update = now;
+                        entry.suspicion = None;
+                        detector.protocol.enqueue(MemberUpdate {
+                            peer: *peer,
+                            state: PeerState::Dead,
+                            incarnation: entry.incarnation,
+                            source: detector.local_peer,
+                        });
+                        detector.emit(MembershipEvent::PeerDead(*peer));
+                        warn!(peer_id = %peer, "SWIM: Suspect timeout → marked dead");
+                    }
+                }
+
+                let dead_timeout = detector.config.dead_timeout;
+                states_guard.retain(|peer, entry| {
+                    let expired = entry.state == PeerState::Dead
+                        && now.duration_since(entry.last_update) > dead_timeout;
+                    if expired {
+                        debug!(peer_id = %peer, "SWIM: Forgetting dead peer");
+                    }
+                    !expired
+                });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saorsa_gossip_transport::{
+        LinkAction, MemoryNetwork, MemoryTransport, QuicTransport, TransportConfig,
+    };
+
+    fn test_transport() -> Arc<QuicTransport> {
+        Arc::new(QuicTransport::new(TransportConfig::default()))
+    }
+
+    fn local_peer() -> PeerId {
+        PeerId::new([0xAA; 32])
+    }
+
+    /// Fast protocol periods so tests see several rounds
+    fn fast_config() -> SwimConfig {
+        SwimConfig::new(Duration::from_millis(100), Duration::from_millis(400))
+            .with_ack_timeout(Duration::from_millis(40))
+    }
+
+    /// A detector on a memory network whose inbound messages drive it
+    async fn memory_detector(network: &MemoryNetwork, id: u8) -> SwimDetector<MemoryTransport> {
+        let peer = PeerId::new([id; 32]);
+        let transport = Arc::new(network.add_node(peer).await.expect("node"));
+        let detector = SwimDetector::with_config(peer, fast_config(), transport);
+        detector.spawn_receive_task();
+        detector
+    }
+
+    #[tokio::test]
+    async fn test_swim_reconnect_marks_alive() {
+        let transport = test_transport();
+        let swim = SwimDetector::new(local_peer(), 60, 60, transport.clone());
+        let peer = PeerId::new([1u8; 32]);
+
+        swim.mark_dead(peer).await;
+        let addr = "127.0.0.1:9000".parse().expect("Invalid address");
+        transport.dial(peer, addr).await.expect("dial");
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Alive));
+    }
+
+    #[tokio::test]
+    async fn test_swim_states() {
+        let transport = test_transport();
+        let swim = SwimDetector::new(local_peer(), 1, 3, transport);
+        let peer = PeerId::new([1u8; 32]);
+
+        swim.mark_alive(peer).await;
+        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Alive));
+
+        swim.mark_suspect(peer).await;
+        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Suspect));
+
+        swim.mark_dead(peer).await;
+        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Dead));
+    }
+
+    #[tokio::test]
+    async fn test_swim_suspect_timeout() {
+        let transport = test_transport();
+        let swim = SwimDetector::new(local_peer(), 1, 1, transport); // 1s timeout
+        let peer = PeerId::new([1u8; 32]);
+
+        swim.mark_alive(peer).await;
+        swim.mark_suspect(peer).await;
+
+        // Wait for timeout
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+        // Should be marked dead automatically
+        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Dead));
+    }
+
+    #[tokio::test]
+    async fn test_get_peers_in_state() {
+        let transport = test_transport();
+        let swim = SwimDetector::new(local_peer(), 1, 100, transport); // Long timeout so background task doesn't interfere
+
+        let peer1 = PeerId::new([1u8; 32]);
+        let peer2 = PeerId::new([2u8; 32]);
+        let peer3 = PeerId::new([3u8; 32]);
+
+        swim.mark_alive(peer1).await;
+        swim.mark_alive(peer2).await; // Start as alive
+        swim.mark_suspect(peer2).await; // Then mark suspect
+        swim.mark_dead(peer3).await;
+
+        let alive = swim.get_peers_in_state(PeerState::Alive).await;
+        let suspects = swim.get_peers_in_state(PeerState::Suspect).await;
+        let dead = swim.get_peers_in_state(PeerState::Dead).await;
+
+        assert_eq!(alive.len(), 1);
+        assert_eq!(suspects.len(), 1);
+        assert_eq!(dead.len(), 1);
+
+        assert!(alive.contains(&peer1));
+        assert!(suspects.contains(&peer2));
+        assert!(dead.contains(&peer3));
+    }
+
+    #[tokio::test]
+    async fn test_acked_probes_keep_peers_alive() {
+        let network = MemoryNetwork::new();
+        let a = memory_detector(&network, 1).await;
+        let b = memory_detector(&network, 2).await;
+        a.mark_alive(b.local_peer).await;
+        b.mark_alive(a.local_peer).await;
+
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert_eq!(a.get_state(&b.local_peer).await, Some(PeerState::Alive));
+        assert_eq!(b.get_state(&a.local_peer).await, Some(PeerState::Alive));
+    }
+
+    #[tokio::test]
+    async fn test_indirect_probe_reaches_peer_behind_broken_link() {
+        let network = MemoryNetwork::new();
+        let a = memory_detector(&network, 1).await;
+        let b = memory_detector(&network, 2).await;
+        let c = memory_detector(&network, 3).await;
+        let (id_a, id_b) = (a.local_peer, b.local_peer);
+        network
+            .set_hook(move |from, to, _, _| {
+                if (from, to) == (id_a, id_b) || (from, to) == (id_b, id_a) {
+                    LinkAction::Drop
+                } else {
+                    LinkAction::Deliver
+                }
+            })
+            .await;
+        for (node, peers) in [(&a, [id_b, c.local_peer]), (&c, [id_a, id_b])] {
+            for peer in peers {
+                node.mark_alive(peer).await;
+            }
+        }
+
+        // Every direct ping to b is lost, but c relays the acks
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert_eq!(a.get_state(&id_b).await, Some(PeerState::Alive));
+        assert!(network.dropped() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_silent_peer_is_suspected_then_dead() {
+        let network = MemoryNetwork::new();
+        let a = memory_detector(&network, 1).await;
+        let silent = PeerId::new([2u8; 32]);
+        let _silent_transport = network.add_node(silent).await.expect("silent");
+        a.mark_alive(silent).await;
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(a.get_state(&silent).await, Some(PeerState::Suspect));
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(a.get_state(&silent).await, Some(PeerState::Dead));
+    }
+
+    #[tokio::test]
+    async fn test_acks_count_only_from_expected_responders() {
+        let swim = SwimDetector::new(local_peer(), 60, 60, test_transport());
+        let target = PeerId::new([1u8; 32]);
+        let stranger = PeerId::new([2u8; 32]);
+        let seq = swim.protocol.next_seq();
+        let (ack_tx, mut ack_rx) = oneshot::channel();
+        swim.protocol.acks().insert(
+            seq,
+            PendingAck {
+                responders: HashSet::from([target]),
+                ack: ack_tx,
+            },
+        );
+
+        let ack = SwimMessage::Ack {
+            seq,
+            updates: Vec::new(),
+        };
+        swim.handle_message(stranger, ack.clone())
+            .await
+            .expect("ignored");
+        assert!(ack_rx.try_recv().is_err());
+        assert!(swim.protocol.acks().contains_key(&seq));
+
+        swim.handle_message(target, ack).await.expect("acked");
+        assert_eq!(ack_rx.try_recv(), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_dead_peers_are_forgotten() {
+        let config = fast_config().with_dead_timeout(Duration::from_millis(200));
+        let swim = SwimDetector::with_config(local_peer(), config, test_transport());
+        let peer = PeerId::new([1u8; 32]);
+
+        swim.mark_dead(peer).await;
+        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Dead));
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(swim.get_state(&peer).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_suspected_peer_refutes() {
+        let network = MemoryNetwork::new();
+        let a = memory_detector(&network, 1).await;
+        let b = memory_detector(&network, 2).await;
+        a.mark_alive(b.local_peer).await;
+        b.mark_alive(a.local_peer).await;
+
+        // The suspicion reaches b on a's next ping and b answers with a
+        // higher incarnation
+        a.mark_suspect(b.local_peer).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(b.incarnation(), 1);
+        assert_eq!(a.get_state(&b.local_peer).await, Some(PeerState::Alive));
+        assert_eq!(a.get_incarnation(&b.local_peer).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_reports_liveness_changes() {
+        let swim = SwimDetector::new(local_peer(), 60, 60, test_transport());
+        let mut events = swim.subscribe();
+        let peer = PeerId::new([1u8; 32]);
+
+        swim.mark_alive(peer).await;
+        swim.mark_suspect(peer).await;
+        let refutation = MemberUpdate {
+            peer,
+            state: PeerState::Alive,
+            incarnation: 1,
+            source: peer,
+        };
+        assert!(swim.apply_update(refutation).await);
+        swim.mark_dead(peer).await;
+
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(MembershipEvent::PeerSuspected(peer))
+        );
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(MembershipEvent::PeerRefuted(peer))
+        );
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(MembershipEvent::PeerDead(peer))
+        );
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_incarnation_rules() {
+        let swim = SwimDetector::new(local_peer(), 60, 60, test_transport());
+        let peer = PeerId::new([1u8; 32]);
+        let source = PeerId::new([3u8; 32]);
+        let update = |state, incarnation| MemberUpdate {
+            peer,
+            state,
+            incarnation,
+            source,
+        };
+        swim.mark_alive(peer).await;
+
+        // Suspicion overrides alive at the same incarnation, not the reverse
+        assert!(swim.apply_update(update(PeerState::Suspect, 0)).await);
+        assert!(!swim.apply_update(update(PeerState::Alive, 0)).await);
+        assert!(!swim.apply_update(update(PeerState::Suspect, 0)).await);
+        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Suspect));
+
+        // A higher incarnation refutes
+        assert!(swim.apply_update(update(PeerState::Alive, 1)).await);
+        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Alive));
+
+        // Death is final unless the peer comes back with a newer incarnation
+        assert!(swim.apply_update(update(PeerState::Dead, 1)).await);
+        assert!(!swim.apply_update(update(PeerState::Suspect, 5)).await);
+        assert!(!swim.apply_update(update(PeerState::Alive, 1)).await);
+        assert_eq!(swim.get_state(&peer).await, Some(PeerState::Dead));
+
+        // Updates about untracked peers are ignored
+        let stranger = MemberUpdate {
+            peer: PeerId::new([2u8; 32]),
+            state: PeerState::Alive,
+            incarnation: 0,
+            source,
+        };
+        assert!(!swim.apply_update(stranger).await);
+        assert_eq!(swim.get_state(&stranger.peer).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_piggybacked_updates_are_retransmitted_a_bounded_number_of_times() {
+        let swim = SwimDetector::new(local_peer(), 60, 60, test_transport());
+        let peer = PeerId::new([1u8; 32]);
+        swim.mark_alive(peer).await;
+        swim.mark_suspect(peer).await;
+
+        // One member: retransmit_mult * log2(2) = 4 transmissions
+        let expected = vec![MemberUpdate {
+            peer,
+            state: PeerState::Suspect,
+            incarnation: 0,
+            source: local_peer(),
+        }];
+        for _ in 0..4 {
+            assert_eq!(swim.piggyback().await, expected);
+        }
+        assert!(swim.piggyback().await.is_empty());
+    }
+
+    #[test]
+    fn test_suspicion_timeout_shrinks_with_confirmations() {
+        let max = Duration::from_secs(3);
+        let min = Duration::from_millis(750);
+        let mut suspicion = Suspicion::new(PeerId::new([1u8; 32]), min, max);
+        assert_eq!(suspicion.timeout(3), max);
+
+        // log(2) / log(4) of the way down after one confirmation
+        suspicion.sources.insert(PeerId::new([2u8; 32]));
+        assert_eq!(suspicion.timeout(3), Duration::from_millis(1875));
+
+        for id in [3u8, 4] {
+            suspicion.sources.insert(PeerId::new([id; 32]));
+        }
+        assert_eq!(suspicion.timeout(3), min);
+    }
+
+    #[tokio::test]
+    async fn test_confirmed_suspicion_expires_sooner() {
+        let config = SwimConfig::new(Duration::from_secs(60), Duration::from_secs(60))
+            .with_suspicion(Duration::from_millis(200), 2);
+        let swim = SwimDetector::with_config(local_peer(), config, test_transport());
+        let confirmed = PeerId::new([1u8; 32]);
+        let unconfirmed = PeerId::new([2u8; 32]);
+        swim.mark_alive(confirmed).await;
+        swim.mark_alive(unconfirmed).await;
+
+        let suspect = |peer, source| MemberUpdate {
+            peer,
+            state: PeerState::Suspect,
+            incarnation: 0,
+            source: PeerId::new([source; 32]),
+        };
+        assert!(swim.apply_update(suspect(unconfirmed, 3)).await);
+        assert!(swim.apply_update(suspect(confirmed, 3)).await);
+        assert!(!swim.apply_update(suspect(confirmed, 4)).await);
+        assert!(!swim.apply_update(suspect(confirmed, 5)).await);
+
+        // A repeat from a member already counted is not a confirmation
+        assert!(!swim.apply_update(suspect(unconfirmed, 3)).await);
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(swim.get_state(&confirmed).await, Some(PeerState::Dead));
+        assert_eq!(swim.get_state(&unconfirmed).await, Some(PeerState::Suspect));
+    }
+
+    #[tokio::test]
+    async fn test_local_health_stretches_and_recovers() {
+        let network = MemoryNetwork::new();
+        let a = memory_detector(&network, 1).await;
+        let silent = PeerId::new([2u8; 32]);
+        let _silent_transport = network.add_node(silent).await.expect("silent");
+        a.mark_alive(silent).await;
+
+        // Unanswered probes make us doubt ourselves and slow down
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        let health = a.local_health();
+        assert!(health >= 1);
+        assert_eq!(
+            a.effective_probe_period(),
+            Duration::from_millis(100) * (health + 1)
+        );
+        assert_eq!(
+            a.effective_ack_timeout(),
+            Duration::from_millis(40) * (health + 1)
+        );
+
+        // Timely acks from a live peer restore full speed
+        a.remove_peer(&silent).await;
+        let b = memory_detector(&network, 3).await;
+        a.mark_alive(b.local_peer).await;
+        for _ in 0..50 {
+            if a.local_health() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(a.local_health(), 0);
+        assert_eq!(a.effective_probe_period(), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_refuting_raises_local_health() {
+        let swim = SwimDetector::new(local_peer(), 60, 60, test_transport());
+        let accusation = MemberUpdate {
+            peer: local_peer(),
+            state: PeerState::Suspect,
+            incarnation: 0,
+            source: PeerId::new([1u8; 32]),
+        };
+        assert!(!swim.apply_update(accusation).await);
+        assert_eq!(swim.incarnation(), 1);
+        assert_eq!(swim.local_health(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_claim_at_maximum_incarnation_is_ignored() {
+        let swim = SwimDetector::new(local_peer(), 60, 60, test_transport());
+        let accusation = MemberUpdate {
+            peer: local_peer(),
+            state: PeerState::Dead,
+            incarnation: u64::MAX,
+            source: PeerId::new([1u8; 32]),
+        };
+        assert!(!swim.apply_update(accusation).await);
+        assert_eq!(swim.incarnation(), 0);
+        assert_eq!(swim.local_health(), 0);
+        assert!(swim.piggyback().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_local_health_is_bounded() {
+        let config = SwimConfig::new(Duration::from_secs(60), Duration::from_secs(60))
+            .with_max_local_health(2);
+        let swim = SwimDetector::with_config(local_peer(), config, test_transport());
+
+        for _ in 0..5 {
+            swim.adjust_health(1);
+        }
+        assert_eq!(swim.local_health(), 2);
+        assert_eq!(swim.effective_probe_period(), Duration::from_secs(180));
+
+        for _ in 0..5 {
+            swim.adjust_health(-1);
+        }
+        assert_eq!(swim.local_health(), 0);
+    }
+}
@@ -87,6 +87,8 @@ pub enum MessageType {
     Presence,
     /// Control messages (simulator internal)
     Control,
+    /// Relayed circuit messages
+    Relay,
 }
 
 /// Network link configuration between nodes
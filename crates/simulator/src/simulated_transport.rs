@@ -9,10 +9,14 @@ use bytes::Bytes;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
 
-use saorsa_gossip_transport::{GossipTransport, StreamType};
+use saorsa_gossip_transport::{
+    CloseReason, DisconnectReason, GossipTransport, PeerSendQueue, SendQueueConfig, StreamType,
+    TransportEvent, EVENT_CHANNEL_CAPACITY,
+};
 use saorsa_gossip_types::PeerId;
+use tracing::warn;
 
 use crate::{MessageType, NetworkSimulator, NodeId, SimulatedMessage};
 
@@ -20,6 +24,9 @@ use crate::{MessageType, NetworkSimulator, NodeId, SimulatedMessage};
 type PeerMap = HashMap<PeerId, NodeId>;
 type NodePeerMap = HashMap<NodeId, PeerId>;
 
+/// Capacity of each transport's receive channel
+const RECEIVE_CHANNEL_CAPACITY: usize = 1024;
+
 /// Simulated transport that bridges simulator and gossip protocols
 pub struct SimulatedGossipTransport {
     /// This peer's ID
@@ -34,11 +41,17 @@ pub struct SimulatedGossipTransport {
     #[allow(dead_code)]
     node_to_peer: Arc<RwLock<NodePeerMap>>,
     /// Channel for receiving messages
-    receiver: Arc<Mutex<mpsc::UnboundedReceiver<(PeerId, StreamType, Bytes)>>>,
+    receiver: Arc<Mutex<mpsc::Receiver<(PeerId, StreamType, Bytes)>>>,
     /// Channel for sending to receiver
-    sender: mpsc::UnboundedSender<(PeerId, StreamType, Bytes)>,
+    sender: mpsc::Sender<(PeerId, StreamType, Bytes)>,
+    /// Per-peer send queue limits and drop policies
+    send_queue_config: SendQueueConfig,
+    /// Outbound queue per peer, drained into the simulator by a worker task
+    send_queues: Arc<RwLock<HashMap<PeerId, Arc<PeerSendQueue>>>>,
     /// Listening state
     listening: Arc<RwLock<bool>>,
+    /// Transport event broadcaster
+    events: broadcast::Sender<TransportEvent>,
 }
 
 impl SimulatedGossipTransport {
@@ -50,7 +63,7 @@ impl SimulatedGossipTransport {
         peer_to_node: Arc<RwLock<PeerMap>>,
         node_to_peer: Arc<RwLock<NodePeerMap>>,
     ) -> Self {
-        let (sender, receiver) = mpsc::unbounded_channel();
+        let (sender, receiver) = mpsc::channel(RECEIVE_CHANNEL_CAPACITY);
 
         Self {
             peer_id,
@@ -60,21 +73,81 @@ impl SimulatedGossipTransport {
             node_to_peer,
             receiver: Arc::new(Mutex::new(receiver)),
             sender,
+            send_queue_config: SendQueueConfig::default(),
+            send_queues: Arc::new(RwLock::new(HashMap::new())),
             listening: Arc::new(RwLock::new(false)),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
         }
     }
 
+    /// Set per-peer send queue limits and drop policies
+    pub fn with_send_queue(mut self, config: SendQueueConfig) -> Self {
+        self.send_queue_config = config;
+        self
+    }
+
     /// Get the channel sender for message delivery
-    pub fn get_sender(&self) -> mpsc::UnboundedSender<(PeerId, StreamType, Bytes)> {
+    pub fn get_sender(&self) -> mpsc::Sender<(PeerId, StreamType, Bytes)> {
         self.sender.clone()
     }
 
+    /// Get the send queue for a peer, starting its worker on first use
+    async fn send_queue(&self, peer: PeerId, target_node: NodeId) -> Arc<PeerSendQueue> {
+        let mut queues = self.send_queues.write().await;
+        if let Some(queue) = queues.get(&peer) {
+            return Arc::clone(queue);
+        }
+
+        let queue = Arc::new(PeerSendQueue::new(
+            peer,
+            self.send_queue_config.clone(),
+            self.events.clone(),
+        ));
+        queues.insert(peer, Arc::clone(&queue));
+        self.spawn_send_worker(Arc::clone(&queue), target_node);
+        queue
+    }
+
+    /// Spawn the task that hands a peer's queued messages to the simulator
+    ///
+    /// If the simulator rejects a message the queue is closed and removed,
+    /// failing any senders still waiting for room.
+    fn spawn_send_worker(&self, queue: Arc<PeerSendQueue>, target_node: NodeId) {
+        let simulator = Arc::clone(&self.simulator);
+        let send_queues = Arc::clone(&self.send_queues);
+        let node_id = self.node_id;
+
+        tokio::spawn(async move {
+            while let Some((stream_type, data)) = queue.pop().await {
+                let message_type = Self::stream_to_message_type(stream_type);
+                let sim = simulator.read().await;
+                if let Err(e) = sim
+                    .send_message(node_id, target_node, data.to_vec(), message_type)
+                    .await
+                {
+                    warn!("Failed to send to peer {}: {:?}", queue.peer(), e);
+                    queue.close();
+                    break;
+                }
+            }
+
+            let mut queues = send_queues.write().await;
+            if queues
+                .get(&queue.peer())
+                .is_some_and(|current| Arc::ptr_eq(current, &queue))
+            {
+                queues.remove(&queue.peer());
+            }
+        });
+    }
+
     /// Convert StreamType to simulator MessageType
     fn stream_to_message_type(stream: StreamType) -> MessageType {
         match stream {
             StreamType::Membership => MessageType::Membership,
             StreamType::PubSub => MessageType::PubSub,
             StreamType::Bulk => MessageType::CrdtSync, // Bulk used for CRDT and other data
+            StreamType::Relay => MessageType::Relay,
         }
     }
 
@@ -86,13 +159,14 @@ impl SimulatedGossipTransport {
             MessageType::CrdtSync => StreamType::Bulk,
             MessageType::Presence => StreamType::Bulk, // Map to Bulk stream
             MessageType::Control => StreamType::Membership, // Map to Membership stream
+            MessageType::Relay => StreamType::Relay,
         }
     }
 }
 
 #[async_trait::async_trait]
 impl GossipTransport for SimulatedGossipTransport {
-    async fn dial(&self, peer: PeerId, _addr: SocketAddr) -> Result<()> {
+    async fn dial(&self, peer: PeerId, addr: SocketAddr) -> Result<()> {
         // In simulation, dialing just records the peer mapping
         // The simulator already has all nodes connected based on topology
         let node_map = self.peer_to_node.read().await;
@@ -101,6 +175,9 @@ impl GossipTransport for SimulatedGossipTransport {
             // Register the peer if not already known
             // This would normally be done during network initialization
         }
+        let _ = self
+            .events
+            .send(TransportEvent::PeerConnected { peer, addr });
         Ok(())
     }
 
@@ -127,23 +204,44 @@ impl GossipTransport for SimulatedGossipTransport {
 
     async fn close(&self) -> Result<()> {
         *self.listening.write().await = false;
+        for queue in self
+            .send_queues
+            .write()
+            .await
+            .drain()
+            .map(|(_, queue)| queue)
+        {
+            queue.drain();
+        }
+        Ok(())
+    }
+
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+        // The worker hands queued messages to the simulator before exiting
+        if let Some(queue) = self.send_queues.write().await.remove(&peer) {
+            queue.drain();
+        }
+        let _ = self.events.send(TransportEvent::PeerDisconnected {
+            peer,
+            reason: DisconnectReason::LocalDisconnect(reason),
+        });
         Ok(())
     }
 
     async fn send_to_peer(&self, peer: PeerId, stream_type: StreamType, data: Bytes) -> Result<()> {
         // Look up the target node ID
-        let node_map = self.peer_to_node.read().await;
-        let target_node = node_map
+        let target_node = *self
+            .peer_to_node
+            .read()
+            .await
             .get(&peer)
             .ok_or_else(|| anyhow!("Unknown peer: {:?}", peer))?;
 
-        // Send through the simulator
-        let message_type = Self::stream_to_message_type(stream_type);
-        let sim = self.simulator.read().await;
-        sim.send_message(self.node_id, *target_node, data.to_vec(), message_type)
-            .await?;
-
-        Ok(())
+        // Queue for the worker that sends through the simulator
+        self.send_queue(peer, target_node)
+            .await
+            .push(stream_type, data)
+            .await
     }
 
     async fn receive_message(&self) -> Result<(PeerId, StreamType, Bytes)> {
@@ -154,6 +252,10 @@ impl GossipTransport for SimulatedGossipTransport {
             .await
             .ok_or_else(|| anyhow!("Transport closed"))
     }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        self.events.subscribe()
+    }
 }
 
 /// Builder for creating a simulated gossip network
@@ -225,21 +327,27 @@ impl SimulatedGossipNetwork {
         message: SimulatedMessage,
     ) -> Result<()> {
         // Look up the peer IDs
-        let node_map = self.node_to_peer.read().await;
-        let to_peer = node_map
-            .get(&to_node)
-            .ok_or_else(|| anyhow!("Unknown node: {}", to_node))?;
-        let from_peer = node_map
-            .get(&from_node)
-            .ok_or_else(|| anyhow!("Unknown node: {}", from_node))?;
-
-        // Find the transport and deliver
+        let (to_peer, from_peer) = {
+            let node_map = self.node_to_peer.read().await;
+            let to_peer = *node_map
+                .get(&to_node)
+                .ok_or_else(|| anyhow!("Unknown node: {}", to_node))?;
+            let from_peer = *node_map
+                .get(&from_node)
+                .ok_or_else(|| anyhow!("Unknown node: {}", from_node))?;
+            (to_peer, from_peer)
+        };
+
+        // Find the transport and deliver, waiting if its receive channel is full
         for transport in &self.transports {
-            if transport.peer_id == *to_peer {
+            if transport.peer_id == to_peer {
                 let stream_type =
                     SimulatedGossipTransport::message_type_to_stream(message.message_type);
                 let bytes = Bytes::from(message.payload);
-                transport.sender.send((*from_peer, stream_type, bytes))?;
+                transport
+                    .sender
+                    .send((from_peer, stream_type, bytes))
+                    .await?;
                 return Ok(());
             }
         }
@@ -296,4 +404,37 @@ mod tests {
         // Note: In a real integration, the simulator's message delivery
         // would trigger the receive. For this test, we verify the send succeeds.
     }
+
+    #[tokio::test]
+    async fn test_send_queue_reports_congestion() {
+        let simulator = NetworkSimulator::new()
+            .with_nodes(2)
+            .with_topology(Topology::Mesh);
+        let mut network = SimulatedGossipNetwork::new(simulator);
+
+        let peer1 = PeerId::new([1u8; 32]);
+        let peer2 = PeerId::new([2u8; 32]);
+        let transport1 = network
+            .add_peer(peer1, 0)
+            .await
+            .with_send_queue(SendQueueConfig::default().with_watermarks(2, 0));
+        let _transport2 = network.add_peer(peer2, 1).await;
+        let mut events = transport1.subscribe_events();
+
+        // Hold the simulator so the send worker cannot drain the queue
+        let simulator = network.simulator();
+        let guard = simulator.write().await;
+        for _ in 0..3 {
+            transport1
+                .send_to_peer(peer2, StreamType::PubSub, Bytes::from("ihave"))
+                .await
+                .expect("pubsub drops oldest instead of failing");
+        }
+        drop(guard);
+
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(TransportEvent::Congested(peer2))
+        );
+    }
 }
@@ -2,9 +2,13 @@
 //!
 //! This module provides a production-ready QUIC transport using ant-quic.
 //! Features:
-//! - Full QUIC multiplexing for membership/pubsub/bulk streams
+//! - One long-lived bidirectional QUIC stream per peer for each of
+//!   membership/pubsub/bulk, with per-stream priorities
+//! - Independent receive queues per stream type
+//! - Bounded per-peer send queues with per-stream drop policies
 //! - NAT traversal with hole punching
 //! - Post-quantum cryptography (PQC) support via ML-KEM-768
+//! - Peer IDs verified against each connection's ML-DSA key
 //! - Connection pooling and management
 
 use anyhow::{anyhow, Result};
@@ -14,17 +18,34 @@ use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::{mpsc, RwLock};
-use tracing::{debug, error, info, warn};
-
-use crate::{BootstrapCache, GossipTransport, StreamType};
+use tokio::io::AsyncRead;
+use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::compression::{CompressionConfig, CompressionMetrics};
+use crate::fragment::{FragmentConfig, IncomingTransfer};
+use crate::framing::FrameCodec;
+use crate::handshake::{IdentityError, LocalIdentity, VerifiedIdentity};
+use crate::lifecycle::ConnectionTracker;
+use crate::rate_limit::{InboundRateLimiter, RateLimitConfig};
+use crate::send_queue::{PeerSendQueue, SendQueueConfig};
+use crate::stats::{PeerDetails, TransportStats};
+use crate::streams::{InboundQueues, PeerStreams};
+use crate::{
+    BootstrapCache, CloseReason, GossipTransport, StreamType, TransportEvent,
+    EVENT_CHANNEL_CAPACITY,
+};
 
 // Import ant-quic types (v0.14+ API)
-use ant_quic::{MlDsaPublicKey, MlDsaSecretKey, Node, NodeConfig, PeerId as AntPeerId};
+use ant_quic::{
+    generate_ml_dsa_keypair, HighLevelConnection, MlDsaPublicKey, MlDsaSecretKey, P2pConfig,
+    P2pEndpoint, P2pEvent, PeerId as AntPeerId, VarInt,
+};
 
 // Re-export key utils for tests
 #[cfg(test)]
-use ant_quic::{derive_peer_id_from_public_key, generate_ml_dsa_keypair};
+use ant_quic::derive_peer_id_from_public_key;
 
 /// Configuration for Ant-QUIC transport
 #[derive(Debug, Clone)]
@@ -33,12 +54,22 @@ pub struct AntQuicTransportConfig {
     pub bind_addr: SocketAddr,
     /// List of known peer addresses for initial discovery
     pub known_peers: Vec<SocketAddr>,
-    /// Channel capacity for backpressure (default: 10,000 messages)
+    /// Receive queue capacity per stream type for backpressure (default: 10,000 messages)
     pub channel_capacity: usize,
-    /// Maximum bytes to read per stream (default: 100 MB)
+    /// Maximum size of a single frame (default: 100 MB); larger messages are fragmented
     pub stream_read_limit: usize,
     /// Maximum number of peers to track (default: 1,000)
     pub max_peers: usize,
+    /// Per-peer send queue limits and drop policies
+    pub send_queue: SendQueueConfig,
+    /// Payload compression settings, negotiated with each peer
+    pub compression: CompressionConfig,
+    /// Fragmentation and reassembly limits for large messages
+    pub fragmentation: FragmentConfig,
+    /// Per-peer inbound rate limits (default: unlimited)
+    pub rate_limits: RateLimitConfig,
+    /// Time allowed to flush queued messages on disconnect or shutdown (default: 5 seconds)
+    pub drain_timeout: Duration,
     /// Optional ML-DSA keypair bytes (public_key, secret_key) for identity persistence
     /// If not provided, a fresh keypair is generated.
     /// This ensures the transport peer ID matches the application's identity peer ID.
@@ -54,6 +85,11 @@ impl AntQuicTransportConfig {
             channel_capacity: 10_000,
             stream_read_limit: 100 * 1024 * 1024, // 100 MB
             max_peers: 1_000,
+            send_queue: SendQueueConfig::default(),
+            compression: CompressionConfig::default(),
+            fragmentation: FragmentConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            drain_timeout: Duration::from_secs(5),
             keypair: None,
         }
     }
@@ -71,7 +107,7 @@ impl AntQuicTransportConfig {
         self
     }
 
-    /// Set stream read limit
+    /// Set stream read limit (maximum frame size)
     pub fn with_stream_read_limit(mut self, limit: usize) -> Self {
         self.stream_read_limit = limit;
         self
@@ -82,22 +118,75 @@ impl AntQuicTransportConfig {
         self.max_peers = max;
         self
     }
+
+    /// Set per-peer send queue limits and drop policies
+    pub fn with_send_queue(mut self, send_queue: SendQueueConfig) -> Self {
+        self.send_queue = send_queue;
+        self
+    }
+
+    /// Set payload compression settings
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set fragmentation and reassembly limits
+    pub fn with_fragmentation(mut self, fragmentation: FragmentConfig) -> Self {
+        self.fragmentation = fragmentation;
+        self
+    }
+
+    /// Set per-peer inbound rate limits
+    pub fn with_rate_limits(mut self, rate_limits: RateLimitConfig) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    /// Set the time allowed to flush queued messages on disconnect or shutdown
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
 }
 
 /// Ant-QUIC transport implementation
 ///
-/// Uses the ant-quic Node API for symmetric P2P networking with NAT traversal.
+/// Uses the ant-quic P2P endpoint for symmetric P2P networking with NAT traversal.
 /// All nodes can both connect to peers and accept connections.
+///
+/// Each [`StreamType`] is mapped to its own long-lived QUIC stream per peer, so
+/// a large bulk transfer never head-of-line blocks membership traffic.
+///
+/// Outbound messages go through a bounded queue per peer. When a peer cannot
+/// keep up, [`TransportEvent::Congested`] is reported and messages are dropped
+/// according to the [`SendQueueConfig`] policy for their stream type.
+///
+/// Every connection must prove that the remote holds the ML-DSA key behind
+/// its peer ID before any of its messages are delivered; connections that
+/// fail are closed with [`CloseReason::ProtocolViolation`]. Peers are always
+/// identified by their verified ID.
 pub struct AntQuicTransport {
-    /// The underlying ant-quic P2P node
-    node: Arc<Node>,
-    /// Incoming message channel (bounded for backpressure)
-    recv_tx: mpsc::Sender<(GossipPeerId, StreamType, Bytes)>,
-    recv_rx: Arc<tokio::sync::Mutex<mpsc::Receiver<(GossipPeerId, StreamType, Bytes)>>>,
+    /// The underlying ant-quic P2P endpoint
+    endpoint: Arc<P2pEndpoint>,
+    /// Per-peer outbound streams and inbound stream acceptors
+    streams: PeerStreams,
+    /// Incoming message queues, one per stream type (bounded for backpressure)
+    inbound: Arc<InboundQueues>,
+    /// Incoming streamed transfers
+    transfers: Mutex<mpsc::Receiver<IncomingTransfer>>,
+    /// Outgoing message queues, one per peer (bounded for backpressure)
+    send_queues: Arc<RwLock<HashMap<GossipPeerId, SendWorker>>>,
+    /// Transport event broadcaster
+    events: broadcast::Sender<TransportEvent>,
+    /// Connection lifecycle tracker
+    tracker: ConnectionTracker,
     /// Local peer ID (ant-quic format)
     ant_peer_id: AntPeerId,
     /// Local peer ID (gossip format)
     gossip_peer_id: GossipPeerId,
+    /// ant-quic peer ID of the connection to each verified peer
+    routes: Arc<RwLock<HashMap<GossipPeerId, AntPeerId>>>,
     /// Track connected peers with their addresses and last seen time
     connected_peers: Arc<RwLock<HashMap<GossipPeerId, (SocketAddr, Instant)>>>,
     /// Bootstrap peer IDs mapped to their addresses
@@ -153,50 +242,85 @@ impl AntQuicTransport {
             config.channel_capacity, config.max_peers, config.stream_read_limit
         );
 
-        // Create NodeConfig with our settings
-        let mut node_config_builder = NodeConfig::builder()
-            .bind_addr(config.bind_addr)
-            .known_peers(config.known_peers.clone());
-
-        // If a keypair is provided, use it to ensure peer ID consistency with application identity
-        if let Some((pub_key_bytes, sec_key_bytes)) = &config.keypair {
-            let pub_key = MlDsaPublicKey::from_bytes(pub_key_bytes)
-                .map_err(|e| anyhow!("Invalid ML-DSA public key: {}", e))?;
-            let sec_key = MlDsaSecretKey::from_bytes(sec_key_bytes)
-                .map_err(|e| anyhow!("Invalid ML-DSA secret key: {}", e))?;
-            node_config_builder = node_config_builder.keypair(pub_key, sec_key);
-            info!("Using provided ML-DSA keypair for transport identity");
-        }
+        // Create P2pConfig with our settings
+        let mut p2p_config = P2pConfig {
+            bind_addr: Some(config.bind_addr),
+            known_peers: config.known_peers.clone(),
+            ..Default::default()
+        };
 
-        let node_config = node_config_builder.build();
+        // If a keypair is provided, use it to ensure peer ID consistency with application identity.
+        // Otherwise generate one here, as we need the secret key to prove our identity.
+        let (pub_key, sec_key) = match &config.keypair {
+            Some((pub_key_bytes, sec_key_bytes)) => {
+                let pub_key = MlDsaPublicKey::from_bytes(pub_key_bytes)
+                    .map_err(|e| anyhow!("Invalid ML-DSA public key: {}", e))?;
+                let sec_key = MlDsaSecretKey::from_bytes(sec_key_bytes)
+                    .map_err(|e| anyhow!("Invalid ML-DSA secret key: {}", e))?;
+                info!("Using provided ML-DSA keypair for transport identity");
+                (pub_key, sec_key)
+            }
+            None => generate_ml_dsa_keypair()
+                .map_err(|e| anyhow!("Failed to generate ML-DSA keypair: {}", e))?,
+        };
+        let endpoint_sec_key = MlDsaSecretKey::from_bytes(sec_key.as_bytes())
+            .map_err(|e| anyhow!("Invalid ML-DSA secret key: {}", e))?;
+        p2p_config.keypair = Some((pub_key.clone(), endpoint_sec_key));
+        let identity = LocalIdentity::new(pub_key, sec_key);
 
-        // Create the Node
-        let node = Node::with_config(node_config)
+        // Create the endpoint
+        let endpoint = P2pEndpoint::new(p2p_config)
             .await
-            .map_err(|e| anyhow!("Failed to create Node: {}", e))?;
-
-        let ant_peer_id = node.peer_id();
-        let gossip_peer_id = ant_peer_id_to_gossip(&ant_peer_id);
+            .map_err(|e| anyhow!("Failed to create P2P endpoint: {}", e))?;
+
+        let ant_peer_id = endpoint.peer_id();
+        let gossip_peer_id = identity.peer();
+        if ant_peer_id_to_gossip(&ant_peer_id) != gossip_peer_id {
+            warn!(
+                "Endpoint peer ID {:?} does not match identity key {}",
+                ant_peer_id, gossip_peer_id
+            );
+        }
 
         info!("Peer ID: {:?}", ant_peer_id);
 
-        // Create bounded channel for backpressure
-        let (recv_tx, recv_rx) = mpsc::channel(config.channel_capacity);
+        // Create bounded per-stream queues for backpressure
+        let (inbound_tx, inbound) = InboundQueues::new(config.channel_capacity);
+        let (transfers_tx, transfers_rx) = mpsc::channel(TRANSFER_QUEUE_CAPACITY);
+
+        // Track connection lifecycle for upper layers
+        let events = broadcast::channel(EVENT_CHANNEL_CAPACITY).0;
+        let (tracker, lifecycle_rx) = ConnectionTracker::new(events.clone());
 
         let transport = Self {
-            node: Arc::new(node),
-            recv_tx,
-            recv_rx: Arc::new(tokio::sync::Mutex::new(recv_rx)),
+            endpoint: Arc::new(endpoint),
+            streams: PeerStreams::new(
+                identity,
+                inbound_tx,
+                InboundRateLimiter::new(config.rate_limits.clone(), events.clone()),
+                FrameCodec::new(config.stream_read_limit),
+                config.compression.clone(),
+                config.fragmentation.clone(),
+                transfers_tx,
+            ),
+            inbound: Arc::new(inbound),
+            transfers: Mutex::new(transfers_rx),
+            send_queues: Arc::new(RwLock::new(HashMap::new())),
+            events,
+            tracker,
             ant_peer_id,
             gossip_peer_id,
+            routes: Arc::new(RwLock::new(HashMap::new())),
             connected_peers: Arc::new(RwLock::new(HashMap::new())),
             bootstrap_peer_ids: Arc::new(RwLock::new(HashMap::new())),
             bootstrap_cache: bootstrap_cache.clone(),
             config: config.clone(),
         };
 
-        // Start receiving loop
-        transport.spawn_receiver();
+        // Start accepting incoming connections and following their lifecycle
+        transport.spawn_acceptor();
+        transport.spawn_lifecycle_task(lifecycle_rx);
+        transport.spawn_endpoint_event_task();
 
         // Connect to known peers if any are configured
         if !config.known_peers.is_empty() {
@@ -206,11 +330,28 @@ impl AntQuicTransport {
             );
 
             let connected = transport
-                .node
+                .endpoint
                 .connect_known_peers()
                 .await
                 .map_err(|e| anyhow!("Failed to connect to known peers: {}", e))?;
 
+            for peer_conn in transport.endpoint.connected_peers().await {
+                if let Err(e) = attach_connection(
+                    &transport.endpoint,
+                    &transport.streams,
+                    &transport.tracker,
+                    &transport.routes,
+                    &peer_conn.peer_id,
+                )
+                .await
+                {
+                    warn!(
+                        "Failed to attach known peer {}: {}",
+                        peer_conn.remote_addr, e
+                    );
+                }
+            }
+
             info!(
                 "✓ Connected to {}/{} known peer(s)",
                 connected,
@@ -287,107 +428,209 @@ impl AntQuicTransport {
     /// This returns the public address of this endpoint as seen by other peers.
     /// Returns `None` if no external address has been discovered yet.
     pub fn get_external_address(&self) -> Option<SocketAddr> {
-        self.node.external_addr()
+        self.endpoint.external_addr()
+    }
+
+    /// Get outbound compression metrics for a stream type
+    pub fn compression_metrics(&self, stream_type: StreamType) -> CompressionMetrics {
+        self.streams.compression_stats().snapshot(stream_type)
+    }
+
+    /// Get the number of messages queued for a peer
+    pub async fn send_queue_depth(&self, peer: &GossipPeerId) -> usize {
+        self.send_queues
+            .read()
+            .await
+            .get(peer)
+            .map_or(0, |worker| worker.queue.depth())
     }
 
-    /// Spawn background task to receive incoming messages
+    /// Get traffic statistics per peer and per stream type
     ///
-    /// This spawns two tasks:
-    /// 1. A receiver task that calls `node.recv()` to receive messages from ALL connected peers
-    /// 2. An acceptor task that calls `node.accept()` to track incoming connections
+    /// Includes the current RTT estimate and send queue depth of every
+    /// connected peer, plus aggregate totals since the transport started.
+    pub async fn stats(&self) -> TransportStats {
+        let mut details: HashMap<GossipPeerId, PeerDetails> = self
+            .connected_peers
+            .read()
+            .await
+            .iter()
+            .map(|(peer, (addr, _))| {
+                let details = PeerDetails {
+                    addr: Some(*addr),
+                    ..PeerDetails::default()
+                };
+                (*peer, details)
+            })
+            .collect();
+
+        for (peer, worker) in self.send_queues.read().await.iter() {
+            details.entry(*peer).or_default().queue_depth = worker.queue.depth();
+        }
+
+        for (peer, details) in details.iter_mut() {
+            if let Ok(Some(conn)) = self
+                .endpoint
+                .get_quic_connection(&self.ant_peer_for(peer).await)
+            {
+                details.rtt = Some(conn.rtt());
+            }
+        }
+
+        self.streams.traffic().snapshot(details)
+    }
+
+    /// Receive the next message on a single stream type
     ///
-    /// IMPORTANT: The receiver must start immediately, not wait for accept(), because:
-    /// - `node.recv()` receives from ALL connected peers (both inbound and outbound)
-    /// - If we only dial out (never receive incoming), we still need to receive messages
-    /// - Waiting for accept() would block receiving on outbound-only connections
-    fn spawn_receiver(&self) {
-        let node = Arc::clone(&self.node);
-        let recv_tx = self.recv_tx.clone();
-        let connected_peers = Arc::clone(&self.connected_peers);
-        let max_peers = self.config.max_peers;
+    /// Each stream type has its own inbound queue, so consumers can service
+    /// membership traffic independently of bulk transfers.
+    pub async fn receive_on(
+        &self,
+        stream_type: StreamType,
+    ) -> Result<(GossipPeerId, StreamType, Bytes)> {
+        let message = self
+            .inbound
+            .recv(stream_type)
+            .await
+            .ok_or_else(|| anyhow!("Receive channel closed"))?;
+        update_peer_last_seen(&self.connected_peers, message.0).await;
+        Ok(message)
+    }
+
+    /// Stream a large payload to a peer
+    ///
+    /// Reads `len` bytes from `reader` and sends them on a dedicated stream in
+    /// fragments, so multi-megabyte transfers neither need to fit in memory
+    /// nor hold up other messages to the peer. The peer receives the data
+    /// through [`AntQuicTransport::receive_large`].
+    pub async fn send_large<R>(
+        &self,
+        peer: GossipPeerId,
+        stream_type: StreamType,
+        reader: R,
+        len: u64,
+    ) -> Result<()>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let conn = self.connection(&peer).await?;
+        self.streams
+            .send_large(peer, &conn, stream_type, reader, len)
+            .await
+    }
+
+    /// Receive the next streamed transfer sent with `send_large`
+    ///
+    /// The transfer's data arrives chunk by chunk as the sender streams it.
+    pub async fn receive_large(&self) -> Result<IncomingTransfer> {
+        let transfer = self
+            .transfers
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| anyhow!("Transfer channel closed"))?;
+        update_peer_last_seen(&self.connected_peers, transfer.peer).await;
+        Ok(transfer)
+    }
 
-        // Task 1: Receive messages from ALL connected peers (inbound and outbound)
-        // This must start immediately, not wait for accept()
-        let node_recv = Arc::clone(&node);
-        let tx = recv_tx;
-        let peers_recv = Arc::clone(&connected_peers);
+    /// Spawn background task to accept incoming connections
+    ///
+    /// Every accepted connection is tracked and gets a stream acceptor, which
+    /// reads the per-stream-type streams the remote opens towards us. Outbound
+    /// connections are attached when they are dialed, so receiving never
+    /// depends on the remote having dialed us.
+    fn spawn_acceptor(&self) {
+        let endpoint = Arc::clone(&self.endpoint);
+        let streams = self.streams.clone();
+        let tracker = self.tracker.clone();
+        let routes = Arc::clone(&self.routes);
+        let peers_accept = Arc::clone(&self.connected_peers);
+        let max_peers = self.config.max_peers;
 
         tokio::spawn(async move {
-            info!("Ant-QUIC receiver task started (global message receiver)");
+            info!("Ant-QUIC acceptor task started (incoming connection handler)");
 
             loop {
-                match node_recv.recv(Duration::from_secs(60)).await {
-                    Ok((from_peer_id, data)) => {
-                        if data.is_empty() {
-                            continue;
-                        }
+                if let Some(peer_conn) = endpoint.accept().await {
+                    let peer_id = peer_conn.peer_id;
+                    let peer_addr = peer_conn.remote_addr;
 
-                        let from_gossip_id = ant_peer_id_to_gossip(&from_peer_id);
+                    info!("Accepted connection from {:?} at {}", peer_id, peer_addr);
 
-                        // Parse stream type from first byte
-                        let stream_type = match data.first() {
-                            Some(&0) => StreamType::Membership,
-                            Some(&1) => StreamType::PubSub,
-                            Some(&2) => StreamType::Bulk,
-                            Some(&other) => {
-                                warn!("Unknown stream type byte: {}", other);
-                                continue;
+                    // Verify the peer, then track it under its proven ID
+                    let endpoint = Arc::clone(&endpoint);
+                    let streams = streams.clone();
+                    let tracker = tracker.clone();
+                    let routes = Arc::clone(&routes);
+                    let peers_accept = Arc::clone(&peers_accept);
+                    tokio::spawn(async move {
+                        match attach_connection(&endpoint, &streams, &tracker, &routes, &peer_id)
+                            .await
+                        {
+                            Ok(peer) => {
+                                add_peer_with_lru(&peers_accept, peer, peer_addr, max_peers).await;
                             }
-                            None => continue,
-                        };
-
-                        // Extract payload (skip first byte)
-                        let payload = if data.len() > 1 {
-                            Bytes::copy_from_slice(&data[1..])
-                        } else {
-                            Bytes::new()
-                        };
-
-                        // Update peer tracking - only update timestamp if already known
-                        update_peer_last_seen(&peers_recv, from_gossip_id).await;
-
-                        // Forward to recv channel
-                        if let Err(e) = tx.send((from_gossip_id, stream_type, payload)).await {
-                            error!("Failed to forward message: {}", e);
-                            break;
+                            Err(e) => warn!("Rejected connection from {}: {}", peer_addr, e),
                         }
+                    });
+                }
 
-                        debug!(
-                            "Forwarded {} bytes ({:?}) from {:?}",
-                            data.len() - 1,
-                            stream_type,
-                            from_gossip_id
-                        );
+                // Small delay to prevent busy loop
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+    }
+
+    /// Spawn background task keeping peer state in step with connection lifecycle
+    ///
+    /// Drops the streams, send queue and tracking entry of a peer whose
+    /// connection closed, and follows peers that migrate to a new address.
+    fn spawn_lifecycle_task(&self, mut lifecycle_rx: mpsc::UnboundedReceiver<TransportEvent>) {
+        let streams = self.streams.clone();
+        let send_queues = Arc::clone(&self.send_queues);
+        let routes = Arc::clone(&self.routes);
+        let connected_peers = Arc::clone(&self.connected_peers);
+
+        tokio::spawn(async move {
+            while let Some(event) = lifecycle_rx.recv().await {
+                match event {
+                    TransportEvent::PeerDisconnected { peer, .. } => {
+                        if let Some(worker) = send_queues.write().await.remove(&peer) {
+                            worker.queue.close();
+                        }
+                        streams.forget(&peer).await;
+                        routes.write().await.remove(&peer);
+                        connected_peers.write().await.remove(&peer);
                     }
-                    Err(e) => {
-                        // Timeout is expected, just continue
-                        debug!("Receive timeout or error: {}", e);
+                    TransportEvent::PathMigrated { peer, to, .. } => {
+                        if let Some((addr, _)) = connected_peers.write().await.get_mut(&peer) {
+                            *addr = to;
+                        }
                     }
+                    _ => {}
                 }
             }
         });
+    }
 
-        // Task 2: Accept incoming connections and track them
-        // This runs separately from receiving - it only tracks peer addresses
-        let peers_accept = connected_peers;
+    /// Spawn background task forwarding endpoint events to the tracker
+    fn spawn_endpoint_event_task(&self) {
+        let mut endpoint_events = self.endpoint.subscribe();
+        let tracker = self.tracker.clone();
 
         tokio::spawn(async move {
-            info!("Ant-QUIC acceptor task started (incoming connection handler)");
-
             loop {
-                if let Some(peer_conn) = node.accept().await {
-                    let peer_id = peer_conn.peer_id;
-                    let peer_addr = peer_conn.remote_addr;
-                    let gossip_peer_id = ant_peer_id_to_gossip(&peer_id);
-
-                    info!("Accepted connection from {:?} at {}", peer_id, peer_addr);
-
-                    // Track the peer
-                    add_peer_with_lru(&peers_accept, gossip_peer_id, peer_addr, max_peers).await;
+                match endpoint_events.recv().await {
+                    Ok(P2pEvent::ExternalAddressDiscovered { addr }) => {
+                        tracker.observe_external_address(addr);
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("Skipped {} endpoint events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
                 }
-
-                // Small delay to prevent busy loop
-                tokio::time::sleep(Duration::from_millis(10)).await;
             }
         });
     }
@@ -397,13 +640,268 @@ impl AntQuicTransport {
         add_peer_with_lru(&self.connected_peers, peer_id, addr, self.config.max_peers).await;
     }
 
-    /// Remove a peer from the connected peers map
+    /// Remove a peer from the connected peers map and drop its streams and queue
     async fn remove_peer(&self, peer_id: &GossipPeerId) {
+        if let Some(worker) = self.send_queues.write().await.remove(peer_id) {
+            worker.queue.close();
+        }
+        self.streams.forget(peer_id).await;
+        self.routes.write().await.remove(peer_id);
         let mut peers = self.connected_peers.write().await;
         if peers.remove(peer_id).is_some() {
             debug!("Removed peer {:?} after connection failure", peer_id);
         }
     }
+
+    /// Get the ant-quic peer ID of the connection to a peer
+    async fn ant_peer_for(&self, peer: &GossipPeerId) -> AntPeerId {
+        ant_peer_for(&self.routes, peer).await
+    }
+
+    /// Get the connection to a peer, once it has proven to be that peer
+    ///
+    /// Also makes sure we read any streams the peer opens back to us.
+    async fn connection(&self, peer: &GossipPeerId) -> Result<HighLevelConnection> {
+        let ant_peer_id = self.ant_peer_for(peer).await;
+        let conn = self
+            .endpoint
+            .get_quic_connection(&ant_peer_id)
+            .map_err(|e| anyhow!("Failed to look up connection to peer: {}", e))?
+            .ok_or_else(|| anyhow!("Not connected to peer {}", peer))?;
+
+        self.streams.attach(conn.clone()).await;
+        let identity = self.streams.identify(&conn).await?;
+        if identity.peer() != *peer {
+            return Err(IdentityError::PeerIdMismatch {
+                claimed: *peer,
+                actual: identity.peer(),
+            }
+            .into());
+        }
+
+        self.tracker.track(*peer, conn.clone());
+        Ok(conn)
+    }
+
+    /// Get the send queue for a peer, starting its send worker on first use
+    async fn send_queue(&self, peer: GossipPeerId) -> Arc<PeerSendQueue> {
+        if let Some(worker) = self.send_queues.read().await.get(&peer) {
+            return Arc::clone(&worker.queue);
+        }
+
+        let mut queues = self.send_queues.write().await;
+        if let Some(worker) = queues.get(&peer) {
+            return Arc::clone(&worker.queue);
+        }
+
+        let queue = Arc::new(PeerSendQueue::new(
+            peer,
+            self.config.send_queue.clone(),
+            self.events.clone(),
+        ));
+        let task = self.spawn_send_worker(Arc::clone(&queue));
+        queues.insert(
+            peer,
+            SendWorker {
+                queue: Arc::clone(&queue),
+                task,
+            },
+        );
+        queue
+    }
+
+    /// Spawn the task that drains a peer's send queue onto its streams
+    ///
+    /// On a write failure the queue is closed and the peer is dropped, which
+    /// fails any senders still waiting for room.
+    fn spawn_send_worker(&self, queue: Arc<PeerSendQueue>) -> JoinHandle<()> {
+        let endpoint = Arc::clone(&self.endpoint);
+        let streams = self.streams.clone();
+        let send_queues = Arc::clone(&self.send_queues);
+        let routes = Arc::clone(&self.routes);
+        let connected_peers = Arc::clone(&self.connected_peers);
+        let peer = queue.peer();
+
+        tokio::spawn(async move {
+            while let Some((stream_type, data)) = queue.pop().await {
+                let len = data.len();
+                let ant_peer_id = ant_peer_for(&routes, &peer).await;
+                let result = match endpoint.get_quic_connection(&ant_peer_id) {
+                    Ok(Some(conn)) => streams.send(peer, &conn, stream_type, data).await,
+                    Ok(None) => Err(anyhow!("Not connected to peer {}", peer)),
+                    Err(e) => Err(anyhow!("Failed to look up connection to peer: {}", e)),
+                };
+
+                match result {
+                    Ok(()) => {
+                        streams.traffic().record_sent(peer, stream_type, len);
+                        debug!(
+                            "Successfully sent {} bytes to peer {} on {:?} stream",
+                            len, peer, stream_type
+                        );
+                    }
+                    Err(e) => {
+                        warn!("Failed to send to peer {}: {}", peer, e);
+                        streams.traffic().record_error(peer, stream_type);
+                        queue.close();
+                        break;
+                    }
+                }
+            }
+
+            // Only tear down state that still belongs to this queue
+            let mut queues = send_queues.write().await;
+            if queues
+                .get(&peer)
+                .is_some_and(|current| Arc::ptr_eq(&current.queue, &queue))
+            {
+                queues.remove(&peer);
+                drop(queues);
+                streams.forget(&peer).await;
+                if connected_peers.write().await.remove(&peer).is_some() {
+                    debug!("Removed peer {:?} after connection failure", peer);
+                }
+            }
+            debug!("Send worker for peer {} stopped", peer);
+        })
+    }
+
+    /// Flush a peer's queued messages, then close its connection with `reason`
+    ///
+    /// Gives up flushing at `deadline`; the connection is closed either way.
+    async fn close_peer(
+        &self,
+        peer: GossipPeerId,
+        reason: CloseReason,
+        deadline: tokio::time::Instant,
+    ) -> Result<()> {
+        let ant_peer_id = self.ant_peer_for(&peer).await;
+        let worker = self.send_queues.write().await.remove(&peer);
+        let conn = match self.endpoint.get_quic_connection(&ant_peer_id) {
+            Ok(Some(conn)) => Some(conn),
+            _ => None,
+        };
+        if worker.is_none() && conn.is_none() {
+            return Err(anyhow!("Not connected to peer {}", peer));
+        }
+
+        // Let the send worker write out everything already queued
+        if let Some(SendWorker { queue, mut task }) = worker {
+            queue.drain();
+            if tokio::time::timeout_at(deadline, &mut task).await.is_err() {
+                warn!(
+                    "Timed out flushing {} messages to peer {}",
+                    queue.depth(),
+                    peer
+                );
+                queue.close();
+                task.abort();
+            }
+        }
+
+        // Wait for the peer to acknowledge the flushed streams
+        let acknowledged = self.streams.finish(&peer).await;
+        if tokio::time::timeout_at(deadline, futures::future::join_all(acknowledged))
+            .await
+            .is_err()
+        {
+            debug!("Peer {} did not acknowledge all streams before close", peer);
+        }
+
+        if let Some(conn) = conn {
+            conn.close(VarInt::from_u32(reason.code()), reason.as_str().as_bytes());
+        }
+        let _ = self.endpoint.disconnect(&ant_peer_id).await;
+
+        self.tracker.disconnected(peer, reason);
+        self.remove_peer(&peer).await;
+        Ok(())
+    }
+
+    /// Gracefully shut down the transport
+    ///
+    /// Flushes the queued messages of every peer in parallel, closes each
+    /// connection with [`CloseReason::Shutdown`] and stops the endpoint.
+    /// Messages still queued after `timeout` are discarded.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<()> {
+        info!("Shutting down Ant-QUIC transport");
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        let mut peers: Vec<GossipPeerId> = self.send_queues.read().await.keys().copied().collect();
+        peers.extend(self.connected_peers.read().await.keys().copied());
+        peers.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        peers.dedup();
+
+        futures::future::join_all(
+            peers
+                .into_iter()
+                .map(|peer| self.close_peer(peer, CloseReason::Shutdown, deadline)),
+        )
+        .await;
+
+        self.endpoint.shutdown().await;
+        Ok(())
+    }
+}
+
+/// A peer's send queue and the task draining it onto the network
+struct SendWorker {
+    queue: Arc<PeerSendQueue>,
+    task: JoinHandle<()>,
+}
+
+/// Incoming transfers waiting for `receive_large`
+const TRANSFER_QUEUE_CAPACITY: usize = 64;
+
+/// Verify the identity behind a connection, then track it and accept its streams
+///
+/// Returns the verified peer ID. A connection that fails to prove its
+/// identity is closed with [`CloseReason::ProtocolViolation`].
+async fn attach_connection(
+    endpoint: &P2pEndpoint,
+    streams: &PeerStreams,
+    tracker: &ConnectionTracker,
+    routes: &RwLock<HashMap<GossipPeerId, AntPeerId>>,
+    peer_id: &AntPeerId,
+) -> Result<GossipPeerId> {
+    let conn = endpoint
+        .get_quic_connection(peer_id)
+        .map_err(|e| anyhow!("Failed to look up connection for peer {:?}: {}", peer_id, e))?
+        .ok_or_else(|| anyhow!("No connection to attach for peer {:?}", peer_id))?;
+
+    streams.attach(conn.clone()).await;
+    let identity = match streams.identify(&conn).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            let reason = CloseReason::ProtocolViolation;
+            conn.close(VarInt::from_u32(reason.code()), reason.as_str().as_bytes());
+            let _ = endpoint.disconnect(peer_id).await;
+            return Err(anyhow!(
+                "Peer {:?} failed identity verification: {}",
+                peer_id,
+                e
+            ));
+        }
+    };
+
+    let peer = identity.peer();
+    debug!("Connection {:?} verified as peer {}", peer_id, peer);
+    routes.write().await.insert(peer, *peer_id);
+    tracker.track(peer, conn);
+    Ok(peer)
+}
+
+/// Get the ant-quic peer ID of the connection to a peer
+///
+/// Falls back to the peer's own ID for peers not verified yet.
+async fn ant_peer_for(
+    routes: &RwLock<HashMap<GossipPeerId, AntPeerId>>,
+    peer: &GossipPeerId,
+) -> AntPeerId {
+    match routes.read().await.get(peer) {
+        Some(ant_peer_id) => *ant_peer_id,
+        None => gossip_peer_id_to_ant(peer),
+    }
 }
 
 /// Add a peer with LRU eviction (standalone helper for use in spawned tasks)
@@ -464,22 +962,47 @@ impl GossipTransport for AntQuicTransport {
 
         // Connect to the peer by address
         let start = Instant::now();
-        match self.node.connect_addr(addr).await {
+        match self.endpoint.connect(addr).await {
             Ok(peer_conn) => {
-                let gossip_id = ant_peer_id_to_gossip(&peer_conn.peer_id);
                 let rtt_ms = start.elapsed().as_millis() as u32;
+
+                // Verify the peer before tracking it and reading its streams
+                let gossip_id = attach_connection(
+                    &self.endpoint,
+                    &self.streams,
+                    &self.tracker,
+                    &self.routes,
+                    &peer_conn.peer_id,
+                )
+                .await?;
+                if gossip_id != peer {
+                    warn!(
+                        "Peer at {} proved identity {} instead of {}",
+                        addr, gossip_id, peer
+                    );
+                    let _ = self
+                        .close_peer(
+                            gossip_id,
+                            CloseReason::ProtocolViolation,
+                            tokio::time::Instant::now(),
+                        )
+                        .await;
+                    return Err(IdentityError::PeerIdMismatch {
+                        claimed: peer,
+                        actual: gossip_id,
+                    }
+                    .into());
+                }
+
                 info!(
                     "Successfully connected to peer {} at {} (rtt: {}ms)",
                     gossip_id, addr, rtt_ms
                 );
-
-                // Track the connection
                 self.add_peer(gossip_id, addr).await;
 
                 // Update bootstrap cache if present
                 if let Some(cache) = &self.bootstrap_cache {
-                    let ant_peer_id = gossip_peer_id_to_ant(&gossip_id);
-                    cache.record_success(&ant_peer_id, rtt_ms).await;
+                    cache.record_success(&peer_conn.peer_id, rtt_ms).await;
                 }
 
                 Ok(())
@@ -503,11 +1026,20 @@ impl GossipTransport for AntQuicTransport {
         info!("Dialing bootstrap node at {}", addr);
 
         let start = Instant::now();
-        match self.node.connect_addr(addr).await {
+        match self.endpoint.connect(addr).await {
             Ok(peer_conn) => {
-                let gossip_peer_id = ant_peer_id_to_gossip(&peer_conn.peer_id);
                 let rtt_ms = start.elapsed().as_millis() as u32;
 
+                // Verify the bootstrap node, then start reading its streams
+                let gossip_peer_id = attach_connection(
+                    &self.endpoint,
+                    &self.streams,
+                    &self.tracker,
+                    &self.routes,
+                    &peer_conn.peer_id,
+                )
+                .await?;
+
                 info!(
                     "Successfully connected to bootstrap {} (PeerId: {}, rtt: {}ms)",
                     addr, gossip_peer_id, rtt_ms
@@ -524,8 +1056,7 @@ impl GossipTransport for AntQuicTransport {
 
                 // Update bootstrap cache if present
                 if let Some(cache) = &self.bootstrap_cache {
-                    let ant_peer_id = gossip_peer_id_to_ant(&gossip_peer_id);
-                    cache.record_success(&ant_peer_id, rtt_ms).await;
+                    cache.record_success(&peer_conn.peer_id, rtt_ms).await;
                 }
 
                 Ok(gossip_peer_id)
@@ -538,15 +1069,19 @@ impl GossipTransport for AntQuicTransport {
     }
 
     async fn listen(&self, _bind: SocketAddr) -> Result<()> {
-        // Node handles listening automatically
-        info!("Ant-QUIC node is listening (handled by Node)");
+        // The endpoint handles listening automatically
+        info!("Ant-QUIC node is listening (handled by P2pEndpoint)");
         Ok(())
     }
 
     async fn close(&self) -> Result<()> {
-        info!("Closing Ant-QUIC transport");
-        // Node cleanup is handled by Drop
-        Ok(())
+        self.shutdown(self.config.drain_timeout).await
+    }
+
+    async fn disconnect(&self, peer: GossipPeerId, reason: CloseReason) -> Result<()> {
+        info!("Disconnecting peer {}: {}", peer, reason);
+        let deadline = tokio::time::Instant::now() + self.config.drain_timeout;
+        self.close_peer(peer, reason, deadline).await
     }
 
     async fn send_to_peer(
@@ -562,42 +1097,53 @@ impl GossipTransport for AntQuicTransport {
             stream_type
         );
 
-        // Convert gossip PeerId to ant-quic PeerId
-        let ant_peer_id = gossip_peer_id_to_ant(&peer);
+        // Only send over a connection that has proven to belong to the peer
+        if let Err(e) = self.connection(&peer).await {
+            self.remove_peer(&peer).await;
+            return Err(e);
+        }
 
-        // Encode stream type as first byte
-        let stream_type_byte = match stream_type {
-            StreamType::Membership => 0u8,
-            StreamType::PubSub => 1u8,
-            StreamType::Bulk => 2u8,
-        };
+        // Queue for the peer's send worker, applying the stream's drop policy
+        self.send_queue(peer).await.push(stream_type, data).await
+    }
 
-        // Prepare message: [stream_type_byte | data]
-        let mut buf = Vec::with_capacity(1 + data.len());
-        buf.push(stream_type_byte);
-        buf.extend_from_slice(&data);
+    async fn receive_message(&self) -> Result<(GossipPeerId, StreamType, Bytes)> {
+        let message = self
+            .inbound
+            .recv_any()
+            .await
+            .ok_or_else(|| anyhow!("Receive channel closed"))?;
 
-        // Send via the node
-        match self.node.send(&ant_peer_id, &buf).await {
-            Ok(()) => {
-                info!("Successfully sent {} bytes to peer {}", buf.len(), peer);
-                Ok(())
-            }
-            Err(e) => {
-                warn!("Failed to send to peer {}: {}", peer, e);
-                self.remove_peer(&peer).await;
-                Err(anyhow!("Failed to send to peer: {}", e))
-            }
-        }
+        // Update peer tracking - only updates timestamp if already known
+        update_peer_last_seen(&self.connected_peers, message.0).await;
+
+        Ok(message)
     }
 
-    async fn receive_message(&self) -> Result<(GossipPeerId, StreamType, Bytes)> {
-        let mut recv_rx = self.recv_rx.lock().await;
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        self.events.subscribe()
+    }
 
-        recv_rx
-            .recv()
+    fn peer_identity(&self, peer: &GossipPeerId) -> Option<VerifiedIdentity> {
+        self.streams.identity(peer)
+    }
+
+    async fn peer_addr(&self, peer: &GossipPeerId) -> Option<SocketAddr> {
+        self.connected_peers
+            .read()
             .await
-            .ok_or_else(|| anyhow!("Receive channel closed"))
+            .get(peer)
+            .map(|(addr, _)| *addr)
+    }
+
+    async fn peer_rtt(&self, peer: &GossipPeerId) -> Option<Duration> {
+        match self
+            .endpoint
+            .get_quic_connection(&self.ant_peer_for(peer).await)
+        {
+            Ok(Some(conn)) => Some(conn.rtt()),
+            _ => None,
+        }
     }
 }
 
@@ -671,6 +1217,15 @@ mod tests {
             .await
             .expect("Failed to dial node1");
 
+        // The dial only succeeds once node1 has proven its identity
+        let identity = node2
+            .peer_identity(&node1_peer_id)
+            .expect("node1 identity verified");
+        assert_eq!(
+            GossipPeerId::from_pubkey(identity.public_key()),
+            node1_peer_id
+        );
+
         // Give connection time to establish
         sleep(Duration::from_millis(500)).await;
 
@@ -696,29 +1251,17 @@ mod tests {
 
     #[tokio::test]
     async fn test_stream_type_encoding() {
-        assert_eq!(
-            match StreamType::Membership {
-                StreamType::Membership => 0u8,
-                StreamType::PubSub => 1u8,
-                StreamType::Bulk => 2u8,
-            },
-            0u8
-        );
-        assert_eq!(
-            match StreamType::PubSub {
-                StreamType::Membership => 0u8,
-                StreamType::PubSub => 1u8,
-                StreamType::Bulk => 2u8,
-            },
-            1u8
-        );
-        assert_eq!(
-            match StreamType::Bulk {
-                StreamType::Membership => 0u8,
-                StreamType::PubSub => 1u8,
-                StreamType::Bulk => 2u8,
-            },
-            2u8
-        );
+        assert_eq!(StreamType::Membership.to_u8(), 0u8);
+        assert_eq!(StreamType::PubSub.to_u8(), 1u8);
+        assert_eq!(StreamType::Bulk.to_u8(), 2u8);
+
+        for stream_type in StreamType::ALL {
+            assert_eq!(StreamType::from_u8(stream_type.to_u8()), Some(stream_type));
+        }
+        assert_eq!(StreamType::from_u8(4), None);
+
+        // Membership must never queue behind bulk transfers
+        assert!(StreamType::Membership.priority() > StreamType::PubSub.priority());
+        assert!(StreamType::PubSub.priority() > StreamType::Bulk.priority());
     }
 }
@@ -0,0 +1,380 @@
+//! Per-connection payload compression
+//!
+//! Each side of a connection advertises the algorithms it can decode in a
+//! hello frame when a stream is opened (see [`FrameFlags::HELLO`]). A sender
+//! only compresses once it has seen the peer's hello, picking its most
+//! preferred algorithm that the peer supports, so peers without compression
+//! support keep receiving plain frames.
+//!
+//! A compressed payload starts with the algorithm tag and the original length,
+//! which lets the receiver refuse to inflate beyond its frame size limit:
+//!
+//! ```text
+//! +-----------+-------------------+--------------------+
+//! | algorithm | original len (BE) | compressed payload |
+//! | 1 byte    | 4 bytes           | ...                |
+//! +-----------+-------------------+--------------------+
+//! ```
+//!
+//! [`FrameFlags::HELLO`]: crate::FrameFlags::HELLO
+
+use bytes::{BufMut, Bytes, BytesMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::StreamType;
+
+/// Length of the compressed payload prefix
+const COMPRESSED_HEADER_LEN: usize = 5;
+
+/// Errors produced while compressing or decompressing payloads
+#[derive(thiserror::Error, Debug)]
+pub enum CompressionError {
+    #[error("Unknown compression algorithm: {0}")]
+    UnknownAlgorithm(u8),
+    #[error("Compressed payload is truncated")]
+    Truncated,
+    #[error("Decompressed size {length} exceeds limit of {max} bytes")]
+    TooLarge { length: usize, max: usize },
+    #[error("Decompressed size {actual} does not match declared size {declared}")]
+    LengthMismatch { declared: usize, actual: usize },
+    #[error("Compression failed: {0}")]
+    Codec(String),
+}
+
+/// Payload compression algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionAlgorithm {
+    /// No compression
+    None,
+    /// LZ4 block compression (fast, moderate ratio)
+    Lz4,
+    /// Zstandard compression (slower, better ratio)
+    Zstd,
+}
+
+impl CompressionAlgorithm {
+    /// Convert a wire tag to an algorithm
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::None),
+            1 => Some(Self::Lz4),
+            2 => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Convert to the wire tag
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Lz4 => 1,
+            Self::Zstd => 2,
+        }
+    }
+}
+
+/// Compression settings for a transport
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    /// Algorithms we can use, most preferred first (default: zstd, lz4)
+    pub algorithms: Vec<CompressionAlgorithm>,
+    /// Payloads smaller than this many bytes are sent uncompressed (default: 512)
+    pub threshold: usize,
+    /// Zstandard compression level (default: 3)
+    pub zstd_level: i32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            algorithms: vec![CompressionAlgorithm::Zstd, CompressionAlgorithm::Lz4],
+            threshold: 512,
+            zstd_level: 3,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Configuration that never compresses
+    pub fn disabled() -> Self {
+        Self {
+            algorithms: Vec::new(),
+            ..Self::default()
+        }
+    }
+
+    /// Set the supported algorithms, most preferred first
+    pub fn with_algorithms(mut self, algorithms: Vec<CompressionAlgorithm>) -> Self {
+        self.algorithms = algorithms
+            .into_iter()
+            .filter(|algorithm| *algorithm != CompressionAlgorithm::None)
+            .collect();
+        self
+    }
+
+    /// Set the minimum payload size for compression
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the Zstandard compression level
+    pub fn with_zstd_level(mut self, level: i32) -> Self {
+        self.zstd_level = level;
+        self
+    }
+
+    /// Encode the hello payload advertising our algorithms
+    pub(crate) fn hello(&self) -> Bytes {
+        self.algorithms
+            .iter()
+            .map(|algorithm| algorithm.to_u8())
+            .collect()
+    }
+
+    /// Pick the algorithm to use towards a peer that sent `hello`
+    ///
+    /// Unknown tags in the hello are ignored.
+    pub(crate) fn negotiate(&self, hello: &[u8]) -> CompressionAlgorithm {
+        let remote: Vec<CompressionAlgorithm> = hello
+            .iter()
+            .filter_map(|tag| CompressionAlgorithm::from_u8(*tag))
+            .collect();
+        self.algorithms
+            .iter()
+            .copied()
+            .find(|algorithm| remote.contains(algorithm))
+            .unwrap_or(CompressionAlgorithm::None)
+    }
+
+    /// Compress `data` with `algorithm` if it is large enough and shrinks
+    ///
+    /// Returns `None` if the payload should be sent as is.
+    pub(crate) fn compress(&self, algorithm: CompressionAlgorithm, data: &[u8]) -> Option<Bytes> {
+        if algorithm == CompressionAlgorithm::None
+            || data.len() < self.threshold
+            || data.len() > u32::MAX as usize
+        {
+            return None;
+        }
+
+        let compressed = match algorithm {
+            CompressionAlgorithm::None => return None,
+            CompressionAlgorithm::Lz4 => lz4_flex::block::compress(data),
+            CompressionAlgorithm::Zstd => zstd::bulk::compress(data, self.zstd_level).ok()?,
+        };
+        if compressed.len() + COMPRESSED_HEADER_LEN >= data.len() {
+            return None;
+        }
+
+        let mut buf = BytesMut::with_capacity(COMPRESSED_HEADER_LEN + compressed.len());
+        buf.put_u8(algorithm.to_u8());
+        buf.put_u32(data.len() as u32);
+        buf.put_slice(&compressed);
+        Some(buf.freeze())
+    }
+}
+
+/// Decompress a payload produced by [`CompressionConfig::compress`]
+///
+/// Refuses to inflate beyond `max_size` bytes.
+pub(crate) fn decompress(payload: &[u8], max_size: usize) -> Result<Bytes, CompressionError> {
+    let Some((header, body)) = payload.split_first_chunk::<COMPRESSED_HEADER_LEN>() else {
+        return Err(CompressionError::Truncated);
+    };
+    let algorithm = CompressionAlgorithm::from_u8(header[0])
+        .ok_or(CompressionError::UnknownAlgorithm(header[0]))?;
+    let declared = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if declared > max_size {
+        return Err(CompressionError::TooLarge {
+            length: declared,
+            max: max_size,
+        });
+    }
+
+    let data = match algorithm {
+        CompressionAlgorithm::None => body.to_vec(),
+        CompressionAlgorithm::Lz4 => lz4_flex::block::decompress(body, declared)
+            .map_err(|e| CompressionError::Codec(e.to_string()))?,
+        CompressionAlgorithm::Zstd => zstd::bulk::decompress(body, declared)
+            .map_err(|e| CompressionError::Codec(e.to_string()))?,
+    };
+    if data.len() != declared {
+        return Err(CompressionError::LengthMismatch {
+            declared,
+            actual: data.len(),
+        });
+    }
+    Ok(Bytes::from(data))
+}
+
+/// Snapshot of compression counters for one stream type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionMetrics {
+    /// Stream type the counters belong to
+    pub stream_type: StreamType,
+    /// Messages sent compressed
+    pub compressed_messages: u64,
+    /// Messages sent uncompressed
+    pub uncompressed_messages: u64,
+    /// Original size of the compressed messages in bytes
+    pub original_bytes: u64,
+    /// Size of the compressed messages on the wire in bytes
+    pub compressed_bytes: u64,
+}
+
+impl CompressionMetrics {
+    /// Compression ratio (original / compressed) of compressed messages
+    ///
+    /// Returns 1.0 when nothing has been compressed yet.
+    pub fn ratio(&self) -> f64 {
+        if self.compressed_bytes == 0 {
+            1.0
+        } else {
+            self.original_bytes as f64 / self.compressed_bytes as f64
+        }
+    }
+}
+
+/// Outbound compression counters for one stream type
+#[derive(Debug, Default)]
+struct StreamCounters {
+    compressed_messages: AtomicU64,
+    uncompressed_messages: AtomicU64,
+    original_bytes: AtomicU64,
+    compressed_bytes: AtomicU64,
+}
+
+/// Outbound compression counters for every stream type
+#[derive(Debug, Default)]
+pub(crate) struct CompressionStats {
+    membership: StreamCounters,
+    pubsub: StreamCounters,
+    bulk: StreamCounters,
+    relay: StreamCounters,
+}
+
+impl CompressionStats {
+    fn counters(&self, stream_type: StreamType) -> &StreamCounters {
+        match stream_type {
+            StreamType::Membership => &self.membership,
+            StreamType::PubSub => &self.pubsub,
+            StreamType::Bulk => &self.bulk,
+            StreamType::Relay => &self.relay,
+        }
+    }
+
+    /// Record a message sent compressed
+    pub(crate) fn record_compressed(&self, stream_type: StreamType, original: usize, wire: usize) {
+        let counters = self.counters(stream_type);
+        counters.compressed_messages.fetch_add(1, Ordering::Relaxed);
+        counters
+            .original_bytes
+            .fetch_add(original as u64, Ordering::Relaxed);
+        counters
+            .compressed_bytes
+            .fetch_add(wire as u64, Ordering::Relaxed);
+    }
+
+    /// Record a message sent uncompressed
+    pub(crate) fn record_uncompressed(&self, stream_type: StreamType) {
+        self.counters(stream_type)
+            .uncompressed_messages
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Snapshot the counters for a stream type
+    pub(crate) fn snapshot(&self, stream_type: StreamType) -> CompressionMetrics {
+        let counters = self.counters(stream_type);
+        CompressionMetrics {
+            stream_type,
+            compressed_messages: counters.compressed_messages.load(Ordering::Relaxed),
+            uncompressed_messages: counters.uncompressed_messages.load(Ordering::Relaxed),
+            original_bytes: counters.original_bytes.load(Ordering::Relaxed),
+            compressed_bytes: counters.compressed_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressible(len: usize) -> Vec<u8> {
+        b"saorsa gossip "
+            .iter()
+            .copied()
+            .cycle()
+            .take(len)
+            .collect()
+    }
+
+    #[test]
+    fn test_roundtrip_each_algorithm() {
+        let config = CompressionConfig::default();
+        let data = compressible(4096);
+
+        for algorithm in [CompressionAlgorithm::Lz4, CompressionAlgorithm::Zstd] {
+            let compressed = config.compress(algorithm, &data).expect("compresses");
+            assert!(compressed.len() < data.len());
+            let restored = decompress(&compressed, data.len()).expect("decompress");
+            assert_eq!(restored.as_ref(), data.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_small_payloads_are_not_compressed() {
+        let config = CompressionConfig::default().with_threshold(1024);
+        assert!(config
+            .compress(CompressionAlgorithm::Zstd, &compressible(1023))
+            .is_none());
+        assert!(config
+            .compress(CompressionAlgorithm::Zstd, &compressible(1024))
+            .is_some());
+    }
+
+    #[test]
+    fn test_negotiation_prefers_local_order() {
+        let config = CompressionConfig::default()
+            .with_algorithms(vec![CompressionAlgorithm::Lz4, CompressionAlgorithm::Zstd]);
+
+        assert_eq!(config.negotiate(&[2, 1]), CompressionAlgorithm::Lz4);
+        assert_eq!(config.negotiate(&[2, 99]), CompressionAlgorithm::Zstd);
+        assert_eq!(config.negotiate(&[]), CompressionAlgorithm::None);
+        assert_eq!(
+            CompressionConfig::disabled().negotiate(&[1, 2]),
+            CompressionAlgorithm::None
+        );
+    }
+
+    #[test]
+    fn test_decompress_enforces_size_limit() {
+        let config = CompressionConfig::default();
+        let data = compressible(8192);
+        let compressed = config
+            .compress(CompressionAlgorithm::Lz4, &data)
+            .expect("compresses");
+
+        assert!(matches!(
+            decompress(&compressed, 4096),
+            Err(CompressionError::TooLarge { .. })
+        ));
+        assert!(matches!(
+            decompress(&[1, 0], 4096),
+            Err(CompressionError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn test_metrics_ratio() {
+        let stats = CompressionStats::default();
+        stats.record_compressed(StreamType::Bulk, 1000, 250);
+        stats.record_uncompressed(StreamType::Bulk);
+
+        let metrics = stats.snapshot(StreamType::Bulk);
+        assert_eq!(metrics.compressed_messages, 1);
+        assert_eq!(metrics.uncompressed_messages, 1);
+        assert!((metrics.ratio() - 4.0).abs() < f64::EPSILON);
+        assert!((stats.snapshot(StreamType::PubSub).ratio() - 1.0).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,265 @@
+//! Automatic fallback from one transport to another
+//!
+//! [`FallbackTransport`] dials through a primary transport, typically
+//! [`crate::AntQuicTransport`], and retries through a secondary one,
+//! typically [`crate::TcpTransport`], when the primary cannot connect, e.g.
+//! because the network blocks UDP. Each peer is then served by whichever
+//! transport reached it, preferring the primary, and inbound messages and
+//! events of both are merged, so upper layers see a single transport.
+//!
+//! It is a [`MultiTransport`] with the two children ranked by
+//! [`RouteOrder::Preference`], keeping typed access to both.
+
+use crate::handshake::VerifiedIdentity;
+use crate::multi::{MultiTransport, RouteOrder};
+use crate::{CloseReason, GossipTransport, StreamType, TransportEvent};
+use anyhow::Result;
+use bytes::Bytes;
+use saorsa_gossip_types::PeerId;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Which transport a peer is reached through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Route {
+    /// The primary transport
+    Primary,
+    /// The fallback transport
+    Secondary,
+}
+
+/// A [`GossipTransport`] that falls back to a second transport per peer
+///
+/// Dropping the wrapper stops its dispatch tasks.
+pub struct FallbackTransport<P: GossipTransport + 'static, S: GossipTransport + 'static> {
+    primary: Arc<P>,
+    secondary: Arc<S>,
+    multi: MultiTransport,
+}
+
+impl<P: GossipTransport + 'static, S: GossipTransport + 'static> FallbackTransport<P, S> {
+    /// Combine `primary` and `secondary` and start merging their messages
+    ///
+    /// The wrapper takes over both transports' receive loops; read messages
+    /// through the wrapper rather than the inner transports.
+    pub fn new(primary: P, secondary: S) -> Self {
+        let primary = Arc::new(primary);
+        let secondary = Arc::new(secondary);
+        let multi = MultiTransport::builder()
+            .with_transport("primary", Arc::clone(&primary))
+            .with_transport("fallback", Arc::clone(&secondary))
+            .with_route_order(RouteOrder::Preference)
+            .build();
+
+        Self {
+            primary,
+            secondary,
+            multi,
+        }
+    }
+
+    /// The primary transport
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    /// The fallback transport
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+
+    /// Get the transport a peer is currently reached through
+    pub async fn route(&self, peer: &PeerId) -> Option<Route> {
+        match self.multi.route_index(peer).await? {
+            0 => Some(Route::Primary),
+            _ => Some(Route::Secondary),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: GossipTransport + 'static, S: GossipTransport + 'static> GossipTransport
+    for FallbackTransport<P, S>
+{
+    async fn dial(&self, peer: PeerId, addr: SocketAddr) -> Result<()> {
+        self.multi.dial(peer, addr).await
+    }
+
+    async fn dial_bootstrap(&self, addr: SocketAddr) -> Result<PeerId> {
+        self.multi.dial_bootstrap(addr).await
+    }
+
+    async fn listen(&self, bind: SocketAddr) -> Result<()> {
+        self.multi.listen(bind).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.multi.close().await
+    }
+
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+        self.multi.disconnect(peer, reason).await
+    }
+
+    async fn send_to_peer(&self, peer: PeerId, stream_type: StreamType, data: Bytes) -> Result<()> {
+        self.multi.send_to_peer(peer, stream_type, data).await
+    }
+
+    async fn receive_message(&self) -> Result<(PeerId, StreamType, Bytes)> {
+        self.multi.receive_message().await
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        self.multi.subscribe_events()
+    }
+
+    fn peer_identity(&self, peer: &PeerId) -> Option<VerifiedIdentity> {
+        self.multi.peer_identity(peer)
+    }
+
+    async fn peer_addr(&self, peer: &PeerId) -> Option<SocketAddr> {
+        self.multi.peer_addr(peer).await
+    }
+
+    async fn peer_rtt(&self, peer: &PeerId) -> Option<Duration> {
+        self.multi.peer_rtt(peer).await
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.multi.is_encrypted()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryNetwork, MemoryTransport, TcpTransport};
+    use tokio::time::timeout;
+
+    /// Two nodes whose primary transports sit on separate networks
+    async fn nodes() -> (
+        FallbackTransport<MemoryTransport, MemoryTransport>,
+        FallbackTransport<MemoryTransport, MemoryTransport>,
+    ) {
+        let primary_a = MemoryNetwork::new();
+        let primary_b = MemoryNetwork::new();
+        let fallback = MemoryNetwork::new();
+
+        let mut nodes = Vec::new();
+        for (primary, id) in [(primary_a, [1u8; 32]), (primary_b, [2u8; 32])] {
+            let id = PeerId::new(id);
+            nodes.push(FallbackTransport::new(
+                primary.add_node(id).await.expect("primary node"),
+                fallback.add_node(id).await.expect("fallback node"),
+            ));
+        }
+        let b = nodes.pop().expect("node b");
+        let a = nodes.pop().expect("node a");
+        (a, b)
+    }
+
+    #[tokio::test]
+    async fn test_dial_falls_back_when_primary_fails() {
+        let (a, b) = nodes().await;
+        let id_a = a.primary().peer_id();
+        let id_b = b.primary().peer_id();
+
+        a.dial(id_b, b.secondary().local_addr())
+            .await
+            .expect("dial through fallback");
+        assert_eq!(a.route(&id_b).await, Some(Route::Secondary));
+
+        a.send_to_peer(id_b, StreamType::Membership, Bytes::from_static(b"hello"))
+            .await
+            .expect("send");
+        let (from, stream_type, data) = timeout(Duration::from_secs(5), b.receive_message())
+            .await
+            .expect("message in time")
+            .expect("message");
+        assert_eq!((from, stream_type), (id_a, StreamType::Membership));
+        assert_eq!(data, Bytes::from_static(b"hello"));
+
+        // The receiver learned the route and can answer
+        assert_eq!(b.route(&id_a).await, Some(Route::Secondary));
+        b.send_to_peer(id_a, StreamType::PubSub, Bytes::from_static(b"reply"))
+            .await
+            .expect("reply");
+        let (from, _, _) = timeout(Duration::from_secs(5), a.receive_message())
+            .await
+            .expect("reply in time")
+            .expect("reply");
+        assert_eq!(from, id_b);
+    }
+
+    #[tokio::test]
+    async fn test_dial_reports_both_failures() {
+        let (a, _b) = nodes().await;
+        let unknown = PeerId::new([9u8; 32]);
+        let addr = "127.0.0.1:9".parse().expect("valid address");
+
+        let err = a.dial(unknown, addr).await.expect_err("unreachable");
+        assert!(err.to_string().contains("fallback"));
+        assert_eq!(a.route(&unknown).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_primary_is_preferred_once_reachable() {
+        let primary = MemoryNetwork::new();
+        let fallback = MemoryNetwork::new();
+        let mut nodes = Vec::new();
+        for id in [[1u8; 32], [2u8; 32]] {
+            let id = PeerId::new(id);
+            nodes.push(FallbackTransport::new(
+                primary.add_node(id).await.expect("primary node"),
+                fallback.add_node(id).await.expect("fallback node"),
+            ));
+        }
+        let id_b = nodes[1].primary().peer_id();
+
+        nodes[0]
+            .dial(id_b, nodes[1].primary().local_addr())
+            .await
+            .expect("dial");
+        assert_eq!(nodes[0].route(&id_b).await, Some(Route::Primary));
+
+        nodes[0]
+            .send_to_peer(id_b, StreamType::Membership, Bytes::from_static(b"hello"))
+            .await
+            .expect("send");
+        assert_eq!(primary.delivered(), 1);
+        assert_eq!(fallback.delivered(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_fallback_is_encrypted() {
+        let bind = "127.0.0.1:0".parse().expect("valid address");
+        let network = MemoryNetwork::new();
+        let tcp_b = TcpTransport::new(bind).await.expect("tcp b");
+        let id_b = tcp_b.peer_id();
+
+        let a = FallbackTransport::new(
+            network
+                .add_node(PeerId::new([1u8; 32]))
+                .await
+                .expect("node"),
+            TcpTransport::new(bind).await.expect("tcp a"),
+        );
+        assert!(a.is_encrypted());
+        a.dial(id_b, tcp_b.local_addr())
+            .await
+            .expect("dial over tcp");
+        assert_eq!(a.route(&id_b).await, Some(Route::Secondary));
+
+        a.send_to_peer(id_b, StreamType::PubSub, Bytes::from_static(b"hello"))
+            .await
+            .expect("send");
+        let (from, _, data) = timeout(Duration::from_secs(5), tcp_b.receive_message())
+            .await
+            .expect("message in time")
+            .expect("message");
+        assert_eq!(from, a.secondary().peer_id());
+        assert_eq!(data, Bytes::from_static(b"hello"));
+    }
+}
@@ -0,0 +1,684 @@
+//! Fragmentation and reassembly of large payloads
+//!
+//! Messages larger than the fragment size are split into frames flagged with
+//! [`FrameFlags::FRAGMENT`]. Each fragment payload starts with a header:
+//!
+//! ```text
+//! +------------+-------------+-------------+-------------------+------+
+//! | message id | index (BE)  | count (BE)  | total length (BE) | kind |
+//! | 8 bytes    | 4 bytes     | 4 bytes     | 8 bytes           | 1 B  |
+//! +------------+-------------+-------------+-------------------+------+
+//! ```
+//!
+//! The fragments of a message are written back to back on one stream, so the
+//! receiver reassembles them in order with a single buffer per stream. A
+//! message must complete within the reassembly timeout, and the total memory
+//! held by partial messages is capped across all streams.
+//!
+//! Streamed transfers (see `send_large`) are not buffered: their chunks are
+//! handed to the application as they arrive through an [`IncomingTransfer`].
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use saorsa_gossip_types::PeerId;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::framing::FrameFlags;
+use crate::StreamType;
+
+/// Size of the header at the start of every fragment payload
+pub const FRAGMENT_HEADER_LEN: usize = 25;
+
+/// Chunks buffered per incoming transfer before the stream is paused
+const TRANSFER_CHUNK_CAPACITY: usize = 16;
+
+/// Errors produced while reassembling fragments
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum FragmentError {
+    #[error("Fragment header is truncated")]
+    Truncated,
+    #[error("Unknown fragment kind: {0}")]
+    UnknownKind(u8),
+    #[error("Fragment {index} of message {message_id} is out of sequence")]
+    OutOfSequence { message_id: u64, index: u32 },
+    #[error("Message of {length} bytes exceeds limit of {max} bytes")]
+    TooLarge { length: u64, max: usize },
+    #[error("Reassembly buffer is full")]
+    BudgetExhausted,
+    #[error("Fragment data does not match the declared message length")]
+    LengthMismatch,
+}
+
+/// How the receiver handles a fragmented message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FragmentKind {
+    /// Buffer all fragments and deliver one message
+    Message,
+    /// Hand chunks to the application as they arrive
+    Transfer,
+}
+
+impl FragmentKind {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Message),
+            1 => Some(Self::Transfer),
+            _ => None,
+        }
+    }
+
+    const fn to_u8(self) -> u8 {
+        match self {
+            Self::Message => 0,
+            Self::Transfer => 1,
+        }
+    }
+}
+
+/// Header carried at the start of every fragment payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FragmentHeader {
+    pub(crate) message_id: u64,
+    pub(crate) index: u32,
+    pub(crate) count: u32,
+    pub(crate) total_len: u64,
+    pub(crate) kind: FragmentKind,
+}
+
+impl FragmentHeader {
+    /// Encode the header followed by `chunk`
+    pub(crate) fn encode_with(&self, chunk: &[u8]) -> Bytes {
+        let mut buf = BytesMut::with_capacity(FRAGMENT_HEADER_LEN + chunk.len());
+        buf.put_u64(self.message_id);
+        buf.put_u32(self.index);
+        buf.put_u32(self.count);
+        buf.put_u64(self.total_len);
+        buf.put_u8(self.kind.to_u8());
+        buf.put_slice(chunk);
+        buf.freeze()
+    }
+
+    /// Split a fragment payload into its header and chunk
+    pub(crate) fn decode(mut payload: Bytes) -> Result<(Self, Bytes), FragmentError> {
+        if payload.len() < FRAGMENT_HEADER_LEN {
+            return Err(FragmentError::Truncated);
+        }
+        let message_id = payload.get_u64();
+        let index = payload.get_u32();
+        let count = payload.get_u32();
+        let total_len = payload.get_u64();
+        let kind_tag = payload.get_u8();
+        let kind = FragmentKind::from_u8(kind_tag).ok_or(FragmentError::UnknownKind(kind_tag))?;
+        Ok((
+            Self {
+                message_id,
+                index,
+                count,
+                total_len,
+                kind,
+            },
+            payload,
+        ))
+    }
+}
+
+/// Split `data` into fragment payloads of at most `chunk_size` data bytes
+pub(crate) fn split(message_id: u64, data: &Bytes, chunk_size: usize) -> Vec<Bytes> {
+    let chunk_size = chunk_size.max(1);
+    let count = data.len().div_ceil(chunk_size).max(1) as u32;
+    (0..count)
+        .map(|index| {
+            let start = index as usize * chunk_size;
+            let end = (start + chunk_size).min(data.len());
+            FragmentHeader {
+                message_id,
+                index,
+                count,
+                total_len: data.len() as u64,
+                kind: FragmentKind::Message,
+            }
+            .encode_with(&data[start..end])
+        })
+        .collect()
+}
+
+/// Fragmentation and reassembly settings
+#[derive(Debug, Clone)]
+pub struct FragmentConfig {
+    /// Data bytes per fragment (default: 1 MB, capped by the frame size limit)
+    pub fragment_size: usize,
+    /// Largest message that will be reassembled (default: 1 GB)
+    pub max_message_size: usize,
+    /// Memory held by all partially reassembled messages (default: 256 MB)
+    pub max_reassembly_bytes: usize,
+    /// Time allowed to receive all fragments of a message (default: 30 s)
+    pub reassembly_timeout: Duration,
+}
+
+impl Default for FragmentConfig {
+    fn default() -> Self {
+        Self {
+            fragment_size: 1024 * 1024,
+            max_message_size: 1024 * 1024 * 1024,
+            max_reassembly_bytes: 256 * 1024 * 1024,
+            reassembly_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl FragmentConfig {
+    /// Set the number of data bytes per fragment
+    pub fn with_fragment_size(mut self, size: usize) -> Self {
+        self.fragment_size = size.max(1);
+        self
+    }
+
+    /// Set the largest message that will be reassembled
+    pub fn with_max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Set the memory cap for partially reassembled messages
+    pub fn with_max_reassembly_bytes(mut self, bytes: usize) -> Self {
+        self.max_reassembly_bytes = bytes;
+        self
+    }
+
+    /// Set the time allowed to receive all fragments of a message
+    pub fn with_reassembly_timeout(mut self, timeout: Duration) -> Self {
+        self.reassembly_timeout = timeout;
+        self
+    }
+}
+
+/// Memory shared by every in-progress reassembly
+#[derive(Debug, Clone)]
+pub(crate) struct ReassemblyBudget {
+    used: Arc<AtomicUsize>,
+    max: usize,
+}
+
+impl ReassemblyBudget {
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            used: Arc::new(AtomicUsize::new(0)),
+            max,
+        }
+    }
+
+    fn try_reserve(&self, bytes: usize) -> bool {
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(bytes).filter(|total| *total <= self.max)
+            })
+            .is_ok()
+    }
+
+    fn release(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::AcqRel);
+    }
+
+    /// Bytes currently reserved
+    #[cfg(test)]
+    fn used(&self) -> usize {
+        self.used.load(Ordering::Acquire)
+    }
+}
+
+/// A large transfer being received, delivered chunk by chunk
+#[derive(Debug)]
+pub struct IncomingTransfer {
+    /// Sending peer
+    pub peer: PeerId,
+    /// Stream type the transfer was sent on
+    pub stream_type: StreamType,
+    /// Total transfer size in bytes
+    pub len: u64,
+    chunks: mpsc::Receiver<Bytes>,
+    received: u64,
+}
+
+impl IncomingTransfer {
+    /// Receive the next chunk, or `None` once the transfer has ended
+    ///
+    /// Check [`IncomingTransfer::is_complete`] after `None` to tell a finished
+    /// transfer from one the sender abandoned.
+    pub async fn next_chunk(&mut self) -> Option<Bytes> {
+        let chunk = self.chunks.recv().await?;
+        self.received += chunk.len() as u64;
+        Some(chunk)
+    }
+
+    /// Bytes received so far
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Whether every byte of the transfer has been received
+    pub fn is_complete(&self) -> bool {
+        self.received == self.len
+    }
+
+    /// Collect the remaining chunks into one buffer
+    ///
+    /// Fails if the transfer ends before `len` bytes arrive.
+    pub async fn read_to_end(mut self) -> anyhow::Result<Bytes> {
+        let mut buf = BytesMut::with_capacity(usize::try_from(self.len).unwrap_or(0));
+        while let Some(chunk) = self.next_chunk().await {
+            buf.put_slice(&chunk);
+        }
+        if !self.is_complete() {
+            return Err(anyhow::anyhow!(
+                "Transfer from {} ended after {} of {} bytes",
+                self.peer,
+                self.received,
+                self.len
+            ));
+        }
+        Ok(buf.freeze())
+    }
+}
+
+/// Result of feeding a fragment to a [`Reassembler`]
+#[derive(Debug)]
+pub(crate) enum Reassembled {
+    /// More fragments are needed
+    Pending,
+    /// A complete message, with the flags its fragments carried
+    Message(FrameFlags, Bytes),
+    /// The first chunk of a streamed transfer has arrived
+    Transfer(IncomingTransfer),
+}
+
+/// Message being buffered
+struct PartialMessage {
+    message_id: u64,
+    next_index: u32,
+    count: u32,
+    total_len: usize,
+    flags: FrameFlags,
+    buf: BytesMut,
+    deadline: Instant,
+}
+
+/// Transfer being streamed to the application
+struct ActiveTransfer {
+    message_id: u64,
+    next_index: u32,
+    count: u32,
+    chunks: mpsc::Sender<Bytes>,
+    deadline: Instant,
+}
+
+enum InProgress {
+    Message(PartialMessage),
+    Transfer(ActiveTransfer),
+}
+
+/// Reassembles the fragments arriving on one stream
+pub(crate) struct Reassembler {
+    peer: PeerId,
+    stream_type: StreamType,
+    config: Arc<FragmentConfig>,
+    budget: ReassemblyBudget,
+    current: Option<InProgress>,
+}
+
+impl Reassembler {
+    pub(crate) fn new(
+        peer: PeerId,
+        stream_type: StreamType,
+        config: Arc<FragmentConfig>,
+        budget: ReassemblyBudget,
+    ) -> Self {
+        Self {
+            peer,
+            stream_type,
+            config,
+            budget,
+            current: None,
+        }
+    }
+
+    /// Time by which the next fragment must arrive, if one is expected
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        match &self.current {
+            Some(InProgress::Message(partial)) => Some(partial.deadline),
+            Some(InProgress::Transfer(transfer)) => Some(transfer.deadline),
+            None => None,
+        }
+    }
+
+    /// Whether a message or transfer is partially received
+    pub(crate) fn in_progress(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Drop any partially received message or transfer
+    pub(crate) fn abort(&mut self) {
+        if let Some(InProgress::Message(partial)) = self.current.take() {
+            self.budget.release(partial.total_len);
+        }
+    }
+
+    /// Feed the payload of a fragment frame
+    ///
+    /// On error the partial message is dropped.
+    pub(crate) async fn push(
+        &mut self,
+        flags: FrameFlags,
+        payload: Bytes,
+    ) -> Result<Reassembled, FragmentError> {
+        let result = self.push_inner(flags, payload).await;
+        if result.is_err() {
+            self.abort();
+        }
+        result
+    }
+
+    async fn push_inner(
+        &mut self,
+        flags: FrameFlags,
+        payload: Bytes,
+    ) -> Result<Reassembled, FragmentError> {
+        let (header, chunk) = FragmentHeader::decode(payload)?;
+
+        match self.current.take() {
+            None => self.start(header, flags, chunk),
+            Some(InProgress::Message(partial)) => self.continue_message(partial, header, chunk),
+            Some(InProgress::Transfer(transfer)) => {
+                self.continue_transfer(transfer, header, chunk).await
+            }
+        }
+    }
+
+    fn start(
+        &mut self,
+        header: FragmentHeader,
+        flags: FrameFlags,
+        chunk: Bytes,
+    ) -> Result<Reassembled, FragmentError> {
+        if header.index != 0 || header.count == 0 {
+            return Err(FragmentError::OutOfSequence {
+                message_id: header.message_id,
+                index: header.index,
+            });
+        }
+
+        match header.kind {
+            FragmentKind::Message => {
+                let total_len = usize::try_from(header.total_len)
+                    .ok()
+                    .filter(|len| *len <= self.config.max_message_size)
+                    .ok_or(FragmentError::TooLarge {
+                        length: header.total_len,
+                        max: self.config.max_message_size,
+                    })?;
+                if !self.budget.try_reserve(total_len) {
+                    return Err(FragmentError::BudgetExhausted);
+                }
+
+                let mut buf = BytesMut::with_capacity(total_len);
+                buf.put_slice(&chunk);
+                let partial = PartialMessage {
+                    message_id: header.message_id,
+                    next_index: 1,
+                    count: header.count,
+                    total_len,
+                    flags,
+                    buf,
+                    deadline: Instant::now() + self.config.reassembly_timeout,
+                };
+                self.finish_or_wait(partial)
+            }
+            FragmentKind::Transfer => {
+                let (tx, rx) = mpsc::channel(TRANSFER_CHUNK_CAPACITY);
+                let received = chunk.len() as u64;
+                if received > header.total_len {
+                    return Err(FragmentError::LengthMismatch);
+                }
+                let _ = tx.try_send(chunk);
+                if header.count > 1 {
+                    self.current = Some(InProgress::Transfer(ActiveTransfer {
+                        message_id: header.message_id,
+                        next_index: 1,
+                        count: header.count,
+                        chunks: tx,
+                        deadline: Instant::now() + self.config.reassembly_timeout,
+                    }));
+                }
+                Ok(Reassembled::Transfer(IncomingTransfer {
+                    peer: self.peer,
+                    stream_type: self.stream_type,
+                    len: header.total_len,
+                    chunks: rx,
+                    received: 0,
+                }))
+            }
+        }
+    }
+
+    fn continue_message(
+        &mut self,
+        mut partial: PartialMessage,
+        header: FragmentHeader,
+        chunk: Bytes,
+    ) -> Result<Reassembled, FragmentError> {
+        let in_sequence = header.kind == FragmentKind::Message
+            && header.message_id == partial.message_id
+            && header.index == partial.next_index
+            && header.count == partial.count;
+        if !in_sequence {
+            self.current = Some(InProgress::Message(partial));
+            return Err(FragmentError::OutOfSequence {
+                message_id: header.message_id,
+                index: header.index,
+            });
+        }
+        if partial.buf.len() + chunk.len() > partial.total_len {
+            self.current = Some(InProgress::Message(partial));
+            return Err(FragmentError::LengthMismatch);
+        }
+
+        partial.buf.put_slice(&chunk);
+        partial.next_index += 1;
+        self.finish_or_wait(partial)
+    }
+
+    fn finish_or_wait(&mut self, partial: PartialMessage) -> Result<Reassembled, FragmentError> {
+        if partial.next_index < partial.count {
+            self.current = Some(InProgress::Message(partial));
+            return Ok(Reassembled::Pending);
+        }
+
+        self.budget.release(partial.total_len);
+        if partial.buf.len() != partial.total_len {
+            return Err(FragmentError::LengthMismatch);
+        }
+        let flags = FrameFlags::from_bits(partial.flags.bits() & !FrameFlags::FRAGMENT.bits())
+            .unwrap_or_default();
+        Ok(Reassembled::Message(flags, partial.buf.freeze()))
+    }
+
+    async fn continue_transfer(
+        &mut self,
+        mut transfer: ActiveTransfer,
+        header: FragmentHeader,
+        chunk: Bytes,
+    ) -> Result<Reassembled, FragmentError> {
+        let in_sequence = header.kind == FragmentKind::Transfer
+            && header.message_id == transfer.message_id
+            && header.index == transfer.next_index
+            && header.count == transfer.count;
+        if !in_sequence {
+            return Err(FragmentError::OutOfSequence {
+                message_id: header.message_id,
+                index: header.index,
+            });
+        }
+
+        // Waiting here pauses the stream until the application catches up. If
+        // the application dropped the transfer the rest is discarded.
+        let _ = transfer.chunks.send(chunk).await;
+
+        transfer.next_index += 1;
+        transfer.deadline = Instant::now() + self.config.reassembly_timeout;
+        if transfer.next_index < transfer.count {
+            self.current = Some(InProgress::Transfer(transfer));
+        }
+        Ok(Reassembled::Pending)
+    }
+}
+
+impl Drop for Reassembler {
+    fn drop(&mut self) {
+        self.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reassembler(config: FragmentConfig) -> Reassembler {
+        let budget = ReassemblyBudget::new(config.max_reassembly_bytes);
+        Reassembler::new(
+            PeerId::new([1u8; 32]),
+            StreamType::Bulk,
+            Arc::new(config),
+            budget,
+        )
+    }
+
+    fn fragment_flags() -> FrameFlags {
+        FrameFlags::FRAGMENT
+    }
+
+    #[test]
+    fn test_header_roundtrip() {
+        let header = FragmentHeader {
+            message_id: 42,
+            index: 3,
+            count: 7,
+            total_len: 1 << 33,
+            kind: FragmentKind::Transfer,
+        };
+        let (decoded, chunk) =
+            FragmentHeader::decode(header.encode_with(b"chunk")).expect("decode");
+        assert_eq!(decoded, header);
+        assert_eq!(chunk, Bytes::from_static(b"chunk"));
+        assert_eq!(
+            FragmentHeader::decode(Bytes::from_static(b"short")),
+            Err(FragmentError::Truncated)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_split_and_reassemble() {
+        let data: Bytes = (0..10_000u32).map(|i| i as u8).collect::<Vec<_>>().into();
+        let fragments = split(1, &data, 3000);
+        assert_eq!(fragments.len(), 4);
+
+        let mut reassembler = reassembler(FragmentConfig::default());
+        let mut result = None;
+        for fragment in fragments {
+            if let Reassembled::Message(flags, message) = reassembler
+                .push(fragment_flags().with(FrameFlags::COMPRESSED), fragment)
+                .await
+                .expect("push")
+            {
+                assert_eq!(flags, FrameFlags::COMPRESSED);
+                result = Some(message);
+            }
+        }
+
+        assert_eq!(result, Some(data));
+        assert!(!reassembler.in_progress());
+        assert_eq!(reassembler.budget.used(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_sequence_fragment_aborts() {
+        let data = Bytes::from(vec![1u8; 100]);
+        let fragments = split(1, &data, 30);
+
+        let mut reassembler = reassembler(FragmentConfig::default());
+        reassembler
+            .push(fragment_flags(), fragments[0].clone())
+            .await
+            .expect("first fragment");
+        assert!(reassembler.in_progress());
+
+        let result = reassembler
+            .push(fragment_flags(), fragments[2].clone())
+            .await;
+        assert!(matches!(
+            result,
+            Err(FragmentError::OutOfSequence { index: 2, .. })
+        ));
+        assert!(!reassembler.in_progress());
+        assert_eq!(reassembler.budget.used(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_limits_are_enforced() {
+        let data = Bytes::from(vec![1u8; 100]);
+
+        let mut too_large = reassembler(FragmentConfig::default().with_max_message_size(99));
+        assert!(matches!(
+            too_large
+                .push(fragment_flags(), split(1, &data, 30)[0].clone())
+                .await,
+            Err(FragmentError::TooLarge { length: 100, .. })
+        ));
+
+        let mut over_budget = reassembler(FragmentConfig::default().with_max_reassembly_bytes(50));
+        assert_eq!(
+            over_budget
+                .push(fragment_flags(), split(1, &data, 30)[0].clone())
+                .await
+                .err(),
+            Some(FragmentError::BudgetExhausted)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streamed_transfer() {
+        let mut reassembler = reassembler(FragmentConfig::default());
+        let fragment = |index: u32, chunk: &'static [u8]| {
+            FragmentHeader {
+                message_id: 9,
+                index,
+                count: 2,
+                total_len: 6,
+                kind: FragmentKind::Transfer,
+            }
+            .encode_with(chunk)
+        };
+
+        let Reassembled::Transfer(transfer) = reassembler
+            .push(fragment_flags(), fragment(0, b"abc"))
+            .await
+            .expect("first chunk")
+        else {
+            panic!("expected a transfer");
+        };
+        assert_eq!(transfer.len, 6);
+
+        reassembler
+            .push(fragment_flags(), fragment(1, b"def"))
+            .await
+            .expect("second chunk");
+        assert!(!reassembler.in_progress());
+        drop(reassembler);
+
+        assert_eq!(
+            transfer.read_to_end().await.expect("complete"),
+            Bytes::from_static(b"abcdef")
+        );
+    }
+}
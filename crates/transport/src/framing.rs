@@ -0,0 +1,513 @@
+//! Wire framing for Saorsa Gossip transport messages
+//!
+//! Every message sent on a transport stream is wrapped in a fixed-size header:
+//!
+//! ```text
+//! +--------+---------+-------------+-------+----------------+
+//! | magic  | version | stream type | flags | length (BE)    |
+//! | 2 bytes| 1 byte  | 1 byte      | 1 byte| 4 bytes        |
+//! +--------+---------+-------------+-------+----------------+
+//! ```
+//!
+//! The header layout is identical across protocol versions, so a receiver can
+//! always locate the end of a frame. Frames with an unknown version, stream
+//! type or flag bits are skipped whole and the stream stays usable, which lets
+//! mixed-version networks degrade gracefully. Oversized frames are rejected
+//! before their payload is read, and a payload's buffer grows only as its
+//! bytes arrive, so a header alone never reserves its claimed length.
+//!
+//! Until a peer's identity is verified, frames are read with
+//! [`FrameCodec::handshake`], which only admits payloads up to
+//! [`HANDSHAKE_MAX_FRAME_SIZE`].
+
+use bytes::{BufMut, Bytes, BytesMut};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::StreamType;
+
+/// Magic bytes at the start of every frame
+pub const FRAME_MAGIC: [u8; 2] = *b"SG";
+
+/// Current wire protocol version
+pub const FRAME_VERSION: u8 = 1;
+
+/// Size of the encoded frame header in bytes
+pub const FRAME_HEADER_LEN: usize = 9;
+
+/// Default maximum frame payload size (100 MB)
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 100 * 1024 * 1024;
+
+/// Maximum payload of a frame read before the sender's identity is verified
+///
+/// Fits an ML-DSA identity proof or a hello with room to spare.
+pub const HANDSHAKE_MAX_FRAME_SIZE: usize = 16 * 1024;
+
+/// Buffer reserved up front when reading a frame payload
+const INITIAL_READ_CAPACITY: usize = 64 * 1024;
+
+/// Errors produced while encoding or decoding frames
+#[derive(thiserror::Error, Debug)]
+pub enum FrameError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid frame magic: {0:02x?}")]
+    BadMagic([u8; 2]),
+    #[error("Frame of {length} bytes exceeds limit of {max} bytes")]
+    TooLarge { length: usize, max: usize },
+    #[error("Unsupported protocol version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("Unknown stream type: {0}")]
+    UnknownStreamType(u8),
+    #[error("Unknown frame flags: {0:#04x}")]
+    UnknownFlags(u8),
+}
+
+impl FrameError {
+    /// Whether the offending frame was skipped and the stream can still be read
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            Self::UnsupportedVersion(_) | Self::UnknownStreamType(_) | Self::UnknownFlags(_)
+        )
+    }
+}
+
+/// Per-frame option flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameFlags(u8);
+
+impl FrameFlags {
+    /// No flags set
+    pub const NONE: Self = Self(0);
+    /// Payload is compressed
+    pub const COMPRESSED: Self = Self(0b0000_0001);
+    /// Payload is one fragment of a larger message
+    pub const FRAGMENT: Self = Self(0b0000_0010);
+    /// Payload advertises connection parameters rather than carrying a message
+    pub const HELLO: Self = Self(0b0000_0100);
+    /// Payload proves the sender's identity rather than carrying a message
+    pub const IDENTITY: Self = Self(0b0000_1000);
+    /// Payload carries the sender's close reason; no frames follow it
+    pub const CLOSE: Self = Self(0b0001_0000);
+
+    const KNOWN: u8 =
+        Self::COMPRESSED.0 | Self::FRAGMENT.0 | Self::HELLO.0 | Self::IDENTITY.0 | Self::CLOSE.0;
+
+    /// Convert raw bits to flags, rejecting unknown bits
+    pub const fn from_bits(bits: u8) -> Option<Self> {
+        if bits & !Self::KNOWN == 0 {
+            Some(Self(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Get the raw bits
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Check whether all flags in `other` are set
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Combine with another set of flags
+    pub const fn with(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Check whether no flags are set
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// Decoded frame header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Protocol version
+    pub version: u8,
+    /// Stream the frame belongs to
+    pub stream_type: StreamType,
+    /// Frame flags
+    pub flags: FrameFlags,
+    /// Payload length in bytes
+    pub length: u32,
+}
+
+impl FrameHeader {
+    /// Encode the header into its wire representation
+    pub fn encode(&self) -> [u8; FRAME_HEADER_LEN] {
+        let mut buf = [0u8; FRAME_HEADER_LEN];
+        buf[..2].copy_from_slice(&FRAME_MAGIC);
+        buf[2] = self.version;
+        buf[3] = self.stream_type.to_u8();
+        buf[4] = self.flags.bits();
+        buf[5..].copy_from_slice(&self.length.to_be_bytes());
+        buf
+    }
+}
+
+/// A single framed message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Stream the frame belongs to
+    pub stream_type: StreamType,
+    /// Frame flags
+    pub flags: FrameFlags,
+    /// Frame payload
+    pub payload: Bytes,
+}
+
+impl Frame {
+    /// Create an unflagged frame
+    pub fn new(stream_type: StreamType, payload: Bytes) -> Self {
+        Self {
+            stream_type,
+            flags: FrameFlags::NONE,
+            payload,
+        }
+    }
+
+    /// Set the frame flags
+    pub fn with_flags(mut self, flags: FrameFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+}
+
+/// Encoder/decoder for transport frames with a maximum frame size
+#[derive(Debug, Clone, Copy)]
+pub struct FrameCodec {
+    max_frame_size: usize,
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FRAME_SIZE)
+    }
+}
+
+impl FrameCodec {
+    /// Create a codec that rejects payloads larger than `max_frame_size` bytes
+    pub fn new(max_frame_size: usize) -> Self {
+        Self { max_frame_size }
+    }
+
+    /// Create a codec for frames read before the peer's identity is verified
+    pub fn handshake() -> Self {
+        Self::new(HANDSHAKE_MAX_FRAME_SIZE)
+    }
+
+    /// Get the maximum frame payload size
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// Build the header for a frame, checking its size
+    fn header_for(&self, frame: &Frame) -> Result<FrameHeader, FrameError> {
+        let length = frame.payload.len();
+        if length > self.max_frame_size || length > u32::MAX as usize {
+            return Err(FrameError::TooLarge {
+                length,
+                max: self.max_frame_size,
+            });
+        }
+        Ok(FrameHeader {
+            version: FRAME_VERSION,
+            stream_type: frame.stream_type,
+            flags: frame.flags,
+            length: length as u32,
+        })
+    }
+
+    /// Encode a frame into a contiguous buffer
+    pub fn encode(&self, frame: &Frame) -> Result<Bytes, FrameError> {
+        let header = self.header_for(frame)?;
+        let mut buf = BytesMut::with_capacity(FRAME_HEADER_LEN + frame.payload.len());
+        buf.put_slice(&header.encode());
+        buf.put_slice(&frame.payload);
+        Ok(buf.freeze())
+    }
+
+    /// Validate the magic and length of a raw header
+    ///
+    /// Failures here are fatal: the frame boundary can no longer be trusted.
+    fn check_boundary(&self, buf: &[u8; FRAME_HEADER_LEN]) -> Result<usize, FrameError> {
+        let magic = [buf[0], buf[1]];
+        if magic != FRAME_MAGIC {
+            return Err(FrameError::BadMagic(magic));
+        }
+        let length = u32::from_be_bytes([buf[5], buf[6], buf[7], buf[8]]) as usize;
+        if length > self.max_frame_size {
+            return Err(FrameError::TooLarge {
+                length,
+                max: self.max_frame_size,
+            });
+        }
+        Ok(length)
+    }
+
+    /// Decode a raw header whose boundary has been checked
+    fn parse_header(buf: &[u8; FRAME_HEADER_LEN]) -> Result<FrameHeader, FrameError> {
+        let version = buf[2];
+        if version != FRAME_VERSION {
+            return Err(FrameError::UnsupportedVersion(version));
+        }
+        let stream_type =
+            StreamType::from_u8(buf[3]).ok_or(FrameError::UnknownStreamType(buf[3]))?;
+        let flags = FrameFlags::from_bits(buf[4]).ok_or(FrameError::UnknownFlags(buf[4]))?;
+        Ok(FrameHeader {
+            version,
+            stream_type,
+            flags,
+            length: u32::from_be_bytes([buf[5], buf[6], buf[7], buf[8]]),
+        })
+    }
+
+    /// Decode a header, rejecting bad magic, oversized, unknown-version frames
+    pub fn decode_header(&self, buf: &[u8; FRAME_HEADER_LEN]) -> Result<FrameHeader, FrameError> {
+        self.check_boundary(buf)?;
+        Self::parse_header(buf)
+    }
+
+    /// Decode a single frame from the start of `buf`
+    ///
+    /// Returns the frame and the number of bytes consumed, or `Ok(None)` if
+    /// `buf` does not yet hold a complete frame.
+    pub fn decode(&self, buf: &[u8]) -> Result<Option<(Frame, usize)>, FrameError> {
+        let Some(raw) = buf.first_chunk::<FRAME_HEADER_LEN>() else {
+            return Ok(None);
+        };
+        let header = self.decode_header(raw)?;
+        let end = FRAME_HEADER_LEN + header.length as usize;
+        if buf.len() < end {
+            return Ok(None);
+        }
+        let frame = Frame {
+            stream_type: header.stream_type,
+            flags: header.flags,
+            payload: Bytes::copy_from_slice(&buf[FRAME_HEADER_LEN..end]),
+        };
+        Ok(Some((frame, end)))
+    }
+
+    /// Write a frame to a stream
+    pub async fn write_frame<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        frame: &Frame,
+    ) -> Result<(), FrameError> {
+        let header = self.header_for(frame)?;
+        writer.write_all(&header.encode()).await?;
+        writer.write_all(&frame.payload).await?;
+        Ok(())
+    }
+
+    /// Read the next frame from a stream
+    ///
+    /// Returns `Ok(None)` when the stream has finished cleanly. If the error
+    /// is [recoverable](FrameError::is_recoverable), the frame's payload has
+    /// been discarded and the next call reads the following frame.
+    pub async fn read_frame<R: AsyncRead + Unpin>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Option<Frame>, FrameError> {
+        let mut raw = [0u8; FRAME_HEADER_LEN];
+        match reader.read_exact(&mut raw).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let length = self.check_boundary(&raw)?;
+        let header = match Self::parse_header(&raw) {
+            Ok(header) => header,
+            Err(e) => {
+                // Skip the payload so the stream stays aligned on frame boundaries
+                tokio::io::copy(
+                    &mut (&mut *reader).take(length as u64),
+                    &mut tokio::io::sink(),
+                )
+                .await?;
+                return Err(e);
+            }
+        };
+
+        // Grow the buffer as bytes arrive rather than trusting the header
+        let mut payload = Vec::with_capacity(length.min(INITIAL_READ_CAPACITY));
+        (&mut *reader)
+            .take(length as u64)
+            .read_to_end(&mut payload)
+            .await?;
+        if payload.len() < length {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(Some(Frame {
+            stream_type: header.stream_type,
+            flags: header.flags,
+            payload: Bytes::from(payload),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_frame(version: u8, stream: u8, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = FRAME_MAGIC.to_vec();
+        buf.extend_from_slice(&[version, stream, flags]);
+        buf.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let codec = FrameCodec::default();
+        let frame =
+            Frame::new(StreamType::PubSub, Bytes::from("hello")).with_flags(FrameFlags::COMPRESSED);
+
+        let encoded = codec.encode(&frame).expect("encode");
+        assert_eq!(encoded.len(), FRAME_HEADER_LEN + 5);
+
+        let (decoded, used) = codec.decode(&encoded).expect("decode").expect("complete");
+        assert_eq!(decoded, frame);
+        assert_eq!(used, encoded.len());
+    }
+
+    #[test]
+    fn test_decode_incomplete() {
+        let codec = FrameCodec::default();
+        let encoded = codec
+            .encode(&Frame::new(StreamType::Bulk, Bytes::from("payload")))
+            .expect("encode");
+
+        assert!(codec.decode(&encoded[..4]).expect("decode").is_none());
+        assert!(codec
+            .decode(&encoded[..encoded.len() - 1])
+            .expect("decode")
+            .is_none());
+    }
+
+    #[test]
+    fn test_rejects_bad_magic() {
+        let codec = FrameCodec::default();
+        let mut raw = raw_frame(FRAME_VERSION, 0, 0, b"x");
+        raw[0] = b'X';
+
+        assert!(matches!(codec.decode(&raw), Err(FrameError::BadMagic(_))));
+    }
+
+    #[test]
+    fn test_rejects_oversized_before_payload() {
+        let codec = FrameCodec::new(16);
+        // Header only: the claimed payload never arrives
+        let raw = raw_frame(FRAME_VERSION, 0, 0, &[0u8; 32]);
+
+        let result = codec.decode(&raw[..FRAME_HEADER_LEN]);
+        assert!(matches!(
+            result,
+            Err(FrameError::TooLarge {
+                length: 32,
+                max: 16
+            })
+        ));
+        assert!(codec
+            .encode(&Frame::new(StreamType::Bulk, Bytes::from(vec![0u8; 32])))
+            .is_err());
+    }
+
+    #[test]
+    fn test_rejects_unknown_version_stream_and_flags() {
+        let codec = FrameCodec::default();
+
+        let err = codec
+            .decode(&raw_frame(FRAME_VERSION + 1, 0, 0, b"x"))
+            .expect_err("unknown version");
+        assert!(matches!(err, FrameError::UnsupportedVersion(2)));
+        assert!(err.is_recoverable());
+
+        let err = codec
+            .decode(&raw_frame(FRAME_VERSION, 9, 0, b"x"))
+            .expect_err("unknown stream");
+        assert!(matches!(err, FrameError::UnknownStreamType(9)));
+
+        let err = codec
+            .decode(&raw_frame(FRAME_VERSION, 0, 0x80, b"x"))
+            .expect_err("unknown flags");
+        assert!(matches!(err, FrameError::UnknownFlags(0x80)));
+    }
+
+    #[tokio::test]
+    async fn test_stream_skips_unknown_version_frame() {
+        let codec = FrameCodec::default();
+        let (mut client, mut server) = tokio::io::duplex(1024);
+
+        client
+            .write_all(&raw_frame(FRAME_VERSION + 1, 0, 0, b"from the future"))
+            .await
+            .expect("write");
+        let frame = Frame::new(StreamType::Membership, Bytes::from("ping"));
+        codec.write_frame(&mut client, &frame).await.expect("write");
+        drop(client);
+
+        let err = codec
+            .read_frame(&mut server)
+            .await
+            .expect_err("unknown version");
+        assert!(err.is_recoverable());
+
+        let next = codec.read_frame(&mut server).await.expect("read");
+        assert_eq!(next, Some(frame));
+        assert!(codec.read_frame(&mut server).await.expect("read").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_truncated_payload_is_an_error() {
+        let codec = FrameCodec::default();
+        let (mut client, mut server) = tokio::io::duplex(1024);
+
+        // Claims 50 MB but carries three bytes
+        let mut raw = raw_frame(FRAME_VERSION, 0, 0, b"abc");
+        raw[5..9].copy_from_slice(&(50u32 * 1024 * 1024).to_be_bytes());
+        client.write_all(&raw).await.expect("write");
+        drop(client);
+
+        let err = codec.read_frame(&mut server).await.expect_err("truncated");
+        assert!(matches!(err, FrameError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_codec_caps_payloads() {
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+        let frame = Frame::new(
+            StreamType::Membership,
+            Bytes::from(vec![0u8; HANDSHAKE_MAX_FRAME_SIZE + 1]),
+        );
+        FrameCodec::default()
+            .write_frame(&mut client, &frame)
+            .await
+            .expect("write");
+
+        let err = FrameCodec::handshake()
+            .read_frame(&mut server)
+            .await
+            .expect_err("over the handshake limit");
+        assert!(matches!(err, FrameError::TooLarge { .. }));
+    }
+
+    #[test]
+    fn test_flags() {
+        let flags = FrameFlags::COMPRESSED.with(FrameFlags::FRAGMENT);
+        assert!(flags.contains(FrameFlags::COMPRESSED));
+        assert!(flags.contains(FrameFlags::FRAGMENT));
+        assert!(!FrameFlags::NONE.contains(FrameFlags::COMPRESSED));
+        assert!(FrameFlags::NONE.is_empty());
+        assert_eq!(FrameFlags::from_bits(flags.bits()), Some(flags));
+        assert_eq!(FrameFlags::from_bits(0x20), None);
+    }
+}
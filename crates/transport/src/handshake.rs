@@ -0,0 +1,380 @@
+//! Connection-level peer identity verification
+//!
+//! The peer ID reported for a QUIC connection is not proof of anything: on
+//! inbound connections ant-quic may fall back to an ID derived from the
+//! remote address. Once a connection is up, each side therefore sends an
+//! identity proof on a dedicated stream:
+//!
+//! ```text
+//! +-----------+-------------+------------+-----------------+
+//! | peer ID   | key len (BE)| public key | ML-DSA signature|
+//! | 32 bytes  | 2 bytes     | key len    | remaining bytes |
+//! +-----------+-------------+------------+-----------------+
+//! ```
+//!
+//! The signature covers a domain string, the claimed peer ID and keying
+//! material exported from the connection's TLS session. Both ends derive
+//! the same exported bytes, while a proof replayed on another connection
+//! fails to verify. The receiver checks the signature and that
+//! [`PeerId::from_pubkey`] of the key equals the claimed ID before any
+//! message from the connection is delivered.
+
+use ant_quic::crypto::raw_public_keys::pqc::{
+    sign_with_ml_dsa, verify_with_ml_dsa, MlDsaSignature,
+};
+use ant_quic::{HighLevelConnection, MlDsaPublicKey, MlDsaSecretKey};
+use anyhow::{anyhow, Result};
+use bytes::{BufMut, Bytes, BytesMut};
+use saorsa_gossip_types::PeerId;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Domain separator for identity proof signatures
+const IDENTITY_DOMAIN: &[u8] = b"saorsa-gossip/identity/v1";
+
+/// TLS exporter label for the session binding
+const EXPORTER_LABEL: &[u8] = b"EXPORTER-saorsa-gossip-identity";
+
+/// Length of the exported session binding
+pub(crate) const BINDING_LEN: usize = 32;
+
+/// Time allowed for a peer to prove its identity after connecting
+pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors produced while verifying a peer's identity proof
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum IdentityError {
+    #[error("Malformed identity proof: {0}")]
+    Malformed(&'static str),
+    #[error("Invalid ML-DSA public key")]
+    InvalidPublicKey,
+    #[error("Invalid identity signature")]
+    InvalidSignature,
+    #[error("Peer claimed ID {claimed} but its key belongs to {actual}")]
+    PeerIdMismatch { claimed: PeerId, actual: PeerId },
+    #[error("Peer did not prove its identity within {0:?}")]
+    Timeout(Duration),
+    #[error("Connection has no TLS session to bind the proof to")]
+    Unbound,
+}
+
+/// A peer identity proven during the connection handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedIdentity {
+    peer: PeerId,
+    public_key: Vec<u8>,
+}
+
+impl VerifiedIdentity {
+    /// The peer ID, equal to `PeerId::from_pubkey(public_key)`
+    pub fn peer(&self) -> PeerId {
+        self.peer
+    }
+
+    /// The peer's ML-DSA public key bytes
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Verify an ML-DSA signature made by the peer over `data`
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<(), IdentityError> {
+        let public_key = MlDsaPublicKey::from_bytes(&self.public_key)
+            .map_err(|_| IdentityError::InvalidPublicKey)?;
+        let signature =
+            MlDsaSignature::from_bytes(signature).map_err(|_| IdentityError::InvalidSignature)?;
+        verify_with_ml_dsa(&public_key, data, &signature)
+            .map_err(|_| IdentityError::InvalidSignature)
+    }
+}
+
+/// The local node's signing identity
+pub(crate) struct LocalIdentity {
+    peer: PeerId,
+    public_key: MlDsaPublicKey,
+    secret_key: MlDsaSecretKey,
+}
+
+impl LocalIdentity {
+    /// Create an identity from an ML-DSA keypair
+    pub(crate) fn new(public_key: MlDsaPublicKey, secret_key: MlDsaSecretKey) -> Self {
+        Self {
+            peer: PeerId::from_pubkey(public_key.as_bytes()),
+            public_key,
+            secret_key,
+        }
+    }
+
+    /// The local peer ID
+    pub(crate) fn peer(&self) -> PeerId {
+        self.peer
+    }
+
+    /// Build the identity proof for a session binding
+    pub(crate) fn prove(&self, binding: &[u8]) -> Result<Bytes> {
+        let public_key = self.public_key.as_bytes();
+        let key_len = u16::try_from(public_key.len())
+            .map_err(|_| anyhow!("Public key of {} bytes is too long", public_key.len()))?;
+        let signature = sign_with_ml_dsa(&self.secret_key, &signed_data(&self.peer, binding))
+            .map_err(|e| anyhow!("Failed to sign identity proof: {}", e))?;
+
+        let mut buf = BytesMut::with_capacity(34 + public_key.len() + signature.as_bytes().len());
+        buf.put_slice(self.peer.as_bytes());
+        buf.put_u16(key_len);
+        buf.put_slice(public_key);
+        buf.put_slice(signature.as_bytes());
+        Ok(buf.freeze())
+    }
+}
+
+/// Bytes covered by an identity proof signature
+fn signed_data(peer: &PeerId, binding: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(IDENTITY_DOMAIN.len() + 32 + binding.len());
+    data.extend_from_slice(IDENTITY_DOMAIN);
+    data.extend_from_slice(peer.as_bytes());
+    data.extend_from_slice(binding);
+    data
+}
+
+/// Verify a peer's identity proof against the session binding
+pub(crate) fn verify_proof(
+    proof: &[u8],
+    binding: &[u8],
+) -> Result<VerifiedIdentity, IdentityError> {
+    if proof.len() < 34 {
+        return Err(IdentityError::Malformed("truncated header"));
+    }
+    let mut claimed = [0u8; 32];
+    claimed.copy_from_slice(&proof[..32]);
+    let claimed = PeerId::new(claimed);
+    let key_len = usize::from(u16::from_be_bytes([proof[32], proof[33]]));
+    let rest = &proof[34..];
+    if rest.len() <= key_len {
+        return Err(IdentityError::Malformed(
+            "truncated public key or signature",
+        ));
+    }
+    let (key_bytes, signature) = rest.split_at(key_len);
+
+    let public_key =
+        MlDsaPublicKey::from_bytes(key_bytes).map_err(|_| IdentityError::InvalidPublicKey)?;
+    let signature =
+        MlDsaSignature::from_bytes(signature).map_err(|_| IdentityError::InvalidSignature)?;
+    verify_with_ml_dsa(&public_key, &signed_data(&claimed, binding), &signature)
+        .map_err(|_| IdentityError::InvalidSignature)?;
+
+    let actual = PeerId::from_pubkey(key_bytes);
+    if actual != claimed {
+        return Err(IdentityError::PeerIdMismatch { claimed, actual });
+    }
+    Ok(VerifiedIdentity {
+        peer: actual,
+        public_key: key_bytes.to_vec(),
+    })
+}
+
+/// Derive the session binding both ends of a connection agree on
+pub(crate) fn session_binding(
+    conn: &HighLevelConnection,
+) -> Result<[u8; BINDING_LEN], IdentityError> {
+    let mut binding = [0u8; BINDING_LEN];
+    conn.export_keying_material(&mut binding, EXPORTER_LABEL, IDENTITY_DOMAIN)
+        .map_err(|_| IdentityError::Unbound)?;
+    Ok(binding)
+}
+
+type IdentityState = Option<Result<VerifiedIdentity, IdentityError>>;
+
+/// Identity verification state of every attached connection
+#[derive(Default)]
+pub(crate) struct ConnectionIdentities {
+    /// Verification outcome per connection stable ID
+    connections: Mutex<HashMap<usize, watch::Sender<IdentityState>>>,
+    /// Verified identities by peer
+    peers: Mutex<HashMap<PeerId, VerifiedIdentity>>,
+}
+
+impl ConnectionIdentities {
+    fn state(&self, connection: usize) -> watch::Sender<IdentityState> {
+        self.connections
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(connection)
+            .or_insert_with(|| watch::channel(None).0)
+            .clone()
+    }
+
+    /// Record the outcome of verifying a connection's proof
+    ///
+    /// Only the first outcome for a connection counts.
+    pub(crate) fn resolve(
+        &self,
+        connection: usize,
+        result: Result<VerifiedIdentity, IdentityError>,
+    ) {
+        let state = self.state(connection);
+        let mut first = false;
+        state.send_if_modified(|current| {
+            first = current.is_none();
+            if first {
+                *current = Some(result.clone());
+            }
+            first
+        });
+        if let (true, Ok(identity)) = (first, result) {
+            self.peers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(identity.peer, identity);
+        }
+    }
+
+    /// Wait until a connection's identity is verified or `timeout` passes
+    pub(crate) async fn wait(
+        &self,
+        connection: usize,
+        timeout: Duration,
+    ) -> Result<VerifiedIdentity, IdentityError> {
+        let mut rx = self.state(connection).subscribe();
+        let state = match tokio::time::timeout(timeout, rx.wait_for(Option::is_some)).await {
+            Ok(Ok(state)) => state.clone(),
+            _ => None,
+        };
+        state.unwrap_or(Err(IdentityError::Timeout(timeout)))
+    }
+
+    /// Get the verified identity of a peer
+    pub(crate) fn get(&self, peer: &PeerId) -> Option<VerifiedIdentity> {
+        self.peers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(peer)
+            .cloned()
+    }
+
+    /// Drop the state of a closed connection
+    pub(crate) fn forget_connection(&self, connection: usize) {
+        self.connections
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&connection);
+    }
+
+    /// Drop the verified identity of a peer
+    pub(crate) fn forget(&self, peer: &PeerId) {
+        self.peers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ant_quic::generate_ml_dsa_keypair;
+
+    fn identity() -> LocalIdentity {
+        let (public_key, secret_key) = generate_ml_dsa_keypair().expect("keypair");
+        LocalIdentity::new(public_key, secret_key)
+    }
+
+    #[test]
+    fn test_proof_roundtrip() {
+        let local = identity();
+        let binding = [7u8; BINDING_LEN];
+
+        let proof = local.prove(&binding).expect("prove");
+        let verified = verify_proof(&proof, &binding).expect("verify");
+
+        assert_eq!(verified.peer(), local.peer());
+        assert_eq!(verified.public_key(), local.public_key.as_bytes());
+        assert_eq!(PeerId::from_pubkey(verified.public_key()), verified.peer());
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_session() {
+        let local = identity();
+        let proof = local.prove(&[1u8; BINDING_LEN]).expect("prove");
+
+        assert_eq!(
+            verify_proof(&proof, &[2u8; BINDING_LEN]),
+            Err(IdentityError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_claimed_id_must_match_key() {
+        let local = identity();
+        let binding = [3u8; BINDING_LEN];
+
+        // Claim someone else's ID with a correctly signed proof
+        let claimed = PeerId::new([9u8; 32]);
+        let signature =
+            sign_with_ml_dsa(&local.secret_key, &signed_data(&claimed, &binding)).expect("sign");
+        let public_key = local.public_key.as_bytes();
+        let mut proof = claimed.to_bytes().to_vec();
+        proof.extend_from_slice(&(public_key.len() as u16).to_be_bytes());
+        proof.extend_from_slice(public_key);
+        proof.extend_from_slice(signature.as_bytes());
+
+        assert_eq!(
+            verify_proof(&proof, &binding),
+            Err(IdentityError::PeerIdMismatch {
+                claimed,
+                actual: local.peer()
+            })
+        );
+    }
+
+    #[test]
+    fn test_malformed_proofs_are_rejected() {
+        let local = identity();
+        let binding = [4u8; BINDING_LEN];
+        let proof = local.prove(&binding).expect("prove");
+
+        assert!(matches!(
+            verify_proof(&proof[..20], &binding),
+            Err(IdentityError::Malformed(_))
+        ));
+        assert!(matches!(
+            verify_proof(&proof[..100], &binding),
+            Err(IdentityError::Malformed(_))
+        ));
+
+        let mut tampered = proof.to_vec();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0xff;
+        assert_eq!(
+            verify_proof(&tampered, &binding),
+            Err(IdentityError::InvalidSignature)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_identities_wait_for_first_outcome() {
+        let identities = ConnectionIdentities::default();
+        let local = identity();
+        let verified = VerifiedIdentity {
+            peer: local.peer(),
+            public_key: local.public_key.as_bytes().to_vec(),
+        };
+
+        assert_eq!(
+            identities.wait(1, Duration::from_millis(10)).await,
+            Err(IdentityError::Timeout(Duration::from_millis(10)))
+        );
+
+        identities.resolve(1, Ok(verified.clone()));
+        identities.resolve(1, Err(IdentityError::InvalidSignature));
+        assert_eq!(
+            identities.wait(1, Duration::from_secs(1)).await,
+            Ok(verified.clone())
+        );
+        assert_eq!(identities.get(&local.peer()), Some(verified));
+
+        identities.forget(&local.peer());
+        assert_eq!(identities.get(&local.peer()), None);
+    }
+}
@@ -1,10 +1,26 @@
 //! QUIC transport adapter for Saorsa Gossip
 //!
 //! Provides QUIC transport with:
-//! - Three control streams: `mship`, `pubsub`, `bulk`
+//! - Three control streams: `mship`, `pubsub`, `bulk`, plus `relay` for
+//!   relayed circuits
 //! - 0-RTT resumption where safe
 //! - Path migration by default
 //! - PQC handshake with ant-quic
+//! - Peer IDs proven against each connection's ML-DSA key
+//! - Versioned, length-delimited wire framing
+//! - Optional zstd/lz4 compression negotiated per connection
+//! - Fragmentation of large messages and streamed bulk transfers
+//! - Bounded per-peer send queues with backpressure
+//! - Per-peer inbound rate limits
+//! - Connection lifecycle events for upper layers
+//! - Graceful per-peer disconnect and draining shutdown
+//! - Per-peer and per-stream traffic statistics
+//! - In-memory loopback transport for multi-node tests
+//! - Request/response RPC over any transport
+//! - Relayed circuits for peers that cannot connect directly
+//! - TCP transport with the same identity binding for networks that block UDP
+//! - Automatic fallback from QUIC to TCP per peer
+//! - Per-peer routing across several transports by reachability and RTT
 //!
 //! # Peer Caching
 //!
@@ -12,8 +28,42 @@
 //! with epsilon-greedy selection for balanced exploration and exploitation.
 
 mod ant_quic_transport;
+mod compression;
+mod fallback;
+mod fragment;
+mod framing;
+mod handshake;
+mod lifecycle;
+mod memory;
+mod multi;
+mod rate_limit;
+mod relay;
+mod rpc;
+mod secure;
+mod send_queue;
+mod stats;
+mod streams;
+mod tcp_transport;
 
 pub use ant_quic_transport::{AntQuicTransport, AntQuicTransportConfig};
+pub use compression::{
+    CompressionAlgorithm, CompressionConfig, CompressionError, CompressionMetrics,
+};
+pub use fallback::{FallbackTransport, Route};
+pub use fragment::{FragmentConfig, FragmentError, IncomingTransfer, FRAGMENT_HEADER_LEN};
+pub use framing::{
+    Frame, FrameCodec, FrameError, FrameFlags, FrameHeader, DEFAULT_MAX_FRAME_SIZE,
+    FRAME_HEADER_LEN, FRAME_MAGIC, FRAME_VERSION, HANDSHAKE_MAX_FRAME_SIZE,
+};
+pub use handshake::{IdentityError, VerifiedIdentity};
+pub use memory::{LinkAction, LinkHook, MemoryNetwork, MemoryTransport};
+pub use multi::{MultiTransport, MultiTransportBuilder, RouteOrder};
+pub use rate_limit::{RateLimit, RateLimitConfig};
+pub use relay::{RelayConfig, RelayError, RelayStats, RelayTransport, RELAY_HEADER_LEN};
+pub use rpc::{RpcConfig, RpcError, RpcHandler, RpcTransport, RPC_HEADER_LEN, RPC_MAGIC};
+pub use send_queue::{DropPolicy, PeerSendQueue, QueueFull, SendQueueConfig};
+pub use stats::{PeerStats, StreamStats, TransportStats};
+pub use tcp_transport::{TcpTransport, TcpTransportConfig};
 
 // Re-export ant-quic's bootstrap cache as our peer cache
 pub use ant_quic::{
@@ -22,11 +72,14 @@ pub use ant_quic::{
 
 use anyhow::Result;
 use saorsa_gossip_types::PeerId;
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use tokio::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
 
 /// Stream type identifiers for QUIC streams
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum StreamType {
     /// Membership stream for HyParView+SWIM
     Membership,
@@ -34,8 +87,174 @@ pub enum StreamType {
     PubSub,
     /// Bulk stream for payloads and CRDT deltas
     Bulk,
+    /// Relay stream for circuit control and relayed traffic
+    Relay,
+}
+
+impl StreamType {
+    /// All stream types, highest priority first
+    pub const ALL: [StreamType; 4] = [
+        StreamType::Membership,
+        StreamType::PubSub,
+        StreamType::Relay,
+        StreamType::Bulk,
+    ];
+
+    /// Convert u8 stream tag to StreamType
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Membership),
+            1 => Some(Self::PubSub),
+            2 => Some(Self::Bulk),
+            3 => Some(Self::Relay),
+            _ => None,
+        }
+    }
+
+    /// Convert to the u8 tag sent when a stream is opened
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Self::Membership => 0,
+            Self::PubSub => 1,
+            Self::Bulk => 2,
+            Self::Relay => 3,
+        }
+    }
+
+    /// QUIC send priority (higher values are transmitted first)
+    pub const fn priority(self) -> i32 {
+        match self {
+            Self::Membership => 255,
+            Self::PubSub => 200,
+            Self::Bulk => 50,
+            Self::Relay => 150,
+        }
+    }
+}
+
+/// Why a node deliberately closes a connection to a peer
+///
+/// Sent to the peer as the QUIC application close code, so both sides
+/// report the same reason.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CloseReason {
+    /// The node is shutting down
+    Shutdown,
+    /// The peer was evicted, e.g. to make room in the active view
+    Evicted,
+    /// The peer is banned
+    Banned,
+    /// The peer violated the protocol
+    ProtocolViolation,
+}
+
+impl CloseReason {
+    /// Convert an application close code to a CloseReason
+    pub fn from_code(code: u64) -> Option<Self> {
+        match code {
+            1 => Some(Self::Shutdown),
+            2 => Some(Self::Evicted),
+            3 => Some(Self::Banned),
+            4 => Some(Self::ProtocolViolation),
+            _ => None,
+        }
+    }
+
+    /// Convert to the application close code sent to the peer
+    pub const fn code(self) -> u32 {
+        match self {
+            Self::Shutdown => 1,
+            Self::Evicted => 2,
+            Self::Banned => 3,
+            Self::ProtocolViolation => 4,
+        }
+    }
+
+    /// Human-readable reason sent alongside the close code
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Shutdown => "shutdown",
+            Self::Evicted => "evicted",
+            Self::Banned => "banned",
+            Self::ProtocolViolation => "protocol violation",
+        }
+    }
+}
+
+impl std::fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why a connection to a peer ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The connection was closed by this node
+    LocalClose,
+    /// The connection was closed by the peer, with the reason it gave
+    RemoteClose(String),
+    /// This node disconnected the peer with [`GossipTransport::disconnect`]
+    LocalDisconnect(CloseReason),
+    /// The peer disconnected us with a typed reason
+    RemoteDisconnect(CloseReason),
+    /// The connection was idle for longer than the idle timeout
+    Timeout,
+    /// The connection failed or was reset
+    Error(String),
 }
 
+/// Events reported by a transport
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportEvent {
+    /// A connection to a peer was established
+    PeerConnected {
+        /// The connected peer
+        peer: PeerId,
+        /// The peer's address
+        addr: SocketAddr,
+    },
+    /// The connection to a peer was closed
+    PeerDisconnected {
+        /// The disconnected peer
+        peer: PeerId,
+        /// Why the connection ended
+        reason: DisconnectReason,
+    },
+    /// The connection to a peer moved to a new network path
+    PathMigrated {
+        /// The migrated peer
+        peer: PeerId,
+        /// The peer's previous address
+        from: SocketAddr,
+        /// The peer's new address
+        to: SocketAddr,
+    },
+    /// Our external address, as observed by remote peers, changed
+    ExternalAddressChanged {
+        /// The previously observed address, if any
+        previous: Option<SocketAddr>,
+        /// The newly observed address
+        addr: SocketAddr,
+    },
+    /// The send queue to a peer reached its high watermark
+    Congested(PeerId),
+    /// The send queue to a peer drained back to its low watermark
+    Decongested(PeerId),
+    /// A peer exceeded its inbound rate limit and messages were dropped
+    RateLimited {
+        /// The offending peer
+        peer: PeerId,
+        /// The stream type whose limit was exceeded
+        stream_type: StreamType,
+        /// Messages dropped since the previous report
+        dropped: u64,
+    },
+}
+
+/// Capacity of transport event broadcast channels
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+
 /// QUIC transport trait for dial/listen operations
 #[async_trait::async_trait]
 pub trait GossipTransport: Send + Sync {
@@ -52,6 +271,12 @@ pub trait GossipTransport: Send + Sync {
     /// Close the transport
     async fn close(&self) -> Result<()>;
 
+    /// Gracefully disconnect a peer
+    ///
+    /// Flushes messages already queued for the peer, then closes the
+    /// connection with `reason` so the peer knows why it was dropped.
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()>;
+
     /// Send data to a specific peer on a specific stream type
     async fn send_to_peer(
         &self,
@@ -62,6 +287,44 @@ pub trait GossipTransport: Send + Sync {
 
     /// Receive a message from any peer on any stream
     async fn receive_message(&self) -> Result<(PeerId, StreamType, bytes::Bytes)>;
+
+    /// Subscribe to connection lifecycle and congestion events
+    ///
+    /// Each subscriber receives every event emitted after it subscribed.
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent>;
+
+    /// Get the identity a connected peer proved during the handshake
+    ///
+    /// The returned public key can verify the peer's signatures without
+    /// trusting keys carried in its messages. Transports that do not
+    /// authenticate peers return `None`.
+    fn peer_identity(&self, _peer: &PeerId) -> Option<VerifiedIdentity> {
+        None
+    }
+
+    /// Get the address a connected peer is reached at, as seen locally
+    ///
+    /// Reflectors return this to peers asking for their observed address.
+    /// Transports that do not track addresses return `None`.
+    async fn peer_addr(&self, _peer: &PeerId) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Get the current round-trip time estimate to a connected peer
+    ///
+    /// Used to pick between routes to the same peer. Transports without an
+    /// estimate return `None`.
+    async fn peer_rtt(&self, _peer: &PeerId) -> Option<Duration> {
+        None
+    }
+
+    /// Check if links are encrypted and integrity protected
+    ///
+    /// Transports carrying plaintext return `false`; wrappers combining
+    /// transports are encrypted only if all their transports are.
+    fn is_encrypted(&self) -> bool {
+        true
+    }
 }
 
 // Blanket implementation for Arc<T> to allow calling trait methods through Arc
@@ -83,6 +346,10 @@ impl<T: GossipTransport + ?Sized> GossipTransport for std::sync::Arc<T> {
         (**self).close().await
     }
 
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+        (**self).disconnect(peer, reason).await
+    }
+
     async fn send_to_peer(
         &self,
         peer: PeerId,
@@ -95,6 +362,26 @@ impl<T: GossipTransport + ?Sized> GossipTransport for std::sync::Arc<T> {
     async fn receive_message(&self) -> Result<(PeerId, StreamType, bytes::Bytes)> {
         (**self).receive_message().await
     }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        (**self).subscribe_events()
+    }
+
+    fn peer_identity(&self, peer: &PeerId) -> Option<VerifiedIdentity> {
+        (**self).peer_identity(peer)
+    }
+
+    async fn peer_addr(&self, peer: &PeerId) -> Option<SocketAddr> {
+        (**self).peer_addr(peer).await
+    }
+
+    async fn peer_rtt(&self, peer: &PeerId) -> Option<Duration> {
+        (**self).peer_rtt(peer).await
+    }
+
+    fn is_encrypted(&self) -> bool {
+        (**self).is_encrypted()
+    }
 }
 
 /// Transport configuration
@@ -108,6 +395,8 @@ pub struct TransportConfig {
     pub max_idle_timeout: u64,
     /// Keep-alive interval in seconds
     pub keep_alive_interval: u64,
+    /// Per-peer send queue limits and drop policies
+    pub send_queue: SendQueueConfig,
 }
 
 impl Default for TransportConfig {
@@ -117,52 +406,82 @@ impl Default for TransportConfig {
             enable_migration: true,
             max_idle_timeout: 30,
             keep_alive_interval: 10,
+            send_queue: SendQueueConfig::default(),
         }
     }
 }
 
+/// Capacity of the mock transport's channels and each multiplexed stream
+const CHANNEL_CAPACITY: usize = 1024;
+
 /// Mock QUIC transport implementation (placeholder for ant-quic)
 pub struct QuicTransport {
     #[allow(dead_code)]
     config: TransportConfig,
-    connection_tx: mpsc::UnboundedSender<(PeerId, SocketAddr)>,
-    connection_rx: mpsc::UnboundedReceiver<(PeerId, SocketAddr)>,
-    /// Channel for sending messages to peers
-    send_tx: mpsc::UnboundedSender<(PeerId, StreamType, bytes::Bytes)>,
-    #[allow(dead_code)]
-    send_rx: mpsc::UnboundedReceiver<(PeerId, StreamType, bytes::Bytes)>,
+    connection_tx: mpsc::Sender<(PeerId, SocketAddr)>,
+    connection_rx: mpsc::Receiver<(PeerId, SocketAddr)>,
+    /// Bounded queue of messages sent to each peer
+    send_queues: std::sync::RwLock<HashMap<PeerId, Arc<PeerSendQueue>>>,
     /// Channel for receiving messages from peers
-    recv_tx: mpsc::UnboundedSender<(PeerId, StreamType, bytes::Bytes)>,
+    recv_tx: mpsc::Sender<(PeerId, StreamType, bytes::Bytes)>,
     #[allow(dead_code)]
-    recv_rx: mpsc::UnboundedReceiver<(PeerId, StreamType, bytes::Bytes)>,
+    recv_rx: mpsc::Receiver<(PeerId, StreamType, bytes::Bytes)>,
+    /// Transport event broadcaster
+    events: broadcast::Sender<TransportEvent>,
 }
 
 impl QuicTransport {
     /// Create a new QUIC transport with the given configuration
     pub fn new(config: TransportConfig) -> Self {
-        let (connection_tx, connection_rx) = mpsc::unbounded_channel();
-        let (send_tx, send_rx) = mpsc::unbounded_channel();
-        let (recv_tx, recv_rx) = mpsc::unbounded_channel();
+        let (connection_tx, connection_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (recv_tx, recv_rx) = mpsc::channel(CHANNEL_CAPACITY);
         Self {
             config,
             connection_tx,
             connection_rx,
-            send_tx,
-            send_rx,
+            send_queues: std::sync::RwLock::new(HashMap::new()),
             recv_tx,
             recv_rx,
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
         }
     }
 
     /// Get a receiver for incoming connections
-    pub fn connection_receiver(&mut self) -> &mut mpsc::UnboundedReceiver<(PeerId, SocketAddr)> {
+    pub fn connection_receiver(&mut self) -> &mut mpsc::Receiver<(PeerId, SocketAddr)> {
         &mut self.connection_rx
     }
 
     /// Get a sender for simulating received messages (for testing)
-    pub fn get_recv_tx(&self) -> mpsc::UnboundedSender<(PeerId, StreamType, bytes::Bytes)> {
+    pub fn get_recv_tx(&self) -> mpsc::Sender<(PeerId, StreamType, bytes::Bytes)> {
         self.recv_tx.clone()
     }
+
+    /// Get the queue of messages sent to a peer and not yet taken (for testing)
+    pub fn send_queue(&self, peer: &PeerId) -> Option<Arc<PeerSendQueue>> {
+        self.send_queues
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(peer)
+            .cloned()
+    }
+
+    fn send_queue_or_insert(&self, peer: PeerId) -> Arc<PeerSendQueue> {
+        let mut queues = self.send_queues.write().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(queues.entry(peer).or_insert_with(|| {
+            Arc::new(PeerSendQueue::new(
+                peer,
+                self.config.send_queue.clone(),
+                self.events.clone(),
+            ))
+        }))
+    }
+
+    fn remove_send_queue(&self, peer: &PeerId) -> Option<Arc<PeerSendQueue>> {
+        self.send_queues
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(peer)
+    }
 }
 
 #[async_trait::async_trait]
@@ -170,8 +489,11 @@ impl GossipTransport for QuicTransport {
     async fn dial(&self, peer: PeerId, addr: SocketAddr) -> Result<()> {
         // Placeholder implementation - will integrate with ant-quic
         self.connection_tx
-            .send((peer, addr))
+            .try_send((peer, addr))
             .map_err(|e| anyhow::anyhow!("Failed to send connection: {}", e))?;
+        let _ = self
+            .events
+            .send(TransportEvent::PeerConnected { peer, addr });
         Ok(())
     }
 
@@ -188,8 +510,12 @@ impl GossipTransport for QuicTransport {
         id_bytes[..8].copy_from_slice(&hash.to_le_bytes());
         let peer_id = PeerId::new(id_bytes);
         self.connection_tx
-            .send((peer_id, addr))
+            .try_send((peer_id, addr))
             .map_err(|e| anyhow::anyhow!("Failed to send connection: {}", e))?;
+        let _ = self.events.send(TransportEvent::PeerConnected {
+            peer: peer_id,
+            addr,
+        });
         Ok(peer_id)
     }
 
@@ -199,7 +525,24 @@ impl GossipTransport for QuicTransport {
     }
 
     async fn close(&self) -> Result<()> {
-        // Placeholder implementation
+        // Placeholder implementation - nothing is transmitted, so drop the queues
+        let queues =
+            std::mem::take(&mut *self.send_queues.write().unwrap_or_else(|e| e.into_inner()));
+        for queue in queues.into_values() {
+            queue.close();
+        }
+        Ok(())
+    }
+
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+        // Placeholder implementation - nothing is transmitted, so drop the queue
+        if let Some(queue) = self.remove_send_queue(&peer) {
+            queue.close();
+        }
+        let _ = self.events.send(TransportEvent::PeerDisconnected {
+            peer,
+            reason: DisconnectReason::LocalDisconnect(reason),
+        });
         Ok(())
     }
 
@@ -210,18 +553,17 @@ impl GossipTransport for QuicTransport {
         data: bytes::Bytes,
     ) -> Result<()> {
         // Placeholder implementation - will integrate with ant-quic
-        // In real implementation, this would open a QUIC stream to the peer
-        self.send_tx
-            .send((peer, stream_type, data))
-            .map_err(|e| anyhow::anyhow!("Failed to send to peer: {}", e))?;
-        Ok(())
+        // In real implementation, a worker would drain the queue onto QUIC streams
+        self.send_queue_or_insert(peer)
+            .push(stream_type, data)
+            .await
     }
 
     async fn receive_message(&self) -> Result<(PeerId, StreamType, bytes::Bytes)> {
         // Placeholder implementation - will integrate with ant-quic
         // In real implementation, this would receive from QUIC streams
         self.recv_tx
-            .send((
+            .try_send((
                 PeerId::new([0u8; 32]),
                 StreamType::PubSub,
                 bytes::Bytes::new(),
@@ -229,46 +571,57 @@ impl GossipTransport for QuicTransport {
             .ok();
         Err(anyhow::anyhow!("No messages available"))
     }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        self.events.subscribe()
+    }
 }
 
 /// Stream multiplexer for QUIC streams
 pub struct StreamMultiplexer {
-    membership_tx: mpsc::UnboundedSender<bytes::Bytes>,
-    pubsub_tx: mpsc::UnboundedSender<bytes::Bytes>,
-    bulk_tx: mpsc::UnboundedSender<bytes::Bytes>,
+    membership_tx: mpsc::Sender<bytes::Bytes>,
+    pubsub_tx: mpsc::Sender<bytes::Bytes>,
+    bulk_tx: mpsc::Sender<bytes::Bytes>,
+    relay_tx: mpsc::Sender<bytes::Bytes>,
 }
 
 impl StreamMultiplexer {
-    /// Create a new stream multiplexer
+    /// Create a new stream multiplexer, each stream bounded to 1,024 messages
     pub fn new() -> (Self, StreamReceivers) {
-        let (membership_tx, membership_rx) = mpsc::unbounded_channel();
-        let (pubsub_tx, pubsub_rx) = mpsc::unbounded_channel();
-        let (bulk_tx, bulk_rx) = mpsc::unbounded_channel();
+        let (membership_tx, membership_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (pubsub_tx, pubsub_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (bulk_tx, bulk_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (relay_tx, relay_rx) = mpsc::channel(CHANNEL_CAPACITY);
 
         let mux = Self {
             membership_tx,
             pubsub_tx,
             bulk_tx,
+            relay_tx,
         };
 
         let receivers = StreamReceivers {
             membership_rx,
             pubsub_rx,
             bulk_rx,
+            relay_rx,
         };
 
         (mux, receivers)
     }
 
     /// Send data on the specified stream type
+    ///
+    /// Fails if the stream's receiver has fallen 1,024 messages behind.
     pub fn send(&self, stream_type: StreamType, data: bytes::Bytes) -> Result<()> {
         let tx = match stream_type {
             StreamType::Membership => &self.membership_tx,
             StreamType::PubSub => &self.pubsub_tx,
             StreamType::Bulk => &self.bulk_tx,
+            StreamType::Relay => &self.relay_tx,
         };
 
-        tx.send(data)
+        tx.try_send(data)
             .map_err(|e| anyhow::anyhow!("Failed to send on {:?} stream: {}", stream_type, e))
     }
 }
@@ -282,11 +635,13 @@ impl Default for StreamMultiplexer {
 /// Stream receivers for each stream type
 pub struct StreamReceivers {
     /// Membership stream receiver
-    pub membership_rx: mpsc::UnboundedReceiver<bytes::Bytes>,
+    pub membership_rx: mpsc::Receiver<bytes::Bytes>,
     /// Pub/sub stream receiver
-    pub pubsub_rx: mpsc::UnboundedReceiver<bytes::Bytes>,
+    pub pubsub_rx: mpsc::Receiver<bytes::Bytes>,
     /// Bulk stream receiver
-    pub bulk_rx: mpsc::UnboundedReceiver<bytes::Bytes>,
+    pub bulk_rx: mpsc::Receiver<bytes::Bytes>,
+    /// Relay stream receiver
+    pub relay_rx: mpsc::Receiver<bytes::Bytes>,
 }
 
 #[cfg(test)]
@@ -314,6 +669,55 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_quic_transport_send_queue_congestion() {
+        let config = TransportConfig {
+            send_queue: SendQueueConfig::default().with_watermarks(2, 0),
+            ..TransportConfig::default()
+        };
+        let transport = QuicTransport::new(config);
+        let mut events = transport.subscribe_events();
+        let peer = PeerId::new([1u8; 32]);
+
+        for _ in 0..2 {
+            transport
+                .send_to_peer(peer, StreamType::PubSub, bytes::Bytes::from("ihave"))
+                .await
+                .expect("send");
+        }
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(TransportEvent::Congested(peer))
+        );
+
+        // Bulk messages are rejected once the queue is full
+        assert!(transport
+            .send_to_peer(peer, StreamType::Bulk, bytes::Bytes::from("block"))
+            .await
+            .is_err());
+
+        let queue = transport.send_queue(&peer).expect("queue");
+        queue.pop().await;
+        queue.pop().await;
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(TransportEvent::Decongested(peer))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_multiplexer_is_bounded() {
+        let (mux, _receivers) = StreamMultiplexer::new();
+        for _ in 0..CHANNEL_CAPACITY {
+            mux.send(StreamType::Bulk, bytes::Bytes::new())
+                .expect("send");
+        }
+        assert!(mux.send(StreamType::Bulk, bytes::Bytes::new()).is_err());
+        assert!(mux
+            .send(StreamType::Membership, bytes::Bytes::new())
+            .is_ok());
+    }
+
     #[tokio::test]
     async fn test_transport_dial() {
         let config = TransportConfig::default();
@@ -0,0 +1,295 @@
+//! Connection lifecycle tracking
+//!
+//! Watches every QUIC connection the transport uses and turns its lifecycle
+//! into [`TransportEvent`]s: a peer connecting, its connection closing, the
+//! connection migrating to a new path, and our own external address changing.
+
+use ant_quic::{ConnectionError, HighLevelConnection};
+use saorsa_gossip_types::PeerId as GossipPeerId;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, info};
+
+use crate::{CloseReason, DisconnectReason, TransportEvent};
+
+/// How often a live connection is checked for path migration
+const PATH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The connection currently in use for a peer
+#[derive(Debug, Clone, Copy)]
+struct LiveConnection {
+    stable_id: usize,
+    addr: SocketAddr,
+}
+
+/// Tracks live connections and reports their lifecycle as transport events
+#[derive(Clone)]
+pub(crate) struct ConnectionTracker {
+    /// Live connection per peer
+    live: Arc<Mutex<HashMap<GossipPeerId, LiveConnection>>>,
+    /// Last external address reported by the endpoint
+    external: Arc<Mutex<Option<SocketAddr>>>,
+    /// Transport event broadcaster
+    events: broadcast::Sender<TransportEvent>,
+    /// Lifecycle events for the transport's own bookkeeping (never lagged)
+    updates_tx: mpsc::UnboundedSender<TransportEvent>,
+}
+
+impl ConnectionTracker {
+    /// Create a tracker emitting on `events`
+    ///
+    /// Also returns a lossless receiver of the same lifecycle events, so the
+    /// transport can drop state for disconnected peers.
+    pub(crate) fn new(
+        events: broadcast::Sender<TransportEvent>,
+    ) -> (Self, mpsc::UnboundedReceiver<TransportEvent>) {
+        let (updates_tx, updates_rx) = mpsc::unbounded_channel();
+        let tracker = Self {
+            live: Arc::new(Mutex::new(HashMap::new())),
+            external: Arc::new(Mutex::new(None)),
+            events,
+            updates_tx,
+        };
+        (tracker, updates_rx)
+    }
+
+    fn live(&self) -> std::sync::MutexGuard<'_, HashMap<GossipPeerId, LiveConnection>> {
+        self.live.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Start tracking a connection to `peer`
+    ///
+    /// Emits [`TransportEvent::PeerConnected`] for a peer without a live
+    /// connection, or [`TransportEvent::PathMigrated`] if the peer's
+    /// connection was replaced by one on a different address. Tracking the
+    /// same connection twice is a no-op.
+    pub(crate) fn track(&self, peer: GossipPeerId, conn: HighLevelConnection) {
+        let current = LiveConnection {
+            stable_id: conn.stable_id(),
+            addr: conn.remote_address(),
+        };
+
+        let previous = {
+            let mut live = self.live();
+            match live.insert(peer, current) {
+                Some(prev) if prev.stable_id == current.stable_id => return,
+                prev => prev,
+            }
+        };
+
+        match previous {
+            None => {
+                debug!("Peer {} connected at {}", peer, current.addr);
+                self.emit(TransportEvent::PeerConnected {
+                    peer,
+                    addr: current.addr,
+                });
+            }
+            Some(prev) if prev.addr != current.addr => self.migrated(peer, prev.addr, current.addr),
+            Some(_) => debug!("Connection to peer {} replaced", peer),
+        }
+
+        let tracker = self.clone();
+        tokio::spawn(async move { tracker.watch(peer, conn, current).await });
+    }
+
+    /// Watch a connection for migration until it closes
+    async fn watch(
+        &self,
+        peer: GossipPeerId,
+        conn: HighLevelConnection,
+        mut current: LiveConnection,
+    ) {
+        let mut interval = tokio::time::interval(PATH_CHECK_INTERVAL);
+
+        let error = loop {
+            tokio::select! {
+                error = conn.closed() => break error,
+                _ = interval.tick() => {
+                    let addr = conn.remote_address();
+                    if addr == current.addr {
+                        continue;
+                    }
+
+                    let still_live = {
+                        let mut live = self.live();
+                        match live.get_mut(&peer) {
+                            Some(entry) if entry.stable_id == current.stable_id => {
+                                entry.addr = addr;
+                                true
+                            }
+                            _ => false,
+                        }
+                    };
+                    if still_live {
+                        self.migrated(peer, current.addr, addr);
+                    }
+                    current.addr = addr;
+                }
+            }
+        };
+
+        // Only report the close if no newer connection has replaced this one
+        let was_live = {
+            let mut live = self.live();
+            match live.get(&peer) {
+                Some(entry) if entry.stable_id == current.stable_id => {
+                    live.remove(&peer);
+                    true
+                }
+                _ => false,
+            }
+        };
+        if !was_live {
+            return;
+        }
+
+        let reason = DisconnectReason::from(error);
+        info!("Peer {} disconnected: {:?}", peer, reason);
+        self.emit(TransportEvent::PeerDisconnected { peer, reason });
+    }
+
+    /// Report that this node disconnected `peer` with `reason`
+    ///
+    /// The peer's connection stops being tracked, so its watcher does not
+    /// report the close a second time.
+    pub(crate) fn disconnected(&self, peer: GossipPeerId, reason: CloseReason) {
+        self.live().remove(&peer);
+        info!("Disconnected peer {}: {}", peer, reason);
+        self.emit(TransportEvent::PeerDisconnected {
+            peer,
+            reason: DisconnectReason::LocalDisconnect(reason),
+        });
+    }
+
+    fn migrated(&self, peer: GossipPeerId, from: SocketAddr, to: SocketAddr) {
+        info!("Peer {} migrated from {} to {}", peer, from, to);
+        self.emit(TransportEvent::PathMigrated { peer, from, to });
+    }
+
+    fn emit(&self, event: TransportEvent) {
+        let _ = self.updates_tx.send(event.clone());
+        let _ = self.events.send(event);
+    }
+
+    /// Record the external address reported by the endpoint
+    ///
+    /// Emits [`TransportEvent::ExternalAddressChanged`] if it differs from the
+    /// last one seen.
+    pub(crate) fn observe_external_address(&self, addr: SocketAddr) {
+        let previous = {
+            let mut external = self.external.lock().unwrap_or_else(|e| e.into_inner());
+            if *external == Some(addr) {
+                return;
+            }
+            external.replace(addr)
+        };
+
+        info!("External address changed to {} (was {:?})", addr, previous);
+        self.emit(TransportEvent::ExternalAddressChanged { previous, addr });
+    }
+}
+
+impl From<ConnectionError> for DisconnectReason {
+    fn from(error: ConnectionError) -> Self {
+        match error {
+            ConnectionError::LocallyClosed => Self::LocalClose,
+            ConnectionError::ApplicationClosed(close) => {
+                match CloseReason::from_code(close.error_code.into_inner()) {
+                    Some(reason) => Self::RemoteDisconnect(reason),
+                    None => Self::RemoteClose(String::from_utf8_lossy(&close.reason).into_owned()),
+                }
+            }
+            ConnectionError::TimedOut => Self::Timeout,
+            other => Self::Error(other.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_external_address_changes_are_deduplicated() {
+        let (events, mut rx) = broadcast::channel(8);
+        let (tracker, _updates) = ConnectionTracker::new(events);
+        let first: SocketAddr = "203.0.113.1:9000".parse().expect("addr");
+        let second: SocketAddr = "203.0.113.2:9000".parse().expect("addr");
+
+        tracker.observe_external_address(first);
+        tracker.observe_external_address(first);
+        tracker.observe_external_address(second);
+
+        assert_eq!(
+            rx.try_recv().ok(),
+            Some(TransportEvent::ExternalAddressChanged {
+                previous: None,
+                addr: first
+            })
+        );
+        assert_eq!(
+            rx.try_recv().ok(),
+            Some(TransportEvent::ExternalAddressChanged {
+                previous: Some(first),
+                addr: second
+            })
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_disconnect_reason_from_connection_error() {
+        assert_eq!(
+            DisconnectReason::from(ConnectionError::LocallyClosed),
+            DisconnectReason::LocalClose
+        );
+        assert_eq!(
+            DisconnectReason::from(ConnectionError::TimedOut),
+            DisconnectReason::Timeout
+        );
+        assert!(matches!(
+            DisconnectReason::from(ConnectionError::Reset),
+            DisconnectReason::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_typed_close_reasons_are_decoded() {
+        let close = |code: u32, reason: &'static [u8]| {
+            ConnectionError::ApplicationClosed(ant_quic::frame::ApplicationClose {
+                error_code: ant_quic::VarInt::from_u32(code),
+                reason: bytes::Bytes::from_static(reason),
+            })
+        };
+
+        assert_eq!(
+            DisconnectReason::from(close(CloseReason::Banned.code(), b"banned")),
+            DisconnectReason::RemoteDisconnect(CloseReason::Banned)
+        );
+        assert_eq!(
+            DisconnectReason::from(close(0, b"bye")),
+            DisconnectReason::RemoteClose("bye".to_string())
+        );
+    }
+
+    #[test]
+    fn test_local_disconnect_is_reported_once() {
+        let (events, mut rx) = broadcast::channel(8);
+        let (tracker, mut updates) = ConnectionTracker::new(events);
+        let peer = GossipPeerId::new([1u8; 32]);
+
+        tracker.disconnected(peer, CloseReason::Evicted);
+
+        let expected = TransportEvent::PeerDisconnected {
+            peer,
+            reason: DisconnectReason::LocalDisconnect(CloseReason::Evicted),
+        };
+        assert_eq!(rx.try_recv().ok(), Some(expected.clone()));
+        assert_eq!(updates.try_recv().ok(), Some(expected));
+        assert!(tracker.live().is_empty());
+    }
+}
@@ -0,0 +1,535 @@
+//! In-process loopback transport for multi-node tests
+//!
+//! A [`MemoryNetwork`] is a virtual switch shared by any number of
+//! [`MemoryTransport`]s. Bytes sent between transports on the same network
+//! are really delivered to the receiver's `receive_message`, so protocols can
+//! be tested end to end without QUIC or the full simulator.
+//!
+//! An optional [`LinkHook`] decides per message whether it is delivered,
+//! dropped or delayed, which is enough to model loss, latency and partitions.
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use saorsa_gossip_types::PeerId;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
+use tracing::trace;
+
+use crate::{
+    CloseReason, DisconnectReason, GossipTransport, StreamType, TransportEvent,
+    EVENT_CHANNEL_CAPACITY,
+};
+
+/// What the network does with a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkAction {
+    /// Deliver the message immediately
+    Deliver,
+    /// Silently drop the message
+    Drop,
+    /// Deliver the message after a delay
+    Delay(Duration),
+}
+
+/// Decides the fate of each message: `(from, to, stream_type, data)`
+pub type LinkHook = Arc<dyn Fn(PeerId, PeerId, StreamType, &Bytes) -> LinkAction + Send + Sync>;
+
+/// A message waiting in a node's inbox
+type Envelope = (PeerId, StreamType, Bytes);
+
+/// A node registered on the network
+struct MemoryNode {
+    addr: SocketAddr,
+    inbox: mpsc::UnboundedSender<Envelope>,
+    events: broadcast::Sender<TransportEvent>,
+    connections: HashSet<PeerId>,
+}
+
+/// Shared state of a memory network
+#[derive(Default)]
+struct NetworkState {
+    nodes: HashMap<PeerId, MemoryNode>,
+    addrs: HashMap<SocketAddr, PeerId>,
+    next_port: u16,
+}
+
+/// Shared in-memory switch connecting [`MemoryTransport`]s
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    state: Arc<RwLock<NetworkState>>,
+    hook: Arc<RwLock<Option<LinkHook>>>,
+    delivered: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl MemoryNetwork {
+    /// Create an empty network
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a node with an automatically assigned address
+    pub async fn add_node(&self, peer_id: PeerId) -> Result<MemoryTransport> {
+        let addr = {
+            let mut state = self.state.write().await;
+            state.next_port = state.next_port.wrapping_add(1);
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), state.next_port)
+        };
+        self.add_node_at(peer_id, addr).await
+    }
+
+    /// Register a node at a specific address
+    pub async fn add_node_at(&self, peer_id: PeerId, addr: SocketAddr) -> Result<MemoryTransport> {
+        let (inbox_tx, inbox_rx) = mpsc::unbounded_channel();
+        let events = broadcast::channel(EVENT_CHANNEL_CAPACITY).0;
+
+        let mut state = self.state.write().await;
+        if state.nodes.contains_key(&peer_id) {
+            return Err(anyhow!("Peer {} is already on the network", peer_id));
+        }
+        if state.addrs.contains_key(&addr) {
+            return Err(anyhow!("Address {} is already in use", addr));
+        }
+
+        state.addrs.insert(addr, peer_id);
+        state.nodes.insert(
+            peer_id,
+            MemoryNode {
+                addr,
+                inbox: inbox_tx,
+                events: events.clone(),
+                connections: HashSet::new(),
+            },
+        );
+
+        Ok(MemoryTransport {
+            peer_id,
+            addr,
+            network: self.clone(),
+            inbox: Mutex::new(inbox_rx),
+            events,
+        })
+    }
+
+    /// Install a hook deciding whether each message is delivered, dropped or delayed
+    pub async fn set_hook<F>(&self, hook: F)
+    where
+        F: Fn(PeerId, PeerId, StreamType, &Bytes) -> LinkAction + Send + Sync + 'static,
+    {
+        *self.hook.write().await = Some(Arc::new(hook));
+    }
+
+    /// Remove the hook, delivering every message immediately
+    pub async fn clear_hook(&self) {
+        *self.hook.write().await = None;
+    }
+
+    /// Get the peers registered on the network
+    pub async fn peers(&self) -> Vec<PeerId> {
+        self.state.read().await.nodes.keys().copied().collect()
+    }
+
+    /// Get the number of messages delivered so far
+    pub fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of messages dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Connect two nodes, notifying both sides on first connection
+    async fn connect(&self, from: PeerId, to: PeerId) -> Result<()> {
+        let mut state = self.state.write().await;
+        let to_addr = state
+            .nodes
+            .get(&to)
+            .map(|node| node.addr)
+            .ok_or_else(|| anyhow!("Peer {} is not on the network", to))?;
+        let from_node = state
+            .nodes
+            .get_mut(&from)
+            .ok_or_else(|| anyhow!("Transport {} is closed", from))?;
+        if !from_node.connections.insert(to) {
+            return Ok(());
+        }
+        let from_addr = from_node.addr;
+        let _ = from_node.events.send(TransportEvent::PeerConnected {
+            peer: to,
+            addr: to_addr,
+        });
+
+        if let Some(to_node) = state.nodes.get_mut(&to) {
+            to_node.connections.insert(from);
+            let _ = to_node.events.send(TransportEvent::PeerConnected {
+                peer: from,
+                addr: from_addr,
+            });
+        }
+        Ok(())
+    }
+
+    /// Close the connection between two nodes, notifying both sides
+    ///
+    /// Messages already sent are in the receiver's inbox, so nothing is lost.
+    async fn disconnect(&self, from: PeerId, to: PeerId, reason: CloseReason) -> Result<()> {
+        let mut state = self.state.write().await;
+        let from_node = state
+            .nodes
+            .get_mut(&from)
+            .ok_or_else(|| anyhow!("Transport {} is closed", from))?;
+        if !from_node.connections.remove(&to) {
+            return Err(anyhow!("Not connected to peer {}", to));
+        }
+        let _ = from_node.events.send(TransportEvent::PeerDisconnected {
+            peer: to,
+            reason: DisconnectReason::LocalDisconnect(reason),
+        });
+
+        if let Some(to_node) = state.nodes.get_mut(&to) {
+            to_node.connections.remove(&from);
+            let _ = to_node.events.send(TransportEvent::PeerDisconnected {
+                peer: from,
+                reason: DisconnectReason::RemoteDisconnect(reason),
+            });
+        }
+        Ok(())
+    }
+
+    /// Remove a node, disconnecting it from every peer
+    async fn remove(&self, peer_id: PeerId) {
+        let mut state = self.state.write().await;
+        let Some(node) = state.nodes.remove(&peer_id) else {
+            return;
+        };
+        state.addrs.remove(&node.addr);
+
+        for peer in node.connections {
+            let _ = node.events.send(TransportEvent::PeerDisconnected {
+                peer,
+                reason: DisconnectReason::LocalClose,
+            });
+            if let Some(remote) = state.nodes.get_mut(&peer) {
+                remote.connections.remove(&peer_id);
+                let _ = remote.events.send(TransportEvent::PeerDisconnected {
+                    peer: peer_id,
+                    reason: DisconnectReason::RemoteClose("closed".to_string()),
+                });
+            }
+        }
+    }
+
+    /// Route a message to its destination, applying the hook
+    async fn route(
+        &self,
+        from: PeerId,
+        to: PeerId,
+        stream_type: StreamType,
+        data: Bytes,
+    ) -> Result<()> {
+        let inbox = {
+            let state = self.state.read().await;
+            if !state.nodes.contains_key(&from) {
+                return Err(anyhow!("Transport {} is closed", from));
+            }
+            state
+                .nodes
+                .get(&to)
+                .map(|node| node.inbox.clone())
+                .ok_or_else(|| anyhow!("Peer {} is not on the network", to))?
+        };
+
+        let action = match self.hook.read().await.as_ref() {
+            Some(hook) => hook(from, to, stream_type, &data),
+            None => LinkAction::Deliver,
+        };
+
+        match action {
+            LinkAction::Deliver => self.deliver(&inbox, (from, stream_type, data)),
+            LinkAction::Drop => {
+                trace!("Dropped {:?} message from {} to {}", stream_type, from, to);
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            LinkAction::Delay(delay) => {
+                let network = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    network.deliver(&inbox, (from, stream_type, data));
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn deliver(&self, inbox: &mpsc::UnboundedSender<Envelope>, envelope: Envelope) {
+        // A closed inbox means the receiver went away; count it as lost
+        if inbox.send(envelope).is_ok() {
+            self.delivered.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A [`GossipTransport`] attached to a [`MemoryNetwork`]
+///
+/// Messages may be sent to any peer on the network; dialing only establishes
+/// the connection reported through [`GossipTransport::subscribe_events`].
+pub struct MemoryTransport {
+    peer_id: PeerId,
+    addr: SocketAddr,
+    network: MemoryNetwork,
+    inbox: Mutex<mpsc::UnboundedReceiver<Envelope>>,
+    events: broadcast::Sender<TransportEvent>,
+}
+
+impl MemoryTransport {
+    /// Get this node's peer ID
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    /// Get this node's address on the network
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Get the network this transport is attached to
+    pub fn network(&self) -> &MemoryNetwork {
+        &self.network
+    }
+}
+
+#[async_trait::async_trait]
+impl GossipTransport for MemoryTransport {
+    async fn dial(&self, peer: PeerId, _addr: SocketAddr) -> Result<()> {
+        self.network.connect(self.peer_id, peer).await
+    }
+
+    async fn dial_bootstrap(&self, addr: SocketAddr) -> Result<PeerId> {
+        let peer = self
+            .network
+            .state
+            .read()
+            .await
+            .addrs
+            .get(&addr)
+            .copied()
+            .ok_or_else(|| anyhow!("No node listening at {}", addr))?;
+        self.network.connect(self.peer_id, peer).await?;
+        Ok(peer)
+    }
+
+    async fn listen(&self, _bind: SocketAddr) -> Result<()> {
+        // Nodes accept connections as soon as they join the network
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.network.remove(self.peer_id).await;
+        Ok(())
+    }
+
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+        self.network.disconnect(self.peer_id, peer, reason).await
+    }
+
+    async fn send_to_peer(&self, peer: PeerId, stream_type: StreamType, data: Bytes) -> Result<()> {
+        self.network
+            .route(self.peer_id, peer, stream_type, data)
+            .await
+    }
+
+    async fn receive_message(&self) -> Result<(PeerId, StreamType, Bytes)> {
+        self.inbox
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| anyhow!("Transport closed"))
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        self.events.subscribe()
+    }
+
+    async fn peer_addr(&self, peer: &PeerId) -> Option<SocketAddr> {
+        // Any node on the network is reachable, connected or not
+        self.network
+            .state
+            .read()
+            .await
+            .nodes
+            .get(peer)
+            .map(|node| node.addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    async fn two_nodes() -> (MemoryNetwork, MemoryTransport, MemoryTransport) {
+        let network = MemoryNetwork::new();
+        let a = network
+            .add_node(PeerId::new([1u8; 32]))
+            .await
+            .expect("add node a");
+        let b = network
+            .add_node(PeerId::new([2u8; 32]))
+            .await
+            .expect("add node b");
+        (network, a, b)
+    }
+
+    #[tokio::test]
+    async fn test_messages_are_delivered() {
+        let (network, a, b) = two_nodes().await;
+
+        a.send_to_peer(b.peer_id(), StreamType::PubSub, Bytes::from("hello"))
+            .await
+            .expect("send");
+
+        let (from, stream_type, data) = b.receive_message().await.expect("receive");
+        assert_eq!(from, a.peer_id());
+        assert_eq!(stream_type, StreamType::PubSub);
+        assert_eq!(data, Bytes::from("hello"));
+        assert_eq!(network.delivered(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_to_unknown_peer_fails() {
+        let (_network, a, _b) = two_nodes().await;
+        let result = a
+            .send_to_peer(PeerId::new([9u8; 32]), StreamType::Bulk, Bytes::new())
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_peer_addr() {
+        let (_network, a, b) = two_nodes().await;
+        assert_eq!(a.peer_addr(&b.peer_id()).await, Some(b.local_addr()));
+        assert_eq!(a.peer_addr(&PeerId::new([9u8; 32])).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_dial_and_close_emit_events() {
+        let (_network, a, b) = two_nodes().await;
+        let mut a_events = a.subscribe_events();
+        let mut b_events = b.subscribe_events();
+
+        let peer = a.dial_bootstrap(b.local_addr()).await.expect("dial");
+        assert_eq!(peer, b.peer_id());
+        assert_eq!(
+            a_events.try_recv().ok(),
+            Some(TransportEvent::PeerConnected {
+                peer: b.peer_id(),
+                addr: b.local_addr()
+            })
+        );
+        assert_eq!(
+            b_events.try_recv().ok(),
+            Some(TransportEvent::PeerConnected {
+                peer: a.peer_id(),
+                addr: a.local_addr()
+            })
+        );
+
+        a.close().await.expect("close");
+        assert!(matches!(
+            b_events.try_recv(),
+            Ok(TransportEvent::PeerDisconnected { peer, .. }) if peer == a.peer_id()
+        ));
+        assert!(b
+            .send_to_peer(a.peer_id(), StreamType::Membership, Bytes::new())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_reports_reason_to_both_sides() {
+        let (_network, a, b) = two_nodes().await;
+        a.dial(b.peer_id(), b.local_addr()).await.expect("dial");
+        let mut a_events = a.subscribe_events();
+        let mut b_events = b.subscribe_events();
+
+        a.send_to_peer(b.peer_id(), StreamType::Membership, Bytes::from("bye"))
+            .await
+            .expect("send");
+        a.disconnect(b.peer_id(), CloseReason::Evicted)
+            .await
+            .expect("disconnect");
+
+        assert_eq!(
+            a_events.try_recv().ok(),
+            Some(TransportEvent::PeerDisconnected {
+                peer: b.peer_id(),
+                reason: DisconnectReason::LocalDisconnect(CloseReason::Evicted)
+            })
+        );
+        assert_eq!(
+            b_events.try_recv().ok(),
+            Some(TransportEvent::PeerDisconnected {
+                peer: a.peer_id(),
+                reason: DisconnectReason::RemoteDisconnect(CloseReason::Evicted)
+            })
+        );
+        let (_, _, data) = b.receive_message().await.expect("receive");
+        assert_eq!(data, Bytes::from("bye"));
+
+        assert!(a
+            .disconnect(b.peer_id(), CloseReason::Evicted)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drop_hook() {
+        let (network, a, b) = two_nodes().await;
+        network
+            .set_hook(|_, _, stream_type, _| {
+                if stream_type == StreamType::Bulk {
+                    LinkAction::Drop
+                } else {
+                    LinkAction::Deliver
+                }
+            })
+            .await;
+
+        a.send_to_peer(b.peer_id(), StreamType::Bulk, Bytes::from("lost"))
+            .await
+            .expect("send");
+        a.send_to_peer(b.peer_id(), StreamType::Membership, Bytes::from("kept"))
+            .await
+            .expect("send");
+
+        let (_, _, data) = b.receive_message().await.expect("receive");
+        assert_eq!(data, Bytes::from("kept"));
+        assert_eq!(network.dropped(), 1);
+        assert_eq!(network.delivered(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_delay_hook() {
+        let (network, a, b) = two_nodes().await;
+        network
+            .set_hook(|_, _, _, _| LinkAction::Delay(Duration::from_millis(50)))
+            .await;
+
+        let start = Instant::now();
+        a.send_to_peer(b.peer_id(), StreamType::PubSub, Bytes::from("late"))
+            .await
+            .expect("send");
+        let (_, _, data) = b.receive_message().await.expect("receive");
+
+        assert_eq!(data, Bytes::from("late"));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,885 @@
+//! Per-peer routing over several transports
+//!
+//! [`MultiTransport`] combines child transports, e.g. QUIC, TCP and relayed
+//! circuits, behind a single [`GossipTransport`]. For every peer it tracks
+//! which children can reach it and sends over the best one: by default the
+//! one with the lowest RTT, as reported by [`GossipTransport::peer_rtt`] or
+//! measured while dialing, or the first added with [`RouteOrder::Preference`].
+//! When a send fails because the connection is gone, that route is dropped
+//! and the next best known route is tried; a full send queue is reported to
+//! the caller as backpressure without touching the route.
+//! Dials try the children one at a time in the same order and stop at the
+//! first that connects.
+//!
+//! Inbound messages and events of all children are merged. A peer counts as
+//! connected while any child is connected to it, so upper layers see one
+//! [`TransportEvent::PeerConnected`] and one
+//! [`TransportEvent::PeerDisconnected`] per peer however many routes it has.
+//!
+//! [`crate::FallbackTransport`] configures a multi-transport for a fixed
+//! primary transport with a single fallback.
+
+use crate::handshake::VerifiedIdentity;
+use crate::send_queue::QueueFull;
+use crate::{CloseReason, GossipTransport, StreamType, TransportEvent, EVENT_CHANNEL_CAPACITY};
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use saorsa_gossip_types::PeerId;
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Capacity of the merged inbound message queue
+const INBOUND_QUEUE_CAPACITY: usize = 1024;
+
+/// How the routes to a peer are ranked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RouteOrder {
+    /// Lowest RTT first; children without an estimate come last, in the
+    /// order they were added
+    #[default]
+    Rtt,
+    /// In the order the children were added, whatever their RTT
+    Preference,
+}
+
+/// A named child transport
+struct Child {
+    name: String,
+    transport: Box<dyn GossipTransport>,
+}
+
+/// What is known about the routes to one peer
+#[derive(Debug, Default)]
+struct PeerRoutes {
+    /// Children known to reach the peer
+    reachable: BTreeSet<usize>,
+    /// Children reporting an open connection to the peer
+    connected: BTreeSet<usize>,
+    /// Time taken to dial the peer through each child
+    dial_times: HashMap<usize, Duration>,
+}
+
+impl PeerRoutes {
+    fn is_empty(&self) -> bool {
+        self.reachable.is_empty() && self.connected.is_empty()
+    }
+}
+
+struct MultiShared {
+    children: Vec<Child>,
+    order: RouteOrder,
+    peers: Mutex<HashMap<PeerId, PeerRoutes>>,
+    events: broadcast::Sender<TransportEvent>,
+}
+
+impl MultiShared {
+    fn peers(&self) -> MutexGuard<'_, HashMap<PeerId, PeerRoutes>> {
+        self.peers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record that child `index` reaches `peer`
+    fn learn(&self, peer: PeerId, index: usize, dial_time: Option<Duration>) {
+        let mut peers = self.peers();
+        let routes = peers.entry(peer).or_default();
+        routes.reachable.insert(index);
+        if let Some(dial_time) = dial_time {
+            routes.dial_times.insert(index, dial_time);
+        }
+    }
+
+    /// Drop the route to `peer` through child `index`
+    fn forget(&self, peer: &PeerId, index: usize) {
+        let mut peers = self.peers();
+        if let Some(routes) = peers.get_mut(peer) {
+            routes.reachable.remove(&index);
+            routes.dial_times.remove(&index);
+            if routes.is_empty() {
+                peers.remove(peer);
+            }
+        }
+    }
+
+    /// Order the children in `indices` best first for reaching `peer`
+    async fn rank(&self, peer: &PeerId, indices: Vec<usize>) -> Vec<usize> {
+        if self.order == RouteOrder::Preference {
+            let mut indices = indices;
+            indices.sort_unstable();
+            return indices;
+        }
+
+        let dial_times = self
+            .peers()
+            .get(peer)
+            .map(|routes| routes.dial_times.clone())
+            .unwrap_or_default();
+        let mut ranked = Vec::with_capacity(indices.len());
+        for index in indices {
+            let rtt = match self.children[index].transport.peer_rtt(peer).await {
+                Some(rtt) => Some(rtt),
+                None => dial_times.get(&index).copied(),
+            };
+            ranked.push((rtt.unwrap_or(Duration::MAX), index));
+        }
+        ranked.sort();
+        ranked.into_iter().map(|(_, index)| index).collect()
+    }
+
+    /// Children reaching `peer`, best route first
+    async fn ranked(&self, peer: &PeerId) -> Vec<usize> {
+        let reachable: Vec<usize> = match self.peers().get(peer) {
+            Some(routes) => routes.reachable.iter().copied().collect(),
+            None => return Vec::new(),
+        };
+        self.rank(peer, reachable).await
+    }
+
+    /// Every child, known routes to `peer` first, each group best first
+    async fn candidates(&self, peer: &PeerId) -> Vec<usize> {
+        let mut candidates = self.ranked(peer).await;
+        let others = (0..self.children.len())
+            .filter(|index| !candidates.contains(index))
+            .collect();
+        candidates.extend(self.rank(peer, others).await);
+        candidates
+    }
+}
+
+/// Builder for a [`MultiTransport`]
+#[derive(Default)]
+pub struct MultiTransportBuilder {
+    children: Vec<Child>,
+    order: RouteOrder,
+}
+
+impl MultiTransportBuilder {
+    /// Add a child transport under `name`
+    ///
+    /// Children added first are preferred when no RTT is known.
+    pub fn with_transport(
+        mut self,
+        name: impl Into<String>,
+        transport: impl GossipTransport + 'static,
+    ) -> Self {
+        self.children.push(Child {
+            name: name.into(),
+            transport: Box::new(transport),
+        });
+        self
+    }
+
+    /// Set how the routes to a peer are ranked (default: [`RouteOrder::Rtt`])
+    pub fn with_route_order(mut self, order: RouteOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Start merging the children's messages and events
+    ///
+    /// The multi-transport takes over the children's receive loops; read
+    /// messages through it rather than through the children.
+    pub fn build(self) -> MultiTransport {
+        let shared = Arc::new(MultiShared {
+            children: self.children,
+            order: self.order,
+            peers: Mutex::new(HashMap::new()),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        });
+        let (inbound_tx, inbound_rx) = mpsc::channel(INBOUND_QUEUE_CAPACITY);
+        let dispatchers = shared
+            .children
+            .iter()
+            .enumerate()
+            .map(|(index, child)| {
+                // Subscribe before returning so no early connection event is missed
+                let events = child.transport.subscribe_events();
+                tokio::spawn(dispatch(
+                    Arc::clone(&shared),
+                    index,
+                    events,
+                    inbound_tx.clone(),
+                ))
+            })
+            .collect();
+
+        MultiTransport {
+            shared,
+            inbound: tokio::sync::Mutex::new(inbound_rx),
+            dispatchers,
+        }
+    }
+}
+
+/// A [`GossipTransport`] routing each peer over the best of several transports
+///
+/// Dropping it stops its dispatch tasks.
+pub struct MultiTransport {
+    shared: Arc<MultiShared>,
+    inbound: tokio::sync::Mutex<mpsc::Receiver<(PeerId, StreamType, Bytes)>>,
+    dispatchers: Vec<JoinHandle<()>>,
+}
+
+impl MultiTransport {
+    /// Start building a multi-transport
+    pub fn builder() -> MultiTransportBuilder {
+        MultiTransportBuilder::default()
+    }
+
+    /// Names of the child transports, in the order they were added
+    pub fn transport_names(&self) -> Vec<&str> {
+        self.shared
+            .children
+            .iter()
+            .map(|child| child.name.as_str())
+            .collect()
+    }
+
+    /// Name of the child transport `peer` is currently best reached through
+    pub async fn route(&self, peer: &PeerId) -> Option<&str> {
+        let index = *self.shared.ranked(peer).await.first()?;
+        Some(self.shared.children[index].name.as_str())
+    }
+
+    /// Names of every child transport reaching `peer`, best first
+    pub async fn routes(&self, peer: &PeerId) -> Vec<&str> {
+        self.shared
+            .ranked(peer)
+            .await
+            .into_iter()
+            .map(|index| self.shared.children[index].name.as_str())
+            .collect()
+    }
+
+    /// Index of the child transport `peer` is currently best reached through
+    pub(crate) async fn route_index(&self, peer: &PeerId) -> Option<usize> {
+        self.shared.ranked(peer).await.first().copied()
+    }
+}
+
+impl Drop for MultiTransport {
+    fn drop(&mut self) {
+        for dispatcher in &self.dispatchers {
+            dispatcher.abort();
+        }
+    }
+}
+
+/// Forward one child's messages and events until it closes
+async fn dispatch(
+    shared: Arc<MultiShared>,
+    index: usize,
+    mut events: broadcast::Receiver<TransportEvent>,
+    inbound: mpsc::Sender<(PeerId, StreamType, Bytes)>,
+) {
+    let transport = &shared.children[index].transport;
+    let mut events_open = true;
+
+    loop {
+        tokio::select! {
+            received = transport.receive_message() => {
+                let Ok(message) = received else {
+                    break;
+                };
+                shared.learn(message.0, index, None);
+                if inbound.send(message).await.is_err() {
+                    break;
+                }
+            }
+            event = events.recv(), if events_open => match event {
+                Ok(event) => {
+                    if let Some(event) = merge_event(&shared, index, event) {
+                        let _ = shared.events.send(event);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => events_open = false,
+            },
+        }
+    }
+
+    debug!(
+        "Multi-transport dispatcher for {} stopped",
+        shared.children[index].name
+    );
+}
+
+/// Update routes from a child's event, returning the event to report
+///
+/// Connection events are only reported when the first route to a peer opens
+/// or the last one closes.
+fn merge_event(
+    shared: &MultiShared,
+    index: usize,
+    event: TransportEvent,
+) -> Option<TransportEvent> {
+    let mut peers = shared.peers();
+    match &event {
+        TransportEvent::PeerConnected { peer, .. } => {
+            let routes = peers.entry(*peer).or_default();
+            let first = routes.connected.is_empty();
+            routes.connected.insert(index);
+            routes.reachable.insert(index);
+            first.then_some(event)
+        }
+        TransportEvent::PeerDisconnected { peer, .. } => {
+            let routes = peers.get_mut(peer)?;
+            routes.reachable.remove(&index);
+            routes.dial_times.remove(&index);
+            let last = routes.connected.remove(&index) && routes.connected.is_empty();
+            if routes.is_empty() {
+                peers.remove(peer);
+            }
+            last.then_some(event)
+        }
+        _ => Some(event),
+    }
+}
+
+#[async_trait::async_trait]
+impl GossipTransport for MultiTransport {
+    /// Dial `peer` through one child at a time, best first
+    ///
+    /// Children with a known route to the peer are tried first. Stops at the
+    /// first child that connects, which becomes a route.
+    async fn dial(&self, peer: PeerId, addr: SocketAddr) -> Result<()> {
+        let mut failures = Vec::new();
+        for index in self.shared.candidates(&peer).await {
+            let name = &self.shared.children[index].name;
+            let started = Instant::now();
+            match self.shared.children[index].transport.dial(peer, addr).await {
+                Ok(()) => {
+                    let elapsed = started.elapsed();
+                    debug!("Reached peer {} through {} in {:?}", peer, name, elapsed);
+                    self.shared.learn(peer, index, Some(elapsed));
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to reach peer {} at {} through {}: {}",
+                        peer, addr, name, e
+                    );
+                    failures.push(format!("{}: {}", name, e));
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "Failed to reach peer {} at {} ({})",
+            peer,
+            addr,
+            failures.join("; ")
+        ))
+    }
+
+    /// Dial a bootstrap node through one child at a time, in the order added
+    async fn dial_bootstrap(&self, addr: SocketAddr) -> Result<PeerId> {
+        let mut failures = Vec::new();
+        for index in 0..self.shared.children.len() {
+            let name = &self.shared.children[index].name;
+            let started = Instant::now();
+            match self.shared.children[index]
+                .transport
+                .dial_bootstrap(addr)
+                .await
+            {
+                Ok(peer) => {
+                    self.shared.learn(peer, index, Some(started.elapsed()));
+                    return Ok(peer);
+                }
+                Err(e) => {
+                    warn!("Failed to reach bootstrap {} through {}: {}", addr, name, e);
+                    failures.push(format!("{}: {}", name, e));
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "Failed to reach bootstrap {} ({})",
+            addr,
+            failures.join("; ")
+        ))
+    }
+
+    async fn listen(&self, bind: SocketAddr) -> Result<()> {
+        for child in &self.shared.children {
+            child.transport.listen(bind).await?;
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        let results = futures::future::join_all(
+            self.shared
+                .children
+                .iter()
+                .map(|child| child.transport.close()),
+        )
+        .await;
+        self.shared.peers().clear();
+        results.into_iter().collect()
+    }
+
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+        let routes: BTreeSet<usize> = match self.shared.peers().get(&peer) {
+            Some(routes) => routes.reachable.union(&routes.connected).copied().collect(),
+            None => return Err(anyhow!("Not connected to peer {}", peer)),
+        };
+
+        let mut disconnected = false;
+        let mut last_err = None;
+        for index in routes {
+            match self.shared.children[index]
+                .transport
+                .disconnect(peer, reason)
+                .await
+            {
+                Ok(()) => disconnected = true,
+                Err(e) => last_err = Some(e),
+            }
+            self.shared.forget(&peer, index);
+        }
+
+        match last_err {
+            Some(e) if !disconnected => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Send over the best route to `peer`, failing over to the next on error
+    ///
+    /// Once the known routes are exhausted the other children are tried in turn.
+    async fn send_to_peer(&self, peer: PeerId, stream_type: StreamType, data: Bytes) -> Result<()> {
+        let mut last_err = None;
+        for index in self.shared.ranked(&peer).await {
+            let child = &self.shared.children[index];
+            match child
+                .transport
+                .send_to_peer(peer, stream_type, data.clone())
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(e) if e.downcast_ref::<QueueFull>().is_some() => return Err(e),
+                Err(e) => {
+                    info!(
+                        "Sending to peer {} over {} failed, trying next route: {}",
+                        peer, child.name, e
+                    );
+                    self.shared.forget(&peer, index);
+                    last_err = Some(e);
+                }
+            }
+        }
+
+        Err(match last_err {
+            Some(e) => anyhow!("No working route to peer {}: {}", peer, e),
+            None => anyhow!("No route to peer {}", peer),
+        })
+    }
+
+    async fn receive_message(&self) -> Result<(PeerId, StreamType, Bytes)> {
+        self.inbound
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| anyhow!("Transport closed"))
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        self.shared.events.subscribe()
+    }
+
+    fn peer_identity(&self, peer: &PeerId) -> Option<VerifiedIdentity> {
+        let reachable = self.shared.peers().get(peer)?.reachable.clone();
+        reachable
+            .into_iter()
+            .find_map(|index| self.shared.children[index].transport.peer_identity(peer))
+    }
+
+    async fn peer_addr(&self, peer: &PeerId) -> Option<SocketAddr> {
+        let index = *self.shared.ranked(peer).await.first()?;
+        self.shared.children[index].transport.peer_addr(peer).await
+    }
+
+    async fn peer_rtt(&self, peer: &PeerId) -> Option<Duration> {
+        let index = *self.shared.ranked(peer).await.first()?;
+        self.shared.children[index].transport.peer_rtt(peer).await
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.shared
+            .children
+            .iter()
+            .all(|child| child.transport.is_encrypted())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DisconnectReason, MemoryNetwork, MemoryTransport};
+    use tokio::time::timeout;
+
+    /// A memory transport reporting a fixed RTT to every peer
+    struct FixedRtt(Arc<MemoryTransport>, Duration);
+
+    #[async_trait::async_trait]
+    impl GossipTransport for FixedRtt {
+        async fn dial(&self, peer: PeerId, addr: SocketAddr) -> Result<()> {
+            self.0.dial(peer, addr).await
+        }
+
+        async fn dial_bootstrap(&self, addr: SocketAddr) -> Result<PeerId> {
+            self.0.dial_bootstrap(addr).await
+        }
+
+        async fn listen(&self, bind: SocketAddr) -> Result<()> {
+            self.0.listen(bind).await
+        }
+
+        async fn close(&self) -> Result<()> {
+            self.0.close().await
+        }
+
+        async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+            self.0.disconnect(peer, reason).await
+        }
+
+        async fn send_to_peer(
+            &self,
+            peer: PeerId,
+            stream_type: StreamType,
+            data: Bytes,
+        ) -> Result<()> {
+            self.0.send_to_peer(peer, stream_type, data).await
+        }
+
+        async fn receive_message(&self) -> Result<(PeerId, StreamType, Bytes)> {
+            self.0.receive_message().await
+        }
+
+        fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+            self.0.subscribe_events()
+        }
+
+        async fn peer_rtt(&self, _peer: &PeerId) -> Option<Duration> {
+            Some(self.1)
+        }
+    }
+
+    /// A memory transport whose send queues are always full
+    struct Congested(Arc<MemoryTransport>);
+
+    #[async_trait::async_trait]
+    impl GossipTransport for Congested {
+        async fn dial(&self, peer: PeerId, addr: SocketAddr) -> Result<()> {
+            self.0.dial(peer, addr).await
+        }
+
+        async fn dial_bootstrap(&self, addr: SocketAddr) -> Result<PeerId> {
+            self.0.dial_bootstrap(addr).await
+        }
+
+        async fn listen(&self, bind: SocketAddr) -> Result<()> {
+            self.0.listen(bind).await
+        }
+
+        async fn close(&self) -> Result<()> {
+            self.0.close().await
+        }
+
+        async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+            self.0.disconnect(peer, reason).await
+        }
+
+        async fn send_to_peer(
+            &self,
+            peer: PeerId,
+            stream_type: StreamType,
+            _data: Bytes,
+        ) -> Result<()> {
+            Err(QueueFull { peer, stream_type }.into())
+        }
+
+        async fn receive_message(&self) -> Result<(PeerId, StreamType, Bytes)> {
+            self.0.receive_message().await
+        }
+
+        fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+            self.0.subscribe_events()
+        }
+    }
+
+    struct Node {
+        multi: MultiTransport,
+        slow: Arc<MemoryTransport>,
+        fast: Arc<MemoryTransport>,
+    }
+
+    /// A node on a slow network (added first) and a fast one
+    async fn node(slow: &MemoryNetwork, fast: &MemoryNetwork, id: u8) -> Node {
+        let peer_id = PeerId::new([id; 32]);
+        let slow_transport = Arc::new(slow.add_node(peer_id).await.expect("slow node"));
+        let fast_transport = Arc::new(fast.add_node(peer_id).await.expect("fast node"));
+        let multi = MultiTransport::builder()
+            .with_transport(
+                "slow",
+                FixedRtt(Arc::clone(&slow_transport), Duration::from_millis(80)),
+            )
+            .with_transport(
+                "fast",
+                FixedRtt(Arc::clone(&fast_transport), Duration::from_millis(5)),
+            )
+            .build();
+        Node {
+            multi,
+            slow: slow_transport,
+            fast: fast_transport,
+        }
+    }
+
+    async fn next_event(events: &mut broadcast::Receiver<TransportEvent>) -> TransportEvent {
+        timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("event in time")
+            .expect("event")
+    }
+
+    #[tokio::test]
+    async fn test_routes_over_lowest_rtt() {
+        let slow = MemoryNetwork::new();
+        let fast = MemoryNetwork::new();
+        let a = node(&slow, &fast, 1).await;
+        let b = node(&slow, &fast, 2).await;
+        let id_a = a.slow.peer_id();
+        let id_b = b.slow.peer_id();
+
+        // The dial stops at the fast child, which is tried first
+        a.multi.dial(id_b, b.slow.local_addr()).await.expect("dial");
+        assert_eq!(a.multi.routes(&id_b).await, vec!["fast"]);
+        assert_eq!(
+            a.multi.peer_rtt(&id_b).await,
+            Some(Duration::from_millis(5))
+        );
+
+        a.multi
+            .send_to_peer(id_b, StreamType::PubSub, Bytes::from_static(b"hello"))
+            .await
+            .expect("send");
+        let (from, _, data) = timeout(Duration::from_secs(5), b.multi.receive_message())
+            .await
+            .expect("message in time")
+            .expect("message");
+        assert_eq!(from, id_a);
+        assert_eq!(data, Bytes::from_static(b"hello"));
+        assert_eq!(fast.delivered(), 1);
+        assert_eq!(slow.delivered(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_when_route_breaks() {
+        let slow = MemoryNetwork::new();
+        let fast = MemoryNetwork::new();
+        let a = node(&slow, &fast, 1).await;
+        let b = node(&slow, &fast, 2).await;
+        let id_b = b.slow.peer_id();
+
+        a.multi.dial(id_b, b.slow.local_addr()).await.expect("dial");
+        a.slow
+            .dial(id_b, b.slow.local_addr())
+            .await
+            .expect("dial slow");
+        for _ in 0..100 {
+            if a.multi.routes(&id_b).await.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(a.multi.route(&id_b).await, Some("fast"));
+
+        // b drops off the fast network
+        b.fast.close().await.expect("close");
+        a.multi
+            .send_to_peer(id_b, StreamType::Membership, Bytes::from_static(b"ping"))
+            .await
+            .expect("failover send");
+        let (_, _, data) = timeout(Duration::from_secs(5), b.multi.receive_message())
+            .await
+            .expect("message in time")
+            .expect("message");
+        assert_eq!(data, Bytes::from_static(b"ping"));
+        assert_eq!(slow.delivered(), 1);
+        assert_eq!(a.multi.route(&id_b).await, Some("slow"));
+    }
+
+    #[tokio::test]
+    async fn test_send_skips_children_without_route() {
+        let slow = MemoryNetwork::new();
+        let fast = MemoryNetwork::new();
+        let a = node(&slow, &fast, 1).await;
+        let b = node(&slow, &fast, 2).await;
+        let id_b = b.slow.peer_id();
+
+        // b is only reached over fast, so slow is not a route once fast breaks
+        a.multi.dial(id_b, b.slow.local_addr()).await.expect("dial");
+        assert_eq!(a.multi.routes(&id_b).await, vec!["fast"]);
+        b.fast.close().await.expect("close");
+
+        a.multi
+            .send_to_peer(id_b, StreamType::Membership, Bytes::from_static(b"ping"))
+            .await
+            .expect_err("no route left");
+        assert_eq!(slow.delivered(), 0);
+        assert!(a.multi.routes(&id_b).await.is_empty());
+
+        let err = a
+            .multi
+            .send_to_peer(id_b, StreamType::Membership, Bytes::from_static(b"ping"))
+            .await
+            .expect_err("no route");
+        assert!(err.to_string().contains("No route"));
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_keeps_the_route() {
+        let congested = MemoryNetwork::new();
+        let spare = MemoryNetwork::new();
+        let id_a = PeerId::new([1u8; 32]);
+        let id_b = PeerId::new([2u8; 32]);
+        let congested_b = congested.add_node(id_b).await.expect("congested node");
+        let _spare_b = spare.add_node(id_b).await.expect("spare node");
+        let a = MultiTransport::builder()
+            .with_transport(
+                "congested",
+                Congested(Arc::new(
+                    congested.add_node(id_a).await.expect("congested node"),
+                )),
+            )
+            .with_transport("spare", spare.add_node(id_a).await.expect("spare node"))
+            .with_route_order(RouteOrder::Preference)
+            .build();
+
+        a.dial(id_b, congested_b.local_addr()).await.expect("dial");
+        assert_eq!(a.routes(&id_b).await, vec!["congested"]);
+
+        // Backpressure is reported without dropping the route or failing over
+        let err = a
+            .send_to_peer(id_b, StreamType::PubSub, Bytes::from_static(b"hello"))
+            .await
+            .expect_err("queue full");
+        assert_eq!(
+            err.downcast_ref::<QueueFull>(),
+            Some(&QueueFull {
+                peer: id_b,
+                stream_type: StreamType::PubSub,
+            })
+        );
+        assert_eq!(a.routes(&id_b).await, vec!["congested"]);
+        assert_eq!(spare.delivered(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reports_one_connection_per_peer() {
+        let slow = MemoryNetwork::new();
+        let fast = MemoryNetwork::new();
+        let a = node(&slow, &fast, 1).await;
+        let b = node(&slow, &fast, 2).await;
+        let id_b = b.slow.peer_id();
+        let mut events = a.multi.subscribe_events();
+
+        a.multi.dial(id_b, b.slow.local_addr()).await.expect("dial");
+        assert!(matches!(
+            next_event(&mut events).await,
+            TransportEvent::PeerConnected { peer, .. } if peer == id_b
+        ));
+
+        // A second route opening is not a new connection
+        a.slow
+            .dial(id_b, b.slow.local_addr())
+            .await
+            .expect("dial slow");
+        for _ in 0..100 {
+            if a.multi.routes(&id_b).await.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(a.multi.routes(&id_b).await, vec!["fast", "slow"]);
+
+        // Losing one of two routes is not a disconnect
+        a.fast
+            .disconnect(id_b, CloseReason::Evicted)
+            .await
+            .expect("disconnect fast");
+        a.slow
+            .disconnect(id_b, CloseReason::Evicted)
+            .await
+            .expect("disconnect slow");
+        assert_eq!(
+            next_event(&mut events).await,
+            TransportEvent::PeerDisconnected {
+                peer: id_b,
+                reason: DisconnectReason::LocalDisconnect(CloseReason::Evicted),
+            }
+        );
+        assert!(a.multi.routes(&id_b).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_preference_order_dials_one_child_at_a_time() {
+        let empty = MemoryNetwork::new();
+        let slow = MemoryNetwork::new();
+        let fast = MemoryNetwork::new();
+        let b = node(&slow, &fast, 2).await;
+        let id_a = PeerId::new([1u8; 32]);
+        let id_b = b.slow.peer_id();
+
+        // The first child cannot reach b; the second can, so the third is never tried
+        let slow_a = Arc::new(slow.add_node(id_a).await.expect("slow node"));
+        let fast_a = Arc::new(fast.add_node(id_a).await.expect("fast node"));
+        let a = MultiTransport::builder()
+            .with_transport("empty", empty.add_node(id_a).await.expect("empty node"))
+            .with_transport(
+                "slow",
+                FixedRtt(Arc::clone(&slow_a), Duration::from_millis(80)),
+            )
+            .with_transport(
+                "fast",
+                FixedRtt(Arc::clone(&fast_a), Duration::from_millis(5)),
+            )
+            .with_route_order(RouteOrder::Preference)
+            .build();
+
+        a.dial(id_b, b.slow.local_addr()).await.expect("dial");
+        assert_eq!(a.routes(&id_b).await, vec!["slow"]);
+        assert_eq!(a.peer_rtt(&id_b).await, Some(Duration::from_millis(80)));
+
+        a.send_to_peer(id_b, StreamType::PubSub, Bytes::from_static(b"hello"))
+            .await
+            .expect("send");
+        assert_eq!(slow.delivered(), 1);
+        assert_eq!(fast.delivered(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dial_fails_when_no_child_connects() {
+        let slow = MemoryNetwork::new();
+        let fast = MemoryNetwork::new();
+        let a = node(&slow, &fast, 1).await;
+        let unknown = PeerId::new([9u8; 32]);
+        let addr = "127.0.0.1:9".parse().expect("valid address");
+
+        let err = a.multi.dial(unknown, addr).await.expect_err("unreachable");
+        assert!(err.to_string().contains("slow"));
+        assert!(err.to_string().contains("fast"));
+        assert!(a
+            .multi
+            .send_to_peer(unknown, StreamType::Bulk, Bytes::new())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! Per-peer inbound rate limiting
+//!
+//! Every (peer, [`StreamType`]) pair gets a pair of token buckets, one
+//! counting messages and one counting bytes, refilled at the configured
+//! rates. Messages arriving while either bucket is empty are dropped before
+//! they reach the inbound queue, and the offending peer is reported with a
+//! [`TransportEvent::RateLimited`] so peer scoring can penalise it.
+//!
+//! Limits are charged per wire frame at its length on the wire, before any
+//! reassembly or decompression, so a fragmented message costs one message
+//! per fragment and a compressed one costs its compressed size.
+//!
+//! The byte bucket may go into debt, so a single message larger than one
+//! second's allowance is still accepted but holds back the messages after it.
+
+use saorsa_gossip_types::PeerId;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::debug;
+
+use crate::{StreamType, TransportEvent};
+
+/// Minimum time between two violation reports for the same peer and stream
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Inbound rate limit for one stream type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Sustained messages per second (also the message burst)
+    pub messages_per_sec: u32,
+    /// Sustained bytes per second (also the byte burst)
+    pub bytes_per_sec: u64,
+}
+
+impl RateLimit {
+    /// Create a limit of `messages_per_sec` messages and `bytes_per_sec` bytes
+    pub fn new(messages_per_sec: u32, bytes_per_sec: u64) -> Self {
+        Self {
+            messages_per_sec,
+            bytes_per_sec,
+        }
+    }
+}
+
+/// Per-stream-type inbound rate limits, applied to each peer separately
+///
+/// All stream types are unlimited by default.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitConfig {
+    /// Limit for membership messages
+    pub membership: Option<RateLimit>,
+    /// Limit for pubsub messages
+    pub pubsub: Option<RateLimit>,
+    /// Limit for bulk messages
+    pub bulk: Option<RateLimit>,
+    /// Limit for relay messages
+    pub relay: Option<RateLimit>,
+}
+
+impl RateLimitConfig {
+    /// Set the limit for a stream type
+    pub fn with_limit(mut self, stream_type: StreamType, limit: RateLimit) -> Self {
+        *self.slot(stream_type) = Some(limit);
+        self
+    }
+
+    /// Remove the limit for a stream type
+    pub fn without_limit(mut self, stream_type: StreamType) -> Self {
+        *self.slot(stream_type) = None;
+        self
+    }
+
+    /// Get the limit for a stream type
+    pub fn limit(&self, stream_type: StreamType) -> Option<RateLimit> {
+        match stream_type {
+            StreamType::Membership => self.membership,
+            StreamType::PubSub => self.pubsub,
+            StreamType::Bulk => self.bulk,
+            StreamType::Relay => self.relay,
+        }
+    }
+
+    fn slot(&mut self, stream_type: StreamType) -> &mut Option<RateLimit> {
+        match stream_type {
+            StreamType::Membership => &mut self.membership,
+            StreamType::PubSub => &mut self.pubsub,
+            StreamType::Bulk => &mut self.bulk,
+            StreamType::Relay => &mut self.relay,
+        }
+    }
+}
+
+/// Token buckets for one peer and stream type
+#[derive(Debug)]
+struct Bucket {
+    messages: f64,
+    bytes: f64,
+    refilled: Instant,
+    /// Messages dropped since the last report
+    unreported: u64,
+    reported: Option<Instant>,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            messages: f64::from(limit.messages_per_sec),
+            bytes: limit.bytes_per_sec as f64,
+            refilled: now,
+            unreported: 0,
+            reported: None,
+        }
+    }
+
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.refilled = now;
+
+        let max_messages = f64::from(limit.messages_per_sec);
+        let max_bytes = limit.bytes_per_sec as f64;
+        self.messages = (self.messages + elapsed * max_messages).min(max_messages);
+        self.bytes = (self.bytes + elapsed * max_bytes).min(max_bytes);
+    }
+
+    /// Take one message of `len` bytes, if both buckets allow it
+    fn take(&mut self, len: usize) -> bool {
+        if self.messages < 1.0 || self.bytes <= 0.0 {
+            return false;
+        }
+        self.messages -= 1.0;
+        self.bytes -= len as f64;
+        true
+    }
+}
+
+/// Enforces [`RateLimitConfig`] on inbound messages
+#[derive(Clone)]
+pub(crate) struct InboundRateLimiter {
+    config: Arc<RateLimitConfig>,
+    buckets: Arc<Mutex<HashMap<(PeerId, StreamType), Bucket>>>,
+    events: broadcast::Sender<TransportEvent>,
+}
+
+impl InboundRateLimiter {
+    /// Create a limiter reporting violations on `events`
+    pub(crate) fn new(config: RateLimitConfig, events: broadcast::Sender<TransportEvent>) -> Self {
+        Self {
+            config: Arc::new(config),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+            events,
+        }
+    }
+
+    /// Check whether a message of `len` bytes from `peer` may be delivered
+    ///
+    /// Returns `false` if the message must be dropped.
+    pub(crate) fn check(&self, peer: PeerId, stream_type: StreamType, len: usize) -> bool {
+        self.check_at(peer, stream_type, len, Instant::now())
+    }
+
+    fn check_at(&self, peer: PeerId, stream_type: StreamType, len: usize, now: Instant) -> bool {
+        let Some(limit) = self.config.limit(stream_type) else {
+            return true;
+        };
+
+        let dropped = {
+            let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+            let bucket = buckets
+                .entry((peer, stream_type))
+                .or_insert_with(|| Bucket::new(limit, now));
+            bucket.refill(limit, now);
+            if bucket.take(len) {
+                return true;
+            }
+
+            bucket.unreported += 1;
+            let due = bucket
+                .reported
+                .is_none_or(|at| now.duration_since(at) >= REPORT_INTERVAL);
+            if !due {
+                return false;
+            }
+            bucket.reported = Some(now);
+            std::mem::take(&mut bucket.unreported)
+        };
+
+        debug!(
+            "Peer {} exceeded {:?} rate limit, dropped {} messages",
+            peer, stream_type, dropped
+        );
+        let _ = self.events.send(TransportEvent::RateLimited {
+            peer,
+            stream_type,
+            dropped,
+        });
+        false
+    }
+
+    /// Drop the buckets of a peer
+    pub(crate) fn forget(&self, peer: &PeerId) {
+        self.buckets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(bucket_peer, _), _| bucket_peer != peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(
+        config: RateLimitConfig,
+    ) -> (InboundRateLimiter, broadcast::Receiver<TransportEvent>) {
+        let (events, rx) = broadcast::channel(16);
+        (InboundRateLimiter::new(config, events), rx)
+    }
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let (limiter, mut rx) = limiter(RateLimitConfig::default());
+        let peer = PeerId::new([1u8; 32]);
+
+        for _ in 0..10_000 {
+            assert!(limiter.check(peer, StreamType::PubSub, 1_000));
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_message_rate_is_enforced_and_reported() {
+        let config =
+            RateLimitConfig::default().with_limit(StreamType::PubSub, RateLimit::new(3, 1 << 20));
+        let (limiter, mut rx) = limiter(config);
+        let peer = PeerId::new([1u8; 32]);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check_at(peer, StreamType::PubSub, 10, start));
+        }
+        assert!(!limiter.check_at(peer, StreamType::PubSub, 10, start));
+        assert!(!limiter.check_at(peer, StreamType::PubSub, 10, start));
+
+        // Other stream types and peers are unaffected
+        assert!(limiter.check_at(peer, StreamType::Membership, 10, start));
+        assert!(limiter.check_at(PeerId::new([2u8; 32]), StreamType::PubSub, 10, start));
+
+        // One violation report per interval
+        assert_eq!(
+            rx.try_recv().ok(),
+            Some(TransportEvent::RateLimited {
+                peer,
+                stream_type: StreamType::PubSub,
+                dropped: 1
+            })
+        );
+        assert!(rx.try_recv().is_err());
+
+        // Tokens refill over time
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.check_at(peer, StreamType::PubSub, 10, later));
+    }
+
+    #[test]
+    fn test_byte_rate_allows_debt() {
+        let config =
+            RateLimitConfig::default().with_limit(StreamType::Bulk, RateLimit::new(100, 1_000));
+        let (limiter, mut rx) = limiter(config);
+        let peer = PeerId::new([1u8; 32]);
+        let start = Instant::now();
+
+        // An oversized message is accepted once, then the bucket is in debt
+        assert!(limiter.check_at(peer, StreamType::Bulk, 3_000, start));
+        assert!(!limiter.check_at(peer, StreamType::Bulk, 1, start));
+        assert!(!limiter.check_at(peer, StreamType::Bulk, 1, start + Duration::from_secs(1)));
+        assert!(limiter.check_at(peer, StreamType::Bulk, 1, start + Duration::from_secs(3)));
+
+        // The second violation falls in a new report interval
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(TransportEvent::RateLimited { dropped: 1, .. })
+        ));
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(TransportEvent::RateLimited { dropped: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_forget_resets_buckets() {
+        let config =
+            RateLimitConfig::default().with_limit(StreamType::PubSub, RateLimit::new(1, 1 << 20));
+        let (limiter, _rx) = limiter(config);
+        let peer = PeerId::new([1u8; 32]);
+
+        assert!(limiter.check(peer, StreamType::PubSub, 10));
+        assert!(!limiter.check(peer, StreamType::PubSub, 10));
+        limiter.forget(&peer);
+        assert!(limiter.check(peer, StreamType::PubSub, 10));
+    }
+}
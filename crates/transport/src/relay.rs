@@ -0,0 +1,1722 @@
+//! Relayed circuits for peers that cannot reach each other directly
+//!
+//! [`RelayTransport`] wraps a transport and lets two peers behind symmetric
+//! NATs talk through a third peer both can reach, typically a coordinator
+//! advertising the relay role. A peer opens a circuit to a target through a
+//! relay; the relay tells the target about it and from then on forwards the
+//! circuit's frames between them. Once a circuit is open, sending to the
+//! remote peer through the wrapper transparently goes over the circuit and
+//! relayed messages are received as if they came from the remote peer.
+//!
+//! Before a circuit carries data, its two ends run the handshake of
+//! [`crate::secure`] through it, each proving its ML-DSA identity. The
+//! opener only accepts the circuit if the other end proves to be the target
+//! it asked for, and the target only if the opener proves to be the source
+//! the relay announced, so a relay can neither read nor alter circuit
+//! payloads nor pass itself off as either end. Ending a circuit therefore
+//! takes the keypair of the wrapped transport, set with
+//! [`RelayConfig::with_keypair`]; without one a node can only serve as a
+//! relay.
+//!
+//! A peer only runs handshakes for circuits opened to it by relays it
+//! trusts, configured up front or added with [`RelayTransport::trust_relay`]
+//! once a relay advertises its role, and never lets a circuit stand in for a
+//! peer it is directly connected to.
+//!
+//! A serving relay limits the number of circuits overall and per peer, and
+//! charges every forwarded frame to the sending peer's bandwidth quota.
+//! Frames beyond the quota are dropped. Circuits are torn down when either
+//! end closes them or disconnects from the relay.
+//!
+//! Messages for the application are buffered in a bounded queue; when it is
+//! full the wrapper stops reading the inner transport.
+//!
+//! # Wire format
+//!
+//! ```text
+//! [kind: u8][id: u64 BE][body]
+//! ```
+//!
+//! Every relay message travels on [`StreamType::Relay`], so frames on the
+//! other streams are never mistaken for relay traffic. A circuit data body
+//! is sealed with the circuit's session keys, authenticating the circuit
+//! ID, and holds the stream type the payload was sent on followed by the
+//! payload; the far end restores the stream type.
+
+use anyhow::{anyhow, Result};
+use bytes::{BufMut, Bytes, BytesMut};
+use saorsa_gossip_types::PeerId;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{debug, trace, warn};
+
+use ant_quic::{MlDsaPublicKey, MlDsaSecretKey};
+
+use crate::handshake::LocalIdentity;
+use crate::secure::{Initiator, Responder, Session};
+use crate::{CloseReason, GossipTransport, StreamType, TransportEvent, VerifiedIdentity};
+
+/// Length of the relay header (kind and ID)
+pub const RELAY_HEADER_LEN: usize = 9;
+
+/// Relay settings, for both opening circuits and serving them
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    /// Forward circuits for other peers (default: false)
+    pub serve: bool,
+    /// Maximum circuits a serving relay keeps open (default: 1,024)
+    pub max_circuits: usize,
+    /// Maximum circuits per peer on a serving relay (default: 16)
+    pub max_circuits_per_peer: usize,
+    /// Bytes per second each peer may send through a serving relay (default: 1 MiB)
+    pub bytes_per_sec: u64,
+    /// Time to wait for a relay to open a circuit (default: 10 seconds)
+    pub open_timeout: Duration,
+    /// Relays allowed to open circuits to this node (default: none)
+    pub trusted_relays: HashSet<PeerId>,
+    /// Messages buffered for `receive_message` (default: 1,024)
+    pub passthrough_capacity: usize,
+    /// ML-DSA keypair bytes (public_key, secret_key) proven to circuit ends
+    /// Must be the wrapped transport's keypair; without one circuits can
+    /// only be served, not opened or accepted (default: none)
+    pub keypair: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            serve: false,
+            max_circuits: 1_024,
+            max_circuits_per_peer: 16,
+            bytes_per_sec: 1024 * 1024,
+            open_timeout: Duration::from_secs(10),
+            trusted_relays: HashSet::new(),
+            passthrough_capacity: 1_024,
+            keypair: None,
+        }
+    }
+}
+
+impl RelayConfig {
+    /// Set whether to forward circuits for other peers
+    pub fn with_serve(mut self, serve: bool) -> Self {
+        self.serve = serve;
+        self
+    }
+
+    /// Set the circuit limits of a serving relay
+    pub fn with_circuit_limits(mut self, max_circuits: usize, per_peer: usize) -> Self {
+        self.max_circuits = max_circuits;
+        self.max_circuits_per_peer = per_peer;
+        self
+    }
+
+    /// Set the per-peer bandwidth quota of a serving relay
+    pub fn with_bytes_per_sec(mut self, bytes_per_sec: u64) -> Self {
+        self.bytes_per_sec = bytes_per_sec;
+        self
+    }
+
+    /// Set the time to wait for a relay to open a circuit
+    pub fn with_open_timeout(mut self, timeout: Duration) -> Self {
+        self.open_timeout = timeout;
+        self
+    }
+
+    /// Allow `relay` to open circuits to this node
+    pub fn with_trusted_relay(mut self, relay: PeerId) -> Self {
+        self.trusted_relays.insert(relay);
+        self
+    }
+
+    /// Set the number of messages buffered for `receive_message`
+    pub fn with_passthrough_capacity(mut self, capacity: usize) -> Self {
+        self.passthrough_capacity = capacity.max(1);
+        self
+    }
+
+    /// Set the ML-DSA keypair proven to the other end of circuits
+    /// Use the keypair of the wrapped transport so both share one peer ID.
+    pub fn with_keypair(mut self, public_key: Vec<u8>, secret_key: Vec<u8>) -> Self {
+        self.keypair = Some((public_key, secret_key));
+        self
+    }
+}
+
+/// Errors returned by [`RelayTransport::open_circuit`]
+#[derive(thiserror::Error, Debug)]
+pub enum RelayError {
+    #[error("relay did not answer within {0:?}")]
+    Timeout(Duration),
+
+    #[error("relay refused circuit: {0}")]
+    Rejected(String),
+
+    #[error("circuit handshake failed: {0}")]
+    Handshake(String),
+
+    #[error("no keypair configured for ending circuits")]
+    NoIdentity,
+
+    #[error("relay transport closed")]
+    Closed,
+
+    #[error("transport error: {0}")]
+    Transport(#[from] anyhow::Error),
+}
+
+/// Counters of a serving relay
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayStats {
+    /// Circuits currently open through this relay
+    pub circuits: usize,
+    /// Frames forwarded
+    pub frames_relayed: u64,
+    /// Payload bytes forwarded
+    pub bytes_relayed: u64,
+    /// Frames dropped for exceeding the sender's quota
+    pub frames_dropped: u64,
+}
+
+/// A relay protocol message
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RelayMessage {
+    /// Ask a relay to open a circuit to `target`
+    Open { request: u64, target: PeerId },
+    /// The relay opened the requested circuit
+    Opened { request: u64, circuit: u64 },
+    /// The relay refused the requested circuit
+    Rejected { request: u64, reason: String },
+    /// The relay opened a circuit to us from `source`
+    Incoming { circuit: u64, source: PeerId },
+    /// Sealed circuit payload, opaque to the relay
+    Data { circuit: u64, payload: Bytes },
+    /// Secure session handshake message between the circuit's ends
+    Handshake { circuit: u64, payload: Bytes },
+    /// Tear down a circuit
+    Close { circuit: u64 },
+}
+
+impl RelayMessage {
+    fn encode(&self) -> Bytes {
+        let circuit_bytes;
+        let (kind, id, body): (u8, u64, &[u8]) = match self {
+            Self::Open { request, target } => (0, *request, target.as_bytes()),
+            Self::Opened { request, circuit } => {
+                circuit_bytes = circuit.to_be_bytes();
+                (1, *request, &circuit_bytes)
+            }
+            Self::Rejected { request, reason } => (2, *request, reason.as_bytes()),
+            Self::Incoming { circuit, source } => (3, *circuit, source.as_bytes()),
+            Self::Data { circuit, payload } => (4, *circuit, payload),
+            Self::Close { circuit } => (5, *circuit, &[]),
+            Self::Handshake { circuit, payload } => (6, *circuit, payload),
+        };
+
+        let mut buf = BytesMut::with_capacity(RELAY_HEADER_LEN + body.len());
+        buf.put_u8(kind);
+        buf.put_u64(id);
+        buf.put_slice(body);
+        buf.freeze()
+    }
+
+    /// Decode a relay message, or `None` if `data` is malformed
+    fn decode(data: &Bytes) -> Option<Self> {
+        if data.len() < RELAY_HEADER_LEN {
+            return None;
+        }
+        let id = u64::from_be_bytes(data[1..RELAY_HEADER_LEN].try_into().ok()?);
+        let body = data.slice(RELAY_HEADER_LEN..);
+        let peer =
+            |body: &Bytes| -> Option<PeerId> { Some(PeerId::new(body[..].try_into().ok()?)) };
+
+        match data[0] {
+            0 => Some(Self::Open {
+                request: id,
+                target: peer(&body)?,
+            }),
+            1 => Some(Self::Opened {
+                request: id,
+                circuit: u64::from_be_bytes(body[..].try_into().ok()?),
+            }),
+            2 => Some(Self::Rejected {
+                request: id,
+                reason: String::from_utf8_lossy(&body).into_owned(),
+            }),
+            3 => Some(Self::Incoming {
+                circuit: id,
+                source: peer(&body)?,
+            }),
+            4 => Some(Self::Data {
+                circuit: id,
+                payload: body,
+            }),
+            5 => Some(Self::Close { circuit: id }),
+            6 => Some(Self::Handshake {
+                circuit: id,
+                payload: body,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// An open circuit request awaiting the relay's answer and the handshake
+struct PendingOpen {
+    relay: PeerId,
+    target: PeerId,
+    reply: oneshot::Sender<Result<u64, RelayError>>,
+    /// The circuit and our side of its handshake, once the relay opened it
+    handshake: Option<(u64, Initiator)>,
+}
+
+/// Progress of the secure session on a circuit accepted by this node
+enum EndState {
+    /// Waiting for the opener's offer
+    AwaitingOffer,
+    /// Waiting for the opener's confirmation
+    Accepting(Responder),
+    /// Handshake done, payloads are sealed
+    Open(Session),
+}
+
+/// A circuit this node is an end of
+struct CircuitEnd {
+    /// The peer at the other end
+    remote: PeerId,
+    state: EndState,
+}
+
+/// Circuits this node is an end of
+#[derive(Default)]
+struct ClientState {
+    pending: HashMap<u64, PendingOpen>,
+    /// (relay, circuit) → other end
+    circuits: HashMap<(PeerId, u64), CircuitEnd>,
+    /// remote peer → (relay, circuit), once its session is open
+    routes: HashMap<PeerId, (PeerId, u64)>,
+    /// Relays allowed to open circuits to us
+    trusted: HashSet<PeerId>,
+    /// Peers connected directly through the inner transport
+    direct: HashSet<PeerId>,
+}
+
+impl ClientState {
+    fn insert(&mut self, relay: PeerId, circuit: u64, end: CircuitEnd) {
+        let remote = end.remote;
+        let open = matches!(end.state, EndState::Open(_));
+        self.circuits.insert((relay, circuit), end);
+        if !open {
+            return;
+        }
+        if let Some(previous) = self.routes.insert(remote, (relay, circuit)) {
+            if previous != (relay, circuit) {
+                self.circuits.remove(&previous);
+            }
+        }
+    }
+
+    fn remove(&mut self, relay: PeerId, circuit: u64) -> Option<PeerId> {
+        let remote = self.circuits.remove(&(relay, circuit))?.remote;
+        if self.routes.get(&remote) == Some(&(relay, circuit)) {
+            self.routes.remove(&remote);
+        }
+        Some(remote)
+    }
+
+    /// Take the open request whose handshake runs on `circuit` of `relay`
+    fn take_handshaking(&mut self, relay: PeerId, circuit: u64) -> Option<PendingOpen> {
+        let request = self
+            .pending
+            .iter()
+            .find(|(_, pending)| {
+                pending.relay == relay
+                    && matches!(pending.handshake, Some((id, _)) if id == circuit)
+            })
+            .map(|(request, _)| *request)?;
+        self.pending.remove(&request)
+    }
+
+    /// Remove every circuit through `relay`
+    fn remove_relay(&mut self, relay: PeerId) {
+        let lost: Vec<(PeerId, u64)> = self
+            .circuits
+            .keys()
+            .filter(|(via, _)| *via == relay)
+            .copied()
+            .collect();
+        for (relay, circuit) in lost {
+            self.remove(relay, circuit);
+        }
+    }
+}
+
+/// A circuit forwarded by this node
+#[derive(Debug, Clone, Copy)]
+struct Circuit {
+    initiator: PeerId,
+    target: PeerId,
+}
+
+impl Circuit {
+    /// The other end of the circuit, if `peer` is one of its ends
+    fn other(&self, peer: PeerId) -> Option<PeerId> {
+        if peer == self.initiator {
+            Some(self.target)
+        } else if peer == self.target {
+            Some(self.initiator)
+        } else {
+            None
+        }
+    }
+}
+
+/// Byte token bucket for one peer's relayed traffic
+///
+/// Like the inbound rate limiter, the bucket may go into debt so a frame
+/// larger than one second's allowance still gets through once.
+#[derive(Debug)]
+struct Quota {
+    bytes: f64,
+    refilled: Instant,
+}
+
+impl Quota {
+    fn new(bytes_per_sec: u64, now: Instant) -> Self {
+        Self {
+            bytes: bytes_per_sec as f64,
+            refilled: now,
+        }
+    }
+
+    fn take(&mut self, bytes_per_sec: u64, len: usize, now: Instant) -> bool {
+        let max = bytes_per_sec as f64;
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.refilled = now;
+        self.bytes = (self.bytes + elapsed * max).min(max);
+        if self.bytes <= 0.0 {
+            return false;
+        }
+        self.bytes -= len as f64;
+        true
+    }
+}
+
+/// Circuits this node forwards for others
+#[derive(Default)]
+struct ServerState {
+    circuits: HashMap<u64, Circuit>,
+    quotas: HashMap<PeerId, Quota>,
+    stats: RelayStats,
+}
+
+impl ServerState {
+    fn circuits_of(&self, peer: PeerId) -> usize {
+        self.circuits
+            .values()
+            .filter(|circuit| circuit.other(peer).is_some())
+            .count()
+    }
+}
+
+/// State shared with the dispatch task
+struct RelayShared<T> {
+    transport: Arc<T>,
+    config: RelayConfig,
+    identity: Option<LocalIdentity>,
+    next_id: AtomicU64,
+    client: Mutex<ClientState>,
+    server: Mutex<ServerState>,
+    closed: AtomicBool,
+}
+
+impl<T: GossipTransport> RelayShared<T> {
+    fn client(&self) -> MutexGuard<'_, ClientState> {
+        self.client.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn server(&self) -> MutexGuard<'_, ServerState> {
+        self.server.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn send(&self, peer: PeerId, message: RelayMessage) -> Result<()> {
+        self.transport
+            .send_to_peer(peer, StreamType::Relay, message.encode())
+            .await
+    }
+
+    async fn send_control(&self, peer: PeerId, message: RelayMessage) {
+        if let Err(e) = self.send(peer, message).await {
+            debug!(peer_id = %peer, "Failed to send relay control message: {}", e);
+        }
+    }
+
+    /// Check whether `peer` is reached directly rather than over a circuit
+    ///
+    /// A peer counts as direct once the inner transport reports it connected
+    /// or has verified its identity.
+    fn is_direct(&self, peer: &PeerId) -> bool {
+        self.client().direct.contains(peer) || self.transport.peer_identity(peer).is_some()
+    }
+}
+
+/// Removes a pending open request when the caller stops waiting for it
+struct PendingGuard<'a, T: GossipTransport> {
+    shared: &'a RelayShared<T>,
+    request: u64,
+}
+
+impl<T: GossipTransport> Drop for PendingGuard<'_, T> {
+    fn drop(&mut self) {
+        self.shared.client().pending.remove(&self.request);
+    }
+}
+
+/// A [`GossipTransport`] wrapper adding relayed circuits
+///
+/// Dropping the wrapper stops its dispatch task.
+pub struct RelayTransport<T: GossipTransport + 'static> {
+    shared: Arc<RelayShared<T>>,
+    passthrough: tokio::sync::Mutex<mpsc::Receiver<(PeerId, StreamType, Bytes)>>,
+    dispatcher: JoinHandle<()>,
+}
+
+impl<T: GossipTransport + 'static> RelayTransport<T> {
+    /// Wrap `transport` and start dispatching its inbound messages
+    ///
+    /// The wrapper takes over the transport's receive loop; read messages,
+    /// including relayed ones, through the wrapper rather than the inner
+    /// transport. Fails if the configured keypair is invalid.
+    pub fn new(transport: T, config: RelayConfig) -> Result<Self> {
+        let identity = match &config.keypair {
+            Some((public_key, secret_key)) => Some(LocalIdentity::new(
+                MlDsaPublicKey::from_bytes(public_key)
+                    .map_err(|e| anyhow!("Invalid ML-DSA public key: {}", e))?,
+                MlDsaSecretKey::from_bytes(secret_key)
+                    .map_err(|e| anyhow!("Invalid ML-DSA secret key: {}", e))?,
+            )),
+            None => None,
+        };
+        let (passthrough_tx, passthrough_rx) = mpsc::channel(config.passthrough_capacity.max(1));
+        let client = ClientState {
+            trusted: config.trusted_relays.clone(),
+            ..ClientState::default()
+        };
+        let shared = Arc::new(RelayShared {
+            transport: Arc::new(transport),
+            config,
+            identity,
+            next_id: AtomicU64::new(1),
+            client: Mutex::new(client),
+            server: Mutex::new(ServerState::default()),
+            closed: AtomicBool::new(false),
+        });
+        // Subscribe before spawning so no connection event is missed
+        let events = shared.transport.subscribe_events();
+        let dispatcher = tokio::spawn(dispatch(shared.clone(), events, passthrough_tx));
+
+        Ok(Self {
+            shared,
+            passthrough: tokio::sync::Mutex::new(passthrough_rx),
+            dispatcher,
+        })
+    }
+
+    /// The wrapped transport
+    pub fn inner(&self) -> &T {
+        &self.shared.transport
+    }
+
+    /// Open a circuit to `target` through `relay`
+    ///
+    /// Returns once `target` has proven its identity over the circuit.
+    /// Messages sent to `target` through this transport then travel over
+    /// the circuit. Returns the existing circuit if one is already open.
+    pub async fn open_circuit(&self, relay: PeerId, target: PeerId) -> Result<u64, RelayError> {
+        if self.shared.closed.load(Ordering::Acquire) {
+            return Err(RelayError::Closed);
+        }
+        if self.shared.identity.is_none() {
+            return Err(RelayError::NoIdentity);
+        }
+        if let Some((_, circuit)) = self.shared.client().routes.get(&target) {
+            return Ok(*circuit);
+        }
+
+        let request = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.shared.client().pending.insert(
+            request,
+            PendingOpen {
+                relay,
+                target,
+                reply: reply_tx,
+                handshake: None,
+            },
+        );
+        let _guard = PendingGuard {
+            shared: &self.shared,
+            request,
+        };
+
+        debug!(peer_id = %target, relay = %relay, "Opening relay circuit");
+        self.shared
+            .send(relay, RelayMessage::Open { request, target })
+            .await?;
+
+        let timeout = self.shared.config.open_timeout;
+        match tokio::time::timeout(timeout, reply_rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(RelayError::Closed),
+            Err(_) => Err(RelayError::Timeout(timeout)),
+        }
+    }
+
+    /// Close the circuit to `peer`
+    pub async fn close_circuit(&self, peer: PeerId) -> Result<()> {
+        let (relay, circuit) = {
+            let mut client = self.shared.client();
+            let route = client
+                .routes
+                .get(&peer)
+                .copied()
+                .ok_or_else(|| anyhow!("No relay circuit to peer {}", peer))?;
+            client.remove(route.0, route.1);
+            route
+        };
+
+        debug!(peer_id = %peer, relay = %relay, circuit, "Closing relay circuit");
+        self.shared
+            .send(relay, RelayMessage::Close { circuit })
+            .await
+    }
+
+    /// Allow `relay` to open circuits to this node
+    ///
+    /// Call this for peers seen advertising the relay role.
+    pub fn trust_relay(&self, relay: PeerId) {
+        self.shared.client().trusted.insert(relay);
+    }
+
+    /// Stop accepting circuits from `relay` and drop those it opened
+    pub fn distrust_relay(&self, relay: PeerId) {
+        let mut client = self.shared.client();
+        client.trusted.remove(&relay);
+        client.remove_relay(relay);
+    }
+
+    /// Check whether `relay` may open circuits to this node
+    pub fn is_trusted_relay(&self, relay: &PeerId) -> bool {
+        self.shared.client().trusted.contains(relay)
+    }
+
+    /// Peers currently reached over a circuit
+    pub fn relayed_peers(&self) -> Vec<PeerId> {
+        self.shared.client().routes.keys().copied().collect()
+    }
+
+    /// Check whether `peer` is reached over a circuit
+    pub fn is_relayed(&self, peer: &PeerId) -> bool {
+        self.shared.client().routes.contains_key(peer)
+    }
+
+    /// Counters of the circuits this node forwards for others
+    pub fn relay_stats(&self) -> RelayStats {
+        let server = self.shared.server();
+        RelayStats {
+            circuits: server.circuits.len(),
+            ..server.stats
+        }
+    }
+}
+
+impl<T: GossipTransport + 'static> Drop for RelayTransport<T> {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
+/// Route inbound messages and follow disconnects until the transport closes
+async fn dispatch<T: GossipTransport + 'static>(
+    shared: Arc<RelayShared<T>>,
+    mut events: broadcast::Receiver<TransportEvent>,
+    passthrough: mpsc::Sender<(PeerId, StreamType, Bytes)>,
+) {
+    let mut events_open = true;
+
+    loop {
+        tokio::select! {
+            received = shared.transport.receive_message() => {
+                let Ok((peer, stream_type, data)) = received else {
+                    break;
+                };
+                if stream_type != StreamType::Relay {
+                    // Waiting here stops reading the transport until the reader catches up
+                    let _ = passthrough.send((peer, stream_type, data)).await;
+                    continue;
+                }
+                match RelayMessage::decode(&data) {
+                    Some(message) => handle(&shared, peer, message, &passthrough).await,
+                    None => trace!(peer_id = %peer, "Dropped malformed relay message"),
+                }
+            }
+            event = events.recv(), if events_open => match event {
+                Ok(TransportEvent::PeerConnected { peer, .. }) => {
+                    shared.client().direct.insert(peer);
+                }
+                Ok(TransportEvent::PeerDisconnected { peer, .. }) => {
+                    peer_gone(&shared, peer).await;
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => events_open = false,
+            },
+        }
+    }
+
+    debug!("Relay dispatcher stopped: transport closed");
+    shared.closed.store(true, Ordering::Release);
+    for (_, pending) in shared.client().pending.drain() {
+        let _ = pending.reply.send(Err(RelayError::Closed));
+    }
+}
+
+/// Handle one relay message from `peer`
+async fn handle<T: GossipTransport>(
+    shared: &RelayShared<T>,
+    peer: PeerId,
+    message: RelayMessage,
+    passthrough: &mpsc::Sender<(PeerId, StreamType, Bytes)>,
+) {
+    if forward(shared, peer, &message).await {
+        return;
+    }
+    match message {
+        RelayMessage::Open { request, target } => {
+            let reply = match open(shared, peer, target).await {
+                Ok(circuit) => RelayMessage::Opened { request, circuit },
+                Err(reason) => {
+                    debug!(peer_id = %peer, target = %target, "Refused relay circuit: {}", reason);
+                    RelayMessage::Rejected { request, reason }
+                }
+            };
+            shared.send_control(peer, reply).await;
+        }
+        RelayMessage::Opened { request, circuit } => {
+            let started = {
+                let mut client = shared.client();
+                match client.pending.get_mut(&request) {
+                    Some(pending) if pending.relay == peer && pending.handshake.is_none() => {
+                        Some(Initiator::start().map(|(initiator, offer)| {
+                            pending.handshake = Some((circuit, initiator));
+                            (pending.target, offer)
+                        }))
+                    }
+                    _ => None,
+                }
+            };
+            match started {
+                Some(Ok((target, offer))) => {
+                    debug!(peer_id = %target, relay = %peer, circuit, "Relay circuit open, starting handshake");
+                    let offer = RelayMessage::Handshake {
+                        circuit,
+                        payload: offer,
+                    };
+                    shared.send_control(peer, offer).await;
+                }
+                Some(Err(e)) => {
+                    if let Some(pending) = shared.client().pending.remove(&request) {
+                        let _ = pending
+                            .reply
+                            .send(Err(RelayError::Handshake(e.to_string())));
+                    }
+                    shared
+                        .send_control(peer, RelayMessage::Close { circuit })
+                        .await;
+                }
+                // Nobody is waiting any more, so tear the circuit down again
+                None => {
+                    shared
+                        .send_control(peer, RelayMessage::Close { circuit })
+                        .await;
+                }
+            }
+        }
+        RelayMessage::Rejected { request, reason } => {
+            let pending = {
+                let mut client = shared.client();
+                match client.pending.get(&request) {
+                    Some(pending) if pending.relay == peer => client.pending.remove(&request),
+                    _ => None,
+                }
+            };
+            if let Some(pending) = pending {
+                let _ = pending.reply.send(Err(RelayError::Rejected(reason)));
+            }
+        }
+        RelayMessage::Incoming { circuit, source } => {
+            if !shared.client().trusted.contains(&peer) {
+                debug!(peer_id = %source, relay = %peer, circuit, "Refused relay circuit from untrusted peer");
+                shared
+                    .send_control(peer, RelayMessage::Close { circuit })
+                    .await;
+                return;
+            }
+            if shared.is_direct(&source) {
+                debug!(peer_id = %source, relay = %peer, circuit, "Refused relay circuit to directly connected peer");
+                shared
+                    .send_control(peer, RelayMessage::Close { circuit })
+                    .await;
+                return;
+            }
+            if shared.identity.is_none() {
+                debug!(peer_id = %source, relay = %peer, circuit, "Refused relay circuit without a keypair");
+                shared
+                    .send_control(peer, RelayMessage::Close { circuit })
+                    .await;
+                return;
+            }
+            debug!(peer_id = %source, relay = %peer, circuit, "Accepted relay circuit, awaiting handshake");
+            let end = CircuitEnd {
+                remote: source,
+                state: EndState::AwaitingOffer,
+            };
+            shared.client().insert(peer, circuit, end);
+        }
+        RelayMessage::Handshake { circuit, payload } => {
+            handshake(shared, peer, circuit, &payload).await;
+        }
+        RelayMessage::Data { circuit, payload } => {
+            let opened = {
+                let mut client = shared.client();
+                match client.circuits.get_mut(&(peer, circuit)) {
+                    Some(CircuitEnd {
+                        remote,
+                        state: EndState::Open(session),
+                    }) => match session.opener.open(&circuit.to_be_bytes(), &payload) {
+                        Ok(body) => Some((*remote, body)),
+                        Err(e) => {
+                            trace!(peer_id = %remote, relay = %peer, circuit, "Dropped relay circuit data: {}", e);
+                            return;
+                        }
+                    },
+                    Some(_) => {
+                        trace!(relay = %peer, circuit, "Dropped relay circuit data before handshake");
+                        return;
+                    }
+                    None => None,
+                }
+            };
+            let Some((remote, body)) = opened else {
+                trace!(peer_id = %peer, circuit, "Data for unknown relay circuit");
+                shared
+                    .send_control(peer, RelayMessage::Close { circuit })
+                    .await;
+                return;
+            };
+            match unwrap_data(body) {
+                Some((stream_type, payload)) => {
+                    let _ = passthrough.send((remote, stream_type, payload)).await;
+                }
+                None => {
+                    trace!(peer_id = %remote, relay = %peer, circuit, "Dropped malformed circuit data");
+                }
+            }
+        }
+        RelayMessage::Close { circuit } => {
+            let other = {
+                let mut server = shared.server();
+                match server.circuits.get(&circuit).and_then(|c| c.other(peer)) {
+                    Some(other) => {
+                        server.circuits.remove(&circuit);
+                        Some(other)
+                    }
+                    None => None,
+                }
+            };
+            match other {
+                Some(other) => {
+                    debug!(peer_id = %peer, circuit, "Relay circuit closed");
+                    shared
+                        .send_control(other, RelayMessage::Close { circuit })
+                        .await;
+                }
+                None => {
+                    let mut client = shared.client();
+                    if let Some(remote) = client.remove(peer, circuit) {
+                        debug!(peer_id = %remote, relay = %peer, circuit, "Relay circuit closed by relay");
+                    }
+                    if let Some(pending) = client.take_handshaking(peer, circuit) {
+                        let reason = "circuit closed during handshake".to_string();
+                        let _ = pending.reply.send(Err(RelayError::Rejected(reason)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Open a circuit from `initiator` to `target` on this relay
+async fn open<T: GossipTransport>(
+    shared: &RelayShared<T>,
+    initiator: PeerId,
+    target: PeerId,
+) -> Result<u64, String> {
+    let config = &shared.config;
+    if !config.serve {
+        return Err("relaying is disabled".to_string());
+    }
+    if target == initiator {
+        return Err("cannot relay to self".to_string());
+    }
+
+    let circuit = shared.next_id.fetch_add(1, Ordering::Relaxed);
+    {
+        let mut server = shared.server();
+        if server.circuits.len() >= config.max_circuits {
+            return Err("relay circuit limit reached".to_string());
+        }
+        if server.circuits_of(initiator) >= config.max_circuits_per_peer
+            || server.circuits_of(target) >= config.max_circuits_per_peer
+        {
+            return Err("per-peer circuit limit reached".to_string());
+        }
+        server
+            .circuits
+            .insert(circuit, Circuit { initiator, target });
+    }
+
+    let incoming = RelayMessage::Incoming {
+        circuit,
+        source: initiator,
+    };
+    if let Err(e) = shared.send(target, incoming).await {
+        shared.server().circuits.remove(&circuit);
+        return Err(format!("target unreachable: {}", e));
+    }
+
+    debug!(peer_id = %initiator, target = %target, circuit, "Opened relay circuit");
+    Ok(circuit)
+}
+
+/// Run the next handshake step of a circuit this node is an end of
+///
+/// On failure the circuit is closed.
+async fn handshake<T: GossipTransport>(
+    shared: &RelayShared<T>,
+    relay: PeerId,
+    circuit: u64,
+    payload: &[u8],
+) {
+    let step = match &shared.identity {
+        Some(identity) => advance(&mut shared.client(), identity, relay, circuit, payload),
+        None => Err(anyhow!("No keypair configured")),
+    };
+    match step {
+        Ok(Some(reply)) => {
+            let reply = RelayMessage::Handshake {
+                circuit,
+                payload: reply,
+            };
+            shared.send_control(relay, reply).await;
+        }
+        Ok(None) => {}
+        Err(e) => {
+            debug!(relay = %relay, circuit, "Relay circuit handshake failed: {}", e);
+            shared.client().remove(relay, circuit);
+            shared
+                .send_control(relay, RelayMessage::Close { circuit })
+                .await;
+        }
+    }
+}
+
+/// Process a handshake message, returning the reply to send, if any
+///
+/// A circuit only opens once the other end proved to be the peer it was
+/// opened to, or announced from.
+fn advance(
+    client: &mut ClientState,
+    identity: &LocalIdentity,
+    relay: PeerId,
+    circuit: u64,
+    payload: &[u8],
+) -> Result<Option<Bytes>> {
+    // The relay may not pass another peer off as the far end
+    let check = |proven: PeerId, expected: PeerId| {
+        if proven == expected {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Circuit end proved to be {} rather than {}",
+                proven,
+                expected
+            ))
+        }
+    };
+
+    if let Some(mut pending) = client.take_handshaking(relay, circuit) {
+        let Some((_, initiator)) = pending.handshake.take() else {
+            return Err(anyhow!("Handshake not started"));
+        };
+        let finished = initiator
+            .finish(identity, payload)
+            .and_then(|(confirm, session)| {
+                check(session.remote.peer(), pending.target)?;
+                Ok((confirm, session))
+            });
+        return match finished {
+            Ok((confirm, session)) => {
+                debug!(peer_id = %pending.target, relay = %relay, circuit, "Relay circuit established");
+                let end = CircuitEnd {
+                    remote: pending.target,
+                    state: EndState::Open(session),
+                };
+                client.insert(relay, circuit, end);
+                let _ = pending.reply.send(Ok(circuit));
+                Ok(Some(confirm))
+            }
+            Err(e) => {
+                let _ = pending
+                    .reply
+                    .send(Err(RelayError::Handshake(e.to_string())));
+                Err(e)
+            }
+        };
+    }
+
+    let end = client
+        .circuits
+        .remove(&(relay, circuit))
+        .ok_or_else(|| anyhow!("Handshake for unknown circuit"))?;
+    let remote = end.remote;
+    match end.state {
+        EndState::AwaitingOffer => {
+            let (responder, accept) = Responder::accept(identity, payload)?;
+            let end = CircuitEnd {
+                remote,
+                state: EndState::Accepting(responder),
+            };
+            client.insert(relay, circuit, end);
+            Ok(Some(accept))
+        }
+        EndState::Accepting(responder) => {
+            let session = responder.finish(payload)?;
+            check(session.remote.peer(), remote)?;
+            debug!(peer_id = %remote, relay = %relay, circuit, "Relay circuit established");
+            let end = CircuitEnd {
+                remote,
+                state: EndState::Open(session),
+            };
+            client.insert(relay, circuit, end);
+            Ok(None)
+        }
+        EndState::Open(session) => {
+            // Put it back so the caller's teardown also drops the route
+            let end = CircuitEnd {
+                remote,
+                state: EndState::Open(session),
+            };
+            client.circuits.insert((relay, circuit), end);
+            Err(anyhow!("Handshake on established circuit"))
+        }
+    }
+}
+
+/// Forward a circuit message if this node relays its circuit for `peer`
+///
+/// Returns `false` if the message is not for a circuit this node forwards.
+async fn forward<T: GossipTransport>(
+    shared: &RelayShared<T>,
+    peer: PeerId,
+    message: &RelayMessage,
+) -> bool {
+    let (circuit, len) = match message {
+        RelayMessage::Data { circuit, payload } | RelayMessage::Handshake { circuit, payload } => {
+            (*circuit, payload.len())
+        }
+        _ => return false,
+    };
+    let other = {
+        let mut server = shared.server();
+        let Some(other) = server.circuits.get(&circuit).and_then(|c| c.other(peer)) else {
+            return false;
+        };
+
+        let bytes_per_sec = shared.config.bytes_per_sec;
+        let now = Instant::now();
+        let allowed = server
+            .quotas
+            .entry(peer)
+            .or_insert_with(|| Quota::new(bytes_per_sec, now))
+            .take(bytes_per_sec, len, now);
+        if !allowed {
+            server.stats.frames_dropped += 1;
+            trace!(peer_id = %peer, circuit, "Dropped relayed frame over quota");
+            return true;
+        }
+        other
+    };
+
+    match shared.send(other, message.clone()).await {
+        Ok(()) => {
+            let mut server = shared.server();
+            server.stats.frames_relayed += 1;
+            server.stats.bytes_relayed += len as u64;
+        }
+        Err(e) => {
+            warn!(peer_id = %other, circuit, "Failed to relay frame, closing circuit: {}", e);
+            shared.server().circuits.remove(&circuit);
+            shared
+                .send_control(peer, RelayMessage::Close { circuit })
+                .await;
+        }
+    }
+    true
+}
+
+/// Build a circuit data body carrying `data` sent on `stream_type`
+fn wrap_data(stream_type: StreamType, data: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(1 + data.len());
+    buf.put_u8(stream_type.to_u8());
+    buf.put_slice(data);
+    buf.freeze()
+}
+
+/// Split a circuit data body into its stream type and payload
+fn unwrap_data(body: Bytes) -> Option<(StreamType, Bytes)> {
+    let stream_type = StreamType::from_u8(*body.first()?)?;
+    Some((stream_type, body.slice(1..)))
+}
+
+/// Tear down every circuit through or via a disconnected peer
+async fn peer_gone<T: GossipTransport>(shared: &RelayShared<T>, peer: PeerId) {
+    let orphaned: Vec<(u64, PeerId)> = {
+        let mut server = shared.server();
+        server.quotas.remove(&peer);
+        let orphaned: Vec<(u64, PeerId)> = server
+            .circuits
+            .iter()
+            .filter_map(|(id, circuit)| circuit.other(peer).map(|other| (*id, other)))
+            .collect();
+        for (id, _) in &orphaned {
+            server.circuits.remove(id);
+        }
+        orphaned
+    };
+    for (circuit, other) in orphaned {
+        debug!(peer_id = %peer, circuit, "Relay circuit end disconnected");
+        shared
+            .send_control(other, RelayMessage::Close { circuit })
+            .await;
+    }
+
+    let mut client = shared.client();
+    client.direct.remove(&peer);
+    client.remove_relay(peer);
+}
+
+#[async_trait::async_trait]
+impl<T: GossipTransport + 'static> GossipTransport for RelayTransport<T> {
+    async fn dial(&self, peer: PeerId, addr: SocketAddr) -> Result<()> {
+        self.shared.transport.dial(peer, addr).await
+    }
+
+    async fn dial_bootstrap(&self, addr: SocketAddr) -> Result<PeerId> {
+        self.shared.transport.dial_bootstrap(addr).await
+    }
+
+    async fn listen(&self, bind: SocketAddr) -> Result<()> {
+        self.shared.transport.listen(bind).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.shared.transport.close().await
+    }
+
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+        if self.is_relayed(&peer) {
+            return self.close_circuit(peer).await;
+        }
+        self.shared.transport.disconnect(peer, reason).await
+    }
+
+    async fn send_to_peer(&self, peer: PeerId, stream_type: StreamType, data: Bytes) -> Result<()> {
+        // A direct connection always wins over a circuit
+        let route = if self.shared.is_direct(&peer) {
+            None
+        } else {
+            self.shared.client().routes.get(&peer).copied()
+        };
+        match route {
+            Some((relay, circuit)) => {
+                let sealed = {
+                    let mut client = self.shared.client();
+                    match client.circuits.get_mut(&(relay, circuit)) {
+                        Some(CircuitEnd {
+                            state: EndState::Open(session),
+                            ..
+                        }) => session
+                            .sealer
+                            .seal(&circuit.to_be_bytes(), &wrap_data(stream_type, &data))?,
+                        _ => return Err(anyhow!("Relay circuit to peer {} is not open", peer)),
+                    }
+                };
+                let data = RelayMessage::Data {
+                    circuit,
+                    payload: sealed,
+                };
+                self.shared.send(relay, data).await
+            }
+            None => {
+                self.shared
+                    .transport
+                    .send_to_peer(peer, stream_type, data)
+                    .await
+            }
+        }
+    }
+
+    async fn receive_message(&self) -> Result<(PeerId, StreamType, Bytes)> {
+        self.passthrough
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| anyhow!("Transport closed"))
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        self.shared.transport.subscribe_events()
+    }
+
+    fn peer_identity(&self, peer: &PeerId) -> Option<VerifiedIdentity> {
+        self.shared.transport.peer_identity(peer)
+    }
+
+    async fn peer_addr(&self, peer: &PeerId) -> Option<SocketAddr> {
+        self.shared.transport.peer_addr(peer).await
+    }
+
+    async fn peer_rtt(&self, peer: &PeerId) -> Option<Duration> {
+        // The direct RTT says nothing about a relayed path
+        if self.is_relayed(peer) {
+            return None;
+        }
+        self.shared.transport.peer_rtt(peer).await
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.shared.transport.is_encrypted()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secure::SEAL_OVERHEAD;
+    use crate::{LinkAction, MemoryNetwork, MemoryTransport};
+    use ant_quic::generate_ml_dsa_keypair;
+
+    struct Nodes {
+        network: MemoryNetwork,
+        a: RelayTransport<MemoryTransport>,
+        b: RelayTransport<MemoryTransport>,
+        relay: RelayTransport<MemoryTransport>,
+    }
+
+    /// A node whose peer ID is derived from a fresh keypair
+    async fn node(network: &MemoryNetwork, config: RelayConfig) -> RelayTransport<MemoryTransport> {
+        let (public_key, secret_key) = generate_ml_dsa_keypair().expect("keypair");
+        let id = PeerId::from_pubkey(public_key.as_bytes());
+        let config = config.with_keypair(
+            public_key.as_bytes().to_vec(),
+            secret_key.as_bytes().to_vec(),
+        );
+        let transport = network.add_node(id).await.expect("add node");
+        RelayTransport::new(transport, config).expect("relay transport")
+    }
+
+    /// A local identity for a node not wrapped in a relay transport
+    fn identity() -> LocalIdentity {
+        let (public_key, secret_key) = generate_ml_dsa_keypair().expect("keypair");
+        LocalIdentity::new(public_key, secret_key)
+    }
+
+    /// Nodes a and b can only reach each other through the relay
+    async fn nodes(config: RelayConfig) -> Nodes {
+        let network = MemoryNetwork::new();
+        let relay = node(&network, config).await;
+        let relay_id = relay.inner().peer_id();
+        let client = RelayConfig::default().with_trusted_relay(relay_id);
+        let a = node(&network, client.clone()).await;
+        let b = node(&network, client).await;
+        let (a_id, b_id) = (a.inner().peer_id(), b.inner().peer_id());
+
+        network
+            .set_hook(move |from, to, _, _| {
+                if (from == a_id && to == b_id) || (from == b_id && to == a_id) {
+                    LinkAction::Drop
+                } else {
+                    LinkAction::Deliver
+                }
+            })
+            .await;
+
+        Nodes {
+            network,
+            a,
+            b,
+            relay,
+        }
+    }
+
+    async fn receive(transport: &RelayTransport<MemoryTransport>) -> (PeerId, StreamType, Bytes) {
+        tokio::time::timeout(Duration::from_secs(1), transport.receive_message())
+            .await
+            .expect("receive timeout")
+            .expect("receive")
+    }
+
+    #[test]
+    fn test_message_roundtrip() {
+        let peer = PeerId::new([7u8; 32]);
+        let messages = [
+            RelayMessage::Open {
+                request: 1,
+                target: peer,
+            },
+            RelayMessage::Opened {
+                request: 2,
+                circuit: 9,
+            },
+            RelayMessage::Rejected {
+                request: 3,
+                reason: "full".to_string(),
+            },
+            RelayMessage::Incoming {
+                circuit: 4,
+                source: peer,
+            },
+            RelayMessage::Data {
+                circuit: 5,
+                payload: Bytes::from("opaque"),
+            },
+            RelayMessage::Close { circuit: 6 },
+            RelayMessage::Handshake {
+                circuit: 7,
+                payload: Bytes::from("offer"),
+            },
+        ];
+        for message in messages {
+            assert_eq!(RelayMessage::decode(&message.encode()), Some(message));
+        }
+
+        assert_eq!(RelayMessage::decode(&Bytes::from("PING")), None);
+        assert_eq!(
+            RelayMessage::decode(&Bytes::from_static(b"\x00\0\0\0\0\0\0\0\x01short")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_circuit_carries_traffic_both_ways() {
+        let nodes = nodes(RelayConfig::default().with_serve(true)).await;
+        let (a_id, b_id) = (nodes.a.inner().peer_id(), nodes.b.inner().peer_id());
+        let relay_id = nodes.relay.inner().peer_id();
+
+        // Without a circuit the direct link drops everything
+        nodes
+            .a
+            .send_to_peer(b_id, StreamType::PubSub, Bytes::from("lost"))
+            .await
+            .expect("send");
+        assert_eq!(nodes.network.dropped(), 1);
+
+        nodes
+            .a
+            .open_circuit(relay_id, b_id)
+            .await
+            .expect("open circuit");
+        assert!(nodes.a.is_relayed(&b_id));
+
+        // The relay only ever sees sealed payloads
+        let leaked = Arc::new(AtomicBool::new(false));
+        let seen = leaked.clone();
+        nodes
+            .network
+            .set_hook(move |from, to, _, data| {
+                if data
+                    .windows(5)
+                    .any(|window| window == b"hello" || window == b"reply")
+                {
+                    seen.store(true, Ordering::SeqCst);
+                }
+                if (from == a_id && to == b_id) || (from == b_id && to == a_id) {
+                    LinkAction::Drop
+                } else {
+                    LinkAction::Deliver
+                }
+            })
+            .await;
+        let before = nodes.relay.relay_stats();
+
+        nodes
+            .a
+            .send_to_peer(b_id, StreamType::PubSub, Bytes::from("hello"))
+            .await
+            .expect("send");
+        assert_eq!(
+            receive(&nodes.b).await,
+            (a_id, StreamType::PubSub, Bytes::from("hello"))
+        );
+        assert!(nodes.b.is_relayed(&a_id));
+
+        nodes
+            .b
+            .send_to_peer(a_id, StreamType::Bulk, Bytes::from("reply"))
+            .await
+            .expect("send");
+        assert_eq!(
+            receive(&nodes.a).await,
+            (b_id, StreamType::Bulk, Bytes::from("reply"))
+        );
+
+        let stats = nodes.relay.relay_stats();
+        assert_eq!(stats.circuits, 1);
+        assert_eq!(stats.frames_relayed - before.frames_relayed, 2);
+        // Each payload carries its stream type byte and the seal
+        assert_eq!(
+            stats.bytes_relayed - before.bytes_relayed,
+            2 * (1 + 5 + SEAL_OVERHEAD) as u64
+        );
+        assert!(!leaked.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_circuits_are_refused_when_not_serving_or_full() {
+        let nodes = nodes(RelayConfig::default()).await;
+        let relay_id = nodes.relay.inner().peer_id();
+        let b_id = nodes.b.inner().peer_id();
+
+        let err = nodes
+            .a
+            .open_circuit(relay_id, b_id)
+            .await
+            .expect_err("relaying disabled");
+        assert!(matches!(err, RelayError::Rejected(_)));
+
+        let nodes = self::nodes(
+            RelayConfig::default()
+                .with_serve(true)
+                .with_circuit_limits(8, 1),
+        )
+        .await;
+        let relay_id = nodes.relay.inner().peer_id();
+        let b_id = nodes.b.inner().peer_id();
+
+        let err = nodes
+            .a
+            .open_circuit(relay_id, PeerId::new([9u8; 32]))
+            .await
+            .expect_err("unknown target");
+        assert!(matches!(err, RelayError::Rejected(_)));
+
+        nodes
+            .a
+            .open_circuit(relay_id, b_id)
+            .await
+            .expect("first circuit");
+
+        // a already has its one circuit
+        let c_id = PeerId::new([4u8; 32]);
+        let _c = nodes.network.add_node(c_id).await.expect("add node");
+        let err = nodes
+            .a
+            .open_circuit(relay_id, c_id)
+            .await
+            .expect_err("per-peer limit");
+        assert!(matches!(err, RelayError::Rejected(_)));
+        assert_eq!(nodes.relay.relay_stats().circuits, 1);
+    }
+
+    #[tokio::test]
+    async fn test_quota_drops_excess_frames() {
+        let nodes = nodes(
+            RelayConfig::default()
+                .with_serve(true)
+                .with_bytes_per_sec(30_000),
+        )
+        .await;
+        let relay_id = nodes.relay.inner().peer_id();
+        let b_id = nodes.b.inner().peer_id();
+        nodes
+            .a
+            .open_circuit(relay_id, b_id)
+            .await
+            .expect("open circuit");
+        let before = nodes.relay.relay_stats();
+        assert_eq!(before.frames_dropped, 0);
+
+        // The handshake took well under half the quota, so two frames fit
+        for _ in 0..5 {
+            nodes
+                .a
+                .send_to_peer(b_id, StreamType::Bulk, Bytes::from(vec![0u8; 15_000]))
+                .await
+                .expect("send");
+        }
+
+        receive(&nodes.b).await;
+        receive(&nodes.b).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let stats = nodes.relay.relay_stats();
+        assert_eq!(stats.frames_relayed - before.frames_relayed, 2);
+        assert_eq!(stats.frames_dropped, 3);
+    }
+
+    #[tokio::test]
+    async fn test_close_and_disconnect_tear_down_circuits() {
+        let nodes = nodes(RelayConfig::default().with_serve(true)).await;
+        let relay_id = nodes.relay.inner().peer_id();
+        let (a_id, b_id) = (nodes.a.inner().peer_id(), nodes.b.inner().peer_id());
+
+        nodes
+            .a
+            .open_circuit(relay_id, b_id)
+            .await
+            .expect("open circuit");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(nodes.b.is_relayed(&a_id));
+
+        // Closing one end closes the other
+        nodes
+            .a
+            .disconnect(b_id, CloseReason::Evicted)
+            .await
+            .expect("close circuit");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!nodes.b.is_relayed(&a_id));
+        assert_eq!(nodes.relay.relay_stats().circuits, 0);
+
+        // An end disconnecting from the relay closes the circuit too
+        nodes
+            .a
+            .dial(relay_id, nodes.relay.inner().local_addr())
+            .await
+            .expect("dial relay");
+        nodes
+            .a
+            .open_circuit(relay_id, b_id)
+            .await
+            .expect("reopen circuit");
+        nodes
+            .a
+            .inner()
+            .disconnect(relay_id, CloseReason::Shutdown)
+            .await
+            .expect("disconnect relay");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!nodes.a.is_relayed(&b_id));
+        assert!(!nodes.b.is_relayed(&a_id));
+        assert_eq!(nodes.relay.relay_stats().circuits, 0);
+    }
+
+    #[tokio::test]
+    async fn test_incoming_from_untrusted_peer_is_refused() {
+        let nodes = nodes(RelayConfig::default()).await;
+        let (a_id, b_id) = (nodes.a.inner().peer_id(), nodes.b.inner().peer_id());
+        let mallory = nodes
+            .network
+            .add_node(PeerId::new([6u8; 32]))
+            .await
+            .expect("add node");
+
+        // Mallory claims to relay for b and injects a message from "b"
+        let incoming = RelayMessage::Incoming {
+            circuit: 7,
+            source: b_id,
+        };
+        let data = RelayMessage::Data {
+            circuit: 7,
+            payload: Bytes::from("forged"),
+        };
+        for message in [incoming, data] {
+            mallory
+                .send_to_peer(a_id, StreamType::Relay, message.encode())
+                .await
+                .expect("send");
+        }
+
+        // The refusal is answered so the relay can free the circuit
+        let (from, _, reply) =
+            tokio::time::timeout(Duration::from_secs(1), mallory.receive_message())
+                .await
+                .expect("refusal in time")
+                .expect("refusal");
+        assert_eq!(from, a_id);
+        assert_eq!(
+            RelayMessage::decode(&reply),
+            Some(RelayMessage::Close { circuit: 7 })
+        );
+
+        assert!(!nodes.a.is_relayed(&b_id));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), nodes.a.receive_message())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refused_circuit_is_freed_on_the_relay() {
+        let nodes = nodes(RelayConfig::default().with_serve(true)).await;
+        let relay_id = nodes.relay.inner().peer_id();
+        let b_id = nodes.b.inner().peer_id();
+        nodes.b.distrust_relay(relay_id);
+
+        let err = nodes
+            .a
+            .open_circuit(relay_id, b_id)
+            .await
+            .expect_err("target refused the circuit");
+        assert!(matches!(err, RelayError::Rejected(_)));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(nodes.relay.relay_stats().circuits, 0);
+        assert!(!nodes.a.is_relayed(&b_id));
+    }
+
+    #[tokio::test]
+    async fn test_trusted_relay_cannot_impersonate_source() {
+        let nodes = nodes(RelayConfig::default()).await;
+        let (a_id, b_id) = (nodes.a.inner().peer_id(), nodes.b.inner().peer_id());
+        let mallory_id = PeerId::new([6u8; 32]);
+        let mallory = nodes.network.add_node(mallory_id).await.expect("add node");
+        nodes.a.trust_relay(mallory_id);
+        let next = || async {
+            let (_, stream_type, data) =
+                tokio::time::timeout(Duration::from_secs(1), mallory.receive_message())
+                    .await
+                    .expect("reply in time")
+                    .expect("reply");
+            assert_eq!(stream_type, StreamType::Relay);
+            RelayMessage::decode(&data).expect("relay message")
+        };
+
+        // Mallory announces a circuit from b but can only prove her own key
+        let (initiator, offer) = Initiator::start().expect("offer");
+        for message in [
+            RelayMessage::Incoming {
+                circuit: 7,
+                source: b_id,
+            },
+            RelayMessage::Handshake {
+                circuit: 7,
+                payload: offer,
+            },
+        ] {
+            mallory
+                .send_to_peer(a_id, StreamType::Relay, message.encode())
+                .await
+                .expect("send");
+        }
+        let RelayMessage::Handshake { payload, .. } = next().await else {
+            panic!("expected handshake accept");
+        };
+        let (confirm, _) = initiator
+            .finish(&identity(), &payload)
+            .expect("a proved its identity");
+        let confirm = RelayMessage::Handshake {
+            circuit: 7,
+            payload: confirm,
+        };
+        mallory
+            .send_to_peer(a_id, StreamType::Relay, confirm.encode())
+            .await
+            .expect("send");
+
+        assert_eq!(next().await, RelayMessage::Close { circuit: 7 });
+        assert!(!nodes.a.is_relayed(&b_id));
+    }
+
+    #[tokio::test]
+    async fn test_circuits_need_a_keypair() {
+        let network = MemoryNetwork::new();
+        let relay = node(&network, RelayConfig::default().with_serve(true)).await;
+        let target = node(&network, RelayConfig::default()).await;
+        let transport = network
+            .add_node(PeerId::new([1u8; 32]))
+            .await
+            .expect("add node");
+        let anonymous = RelayTransport::new(transport, RelayConfig::default()).expect("relay");
+
+        let err = anonymous
+            .open_circuit(relay.inner().peer_id(), target.inner().peer_id())
+            .await
+            .expect_err("no keypair");
+        assert!(matches!(err, RelayError::NoIdentity));
+    }
+
+    #[tokio::test]
+    async fn test_relay_messages_on_other_streams_pass_through() {
+        let nodes = nodes(RelayConfig::default().with_serve(true)).await;
+        let relay_id = nodes.relay.inner().peer_id();
+        let a_id = nodes.a.inner().peer_id();
+
+        // Application data that happens to parse as a relay message
+        let lookalike = RelayMessage::Close { circuit: 1 }.encode();
+        nodes
+            .relay
+            .inner()
+            .send_to_peer(a_id, StreamType::Membership, lookalike.clone())
+            .await
+            .expect("send");
+        assert_eq!(
+            receive(&nodes.a).await,
+            (relay_id, StreamType::Membership, lookalike)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_circuit_never_replaces_direct_connection() {
+        let nodes = nodes(RelayConfig::default().with_serve(true)).await;
+        nodes.network.clear_hook().await;
+        let (a_id, b_id) = (nodes.a.inner().peer_id(), nodes.b.inner().peer_id());
+        nodes
+            .a
+            .dial(b_id, nodes.b.inner().local_addr())
+            .await
+            .expect("dial b");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // Even a trusted relay cannot route a directly connected peer
+        let incoming = RelayMessage::Incoming {
+            circuit: 7,
+            source: b_id,
+        };
+        nodes
+            .relay
+            .inner()
+            .send_to_peer(a_id, StreamType::Relay, incoming.encode())
+            .await
+            .expect("send");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!nodes.a.is_relayed(&b_id));
+
+        nodes
+            .a
+            .send_to_peer(b_id, StreamType::PubSub, Bytes::from("direct"))
+            .await
+            .expect("send");
+        assert_eq!(
+            receive(&nodes.b).await,
+            (a_id, StreamType::PubSub, Bytes::from("direct"))
+        );
+        assert_eq!(nodes.relay.relay_stats().frames_relayed, 0);
+    }
+}
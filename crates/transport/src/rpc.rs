@@ -0,0 +1,725 @@
+//! Request/response RPC over any [`GossipTransport`]
+//!
+//! [`RpcTransport`] wraps a transport and correlates replies with requests,
+//! so protocols no longer need to match responses by hand. A request carries
+//! a unique ID and is answered by the handler registered for its stream type
+//! on the remote side; the caller awaits the reply with a timeout.
+//!
+//! RPC messages are tagged with [`RPC_MAGIC`]. All other messages pass through
+//! untouched and are returned by the wrapper's own `receive_message`, so the
+//! wrapper can stand in for the inner transport. Passthrough messages are
+//! buffered in a bounded queue; when it is full the wrapper stops reading the
+//! inner transport, so backpressure reaches the sender.
+//!
+//! The number of requests being handled at once is capped overall and per
+//! peer. Requests beyond either cap are refused with an error response.
+//!
+//! # Wire format
+//!
+//! ```text
+//! [magic: 4 bytes "SGRP"][kind: u8][request_id: u64 BE][payload]
+//! ```
+
+use anyhow::Result;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::future::BoxFuture;
+use saorsa_gossip_types::PeerId;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{debug, trace, warn};
+
+use crate::{CloseReason, GossipTransport, StreamType, TransportEvent, VerifiedIdentity};
+
+/// Magic prefix identifying RPC messages
+pub const RPC_MAGIC: [u8; 4] = *b"SGRP";
+
+/// Length of the RPC header (magic, kind and request ID)
+pub const RPC_HEADER_LEN: usize = 13;
+
+/// Handles an incoming request: `(from, payload) -> response`
+pub type RpcHandler = Arc<dyn Fn(PeerId, Bytes) -> BoxFuture<'static, Result<Bytes>> + Send + Sync>;
+
+/// Limits of an [`RpcTransport`]
+#[derive(Debug, Clone)]
+pub struct RpcConfig {
+    /// Requests handled at once across all peers (default: 256)
+    pub max_in_flight: usize,
+    /// Requests handled at once for a single peer (default: 32)
+    pub max_in_flight_per_peer: usize,
+    /// Non-RPC messages buffered for `receive_message` (default: 1,024)
+    pub passthrough_capacity: usize,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 256,
+            max_in_flight_per_peer: 32,
+            passthrough_capacity: 1_024,
+        }
+    }
+}
+
+impl RpcConfig {
+    /// Set the number of requests handled at once, overall and per peer
+    pub fn with_in_flight_limits(mut self, max_in_flight: usize, per_peer: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self.max_in_flight_per_peer = per_peer;
+        self
+    }
+
+    /// Set the number of non-RPC messages buffered for `receive_message`
+    pub fn with_passthrough_capacity(mut self, capacity: usize) -> Self {
+        self.passthrough_capacity = capacity.max(1);
+        self
+    }
+}
+
+/// Errors returned by [`RpcTransport::request`]
+#[derive(thiserror::Error, Debug)]
+pub enum RpcError {
+    #[error("request timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("remote handler failed: {0}")]
+    Remote(String),
+
+    #[error("RPC transport closed")]
+    Closed,
+
+    #[error("transport error: {0}")]
+    Transport(#[from] anyhow::Error),
+}
+
+/// Kind of RPC message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RpcKind {
+    Request = 0,
+    Response = 1,
+    Error = 2,
+}
+
+impl RpcKind {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Request),
+            1 => Some(Self::Response),
+            2 => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Encode an RPC message
+fn encode(kind: RpcKind, id: u64, payload: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(RPC_HEADER_LEN + payload.len());
+    buf.put_slice(&RPC_MAGIC);
+    buf.put_u8(kind as u8);
+    buf.put_u64(id);
+    buf.put_slice(payload);
+    buf.freeze()
+}
+
+/// Decode an RPC message, or `None` if `data` is not one
+fn decode(data: &Bytes) -> Option<(RpcKind, u64, Bytes)> {
+    if data.len() < RPC_HEADER_LEN || data[..4] != RPC_MAGIC {
+        return None;
+    }
+    let kind = RpcKind::from_u8(data[4])?;
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&data[5..RPC_HEADER_LEN]);
+    Some((kind, u64::from_be_bytes(id), data.slice(RPC_HEADER_LEN..)))
+}
+
+/// A request awaiting its response
+struct PendingRequest {
+    peer: PeerId,
+    reply: oneshot::Sender<Result<Bytes, RpcError>>,
+}
+
+/// Requests currently being handled
+#[derive(Default)]
+struct InFlight {
+    total: usize,
+    per_peer: HashMap<PeerId, usize>,
+}
+
+/// A reserved handler slot, released when the handler finishes
+struct HandlerSlot {
+    in_flight: Arc<Mutex<InFlight>>,
+    peer: PeerId,
+}
+
+impl Drop for HandlerSlot {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        in_flight.total -= 1;
+        if let Some(count) = in_flight.per_peer.get_mut(&self.peer) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.per_peer.remove(&self.peer);
+            }
+        }
+    }
+}
+
+/// State shared with the dispatch task
+struct RpcShared<T> {
+    transport: Arc<T>,
+    config: RpcConfig,
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, PendingRequest>>,
+    handlers: RwLock<HashMap<StreamType, RpcHandler>>,
+    in_flight: Arc<Mutex<InFlight>>,
+    closed: AtomicBool,
+}
+
+impl<T> RpcShared<T> {
+    fn pending(&self) -> std::sync::MutexGuard<'_, HashMap<u64, PendingRequest>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Reserve a handler slot for a request from `peer`, if under both caps
+    fn reserve_slot(&self, peer: PeerId) -> Option<HandlerSlot> {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        let of_peer = in_flight.per_peer.get(&peer).copied().unwrap_or_default();
+        if in_flight.total >= self.config.max_in_flight
+            || of_peer >= self.config.max_in_flight_per_peer
+        {
+            return None;
+        }
+        in_flight.total += 1;
+        *in_flight.per_peer.entry(peer).or_default() += 1;
+        Some(HandlerSlot {
+            in_flight: Arc::clone(&self.in_flight),
+            peer,
+        })
+    }
+
+    fn handler(&self, stream_type: StreamType) -> Option<RpcHandler> {
+        self.handlers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&stream_type)
+            .cloned()
+    }
+}
+
+/// Removes a pending request when the caller stops waiting for it
+struct PendingGuard<'a, T> {
+    shared: &'a RpcShared<T>,
+    id: u64,
+}
+
+impl<T> Drop for PendingGuard<'_, T> {
+    fn drop(&mut self) {
+        self.shared.pending().remove(&self.id);
+    }
+}
+
+/// A [`GossipTransport`] wrapper adding request/response RPC
+///
+/// Dropping the wrapper stops its dispatch task.
+pub struct RpcTransport<T: GossipTransport + 'static> {
+    shared: Arc<RpcShared<T>>,
+    passthrough: tokio::sync::Mutex<mpsc::Receiver<(PeerId, StreamType, Bytes)>>,
+    dispatcher: JoinHandle<()>,
+}
+
+impl<T: GossipTransport + 'static> RpcTransport<T> {
+    /// Wrap `transport` with default limits and start dispatching its inbound messages
+    ///
+    /// The wrapper takes over the transport's receive loop; read non-RPC
+    /// messages through the wrapper rather than the inner transport.
+    pub fn new(transport: T) -> Self {
+        Self::with_config(transport, RpcConfig::default())
+    }
+
+    /// Wrap `transport` with the given limits and start dispatching its inbound messages
+    pub fn with_config(transport: T, config: RpcConfig) -> Self {
+        let (passthrough_tx, passthrough_rx) = mpsc::channel(config.passthrough_capacity.max(1));
+        let shared = Arc::new(RpcShared {
+            transport: Arc::new(transport),
+            config,
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            handlers: RwLock::new(HashMap::new()),
+            in_flight: Arc::new(Mutex::new(InFlight::default())),
+            closed: AtomicBool::new(false),
+        });
+        let dispatcher = tokio::spawn(dispatch(shared.clone(), passthrough_tx));
+
+        Self {
+            shared,
+            passthrough: tokio::sync::Mutex::new(passthrough_rx),
+            dispatcher,
+        }
+    }
+
+    /// The wrapped transport
+    pub fn inner(&self) -> &T {
+        &self.shared.transport
+    }
+
+    /// Register the handler answering requests on `stream_type`
+    ///
+    /// Replaces any handler previously registered for the stream type.
+    pub fn register_handler<F, Fut>(&self, stream_type: StreamType, handler: F)
+    where
+        F: Fn(PeerId, Bytes) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Bytes>> + Send + 'static,
+    {
+        let handler: RpcHandler = Arc::new(move |peer, data| Box::pin(handler(peer, data)));
+        self.shared
+            .handlers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(stream_type, handler);
+    }
+
+    /// Remove the handler for `stream_type`
+    ///
+    /// Later requests on the stream type are answered with an error.
+    pub fn unregister_handler(&self, stream_type: StreamType) {
+        self.shared
+            .handlers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&stream_type);
+    }
+
+    /// Send a request to `peer` and wait up to `timeout` for its response
+    ///
+    /// Dropping the returned future cancels the request; a response arriving
+    /// afterwards is discarded.
+    pub async fn request(
+        &self,
+        peer: PeerId,
+        stream_type: StreamType,
+        data: Bytes,
+        timeout: Duration,
+    ) -> Result<Bytes, RpcError> {
+        if self.shared.closed.load(Ordering::Acquire) {
+            return Err(RpcError::Closed);
+        }
+
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.shared.pending().insert(
+            id,
+            PendingRequest {
+                peer,
+                reply: reply_tx,
+            },
+        );
+        let _guard = PendingGuard {
+            shared: &self.shared,
+            id,
+        };
+
+        trace!(peer_id = %peer, request_id = id, "Sending RPC request");
+        self.shared
+            .transport
+            .send_to_peer(peer, stream_type, encode(RpcKind::Request, id, &data))
+            .await?;
+
+        match tokio::time::timeout(timeout, reply_rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(RpcError::Closed),
+            Err(_) => {
+                debug!(peer_id = %peer, request_id = id, "RPC request timed out");
+                Err(RpcError::Timeout(timeout))
+            }
+        }
+    }
+
+    /// Number of requests awaiting a response
+    pub fn pending_requests(&self) -> usize {
+        self.shared.pending().len()
+    }
+}
+
+impl<T: GossipTransport + 'static> Drop for RpcTransport<T> {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
+/// Route inbound messages until the transport closes
+async fn dispatch<T: GossipTransport + 'static>(
+    shared: Arc<RpcShared<T>>,
+    passthrough: mpsc::Sender<(PeerId, StreamType, Bytes)>,
+) {
+    while let Ok((peer, stream_type, data)) = shared.transport.receive_message().await {
+        let Some((kind, id, payload)) = decode(&data) else {
+            // Waiting here stops reading the transport until the reader catches up
+            let _ = passthrough.send((peer, stream_type, data)).await;
+            continue;
+        };
+
+        match kind {
+            RpcKind::Request => match shared.reserve_slot(peer) {
+                Some(slot) => {
+                    let shared = shared.clone();
+                    tokio::spawn(async move {
+                        answer(&shared, peer, stream_type, id, payload).await;
+                        drop(slot);
+                    });
+                }
+                None => {
+                    debug!(peer_id = %peer, request_id = id, "Refusing RPC request: too many in flight");
+                    let response = encode(RpcKind::Error, id, b"too many requests in flight");
+                    respond(&shared, peer, stream_type, id, response).await;
+                }
+            },
+            RpcKind::Response | RpcKind::Error => {
+                let pending = {
+                    let mut pending = shared.pending();
+                    match pending.get(&id) {
+                        Some(request) if request.peer == peer => pending.remove(&id),
+                        _ => None,
+                    }
+                };
+                let Some(pending) = pending else {
+                    trace!(peer_id = %peer, request_id = id, "Discarding unmatched RPC response");
+                    continue;
+                };
+                let result = if kind == RpcKind::Response {
+                    Ok(payload)
+                } else {
+                    Err(RpcError::Remote(
+                        String::from_utf8_lossy(&payload).into_owned(),
+                    ))
+                };
+                let _ = pending.reply.send(result);
+            }
+        }
+    }
+
+    debug!("RPC dispatcher stopped: transport closed");
+    shared.closed.store(true, Ordering::Release);
+    for (_, pending) in shared.pending().drain() {
+        let _ = pending.reply.send(Err(RpcError::Closed));
+    }
+}
+
+/// Run the handler for a request and send back its response
+async fn answer<T: GossipTransport>(
+    shared: &RpcShared<T>,
+    peer: PeerId,
+    stream_type: StreamType,
+    id: u64,
+    payload: Bytes,
+) {
+    let response = match shared.handler(stream_type) {
+        Some(handler) => match handler(peer, payload).await {
+            Ok(response) => encode(RpcKind::Response, id, &response),
+            Err(e) => encode(RpcKind::Error, id, e.to_string().as_bytes()),
+        },
+        None => encode(
+            RpcKind::Error,
+            id,
+            format!("no handler for {:?} requests", stream_type).as_bytes(),
+        ),
+    };
+
+    respond(shared, peer, stream_type, id, response).await;
+}
+
+/// Send an encoded response back to the requesting peer
+async fn respond<T: GossipTransport>(
+    shared: &RpcShared<T>,
+    peer: PeerId,
+    stream_type: StreamType,
+    id: u64,
+    response: Bytes,
+) {
+    if let Err(e) = shared
+        .transport
+        .send_to_peer(peer, stream_type, response)
+        .await
+    {
+        warn!(peer_id = %peer, request_id = id, "Failed to send RPC response: {}", e);
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: GossipTransport + 'static> GossipTransport for RpcTransport<T> {
+    async fn dial(&self, peer: PeerId, addr: SocketAddr) -> Result<()> {
+        self.shared.transport.dial(peer, addr).await
+    }
+
+    async fn dial_bootstrap(&self, addr: SocketAddr) -> Result<PeerId> {
+        self.shared.transport.dial_bootstrap(addr).await
+    }
+
+    async fn listen(&self, bind: SocketAddr) -> Result<()> {
+        self.shared.transport.listen(bind).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.shared.transport.close().await
+    }
+
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+        self.shared.transport.disconnect(peer, reason).await
+    }
+
+    async fn send_to_peer(&self, peer: PeerId, stream_type: StreamType, data: Bytes) -> Result<()> {
+        self.shared
+            .transport
+            .send_to_peer(peer, stream_type, data)
+            .await
+    }
+
+    async fn receive_message(&self) -> Result<(PeerId, StreamType, Bytes)> {
+        self.passthrough
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Transport closed"))
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        self.shared.transport.subscribe_events()
+    }
+
+    fn peer_identity(&self, peer: &PeerId) -> Option<VerifiedIdentity> {
+        self.shared.transport.peer_identity(peer)
+    }
+
+    async fn peer_addr(&self, peer: &PeerId) -> Option<SocketAddr> {
+        self.shared.transport.peer_addr(peer).await
+    }
+
+    async fn peer_rtt(&self, peer: &PeerId) -> Option<Duration> {
+        self.shared.transport.peer_rtt(peer).await
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.shared.transport.is_encrypted()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LinkAction, MemoryNetwork};
+
+    async fn rpc_pair() -> (
+        MemoryNetwork,
+        RpcTransport<crate::MemoryTransport>,
+        RpcTransport<crate::MemoryTransport>,
+    ) {
+        let network = MemoryNetwork::new();
+        let a = network
+            .add_node(PeerId::new([1u8; 32]))
+            .await
+            .expect("add node a");
+        let b = network
+            .add_node(PeerId::new([2u8; 32]))
+            .await
+            .expect("add node b");
+        (network, RpcTransport::new(a), RpcTransport::new(b))
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let encoded = encode(RpcKind::Response, 42, b"payload");
+        assert_eq!(encoded.len(), RPC_HEADER_LEN + 7);
+
+        let (kind, id, payload) = decode(&encoded).expect("decode");
+        assert_eq!(kind, RpcKind::Response);
+        assert_eq!(id, 42);
+        assert_eq!(payload, Bytes::from("payload"));
+
+        assert!(decode(&Bytes::from("PING")).is_none());
+        assert!(decode(&Bytes::from_static(b"SGRP\x09\0\0\0\0\0\0\0\x01")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_request_is_answered_by_handler() {
+        let (_network, a, b) = rpc_pair().await;
+        b.register_handler(StreamType::Membership, |_peer, data: Bytes| async move {
+            let mut reply = data.to_vec();
+            reply.reverse();
+            Ok(Bytes::from(reply))
+        });
+
+        let reply = a
+            .request(
+                b.inner().peer_id(),
+                StreamType::Membership,
+                Bytes::from("abc"),
+                Duration::from_secs(1),
+            )
+            .await
+            .expect("request");
+
+        assert_eq!(reply, Bytes::from("cba"));
+        assert_eq!(a.pending_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_handler_errors_and_missing_handlers_are_reported() {
+        let (_network, a, b) = rpc_pair().await;
+        b.register_handler(StreamType::PubSub, |_peer, _data| async {
+            Err(anyhow::anyhow!("manifest not found"))
+        });
+
+        let err = a
+            .request(
+                b.inner().peer_id(),
+                StreamType::PubSub,
+                Bytes::new(),
+                Duration::from_secs(1),
+            )
+            .await
+            .expect_err("handler error");
+        assert!(matches!(err, RpcError::Remote(msg) if msg == "manifest not found"));
+
+        let err = a
+            .request(
+                b.inner().peer_id(),
+                StreamType::Bulk,
+                Bytes::new(),
+                Duration::from_secs(1),
+            )
+            .await
+            .expect_err("no handler");
+        assert!(matches!(err, RpcError::Remote(_)));
+    }
+
+    #[tokio::test]
+    async fn test_request_times_out_and_late_response_is_discarded() {
+        let (network, a, b) = rpc_pair().await;
+        b.register_handler(
+            StreamType::Membership,
+            |_peer, data| async move { Ok(data) },
+        );
+        network
+            .set_hook(|_, _, _, _| LinkAction::Delay(Duration::from_millis(200)))
+            .await;
+
+        let err = a
+            .request(
+                b.inner().peer_id(),
+                StreamType::Membership,
+                Bytes::from("slow"),
+                Duration::from_millis(50),
+            )
+            .await
+            .expect_err("timeout");
+        assert!(matches!(err, RpcError::Timeout(_)));
+        assert_eq!(a.pending_requests(), 0);
+
+        // The late response must not leak into the passthrough queue
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), a.receive_message())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_is_cancelled() {
+        let (network, a, b) = rpc_pair().await;
+        network.set_hook(|_, _, _, _| LinkAction::Drop).await;
+
+        let request = a.request(
+            b.inner().peer_id(),
+            StreamType::Membership,
+            Bytes::from("lost"),
+            Duration::from_secs(10),
+        );
+        let _ = tokio::time::timeout(Duration::from_millis(50), request).await;
+
+        assert_eq!(a.pending_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_requests_beyond_in_flight_limit_are_refused() {
+        let network = MemoryNetwork::new();
+        let a = RpcTransport::new(
+            network
+                .add_node(PeerId::new([1u8; 32]))
+                .await
+                .expect("add node a"),
+        );
+        let b = RpcTransport::with_config(
+            network
+                .add_node(PeerId::new([2u8; 32]))
+                .await
+                .expect("add node b"),
+            RpcConfig::default().with_in_flight_limits(8, 1),
+        );
+        let (release_tx, release_rx) = tokio::sync::watch::channel(false);
+        b.register_handler(StreamType::Bulk, move |_peer, data| {
+            let mut release = release_rx.clone();
+            async move {
+                let _ = release.wait_for(|released| *released).await;
+                Ok(data)
+            }
+        });
+
+        let target = b.inner().peer_id();
+        let slow = a.request(
+            target,
+            StreamType::Bulk,
+            Bytes::from("first"),
+            Duration::from_secs(5),
+        );
+        let refused = async {
+            // Let the first request reach the handler
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let result = a
+                .request(
+                    target,
+                    StreamType::Bulk,
+                    Bytes::from("second"),
+                    Duration::from_secs(1),
+                )
+                .await;
+            let _ = release_tx.send(true);
+            result
+        };
+        let (slow, refused) = tokio::join!(slow, refused);
+
+        assert_eq!(slow.expect("first request"), Bytes::from("first"));
+        assert!(matches!(refused, Err(RpcError::Remote(msg)) if msg.contains("too many")));
+
+        // The slot is released once the handler finishes
+        let reply = a
+            .request(
+                target,
+                StreamType::Bulk,
+                Bytes::from("third"),
+                Duration::from_secs(1),
+            )
+            .await
+            .expect("third request");
+        assert_eq!(reply, Bytes::from("third"));
+    }
+
+    #[tokio::test]
+    async fn test_plain_messages_pass_through() {
+        let (_network, a, b) = rpc_pair().await;
+
+        a.send_to_peer(b.inner().peer_id(), StreamType::PubSub, Bytes::from("hi"))
+            .await
+            .expect("send");
+
+        let (from, stream_type, data) = b.receive_message().await.expect("receive");
+        assert_eq!(from, a.inner().peer_id());
+        assert_eq!(stream_type, StreamType::PubSub);
+        assert_eq!(data, Bytes::from("hi"));
+    }
+}
@@ -0,0 +1,419 @@
+//! Authenticated key exchange and message encryption for links without TLS
+//!
+//! QUIC connections are encrypted by their TLS session. Links that have no
+//! TLS session of their own, TCP connections and relayed circuits, first run
+//! this three-message handshake between their two ends:
+//!
+//! 1. Offer (initiator to responder): an ephemeral ML-KEM-768 public key
+//! 2. Accept (responder to initiator): the KEM ciphertext and the
+//!    responder's identity proof
+//! 3. Confirm (initiator to responder): the initiator's identity proof
+//!
+//! The proofs use the format of [`crate::handshake`], signed over a hash of
+//! the transcript so far: the responder signs the offer and ciphertext, the
+//! initiator additionally the responder's proof. A party in the middle can
+//! neither swap the KEM key or ciphertext nor replay a proof made for
+//! another session. The shared secret and the transcript hash then yield
+//! one ChaCha20-Poly1305 key per direction.
+//!
+//! A sealed message is the sender's message counter followed by the
+//! ciphertext:
+//!
+//! ```text
+//! +--------------+----------------------------+
+//! | counter (BE) | ciphertext and 16-byte tag |
+//! | 8 bytes      | remaining bytes            |
+//! +--------------+----------------------------+
+//! ```
+//!
+//! The counter is the nonce. Each counter opens at most once, so replayed
+//! messages fail to open. Gaps are allowed, since a relay may drop messages
+//! over its quota, and so is reordering among the last
+//! [`REPLAY_WINDOW`] counters, since concurrent senders may overtake each
+//! other.
+
+use ant_quic::crypto::pqc::types::{
+    MlKemCiphertext, MlKemPublicKey, MlKemSecretKey, ML_KEM_768_CIPHERTEXT_SIZE,
+};
+use ant_quic::crypto::pqc::{MlKem768, MlKemOperations};
+use anyhow::{anyhow, Result};
+use bytes::{BufMut, Bytes, BytesMut};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::handshake::{verify_proof, LocalIdentity, VerifiedIdentity};
+
+/// Context for transcript hashes
+const TRANSCRIPT_CONTEXT: &str = "saorsa-gossip 2025 secure session transcript v1";
+
+/// Context for the binding signed by the responder
+const RESPONDER_PROOF_CONTEXT: &str = "saorsa-gossip 2025 secure session responder proof v1";
+
+/// Context for the binding signed by the initiator
+const INITIATOR_PROOF_CONTEXT: &str = "saorsa-gossip 2025 secure session initiator proof v1";
+
+/// Context for the key protecting initiator-to-responder messages
+const INITIATOR_KEY_CONTEXT: &str = "saorsa-gossip 2025 secure session initiator key v1";
+
+/// Context for the key protecting responder-to-initiator messages
+const RESPONDER_KEY_CONTEXT: &str = "saorsa-gossip 2025 secure session responder key v1";
+
+/// Length of the message counter prefixing every sealed message
+const COUNTER_LEN: usize = 8;
+
+/// Length of the authentication tag of every sealed message
+pub(crate) const TAG_LEN: usize = 16;
+
+/// Number of counters below the highest seen that may still open
+const REPLAY_WINDOW: u64 = 64;
+
+/// Bytes a sealed message adds to its plaintext
+pub(crate) const SEAL_OVERHEAD: usize = COUNTER_LEN + TAG_LEN;
+
+/// The initiator's side of a handshake in progress
+pub(crate) struct Initiator {
+    secret_key: MlKemSecretKey,
+    offer: Bytes,
+}
+
+impl Initiator {
+    /// Start a handshake, returning the offer to send to the responder
+    pub(crate) fn start() -> Result<(Self, Bytes)> {
+        let (public_key, secret_key) = MlKem768::new()
+            .generate_keypair()
+            .map_err(|e| anyhow!("Failed to generate ML-KEM keypair: {}", e))?;
+        let offer = Bytes::copy_from_slice(public_key.as_bytes());
+        Ok((
+            Self {
+                secret_key,
+                offer: offer.clone(),
+            },
+            offer,
+        ))
+    }
+
+    /// Verify the responder's accept message
+    ///
+    /// Returns the confirmation to send back and the established session.
+    pub(crate) fn finish(
+        self,
+        identity: &LocalIdentity,
+        accept: &[u8],
+    ) -> Result<(Bytes, Session)> {
+        if accept.len() <= ML_KEM_768_CIPHERTEXT_SIZE {
+            return Err(anyhow!("Truncated handshake accept message"));
+        }
+        let (ciphertext, responder_proof) = accept.split_at(ML_KEM_768_CIPHERTEXT_SIZE);
+        let ciphertext = MlKemCiphertext::from_bytes(ciphertext)
+            .map_err(|e| anyhow!("Invalid ML-KEM ciphertext: {}", e))?;
+        let shared = MlKem768::new()
+            .decapsulate(&self.secret_key, &ciphertext)
+            .map_err(|e| anyhow!("Failed to decapsulate session secret: {}", e))?;
+
+        let transcript = transcript(&[&self.offer, ciphertext.as_bytes()]);
+        let remote = verify_proof(
+            responder_proof,
+            &blake3::derive_key(RESPONDER_PROOF_CONTEXT, &transcript),
+        )?;
+
+        let transcript = transcript_with_proof(&transcript, responder_proof);
+        let confirm = identity.prove(&blake3::derive_key(INITIATOR_PROOF_CONTEXT, &transcript))?;
+        let keys = SessionKeys::derive(shared.as_bytes(), &transcript);
+        Ok((
+            confirm,
+            Session {
+                remote,
+                sealer: Sealer::new(&keys.initiator),
+                opener: Opener::new(&keys.responder),
+            },
+        ))
+    }
+}
+
+/// The responder's side of a handshake awaiting confirmation
+pub(crate) struct Responder {
+    transcript: [u8; 32],
+    keys: SessionKeys,
+}
+
+impl Responder {
+    /// Answer an initiator's offer, returning the accept message to send
+    pub(crate) fn accept(identity: &LocalIdentity, offer: &[u8]) -> Result<(Self, Bytes)> {
+        let public_key = MlKemPublicKey::from_bytes(offer)
+            .map_err(|e| anyhow!("Invalid ML-KEM public key: {}", e))?;
+        let (ciphertext, shared) = MlKem768::new()
+            .encapsulate(&public_key)
+            .map_err(|e| anyhow!("Failed to encapsulate session secret: {}", e))?;
+
+        let transcript = transcript(&[offer, ciphertext.as_bytes()]);
+        let proof = identity.prove(&blake3::derive_key(RESPONDER_PROOF_CONTEXT, &transcript))?;
+
+        let mut accept = BytesMut::with_capacity(ciphertext.as_bytes().len() + proof.len());
+        accept.put_slice(ciphertext.as_bytes());
+        accept.put_slice(&proof);
+
+        let transcript = transcript_with_proof(&transcript, &proof);
+        Ok((
+            Self {
+                keys: SessionKeys::derive(shared.as_bytes(), &transcript),
+                transcript,
+            },
+            accept.freeze(),
+        ))
+    }
+
+    /// Verify the initiator's confirmation, establishing the session
+    pub(crate) fn finish(self, confirm: &[u8]) -> Result<Session> {
+        let remote = verify_proof(
+            confirm,
+            &blake3::derive_key(INITIATOR_PROOF_CONTEXT, &self.transcript),
+        )?;
+        Ok(Session {
+            remote,
+            sealer: Sealer::new(&self.keys.responder),
+            opener: Opener::new(&self.keys.initiator),
+        })
+    }
+}
+
+/// Hash the handshake messages exchanged so far
+fn transcript(messages: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key(TRANSCRIPT_CONTEXT);
+    for message in messages {
+        hasher.update(&(message.len() as u64).to_be_bytes());
+        hasher.update(message);
+    }
+    *hasher.finalize().as_bytes()
+}
+
+/// Extend a transcript hash with the responder's proof
+fn transcript_with_proof(transcript: &[u8; 32], proof: &[u8]) -> [u8; 32] {
+    self::transcript(&[transcript, proof])
+}
+
+/// One key per direction, derived from the shared secret and transcript
+struct SessionKeys {
+    initiator: [u8; 32],
+    responder: [u8; 32],
+}
+
+impl SessionKeys {
+    fn derive(shared: &[u8], transcript: &[u8; 32]) -> Self {
+        let mut material = Vec::with_capacity(shared.len() + transcript.len());
+        material.extend_from_slice(shared);
+        material.extend_from_slice(transcript);
+        Self {
+            initiator: blake3::derive_key(INITIATOR_KEY_CONTEXT, &material),
+            responder: blake3::derive_key(RESPONDER_KEY_CONTEXT, &material),
+        }
+    }
+}
+
+/// An established session with a verified remote identity
+pub(crate) struct Session {
+    /// The identity the remote end proved
+    pub(crate) remote: VerifiedIdentity,
+    /// Seals messages to the remote end
+    pub(crate) sealer: Sealer,
+    /// Opens messages from the remote end
+    pub(crate) opener: Opener,
+}
+
+/// Build the nonce for a message counter
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    Nonce::from(nonce)
+}
+
+/// Encrypts and authenticates outgoing messages
+pub(crate) struct Sealer {
+    cipher: ChaCha20Poly1305,
+    next: u64,
+}
+
+impl Sealer {
+    fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+            next: 0,
+        }
+    }
+
+    /// Seal `plaintext`, authenticating `aad` along with it
+    pub(crate) fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> Result<Bytes> {
+        let counter = self.next;
+        self.next = counter
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("Session message counter exhausted"))?;
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce(counter),
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| anyhow!("Failed to seal message"))?;
+
+        let mut sealed = BytesMut::with_capacity(COUNTER_LEN + ciphertext.len());
+        sealed.put_u64(counter);
+        sealed.put_slice(&ciphertext);
+        Ok(sealed.freeze())
+    }
+}
+
+/// Decrypts and authenticates incoming messages
+pub(crate) struct Opener {
+    cipher: ChaCha20Poly1305,
+    /// One more than the highest counter opened
+    next: u64,
+    /// Bit `i` is set if counter `next - 1 - i` was opened
+    seen: u64,
+}
+
+impl Opener {
+    fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+            next: 0,
+            seen: 0,
+        }
+    }
+
+    /// Open a sealed message, checking it was sealed with `aad`
+    ///
+    /// Fails for forged, altered or replayed messages, and for messages
+    /// older than the replay window.
+    pub(crate) fn open(&mut self, aad: &[u8], sealed: &[u8]) -> Result<Bytes> {
+        let Some((counter, ciphertext)) = sealed.split_first_chunk::<COUNTER_LEN>() else {
+            return Err(anyhow!("Truncated sealed message"));
+        };
+        let counter = u64::from_be_bytes(*counter);
+        if counter < self.next {
+            let age = self.next - 1 - counter;
+            if age >= REPLAY_WINDOW || self.seen & (1 << age) != 0 {
+                return Err(anyhow!("Replayed sealed message {}", counter));
+            }
+        }
+        let plaintext = self
+            .cipher
+            .decrypt(
+                &nonce(counter),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| anyhow!("Failed to authenticate sealed message"))?;
+
+        if counter < self.next {
+            self.seen |= 1 << (self.next - 1 - counter);
+        } else {
+            let shift = counter - self.next + 1;
+            self.seen = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.seen << shift
+            };
+            self.seen |= 1;
+            self.next = counter.saturating_add(1);
+        }
+        Ok(Bytes::from(plaintext))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ant_quic::generate_ml_dsa_keypair;
+
+    fn identity() -> LocalIdentity {
+        let (public_key, secret_key) = generate_ml_dsa_keypair().expect("keypair");
+        LocalIdentity::new(public_key, secret_key)
+    }
+
+    fn handshake(initiator: &LocalIdentity, responder: &LocalIdentity) -> (Session, Session) {
+        let (pending, offer) = Initiator::start().expect("offer");
+        let (accepting, accept) = Responder::accept(responder, &offer).expect("accept");
+        let (confirm, initiator_session) = pending.finish(initiator, &accept).expect("finish");
+        let responder_session = accepting.finish(&confirm).expect("confirm");
+        (initiator_session, responder_session)
+    }
+
+    #[test]
+    fn test_handshake_proves_both_identities() {
+        let (alice, bob) = (identity(), identity());
+        let (mut a, mut b) = handshake(&alice, &bob);
+        assert_eq!(a.remote.peer(), bob.peer());
+        assert_eq!(b.remote.peer(), alice.peer());
+
+        let sealed = a.sealer.seal(b"aad", b"hello").expect("seal");
+        assert_ne!(&sealed[COUNTER_LEN..], b"hello");
+        assert_eq!(sealed.len(), 5 + SEAL_OVERHEAD);
+        assert_eq!(b.opener.open(b"aad", &sealed).expect("open"), "hello");
+
+        let sealed = b.sealer.seal(b"", b"reply").expect("seal");
+        assert_eq!(a.opener.open(b"", &sealed).expect("open"), "reply");
+    }
+
+    #[test]
+    fn test_altered_replayed_and_misdirected_messages_fail() {
+        let (alice, bob) = (identity(), identity());
+        let (mut a, mut b) = handshake(&alice, &bob);
+
+        let sealed = a.sealer.seal(b"aad", b"hello").expect("seal");
+        let mut altered = sealed.to_vec();
+        altered[COUNTER_LEN] ^= 1;
+        assert!(b.opener.open(b"aad", &altered).is_err());
+        assert!(b.opener.open(b"other", &sealed).is_err());
+
+        b.opener.open(b"aad", &sealed).expect("open");
+        assert!(b.opener.open(b"aad", &sealed).is_err());
+
+        // A message sealed in one direction does not open in the other
+        let sealed = a.sealer.seal(b"", b"mine").expect("seal");
+        assert!(a.opener.open(b"", &sealed).is_err());
+
+        // Gaps and reordering are fine, but each message opens once
+        let first = a.sealer.seal(b"", b"first").expect("seal");
+        let second = a.sealer.seal(b"", b"second").expect("seal");
+        b.opener.open(b"", &second).expect("skip ahead");
+        b.opener.open(b"", &first).expect("overtaken");
+        assert!(b.opener.open(b"", &first).is_err());
+        assert!(b.opener.open(b"", &second).is_err());
+
+        // Messages older than the replay window are refused
+        let stale = a.sealer.seal(b"", b"stale").expect("seal");
+        for _ in 0..REPLAY_WINDOW {
+            let sealed = a.sealer.seal(b"", b"newer").expect("seal");
+            b.opener.open(b"", &sealed).expect("open");
+        }
+        assert!(b.opener.open(b"", &stale).is_err());
+    }
+
+    #[test]
+    fn test_tampered_handshake_is_rejected() {
+        let (alice, bob, mallory) = (identity(), identity(), identity());
+
+        // Mallory answers the offer with her own proof but Bob's ciphertext
+        let (pending, offer) = Initiator::start().expect("offer");
+        let (_, accept) = Responder::accept(&bob, &offer).expect("accept");
+        let (_, forged) = Responder::accept(&mallory, &offer).expect("accept");
+        let mut spliced = accept[..ML_KEM_768_CIPHERTEXT_SIZE].to_vec();
+        spliced.extend_from_slice(&forged[ML_KEM_768_CIPHERTEXT_SIZE..]);
+        assert!(pending.finish(&alice, &spliced).is_err());
+
+        // A confirmation from another session does not verify
+        let (pending, offer) = Initiator::start().expect("offer");
+        let (accepting, accept) = Responder::accept(&bob, &offer).expect("accept");
+        let (other_pending, other_offer) = Initiator::start().expect("offer");
+        let (_, other_accept) = Responder::accept(&bob, &other_offer).expect("accept");
+        let (stale, _) = other_pending
+            .finish(&alice, &other_accept)
+            .expect("other session");
+        pending.finish(&alice, &accept).expect("finish");
+        assert!(accepting.finish(&stale).is_err());
+    }
+}
@@ -0,0 +1,525 @@
+//! Bounded per-peer outbound queues with backpressure
+//!
+//! Each peer gets a [`PeerSendQueue`] holding messages waiting to be written
+//! to the network. Queue depth is bounded by a high watermark; once it is
+//! reached the peer is reported as congested and new messages are handled
+//! according to the [`DropPolicy`] of their [`StreamType`]. Congestion clears
+//! when the queue drains to the low watermark.
+//!
+//! Messages are dequeued in stream priority order, so membership traffic is
+//! flushed before pubsub and bulk traffic.
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use saorsa_gossip_types::PeerId;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::{broadcast, Notify};
+use tracing::{debug, trace};
+
+use crate::{StreamType, TransportEvent};
+
+/// A message was rejected because the peer's send queue is full
+///
+/// This is backpressure rather than a connection failure: the connection is
+/// fine, and the message may be retried once the queue drains.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Send queue to peer {peer} is full, dropped {stream_type:?} message")]
+pub struct QueueFull {
+    /// The congested peer
+    pub peer: PeerId,
+    /// Stream of the rejected message
+    pub stream_type: StreamType,
+}
+
+/// What to do with a message when a peer's queue is at its high watermark
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Never drop: the sender waits until the queue has room
+    Block,
+    /// Drop the oldest queued message of the same stream type
+    DropOldest,
+    /// Reject the new message
+    DropNewest,
+}
+
+/// Configuration for per-peer outbound queues
+#[derive(Debug, Clone)]
+pub struct SendQueueConfig {
+    /// Queue depth (messages) at which a peer is congested (default: 1,024)
+    pub high_watermark: usize,
+    /// Queue depth (messages) at which congestion clears (default: 256)
+    pub low_watermark: usize,
+    /// Drop policy for membership messages (default: never drop)
+    pub membership_policy: DropPolicy,
+    /// Drop policy for pubsub messages (default: drop oldest, e.g. stale IHAVEs)
+    pub pubsub_policy: DropPolicy,
+    /// Drop policy for bulk messages (default: reject new)
+    pub bulk_policy: DropPolicy,
+    /// Drop policy for relay messages (default: reject new)
+    pub relay_policy: DropPolicy,
+}
+
+impl Default for SendQueueConfig {
+    fn default() -> Self {
+        Self {
+            high_watermark: 1_024,
+            low_watermark: 256,
+            membership_policy: DropPolicy::Block,
+            pubsub_policy: DropPolicy::DropOldest,
+            bulk_policy: DropPolicy::DropNewest,
+            relay_policy: DropPolicy::DropNewest,
+        }
+    }
+}
+
+impl SendQueueConfig {
+    /// Set the high and low watermarks
+    pub fn with_watermarks(mut self, high: usize, low: usize) -> Self {
+        self.high_watermark = high.max(1);
+        self.low_watermark = low.min(self.high_watermark - 1);
+        self
+    }
+
+    /// Set the drop policy for a stream type
+    pub fn with_drop_policy(mut self, stream_type: StreamType, policy: DropPolicy) -> Self {
+        match stream_type {
+            StreamType::Membership => self.membership_policy = policy,
+            StreamType::PubSub => self.pubsub_policy = policy,
+            StreamType::Bulk => self.bulk_policy = policy,
+            StreamType::Relay => self.relay_policy = policy,
+        }
+        self
+    }
+
+    /// Get the drop policy for a stream type
+    pub fn policy(&self, stream_type: StreamType) -> DropPolicy {
+        match stream_type {
+            StreamType::Membership => self.membership_policy,
+            StreamType::PubSub => self.pubsub_policy,
+            StreamType::Bulk => self.bulk_policy,
+            StreamType::Relay => self.relay_policy,
+        }
+    }
+}
+
+/// Queue contents, guarded by a mutex that is never held across an await
+#[derive(Default)]
+struct QueueState {
+    membership: VecDeque<Bytes>,
+    pubsub: VecDeque<Bytes>,
+    bulk: VecDeque<Bytes>,
+    relay: VecDeque<Bytes>,
+    congested: bool,
+    closed: bool,
+    dropped: u64,
+}
+
+impl QueueState {
+    fn queue(&mut self, stream_type: StreamType) -> &mut VecDeque<Bytes> {
+        match stream_type {
+            StreamType::Membership => &mut self.membership,
+            StreamType::PubSub => &mut self.pubsub,
+            StreamType::Bulk => &mut self.bulk,
+            StreamType::Relay => &mut self.relay,
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.membership.len() + self.pubsub.len() + self.bulk.len() + self.relay.len()
+    }
+}
+
+/// Bounded outbound queue for a single peer
+pub struct PeerSendQueue {
+    peer: PeerId,
+    config: SendQueueConfig,
+    state: Mutex<QueueState>,
+    /// Wakes the consumer when a message is queued
+    data_ready: Notify,
+    /// Wakes blocked producers when the queue drains
+    space_ready: Notify,
+    events: broadcast::Sender<TransportEvent>,
+}
+
+impl PeerSendQueue {
+    /// Create a queue for `peer`, reporting congestion on `events`
+    pub fn new(
+        peer: PeerId,
+        config: SendQueueConfig,
+        events: broadcast::Sender<TransportEvent>,
+    ) -> Self {
+        Self {
+            peer,
+            config,
+            state: Mutex::new(QueueState::default()),
+            data_ready: Notify::new(),
+            space_ready: Notify::new(),
+            events,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queue a message, applying the stream's drop policy if the queue is full
+    ///
+    /// With [`DropPolicy::Block`] this waits until the queue has room. Returns
+    /// [`QueueFull`] if the message was rejected, or another error if the
+    /// queue has been closed.
+    pub async fn push(&self, stream_type: StreamType, data: Bytes) -> Result<()> {
+        loop {
+            let space = self.space_ready.notified();
+            tokio::pin!(space);
+            space.as_mut().enable();
+
+            {
+                let mut state = self.lock();
+                if state.closed {
+                    return Err(anyhow!("Send queue to peer {} is closed", self.peer));
+                }
+
+                if state.depth() < self.config.high_watermark {
+                    state.queue(stream_type).push_back(data);
+                    self.mark_congestion(&mut state);
+                    drop(state);
+                    self.data_ready.notify_one();
+                    return Ok(());
+                }
+
+                match self.config.policy(stream_type) {
+                    DropPolicy::Block => {
+                        trace!(
+                            "Send queue to {} full, waiting to queue {:?} message",
+                            self.peer,
+                            stream_type
+                        );
+                    }
+                    DropPolicy::DropOldest => {
+                        state.dropped += 1;
+                        if state.queue(stream_type).pop_front().is_none() {
+                            return Err(self.full(stream_type));
+                        }
+                        debug!(
+                            "Send queue to {} full, dropped oldest {:?} message",
+                            self.peer, stream_type
+                        );
+                        state.queue(stream_type).push_back(data);
+                        drop(state);
+                        self.data_ready.notify_one();
+                        return Ok(());
+                    }
+                    DropPolicy::DropNewest => {
+                        state.dropped += 1;
+                        return Err(self.full(stream_type));
+                    }
+                }
+            }
+
+            space.await;
+        }
+    }
+
+    fn full(&self, stream_type: StreamType) -> anyhow::Error {
+        QueueFull {
+            peer: self.peer,
+            stream_type,
+        }
+        .into()
+    }
+
+    /// Take the next message, highest priority stream first
+    ///
+    /// Waits until a message is available. Returns `None` once the queue has
+    /// been closed and drained.
+    pub async fn pop(&self) -> Option<(StreamType, Bytes)> {
+        loop {
+            let ready = self.data_ready.notified();
+            tokio::pin!(ready);
+            ready.as_mut().enable();
+
+            {
+                let mut state = self.lock();
+                for stream_type in StreamType::ALL {
+                    if let Some(data) = state.queue(stream_type).pop_front() {
+                        self.mark_congestion(&mut state);
+                        drop(state);
+                        self.space_ready.notify_waiters();
+                        return Some((stream_type, data));
+                    }
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+
+            ready.await;
+        }
+    }
+
+    /// Update the congestion flag and emit an event on transitions
+    fn mark_congestion(&self, state: &mut QueueState) {
+        let depth = state.depth();
+        if !state.congested && depth >= self.config.high_watermark {
+            state.congested = true;
+            debug!("Peer {} congested ({} queued)", self.peer, depth);
+            let _ = self.events.send(TransportEvent::Congested(self.peer));
+        } else if state.congested && depth <= self.config.low_watermark {
+            state.congested = false;
+            debug!("Peer {} no longer congested ({} queued)", self.peer, depth);
+            let _ = self.events.send(TransportEvent::Decongested(self.peer));
+        }
+    }
+
+    /// Close the queue, discarding pending messages and failing blocked senders
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        let discarded = state.depth();
+        state.membership.clear();
+        state.pubsub.clear();
+        state.bulk.clear();
+        state.dropped += discarded as u64;
+        drop(state);
+        self.data_ready.notify_one();
+        self.space_ready.notify_waiters();
+    }
+
+    /// Stop accepting messages but keep those already queued
+    ///
+    /// The consumer still receives every queued message; [`pop`](Self::pop)
+    /// returns `None` once they are all taken.
+    pub fn drain(&self) {
+        self.lock().closed = true;
+        self.data_ready.notify_one();
+        self.space_ready.notify_waiters();
+    }
+
+    /// Get the peer this queue sends to
+    pub fn peer(&self) -> PeerId {
+        self.peer
+    }
+
+    /// Get the number of queued messages
+    pub fn depth(&self) -> usize {
+        self.lock().depth()
+    }
+
+    /// Check whether the queue is above its high watermark
+    pub fn is_congested(&self) -> bool {
+        self.lock().congested
+    }
+
+    /// Get the number of messages dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    /// Check whether the queue has been closed
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn test_queue(high: usize, low: usize) -> (PeerSendQueue, broadcast::Receiver<TransportEvent>) {
+        let (events, rx) = broadcast::channel(16);
+        let config = SendQueueConfig::default().with_watermarks(high, low);
+        (
+            PeerSendQueue::new(PeerId::new([1u8; 32]), config, events),
+            rx,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_pop_in_priority_order() {
+        let (queue, _events) = test_queue(16, 4);
+
+        queue
+            .push(StreamType::Bulk, Bytes::from("bulk"))
+            .await
+            .expect("push");
+        queue
+            .push(StreamType::PubSub, Bytes::from("pubsub"))
+            .await
+            .expect("push");
+        queue
+            .push(StreamType::Relay, Bytes::from("relay"))
+            .await
+            .expect("push");
+        queue
+            .push(StreamType::Membership, Bytes::from("ping"))
+            .await
+            .expect("push");
+
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            order.push(queue.pop().await.expect("message").0);
+        }
+        assert_eq!(order, StreamType::ALL.to_vec());
+        assert_eq!(queue.depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_pubsub_when_full() {
+        let (queue, _events) = test_queue(2, 0);
+
+        for i in 0..3u8 {
+            queue
+                .push(StreamType::PubSub, Bytes::from(vec![i]))
+                .await
+                .expect("drop oldest keeps the new message");
+        }
+
+        assert_eq!(queue.depth(), 2);
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.pop().await.map(|(_, d)| d[0]), Some(1));
+        assert_eq!(queue.pop().await.map(|(_, d)| d[0]), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest_bulk_when_full() {
+        let (queue, _events) = test_queue(1, 0);
+
+        queue
+            .push(StreamType::Bulk, Bytes::from("first"))
+            .await
+            .expect("push");
+        assert!(queue
+            .push(StreamType::Bulk, Bytes::from("second"))
+            .await
+            .is_err());
+
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(
+            queue.pop().await.map(|(_, d)| d),
+            Some(Bytes::from("first"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_membership_blocks_instead_of_dropping() {
+        let (queue, _events) = test_queue(1, 0);
+        let queue = Arc::new(queue);
+
+        queue
+            .push(StreamType::Membership, Bytes::from("first"))
+            .await
+            .expect("push");
+
+        let pusher = {
+            let queue = Arc::clone(&queue);
+            tokio::spawn(async move {
+                queue
+                    .push(StreamType::Membership, Bytes::from("second"))
+                    .await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(
+            !pusher.is_finished(),
+            "membership push should wait for room"
+        );
+
+        assert_eq!(
+            queue.pop().await.map(|(_, d)| d),
+            Some(Bytes::from("first"))
+        );
+        pusher.await.expect("join").expect("push after drain");
+        assert_eq!(
+            queue.pop().await.map(|(_, d)| d),
+            Some(Bytes::from("second"))
+        );
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_congestion_events_use_watermarks() {
+        let (queue, mut events) = test_queue(3, 1);
+        let peer = queue.peer();
+
+        for _ in 0..3 {
+            queue
+                .push(StreamType::PubSub, Bytes::new())
+                .await
+                .expect("push");
+        }
+        assert!(queue.is_congested());
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(TransportEvent::Congested(peer))
+        );
+
+        // Still above the low watermark after one pop
+        queue.pop().await;
+        assert!(queue.is_congested());
+        assert!(events.try_recv().is_err());
+
+        queue.pop().await;
+        assert!(!queue.is_congested());
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(TransportEvent::Decongested(peer))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_fails_blocked_senders() {
+        let (queue, _events) = test_queue(1, 0);
+        let queue = Arc::new(queue);
+
+        queue
+            .push(StreamType::Membership, Bytes::new())
+            .await
+            .expect("push");
+        let pusher = {
+            let queue = Arc::clone(&queue);
+            tokio::spawn(async move { queue.push(StreamType::Membership, Bytes::new()).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        queue.close();
+
+        assert!(pusher.await.expect("join").is_err());
+        assert!(queue.pop().await.is_none());
+        assert!(queue.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_drain_keeps_queued_messages() {
+        let (queue, _events) = test_queue(16, 4);
+
+        queue
+            .push(StreamType::PubSub, Bytes::from("ihave"))
+            .await
+            .expect("push");
+        queue
+            .push(StreamType::Membership, Bytes::from("disconnect"))
+            .await
+            .expect("push");
+
+        queue.drain();
+
+        assert!(queue
+            .push(StreamType::Membership, Bytes::from("late"))
+            .await
+            .is_err());
+        assert_eq!(
+            queue.pop().await,
+            Some((StreamType::Membership, Bytes::from("disconnect")))
+        );
+        assert_eq!(
+            queue.pop().await,
+            Some((StreamType::PubSub, Bytes::from("ihave")))
+        );
+        assert!(queue.pop().await.is_none());
+        assert_eq!(queue.dropped(), 0);
+    }
+}
@@ -0,0 +1,258 @@
+//! Transport traffic statistics
+//!
+//! Counts messages, bytes and errors per peer and per [`StreamType`].
+//! Counters for a peer are dropped when it disconnects; the aggregate
+//! totals cover the whole lifetime of the transport.
+
+use saorsa_gossip_types::PeerId;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::ops::AddAssign;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::StreamType;
+
+/// Traffic counters for one stream type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// Messages sent
+    pub messages_sent: u64,
+    /// Messages received
+    pub messages_received: u64,
+    /// Payload bytes sent, before compression
+    pub bytes_sent: u64,
+    /// Payload bytes received, after decompression
+    pub bytes_received: u64,
+    /// Failed sends and malformed inbound messages
+    pub errors: u64,
+}
+
+impl AddAssign for StreamStats {
+    fn add_assign(&mut self, other: Self) {
+        self.messages_sent += other.messages_sent;
+        self.messages_received += other.messages_received;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+        self.errors += other.errors;
+    }
+}
+
+/// Counters for every stream type, indexed by stream tag
+type StreamTable = [StreamStats; 4];
+
+fn total(table: &StreamTable) -> StreamStats {
+    let mut total = StreamStats::default();
+    for stats in table {
+        total += *stats;
+    }
+    total
+}
+
+/// Statistics for a single peer
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerStats {
+    /// The peer
+    pub peer: PeerId,
+    /// The peer's address, if connected
+    pub addr: Option<SocketAddr>,
+    /// Current round-trip time estimate of the connection
+    pub rtt: Option<Duration>,
+    /// Messages waiting in the peer's send queue
+    pub queue_depth: usize,
+    streams: StreamTable,
+}
+
+impl PeerStats {
+    /// Counters for one stream type
+    pub fn stream(&self, stream_type: StreamType) -> StreamStats {
+        self.streams[usize::from(stream_type.to_u8())]
+    }
+
+    /// Counters summed over all stream types
+    pub fn totals(&self) -> StreamStats {
+        total(&self.streams)
+    }
+}
+
+/// Snapshot of a transport's statistics
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransportStats {
+    /// Per-peer statistics for known peers
+    pub peers: Vec<PeerStats>,
+    streams: StreamTable,
+}
+
+impl TransportStats {
+    /// Statistics for one peer
+    pub fn peer(&self, peer: &PeerId) -> Option<&PeerStats> {
+        self.peers.iter().find(|stats| stats.peer == *peer)
+    }
+
+    /// Aggregate counters for one stream type
+    pub fn stream(&self, stream_type: StreamType) -> StreamStats {
+        self.streams[usize::from(stream_type.to_u8())]
+    }
+
+    /// Aggregate counters summed over all stream types
+    pub fn totals(&self) -> StreamStats {
+        total(&self.streams)
+    }
+}
+
+/// Connection details merged into a [`TrafficStats`] snapshot
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PeerDetails {
+    pub(crate) addr: Option<SocketAddr>,
+    pub(crate) rtt: Option<Duration>,
+    pub(crate) queue_depth: usize,
+}
+
+#[derive(Default)]
+struct TrafficState {
+    peers: HashMap<PeerId, StreamTable>,
+    totals: StreamTable,
+}
+
+/// Traffic counters shared by the transport's tasks
+#[derive(Default)]
+pub(crate) struct TrafficStats {
+    state: Mutex<TrafficState>,
+}
+
+impl TrafficStats {
+    fn update(&self, peer: PeerId, stream_type: StreamType, f: impl Fn(&mut StreamStats)) {
+        let index = usize::from(stream_type.to_u8());
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut state.peers.entry(peer).or_default()[index]);
+        f(&mut state.totals[index]);
+    }
+
+    /// Record a message sent to `peer`
+    pub(crate) fn record_sent(&self, peer: PeerId, stream_type: StreamType, len: usize) {
+        self.update(peer, stream_type, |stats| {
+            stats.messages_sent += 1;
+            stats.bytes_sent += len as u64;
+        });
+    }
+
+    /// Record a message received from `peer`
+    pub(crate) fn record_received(&self, peer: PeerId, stream_type: StreamType, len: usize) {
+        self.update(peer, stream_type, |stats| {
+            stats.messages_received += 1;
+            stats.bytes_received += len as u64;
+        });
+    }
+
+    /// Record a failed send to, or malformed message from, `peer`
+    pub(crate) fn record_error(&self, peer: PeerId, stream_type: StreamType) {
+        self.update(peer, stream_type, |stats| stats.errors += 1);
+    }
+
+    /// Drop the per-peer counters of a peer
+    pub(crate) fn forget(&self, peer: &PeerId) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .peers
+            .remove(peer);
+    }
+
+    /// Build a snapshot, merging in connection details for each peer
+    ///
+    /// Peers with details but no traffic yet are included with zero counters.
+    pub(crate) fn snapshot(&self, details: HashMap<PeerId, PeerDetails>) -> TransportStats {
+        let (mut tables, streams) = {
+            let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            (state.peers.clone(), state.totals)
+        };
+
+        let mut peers: Vec<PeerStats> = details
+            .into_iter()
+            .map(|(peer, details)| PeerStats {
+                peer,
+                addr: details.addr,
+                rtt: details.rtt,
+                queue_depth: details.queue_depth,
+                streams: tables.remove(&peer).unwrap_or_default(),
+            })
+            .collect();
+        peers.extend(tables.into_iter().map(|(peer, streams)| PeerStats {
+            peer,
+            addr: None,
+            rtt: None,
+            queue_depth: 0,
+            streams,
+        }));
+        peers.sort_by(|a, b| a.peer.as_bytes().cmp(b.peer.as_bytes()));
+
+        TransportStats { peers, streams }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_are_kept_per_peer_and_stream() {
+        let stats = TrafficStats::default();
+        let a = PeerId::new([1u8; 32]);
+        let b = PeerId::new([2u8; 32]);
+
+        stats.record_sent(a, StreamType::Membership, 10);
+        stats.record_sent(a, StreamType::PubSub, 20);
+        stats.record_received(b, StreamType::PubSub, 30);
+        stats.record_error(b, StreamType::Bulk);
+
+        let snapshot = stats.snapshot(HashMap::new());
+        let peer_a = snapshot.peer(&a).expect("peer a");
+        assert_eq!(peer_a.stream(StreamType::Membership).bytes_sent, 10);
+        assert_eq!(peer_a.totals().messages_sent, 2);
+        assert_eq!(peer_a.totals().bytes_sent, 30);
+
+        let peer_b = snapshot.peer(&b).expect("peer b");
+        assert_eq!(peer_b.stream(StreamType::PubSub).bytes_received, 30);
+        assert_eq!(peer_b.stream(StreamType::Bulk).errors, 1);
+
+        assert_eq!(snapshot.stream(StreamType::PubSub).messages_sent, 1);
+        assert_eq!(snapshot.stream(StreamType::PubSub).messages_received, 1);
+        assert_eq!(snapshot.totals().errors, 1);
+    }
+
+    #[test]
+    fn test_totals_survive_forgotten_peers() {
+        let stats = TrafficStats::default();
+        let peer = PeerId::new([1u8; 32]);
+
+        stats.record_sent(peer, StreamType::Bulk, 100);
+        stats.forget(&peer);
+
+        let snapshot = stats.snapshot(HashMap::new());
+        assert!(snapshot.peers.is_empty());
+        assert_eq!(snapshot.totals().bytes_sent, 100);
+    }
+
+    #[test]
+    fn test_snapshot_merges_connection_details() {
+        let stats = TrafficStats::default();
+        let peer = PeerId::new([1u8; 32]);
+        let addr: SocketAddr = "127.0.0.1:7000".parse().expect("addr");
+
+        let details = HashMap::from([(
+            peer,
+            PeerDetails {
+                addr: Some(addr),
+                rtt: Some(Duration::from_millis(12)),
+                queue_depth: 3,
+            },
+        )]);
+        let snapshot = stats.snapshot(details);
+
+        let peer_stats = snapshot.peer(&peer).expect("peer");
+        assert_eq!(peer_stats.addr, Some(addr));
+        assert_eq!(peer_stats.rtt, Some(Duration::from_millis(12)));
+        assert_eq!(peer_stats.queue_depth, 3);
+        assert_eq!(peer_stats.totals(), StreamStats::default());
+    }
+}
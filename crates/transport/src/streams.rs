@@ -0,0 +1,938 @@
+//! Per-stream-type QUIC streams for Saorsa Gossip
+//!
+//! Each [`StreamType`] is carried on its own long-lived bidirectional QUIC
+//! stream per peer (see ADR-008). Every message on a stream is wrapped in a
+//! [`Frame`]; the first frame on a stream fixes its stream type.
+//!
+//! Inbound messages are routed into an independent bounded queue per stream
+//! type, so a backlog of bulk traffic never delays membership traffic.
+//! Every wire frame, including fragments of streamed transfers, is charged
+//! to the sender's inbound rate limit before it is reassembled or
+//! decompressed, and dropped if over the limit.
+//!
+//! The opener of a stream first sends a hello frame advertising its
+//! compression algorithms, and the acceptor replies with its own on the
+//! return half of the stream. Each side then compresses towards the other
+//! with the best algorithm both support.
+//!
+//! Messages larger than the fragment size are split into fragments written
+//! back to back, and reassembled per stream by the receiver. Streamed
+//! transfers get a dedicated stream so they never hold up other messages.
+//!
+//! When a connection is attached, each side proves its identity on a
+//! dedicated stream (see [`crate::handshake`]). Frames on other streams are
+//! held back until that proof is verified, and are then attributed to the
+//! verified peer ID rather than the one reported for the connection.
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use saorsa_gossip_types::PeerId as GossipPeerId;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{mpsc, Mutex, Notify, RwLock};
+use tracing::{debug, trace, warn};
+
+use ant_quic::{HighLevelConnection, HighLevelRecvStream, HighLevelSendStream};
+
+use crate::compression::{decompress, CompressionAlgorithm, CompressionConfig, CompressionStats};
+use crate::fragment::{
+    split, FragmentConfig, FragmentHeader, FragmentKind, IncomingTransfer, Reassembled,
+    Reassembler, ReassemblyBudget, FRAGMENT_HEADER_LEN,
+};
+use crate::framing::{Frame, FrameCodec, FrameFlags};
+use crate::handshake::{
+    session_binding, verify_proof, ConnectionIdentities, IdentityError, LocalIdentity,
+    VerifiedIdentity, BINDING_LEN, HANDSHAKE_TIMEOUT,
+};
+use crate::rate_limit::InboundRateLimiter;
+use crate::stats::TrafficStats;
+use crate::StreamType;
+
+/// A message received from a peer on a specific stream
+pub(crate) type InboundMessage = (GossipPeerId, StreamType, Bytes);
+
+/// Outbound stream slot, opened lazily on first send
+type SendSlot = Arc<Mutex<Option<HighLevelSendStream>>>;
+
+/// Senders for the per-stream-type inbound queues
+#[derive(Clone)]
+pub(crate) struct InboundSenders {
+    membership: mpsc::Sender<InboundMessage>,
+    pubsub: mpsc::Sender<InboundMessage>,
+    bulk: mpsc::Sender<InboundMessage>,
+    relay: mpsc::Sender<InboundMessage>,
+    /// Declared last so it is dropped after the senders
+    ready: Arc<Ready>,
+}
+
+/// Wakes waiting [`InboundQueues::recv_any`] calls, once more when dropped
+struct Ready(Arc<Notify>);
+
+impl Drop for Ready {
+    fn drop(&mut self) {
+        self.0.notify_waiters();
+    }
+}
+
+impl InboundSenders {
+    fn get(&self, stream_type: StreamType) -> &mpsc::Sender<InboundMessage> {
+        match stream_type {
+            StreamType::Membership => &self.membership,
+            StreamType::PubSub => &self.pubsub,
+            StreamType::Bulk => &self.bulk,
+            StreamType::Relay => &self.relay,
+        }
+    }
+
+    /// Queue a message on its stream type's queue, waiting for room
+    pub(crate) async fn send(
+        &self,
+        msg: InboundMessage,
+    ) -> Result<(), mpsc::error::SendError<InboundMessage>> {
+        self.get(msg.1).send(msg).await?;
+        self.ready.0.notify_waiters();
+        Ok(())
+    }
+
+    /// Queue a message on its stream type's queue if it has room
+    pub(crate) fn try_send(
+        &self,
+        msg: InboundMessage,
+    ) -> Result<(), mpsc::error::TrySendError<InboundMessage>> {
+        self.get(msg.1).try_send(msg)?;
+        self.ready.0.notify_waiters();
+        Ok(())
+    }
+}
+
+/// Independent bounded receive queues, one per stream type
+///
+/// Each queue is locked only by the call reading it, so
+/// [`Self::recv_any`] never holds up a [`Self::recv`] on a single stream.
+pub(crate) struct InboundQueues {
+    membership: Mutex<mpsc::Receiver<InboundMessage>>,
+    pubsub: Mutex<mpsc::Receiver<InboundMessage>>,
+    bulk: Mutex<mpsc::Receiver<InboundMessage>>,
+    relay: Mutex<mpsc::Receiver<InboundMessage>>,
+    ready: Arc<Notify>,
+}
+
+impl InboundQueues {
+    /// Create the queues, each bounded to `capacity` messages
+    pub(crate) fn new(capacity: usize) -> (InboundSenders, Self) {
+        let (membership_tx, membership_rx) = mpsc::channel(capacity);
+        let (pubsub_tx, pubsub_rx) = mpsc::channel(capacity);
+        let (bulk_tx, bulk_rx) = mpsc::channel(capacity);
+        let (relay_tx, relay_rx) = mpsc::channel(capacity);
+        let ready = Arc::new(Notify::new());
+
+        let senders = InboundSenders {
+            membership: membership_tx,
+            pubsub: pubsub_tx,
+            bulk: bulk_tx,
+            relay: relay_tx,
+            ready: Arc::new(Ready(Arc::clone(&ready))),
+        };
+
+        let queues = Self {
+            membership: Mutex::new(membership_rx),
+            pubsub: Mutex::new(pubsub_rx),
+            bulk: Mutex::new(bulk_rx),
+            relay: Mutex::new(relay_rx),
+            ready,
+        };
+
+        (senders, queues)
+    }
+
+    fn get(&self, stream_type: StreamType) -> &Mutex<mpsc::Receiver<InboundMessage>> {
+        match stream_type {
+            StreamType::Membership => &self.membership,
+            StreamType::PubSub => &self.pubsub,
+            StreamType::Bulk => &self.bulk,
+            StreamType::Relay => &self.relay,
+        }
+    }
+
+    /// Receive the next message on any stream, preferring higher priority streams
+    ///
+    /// Queues are only locked while checked, never while waiting; a queue
+    /// being read by [`Self::recv`] is skipped.
+    pub(crate) async fn recv_any(&self) -> Option<InboundMessage> {
+        loop {
+            // Register before checking so a message queued meanwhile wakes us
+            let notified = self.ready.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let mut closed = 0;
+            for stream_type in StreamType::ALL {
+                let Ok(mut queue) = self.get(stream_type).try_lock() else {
+                    continue;
+                };
+                match queue.try_recv() {
+                    Ok(msg) => return Some(msg),
+                    Err(TryRecvError::Disconnected) => closed += 1,
+                    Err(TryRecvError::Empty) => {}
+                }
+            }
+            if closed == StreamType::ALL.len() {
+                return None;
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Receive the next message on a single stream type
+    pub(crate) async fn recv(&self, stream_type: StreamType) -> Option<InboundMessage> {
+        let msg = self.get(stream_type).lock().await.recv().await;
+        // The queue may hold more than this call took, and recv_any skipped
+        // it while locked
+        self.ready.notify_waiters();
+        msg
+    }
+}
+
+/// Long-lived per-peer streams, one per stream type
+#[derive(Clone)]
+pub(crate) struct PeerStreams {
+    /// Outbound streams keyed by peer and stream type
+    outbound: Arc<RwLock<HashMap<(GossipPeerId, StreamType), SendSlot>>>,
+    /// Stable IDs of connections with a running stream acceptor
+    attached: Arc<RwLock<HashSet<usize>>>,
+    /// Local identity proven to every connection
+    identity: Arc<LocalIdentity>,
+    /// Verified identity of every attached connection
+    identities: Arc<ConnectionIdentities>,
+    /// Inbound queue senders
+    inbound: InboundSenders,
+    /// Per-peer inbound rate limits
+    limiter: InboundRateLimiter,
+    /// Frame codec enforcing the maximum message size
+    codec: FrameCodec,
+    /// Local compression settings
+    compression: Arc<CompressionConfig>,
+    /// Compression algorithm negotiated with each peer
+    negotiated: Arc<RwLock<HashMap<GossipPeerId, CompressionAlgorithm>>>,
+    /// Outbound compression counters
+    stats: Arc<CompressionStats>,
+    /// Per-peer traffic counters
+    traffic: Arc<TrafficStats>,
+    /// Fragmentation and reassembly settings
+    fragments: Arc<FragmentConfig>,
+    /// Memory shared by all in-progress reassemblies
+    budget: ReassemblyBudget,
+    /// Incoming streamed transfers
+    transfers: mpsc::Sender<IncomingTransfer>,
+    /// ID for the next fragmented message or transfer
+    next_message_id: Arc<AtomicU64>,
+}
+
+impl PeerStreams {
+    /// Create a new stream table delivering into `inbound`
+    pub(crate) fn new(
+        identity: LocalIdentity,
+        inbound: InboundSenders,
+        limiter: InboundRateLimiter,
+        codec: FrameCodec,
+        compression: CompressionConfig,
+        fragments: FragmentConfig,
+        transfers: mpsc::Sender<IncomingTransfer>,
+    ) -> Self {
+        Self {
+            outbound: Arc::new(RwLock::new(HashMap::new())),
+            attached: Arc::new(RwLock::new(HashSet::new())),
+            identity: Arc::new(identity),
+            identities: Arc::new(ConnectionIdentities::default()),
+            inbound,
+            limiter,
+            codec,
+            compression: Arc::new(compression),
+            negotiated: Arc::new(RwLock::new(HashMap::new())),
+            stats: Arc::new(CompressionStats::default()),
+            traffic: Arc::new(TrafficStats::default()),
+            budget: ReassemblyBudget::new(fragments.max_reassembly_bytes),
+            fragments: Arc::new(fragments),
+            transfers,
+            next_message_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Get the outbound compression counters
+    pub(crate) fn compression_stats(&self) -> &CompressionStats {
+        &self.stats
+    }
+
+    /// Get the per-peer traffic counters
+    pub(crate) fn traffic(&self) -> &Arc<TrafficStats> {
+        &self.traffic
+    }
+
+    /// Get the compression algorithm negotiated with a peer
+    pub(crate) async fn compression_for(&self, peer: &GossipPeerId) -> CompressionAlgorithm {
+        self.negotiated
+            .read()
+            .await
+            .get(peer)
+            .copied()
+            .unwrap_or(CompressionAlgorithm::None)
+    }
+
+    /// Record the algorithms a peer advertised in its hello
+    async fn on_hello(&self, peer: GossipPeerId, hello: &[u8]) {
+        let algorithm = self.compression.negotiate(hello);
+        debug!("Negotiated {:?} compression with peer {}", algorithm, peer);
+        self.negotiated.write().await.insert(peer, algorithm);
+    }
+
+    /// Get the verified identity of a peer
+    pub(crate) fn identity(&self, peer: &GossipPeerId) -> Option<VerifiedIdentity> {
+        self.identities.get(peer)
+    }
+
+    /// Wait for the remote side of an attached connection to prove its identity
+    pub(crate) async fn identify(
+        &self,
+        conn: &HighLevelConnection,
+    ) -> Result<VerifiedIdentity, IdentityError> {
+        self.identities
+            .wait(conn.stable_id(), HANDSHAKE_TIMEOUT)
+            .await
+    }
+
+    /// Prove our identity to a connection and accept the streams it opens
+    ///
+    /// Idempotent per connection: attaching the same connection twice is a no-op.
+    pub(crate) async fn attach(&self, conn: HighLevelConnection) {
+        let connection = conn.stable_id();
+        if !self.attached.write().await.insert(connection) {
+            return;
+        }
+
+        let binding = match session_binding(&conn) {
+            Ok(binding) => binding,
+            Err(e) => {
+                warn!("Cannot verify identity on connection {}: {}", connection, e);
+                self.identities.resolve(connection, Err(e));
+                self.attached.write().await.remove(&connection);
+                return;
+            }
+        };
+
+        let streams = self.clone();
+        let prover = conn.clone();
+        tokio::spawn(async move {
+            if let Err(e) = streams.send_identity(&prover, &binding).await {
+                debug!(
+                    "Failed to prove identity on connection {}: {}",
+                    connection, e
+                );
+            }
+        });
+
+        let streams = self.clone();
+        tokio::spawn(async move {
+            debug!("Accepting streams on connection {}", connection);
+            loop {
+                match conn.accept_bi().await {
+                    Ok((send, recv)) => {
+                        let streams = streams.clone();
+                        tokio::spawn(async move {
+                            streams.read_stream(connection, binding, send, recv).await
+                        });
+                    }
+                    Err(e) => {
+                        debug!(
+                            "Stopped accepting streams on connection {}: {}",
+                            connection, e
+                        );
+                        break;
+                    }
+                }
+            }
+            streams.attached.write().await.remove(&connection);
+            streams.identities.forget_connection(connection);
+        });
+    }
+
+    /// Send our identity proof on a dedicated stream
+    async fn send_identity(&self, conn: &HighLevelConnection, binding: &[u8]) -> Result<()> {
+        let proof = self.identity.prove(binding)?;
+        let (mut send, _recv) = conn
+            .open_bi()
+            .await
+            .map_err(|e| anyhow!("Failed to open identity stream: {}", e))?;
+        let frame = Frame::new(StreamType::Membership, proof).with_flags(FrameFlags::IDENTITY);
+        self.codec
+            .write_frame(&mut send, &frame)
+            .await
+            .map_err(|e| anyhow!("Failed to write identity proof: {}", e))?;
+        send.finish()
+            .map_err(|e| anyhow!("Failed to finish identity stream: {}", e))?;
+        Ok(())
+    }
+
+    /// Handle a stream accepted on a connection
+    ///
+    /// An identity proof is verified and recorded for the connection. Any
+    /// other stream waits for the connection's identity before its frames
+    /// are read as messages from the verified peer.
+    async fn read_stream(
+        &self,
+        connection: usize,
+        binding: [u8; BINDING_LEN],
+        send: HighLevelSendStream,
+        mut recv: HighLevelRecvStream,
+    ) {
+        // The sender is unverified until the identity stream resolves, and
+        // every stream opens with a small identity proof or hello
+        let first = match FrameCodec::handshake().read_frame(&mut recv).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return,
+            Err(e) => {
+                debug!("Failed to read stream on connection {}: {}", connection, e);
+                return;
+            }
+        };
+
+        if first.flags.contains(FrameFlags::IDENTITY) {
+            let result = verify_proof(&first.payload, &binding);
+            match &result {
+                Ok(identity) => debug!(
+                    "Verified identity of peer {} on connection {}",
+                    identity.peer(),
+                    connection
+                ),
+                Err(e) => warn!(
+                    "Rejected identity proof on connection {}: {}",
+                    connection, e
+                ),
+            }
+            self.identities.resolve(connection, result);
+            return;
+        }
+
+        match self.identities.wait(connection, HANDSHAKE_TIMEOUT).await {
+            Ok(identity) => self.read_frames(identity.peer(), first, send, recv).await,
+            Err(e) => debug!(
+                "Dropped stream on unverified connection {}: {}",
+                connection, e
+            ),
+        }
+    }
+
+    /// Read frames from an accepted stream until it closes
+    ///
+    /// A hello from the opener is answered with ours on the return half. If a
+    /// fragmented message stalls past its deadline the stream is dropped.
+    async fn read_frames(
+        &self,
+        peer: GossipPeerId,
+        first: Frame,
+        mut send: HighLevelSendStream,
+        mut recv: HighLevelRecvStream,
+    ) {
+        let mut stream_type = None;
+        let mut replied = false;
+        let mut reassembler: Option<Reassembler> = None;
+        let mut pending = Some(first);
+
+        loop {
+            let deadline = reassembler.as_ref().and_then(Reassembler::deadline);
+            let read = match (pending.take(), deadline) {
+                (Some(frame), _) => Ok(Some(frame)),
+                (None, Some(deadline)) => {
+                    match tokio::time::timeout_at(deadline, self.codec.read_frame(&mut recv)).await
+                    {
+                        Ok(read) => read,
+                        Err(_) => {
+                            warn!(
+                                "Timed out reassembling {:?} message from peer {}, closing stream",
+                                stream_type, peer
+                            );
+                            break;
+                        }
+                    }
+                }
+                (None, None) => self.codec.read_frame(&mut recv).await,
+            };
+
+            let frame = match read {
+                Ok(Some(frame)) => frame,
+                Ok(None) => {
+                    debug!("Peer {} finished {:?} stream", peer, stream_type);
+                    break;
+                }
+                Err(e) if e.is_recoverable() => {
+                    debug!("Skipped frame from peer {}: {}", peer, e);
+                    if let Some(stream_type) = stream_type {
+                        self.traffic.record_error(peer, stream_type);
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Error reading stream from peer {}: {}", peer, e);
+                    break;
+                }
+            };
+
+            // The first frame fixes the stream type; later frames must agree
+            let expected = *stream_type.get_or_insert(frame.stream_type);
+            if frame.stream_type != expected {
+                warn!(
+                    "Peer {} sent {:?} frame on {:?} stream, closing stream",
+                    peer, frame.stream_type, expected
+                );
+                break;
+            }
+
+            if frame.flags.contains(FrameFlags::IDENTITY) {
+                debug!(
+                    "Ignored identity proof from peer {} on a message stream",
+                    peer
+                );
+                continue;
+            }
+
+            // QUIC carries close reasons in the connection close code
+            if frame.flags.contains(FrameFlags::CLOSE) {
+                debug!("Ignored close frame from peer {} on a QUIC stream", peer);
+                continue;
+            }
+
+            if frame.flags.contains(FrameFlags::HELLO) {
+                self.on_hello(peer, &frame.payload).await;
+                if !replied {
+                    replied = true;
+                    let hello = Frame::new(expected, self.compression.hello())
+                        .with_flags(FrameFlags::HELLO);
+                    if let Err(e) = self.codec.write_frame(&mut send, &hello).await {
+                        debug!("Failed to answer hello from peer {}: {}", peer, e);
+                    }
+                }
+                continue;
+            }
+
+            // Charge the wire frame before spending memory or CPU on it
+            if !self.limiter.check(peer, expected, frame.payload.len()) {
+                trace!("Dropped rate limited {:?} frame from {}", expected, peer);
+                continue;
+            }
+
+            let reassembler = reassembler.get_or_insert_with(|| {
+                Reassembler::new(
+                    peer,
+                    expected,
+                    Arc::clone(&self.fragments),
+                    self.budget.clone(),
+                )
+            });
+            let (flags, payload) = if frame.flags.contains(FrameFlags::FRAGMENT) {
+                match reassembler.push(frame.flags, frame.payload).await {
+                    Ok(Reassembled::Pending) => continue,
+                    Ok(Reassembled::Message(flags, payload)) => (flags, payload),
+                    Ok(Reassembled::Transfer(transfer)) => {
+                        debug!("Receiving {} byte transfer from {}", transfer.len, peer);
+                        if self.transfers.try_send(transfer).is_err() {
+                            warn!("Dropped transfer from peer {}: queue full", peer);
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        debug!("Dropped fragmented message from peer {}: {}", peer, e);
+                        self.traffic.record_error(peer, expected);
+                        continue;
+                    }
+                }
+            } else {
+                if reassembler.in_progress() {
+                    debug!("Peer {} abandoned a fragmented message", peer);
+                    reassembler.abort();
+                }
+                (frame.flags, frame.payload)
+            };
+
+            let Some(payload) = self.unwrap_payload(peer, flags, payload) else {
+                self.traffic.record_error(peer, expected);
+                continue;
+            };
+
+            self.traffic
+                .record_received(peer, frame.stream_type, payload.len());
+            trace!(
+                "Received {} bytes ({:?}) from {}",
+                payload.len(),
+                frame.stream_type,
+                peer
+            );
+            if self
+                .inbound
+                .send((peer, frame.stream_type, payload))
+                .await
+                .is_err()
+            {
+                debug!("Inbound {:?} queue closed", frame.stream_type);
+                break;
+            }
+        }
+    }
+
+    /// Decode a message payload according to its frame flags
+    ///
+    /// Returns `None` if the frame must be skipped.
+    fn unwrap_payload(
+        &self,
+        peer: GossipPeerId,
+        flags: FrameFlags,
+        payload: Bytes,
+    ) -> Option<Bytes> {
+        if !flags.contains(FrameFlags::COMPRESSED) {
+            return Some(payload);
+        }
+        match decompress(&payload, self.fragments.max_message_size) {
+            Ok(payload) => Some(payload),
+            Err(e) => {
+                debug!("Skipped undecodable frame from peer {}: {}", peer, e);
+                None
+            }
+        }
+    }
+
+    /// Data bytes per fragment, leaving room for the fragment header
+    fn fragment_size(&self) -> usize {
+        self.fragments
+            .fragment_size
+            .min(
+                self.codec
+                    .max_frame_size()
+                    .saturating_sub(FRAGMENT_HEADER_LEN),
+            )
+            .max(1)
+    }
+
+    /// Build the frames for a message, compressing and fragmenting as needed
+    async fn frames_for(
+        &self,
+        peer: &GossipPeerId,
+        stream_type: StreamType,
+        data: Bytes,
+    ) -> Vec<Frame> {
+        let algorithm = self.compression_for(peer).await;
+        let (flags, payload) = match self.compression.compress(algorithm, &data) {
+            Some(compressed) => {
+                self.stats
+                    .record_compressed(stream_type, data.len(), compressed.len());
+                (FrameFlags::COMPRESSED, compressed)
+            }
+            None => {
+                self.stats.record_uncompressed(stream_type);
+                (FrameFlags::NONE, data)
+            }
+        };
+
+        let fragment_size = self.fragment_size();
+        if payload.len() <= fragment_size {
+            return vec![Frame::new(stream_type, payload).with_flags(flags)];
+        }
+
+        let message_id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+        let flags = flags.with(FrameFlags::FRAGMENT);
+        split(message_id, &payload, fragment_size)
+            .into_iter()
+            .map(|fragment| Frame::new(stream_type, fragment).with_flags(flags))
+            .collect()
+    }
+
+    /// Write frames back to back on a stream
+    async fn write_frames(&self, stream: &mut HighLevelSendStream, frames: &[Frame]) -> Result<()> {
+        for frame in frames {
+            self.codec.write_frame(stream, frame).await?;
+        }
+        Ok(())
+    }
+
+    /// Send a message on the peer's stream for `stream_type`
+    ///
+    /// The stream is opened on `conn` on first use. If the cached stream has
+    /// failed (for example because the connection was replaced), a fresh
+    /// stream is opened and the message is written once more.
+    pub(crate) async fn send(
+        &self,
+        peer: GossipPeerId,
+        conn: &HighLevelConnection,
+        stream_type: StreamType,
+        data: Bytes,
+    ) -> Result<()> {
+        let frames = self.frames_for(&peer, stream_type, data).await;
+        let slot = self.slot(peer, stream_type).await;
+        let mut guard = slot.lock().await;
+
+        if let Some(stream) = guard.as_mut() {
+            match self.write_frames(stream, &frames).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    debug!(
+                        "{:?} stream to {} failed ({}), reopening",
+                        stream_type, peer, e
+                    );
+                    *guard = None;
+                }
+            }
+        }
+
+        let mut stream = self.open_stream(peer, conn, stream_type).await?;
+        self.write_frames(&mut stream, &frames)
+            .await
+            .map_err(|e| anyhow!("Failed to write to {:?} stream: {}", stream_type, e))?;
+        *guard = Some(stream);
+        Ok(())
+    }
+
+    /// Stream `len` bytes from `reader` to a peer on a dedicated stream
+    ///
+    /// The data is sent in fragments as it is read, without buffering the
+    /// whole transfer, and is delivered to the peer as an incoming transfer.
+    pub(crate) async fn send_large<R>(
+        &self,
+        peer: GossipPeerId,
+        conn: &HighLevelConnection,
+        stream_type: StreamType,
+        mut reader: R,
+        len: u64,
+    ) -> Result<()>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let fragment_size = self.fragment_size();
+        let count = u32::try_from(len.div_ceil(fragment_size as u64).max(1))
+            .map_err(|_| anyhow!("Transfer of {} bytes has too many fragments", len))?;
+        let message_id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+
+        let mut stream = self.open_stream(peer, conn, stream_type).await?;
+        let mut chunk = vec![0u8; fragment_size];
+        let mut remaining = len;
+
+        for index in 0..count {
+            let chunk_len = remaining.min(fragment_size as u64) as usize;
+            reader
+                .read_exact(&mut chunk[..chunk_len])
+                .await
+                .map_err(|e| anyhow!("Failed to read transfer data: {}", e))?;
+            remaining -= chunk_len as u64;
+
+            let header = FragmentHeader {
+                message_id,
+                index,
+                count,
+                total_len: len,
+                kind: FragmentKind::Transfer,
+            };
+            let frame = Frame::new(stream_type, header.encode_with(&chunk[..chunk_len]))
+                .with_flags(FrameFlags::FRAGMENT);
+            self.codec
+                .write_frame(&mut stream, &frame)
+                .await
+                .map_err(|e| anyhow!("Failed to write transfer to {}: {}", peer, e))?;
+        }
+
+        stream
+            .finish()
+            .map_err(|e| anyhow!("Failed to finish transfer to {}: {}", peer, e))?;
+        debug!("Sent {} byte transfer to {}", len, peer);
+        Ok(())
+    }
+
+    /// Finish all outbound streams to a peer
+    ///
+    /// Waits for any write in progress, then returns futures resolving once
+    /// the peer has acknowledged everything written to each stream.
+    pub(crate) async fn finish(&self, peer: &GossipPeerId) -> Vec<BoxFuture<'static, ()>> {
+        let slots: Vec<SendSlot> = {
+            let mut outbound = self.outbound.write().await;
+            let keys: Vec<_> = outbound
+                .keys()
+                .filter(|(stream_peer, _)| stream_peer == peer)
+                .copied()
+                .collect();
+            keys.iter().filter_map(|key| outbound.remove(key)).collect()
+        };
+
+        let mut acknowledged = Vec::new();
+        for slot in slots {
+            let Some(mut stream) = slot.lock().await.take() else {
+                continue;
+            };
+            if stream.finish().is_ok() {
+                let stopped = stream.stopped();
+                acknowledged.push(Box::pin(async move {
+                    let _ = stopped.await;
+                }) as BoxFuture<'static, ()>);
+            }
+        }
+        acknowledged
+    }
+
+    /// Drop all outbound streams and per-peer state for a peer
+    pub(crate) async fn forget(&self, peer: &GossipPeerId) {
+        self.outbound
+            .write()
+            .await
+            .retain(|(stream_peer, _), _| stream_peer != peer);
+        self.negotiated.write().await.remove(peer);
+        self.identities.forget(peer);
+        self.limiter.forget(peer);
+        self.traffic.forget(peer);
+    }
+
+    /// Open a new stream with the priority for its stream type
+    ///
+    /// Sends our hello and listens on the return half for the peer's answer.
+    async fn open_stream(
+        &self,
+        peer: GossipPeerId,
+        conn: &HighLevelConnection,
+        stream_type: StreamType,
+    ) -> Result<HighLevelSendStream> {
+        let (mut send, mut recv) = conn
+            .open_bi()
+            .await
+            .map_err(|e| anyhow!("Failed to open {:?} stream: {}", stream_type, e))?;
+        send.set_priority(stream_type.priority())
+            .map_err(|e| anyhow!("Failed to set {:?} stream priority: {}", stream_type, e))?;
+
+        let hello = Frame::new(stream_type, self.compression.hello()).with_flags(FrameFlags::HELLO);
+        self.codec
+            .write_frame(&mut send, &hello)
+            .await
+            .map_err(|e| anyhow!("Failed to write hello to {:?} stream: {}", stream_type, e))?;
+
+        let streams = self.clone();
+        tokio::spawn(async move {
+            match FrameCodec::handshake().read_frame(&mut recv).await {
+                Ok(Some(frame)) if frame.flags.contains(FrameFlags::HELLO) => {
+                    streams.on_hello(peer, &frame.payload).await;
+                }
+                Ok(_) => trace!("Peer {} did not answer {:?} hello", peer, stream_type),
+                Err(e) => trace!("No hello from peer {}: {}", peer, e),
+            }
+        });
+
+        Ok(send)
+    }
+
+    /// Get or create the outbound slot for a peer and stream type
+    async fn slot(&self, peer: GossipPeerId, stream_type: StreamType) -> SendSlot {
+        if let Some(slot) = self.outbound.read().await.get(&(peer, stream_type)) {
+            return Arc::clone(slot);
+        }
+        Arc::clone(
+            self.outbound
+                .write()
+                .await
+                .entry((peer, stream_type))
+                .or_default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_inbound_queues_prefer_membership() {
+        let (senders, queues) = InboundQueues::new(8);
+        let peer = GossipPeerId::new([1u8; 32]);
+
+        for stream_type in StreamType::ALL.into_iter().rev() {
+            senders
+                .send((peer, stream_type, Bytes::new()))
+                .await
+                .expect("send");
+        }
+
+        let mut order = Vec::new();
+        for _ in StreamType::ALL {
+            let (_, stream_type, _) = queues.recv_any().await.expect("message");
+            order.push(stream_type);
+        }
+
+        assert_eq!(order, StreamType::ALL.to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_inbound_queues_are_independent() {
+        let (senders, queues) = InboundQueues::new(1);
+        let peer = GossipPeerId::new([1u8; 32]);
+
+        // A full bulk queue must not block membership delivery
+        senders
+            .send((peer, StreamType::Bulk, Bytes::new()))
+            .await
+            .expect("send");
+        assert!(senders
+            .try_send((peer, StreamType::Bulk, Bytes::new()))
+            .is_err());
+
+        senders
+            .try_send((peer, StreamType::Membership, Bytes::from("ping")))
+            .expect("membership queue has room");
+
+        let (_, stream_type, data) = queues
+            .recv(StreamType::Membership)
+            .await
+            .expect("membership message");
+        assert_eq!(stream_type, StreamType::Membership);
+        assert_eq!(data, Bytes::from("ping"));
+    }
+
+    #[tokio::test]
+    async fn test_recv_any_does_not_block_single_stream_reads() {
+        let (senders, queues) = InboundQueues::new(8);
+        let queues = Arc::new(queues);
+        let peer = GossipPeerId::new([1u8; 32]);
+
+        // Park a recv_any on empty queues
+        let any = tokio::spawn({
+            let queues = Arc::clone(&queues);
+            async move { queues.recv_any().await }
+        });
+        tokio::task::yield_now().await;
+
+        let pubsub = tokio::spawn({
+            let queues = Arc::clone(&queues);
+            async move { queues.recv(StreamType::PubSub).await }
+        });
+        tokio::task::yield_now().await;
+        senders
+            .send((peer, StreamType::PubSub, Bytes::from("event")))
+            .await
+            .expect("send");
+        let (_, stream_type, _) = tokio::time::timeout(Duration::from_secs(5), pubsub)
+            .await
+            .expect("single stream read not blocked")
+            .expect("task")
+            .expect("pubsub message");
+        assert_eq!(stream_type, StreamType::PubSub);
+
+        senders
+            .send((peer, StreamType::Membership, Bytes::from("ping")))
+            .await
+            .expect("send");
+        let (_, stream_type, _) = tokio::time::timeout(Duration::from_secs(5), any)
+            .await
+            .expect("recv_any woken")
+            .expect("task")
+            .expect("membership message");
+        assert_eq!(stream_type, StreamType::Membership);
+
+        drop(senders);
+        assert!(queues.recv_any().await.is_none());
+    }
+}
@@ -0,0 +1,1160 @@
+//! TCP transport for networks that block UDP
+//!
+//! Some networks drop all UDP traffic, so QUIC never connects. This module
+//! carries the same gossip protocol over one TCP connection per peer:
+//! - All stream types are multiplexed on the connection, tagged by the
+//!   stream type in each frame header, with independent receive queues
+//! - Bounded per-peer send queues with per-stream drop policies, always
+//!   writing membership traffic before pubsub and bulk
+//! - Peer IDs verified against the peer's ML-DSA key before any of its
+//!   messages are delivered
+//! - Every frame encrypted and integrity protected with keys from an ML-KEM
+//!   exchange bound to both identities
+//! - Connection lifecycle events, typed close reasons and inbound rate limits
+//!
+//! # Handshake
+//!
+//! The dialer and listener agree on session keys and prove their identities:
+//!
+//! 1. The dialer sends a [`FrameFlags::HELLO`] frame with its ML-KEM offer
+//! 2. The listener answers with a [`FrameFlags::IDENTITY`] frame holding the
+//!    KEM ciphertext and its identity proof
+//! 3. The dialer sends a [`FrameFlags::IDENTITY`] frame with its own proof
+//!
+//! Handshake frames are capped at [`crate::HANDSHAKE_MAX_FRAME_SIZE`], and a
+//! connection that fails to prove its identity within the handshake timeout
+//! is dropped. The listener runs a bounded number of handshakes at once and
+//! drops connections beyond that. The dialer also checks that the proven ID
+//! is the peer it meant to reach.
+//!
+//! Every later frame, including the close frame, carries a sealed payload
+//! that authenticates the frame's stream type and flags; a frame that fails
+//! to open ends the connection.
+//!
+//! # Limitations
+//!
+//! Compression, fragmentation and streamed transfers are not supported, and
+//! a large bulk message delays the messages queued behind it.
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use saorsa_gossip_types::PeerId;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, trace, warn};
+
+use crate::framing::{Frame, FrameCodec, FrameFlags, DEFAULT_MAX_FRAME_SIZE};
+use crate::handshake::{IdentityError, LocalIdentity, VerifiedIdentity, HANDSHAKE_TIMEOUT};
+use crate::rate_limit::{InboundRateLimiter, RateLimitConfig};
+use crate::secure::{Initiator, Opener, Responder, Sealer, Session, SEAL_OVERHEAD};
+use crate::send_queue::{PeerSendQueue, SendQueueConfig};
+use crate::stats::{PeerDetails, TrafficStats, TransportStats};
+use crate::streams::{InboundQueues, InboundSenders};
+use crate::{
+    CloseReason, DisconnectReason, GossipTransport, StreamType, TransportEvent,
+    EVENT_CHANNEL_CAPACITY,
+};
+
+use ant_quic::{generate_ml_dsa_keypair, MlDsaPublicKey, MlDsaSecretKey};
+
+/// Configuration for the TCP transport
+#[derive(Debug, Clone)]
+pub struct TcpTransportConfig {
+    /// Local address to listen on
+    pub bind_addr: SocketAddr,
+    /// Receive queue capacity per stream type for backpressure (default: 10,000 messages)
+    pub channel_capacity: usize,
+    /// Maximum size of a single message (default: 100 MB)
+    pub max_frame_size: usize,
+    /// Maximum number of connected peers (default: 1,000)
+    pub max_peers: usize,
+    /// Maximum inbound connections handshaking at once (default: 64)
+    pub max_pending_handshakes: usize,
+    /// Time allowed to open a TCP connection (default: 10 seconds)
+    pub connect_timeout: Duration,
+    /// Per-peer send queue limits and drop policies
+    pub send_queue: SendQueueConfig,
+    /// Per-peer inbound rate limits (default: unlimited)
+    pub rate_limits: RateLimitConfig,
+    /// Time allowed to flush queued messages on disconnect or shutdown (default: 5 seconds)
+    pub drain_timeout: Duration,
+    /// Optional ML-DSA keypair bytes (public_key, secret_key) for identity persistence
+    /// If not provided, a fresh keypair is generated.
+    pub keypair: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl TcpTransportConfig {
+    /// Create a new configuration with sensible defaults
+    pub fn new(bind_addr: SocketAddr) -> Self {
+        Self {
+            bind_addr,
+            channel_capacity: 10_000,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_peers: 1_000,
+            max_pending_handshakes: 64,
+            connect_timeout: Duration::from_secs(10),
+            send_queue: SendQueueConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            drain_timeout: Duration::from_secs(5),
+            keypair: None,
+        }
+    }
+
+    /// Set the ML-DSA keypair for identity persistence
+    /// Use the same keypair as the QUIC transport so both share one peer ID.
+    pub fn with_keypair(mut self, public_key: Vec<u8>, secret_key: Vec<u8>) -> Self {
+        self.keypair = Some((public_key, secret_key));
+        self
+    }
+
+    /// Set channel capacity for backpressure
+    pub fn with_channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = capacity;
+        self
+    }
+
+    /// Set the maximum message size
+    pub fn with_max_frame_size(mut self, size: usize) -> Self {
+        self.max_frame_size = size;
+        self
+    }
+
+    /// Set maximum number of connected peers
+    pub fn with_max_peers(mut self, max: usize) -> Self {
+        self.max_peers = max;
+        self
+    }
+
+    /// Set the maximum number of inbound connections handshaking at once
+    pub fn with_max_pending_handshakes(mut self, max: usize) -> Self {
+        self.max_pending_handshakes = max;
+        self
+    }
+
+    /// Set the time allowed to open a TCP connection
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Set per-peer send queue limits and drop policies
+    pub fn with_send_queue(mut self, send_queue: SendQueueConfig) -> Self {
+        self.send_queue = send_queue;
+        self
+    }
+
+    /// Set per-peer inbound rate limits
+    pub fn with_rate_limits(mut self, rate_limits: RateLimitConfig) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    /// Set the time allowed to flush queued messages on disconnect or shutdown
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+}
+
+/// An established, verified connection to a peer
+struct Connection {
+    /// Distinguishes this connection from earlier ones to the same peer
+    id: u64,
+    addr: SocketAddr,
+    /// Whether we dialed the connection
+    outbound: bool,
+    identity: VerifiedIdentity,
+    /// Round-trip time measured during the handshake
+    rtt: Duration,
+    queue: Arc<PeerSendQueue>,
+    /// Drains the send queue; returns the write half and sealer once drained
+    writer: JoinHandle<Option<(OwnedWriteHalf, Sealer)>>,
+    reader: JoinHandle<()>,
+}
+
+/// Outcome of a successful handshake
+struct Handshake {
+    session: Session,
+    /// Time from sending a handshake message to receiving the peer's answer
+    rtt: Duration,
+}
+
+/// State shared by the transport and its connection tasks
+struct Shared {
+    identity: LocalIdentity,
+    config: TcpTransportConfig,
+    codec: FrameCodec,
+    inbound: InboundSenders,
+    limiter: InboundRateLimiter,
+    traffic: TrafficStats,
+    events: broadcast::Sender<TransportEvent>,
+    connections: Mutex<HashMap<PeerId, Connection>>,
+    next_connection: AtomicU64,
+}
+
+impl Shared {
+    fn connections(&self) -> MutexGuard<'_, HashMap<PeerId, Connection>> {
+        self.connections.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Agree on session keys and prove identities on a fresh connection
+    ///
+    /// The dialer initiates the handshake.
+    async fn handshake(&self, stream: &mut TcpStream, outbound: bool) -> Result<Handshake> {
+        if outbound {
+            let (initiator, offer) = Initiator::start()?;
+            let started = Instant::now();
+            write_handshake_frame(stream, FrameFlags::HELLO, offer).await?;
+            let accept = read_handshake_frame(stream, FrameFlags::IDENTITY).await?;
+            let rtt = started.elapsed();
+
+            let (confirm, session) = initiator.finish(&self.identity, &accept)?;
+            write_handshake_frame(stream, FrameFlags::IDENTITY, confirm).await?;
+            Ok(Handshake { session, rtt })
+        } else {
+            let offer = read_handshake_frame(stream, FrameFlags::HELLO).await?;
+            let (responder, accept) = Responder::accept(&self.identity, &offer)?;
+            let started = Instant::now();
+            write_handshake_frame(stream, FrameFlags::IDENTITY, accept).await?;
+            let confirm = read_handshake_frame(stream, FrameFlags::IDENTITY).await?;
+            let rtt = started.elapsed();
+
+            Ok(Handshake {
+                session: responder.finish(&confirm)?,
+                rtt,
+            })
+        }
+    }
+
+    /// Open a connection to `addr` and verify the identity behind it
+    async fn connect(&self, addr: SocketAddr) -> Result<(TcpStream, Handshake)> {
+        if self.connections().len() >= self.config.max_peers {
+            return Err(anyhow!("Peer limit of {} reached", self.config.max_peers));
+        }
+        let mut stream =
+            tokio::time::timeout(self.config.connect_timeout, TcpStream::connect(addr))
+                .await
+                .map_err(|_| anyhow!("Timed out connecting to {}", addr))??;
+        stream.set_nodelay(true)?;
+
+        let handshake = tokio::time::timeout(HANDSHAKE_TIMEOUT, self.handshake(&mut stream, true))
+            .await
+            .map_err(|_| IdentityError::Timeout(HANDSHAKE_TIMEOUT))??;
+        Ok((stream, handshake))
+    }
+
+    /// Verify an accepted connection, then start serving it
+    async fn accept(self: &Arc<Self>, mut stream: TcpStream, addr: SocketAddr) -> Result<PeerId> {
+        if self.connections().len() >= self.config.max_peers {
+            return Err(anyhow!("Peer limit of {} reached", self.config.max_peers));
+        }
+        stream.set_nodelay(true)?;
+
+        let handshake = tokio::time::timeout(HANDSHAKE_TIMEOUT, self.handshake(&mut stream, false))
+            .await
+            .map_err(|_| IdentityError::Timeout(HANDSHAKE_TIMEOUT))??;
+        Ok(self.register(stream, addr, handshake, false))
+    }
+
+    /// Start the reader and writer tasks of a verified connection
+    ///
+    /// If the peer is already connected, both ends keep the connection
+    /// dialed by the peer with the lower ID so that simultaneous dials settle
+    /// on the same connection. Queued messages move to the kept connection.
+    fn register(
+        self: &Arc<Self>,
+        stream: TcpStream,
+        addr: SocketAddr,
+        handshake: Handshake,
+        outbound: bool,
+    ) -> PeerId {
+        let Handshake { session, rtt } = handshake;
+        let Session {
+            remote: identity,
+            sealer,
+            opener,
+        } = session;
+        let peer = identity.peer();
+        let prefer_outbound = self.identity.peer().as_bytes() < peer.as_bytes();
+
+        let mut connections = self.connections();
+        let queue = match connections.get(&peer) {
+            Some(existing) if existing.outbound == outbound || outbound != prefer_outbound => {
+                debug!(
+                    "Dropped duplicate connection to peer {} from {}",
+                    peer, addr
+                );
+                return peer;
+            }
+            Some(existing) => {
+                debug!(
+                    "Replacing connection to peer {} with one from {}",
+                    peer, addr
+                );
+                existing.writer.abort();
+                existing.reader.abort();
+                Arc::clone(&existing.queue)
+            }
+            None => Arc::new(PeerSendQueue::new(
+                peer,
+                self.config.send_queue.clone(),
+                self.events.clone(),
+            )),
+        };
+        let replaced = connections.contains_key(&peer);
+
+        let id = self.next_connection.fetch_add(1, Ordering::Relaxed);
+        let (read, write) = stream.into_split();
+        let writer = tokio::spawn(write_loop(
+            Arc::clone(self),
+            peer,
+            id,
+            Arc::clone(&queue),
+            write,
+            sealer,
+        ));
+        let reader = tokio::spawn(read_loop(Arc::clone(self), peer, id, read, opener));
+        connections.insert(
+            peer,
+            Connection {
+                id,
+                addr,
+                outbound,
+                identity,
+                rtt,
+                queue,
+                writer,
+                reader,
+            },
+        );
+        drop(connections);
+
+        if !replaced {
+            info!("Connected to peer {} at {} over TCP", peer, addr);
+            let _ = self
+                .events
+                .send(TransportEvent::PeerConnected { peer, addr });
+        }
+        peer
+    }
+
+    /// Tear down a connection that failed or was closed by the peer
+    ///
+    /// Does nothing if the connection has already been replaced or removed.
+    fn drop_connection(&self, peer: PeerId, id: u64, reason: DisconnectReason) {
+        let conn = {
+            let mut connections = self.connections();
+            match connections.get(&peer) {
+                Some(conn) if conn.id == id => connections.remove(&peer),
+                _ => None,
+            }
+        };
+        let Some(conn) = conn else {
+            return;
+        };
+
+        debug!("Connection to peer {} ended: {:?}", peer, reason);
+        conn.queue.close();
+        self.limiter.forget(&peer);
+        let _ = self
+            .events
+            .send(TransportEvent::PeerDisconnected { peer, reason });
+
+        // One of these is the calling task, which has nothing left to await
+        conn.writer.abort();
+        conn.reader.abort();
+    }
+
+    /// Flush a peer's queued messages, then close its connection with `reason`
+    ///
+    /// Gives up flushing at `deadline`; the connection is closed either way.
+    async fn close_peer(
+        &self,
+        peer: PeerId,
+        reason: CloseReason,
+        deadline: tokio::time::Instant,
+    ) -> Result<()> {
+        let conn = self
+            .connections()
+            .remove(&peer)
+            .ok_or_else(|| anyhow!("Not connected to peer {}", peer))?;
+
+        // Let the writer send everything already queued
+        conn.queue.drain();
+        let mut writer = conn.writer;
+        let write = match tokio::time::timeout_at(deadline, &mut writer).await {
+            Ok(write) => write.ok().flatten(),
+            Err(_) => {
+                warn!(
+                    "Timed out flushing {} messages to peer {}",
+                    conn.queue.depth(),
+                    peer
+                );
+                conn.queue.close();
+                writer.abort();
+                None
+            }
+        };
+
+        if let Some((mut write, mut sealer)) = write {
+            if let Err(e) = self
+                .write_sealed(&mut write, &mut sealer, close_frame(reason))
+                .await
+            {
+                debug!("Failed to send close reason to peer {}: {}", peer, e);
+            }
+            let _ = write.shutdown().await;
+        }
+        conn.reader.abort();
+
+        self.limiter.forget(&peer);
+        let _ = self.events.send(TransportEvent::PeerDisconnected {
+            peer,
+            reason: DisconnectReason::LocalDisconnect(reason),
+        });
+        Ok(())
+    }
+
+    /// Seal a frame's payload and write it
+    async fn write_sealed<W>(&self, write: &mut W, sealer: &mut Sealer, frame: Frame) -> Result<()>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        let payload = sealer.seal(&frame_aad(&frame), &frame.payload)?;
+        let frame = Frame { payload, ..frame };
+        self.codec.write_frame(write, &frame).await?;
+        Ok(())
+    }
+}
+
+/// Data authenticated along with a frame's sealed payload
+fn frame_aad(frame: &Frame) -> [u8; 2] {
+    [frame.stream_type.to_u8(), frame.flags.bits()]
+}
+
+/// Write a handshake frame carrying `flag`
+async fn write_handshake_frame(
+    stream: &mut TcpStream,
+    flag: FrameFlags,
+    payload: Bytes,
+) -> Result<()> {
+    let frame = Frame::new(StreamType::Membership, payload).with_flags(flag);
+    FrameCodec::handshake().write_frame(stream, &frame).await?;
+    Ok(())
+}
+
+/// Read the next handshake frame, which must carry `flag`
+///
+/// The peer is not verified yet, so frames are capped at the handshake size.
+async fn read_handshake_frame(stream: &mut TcpStream, flag: FrameFlags) -> Result<Bytes> {
+    match FrameCodec::handshake().read_frame(stream).await? {
+        Some(frame) if frame.flags == flag => Ok(frame.payload),
+        Some(_) => Err(anyhow!("Unexpected frame during handshake")),
+        None => Err(anyhow!("Connection closed during handshake")),
+    }
+}
+
+/// Build the frame announcing that we close the connection with `reason`
+fn close_frame(reason: CloseReason) -> Frame {
+    Frame::new(
+        StreamType::Membership,
+        Bytes::copy_from_slice(&reason.code().to_be_bytes()),
+    )
+    .with_flags(FrameFlags::CLOSE)
+}
+
+/// Interpret the payload of a close frame from a peer
+fn remote_close_reason(payload: &[u8]) -> DisconnectReason {
+    let code = <[u8; 4]>::try_from(payload).map(u32::from_be_bytes);
+    match code
+        .ok()
+        .and_then(|code| CloseReason::from_code(code.into()))
+    {
+        Some(reason) => DisconnectReason::RemoteDisconnect(reason),
+        None => DisconnectReason::RemoteClose("unknown close reason".to_string()),
+    }
+}
+
+/// Deliver a peer's messages until its connection closes
+async fn read_loop(
+    shared: Arc<Shared>,
+    peer: PeerId,
+    id: u64,
+    mut read: OwnedReadHalf,
+    mut opener: Opener,
+) {
+    let reason = loop {
+        let mut frame = match shared.codec.read_frame(&mut read).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break DisconnectReason::RemoteClose("connection closed".to_string()),
+            Err(e) if e.is_recoverable() => {
+                debug!("Skipped frame from peer {}: {}", peer, e);
+                continue;
+            }
+            Err(e) => break DisconnectReason::Error(e.to_string()),
+        };
+        // A frame that does not open was forged or altered on path
+        frame.payload = match opener.open(&frame_aad(&frame), &frame.payload) {
+            Ok(payload) => payload,
+            Err(e) => break DisconnectReason::Error(e.to_string()),
+        };
+
+        if frame.flags.contains(FrameFlags::CLOSE) {
+            break remote_close_reason(&frame.payload);
+        }
+        if !frame.flags.is_empty() {
+            debug!(
+                "Ignored {:?} frame with flags {:#04x} from peer {}",
+                frame.stream_type,
+                frame.flags.bits(),
+                peer
+            );
+            shared.traffic.record_error(peer, frame.stream_type);
+            continue;
+        }
+
+        let len = frame.payload.len();
+        if !shared.limiter.check(peer, frame.stream_type, len) {
+            trace!(
+                "Dropped rate limited {:?} message from {}",
+                frame.stream_type,
+                peer
+            );
+            continue;
+        }
+
+        shared.traffic.record_received(peer, frame.stream_type, len);
+        trace!(
+            "Received {} bytes ({:?}) from {}",
+            len,
+            frame.stream_type,
+            peer
+        );
+        if shared
+            .inbound
+            .send((peer, frame.stream_type, frame.payload))
+            .await
+            .is_err()
+        {
+            debug!("Inbound {:?} queue closed", frame.stream_type);
+            break DisconnectReason::LocalClose;
+        }
+    };
+
+    shared.drop_connection(peer, id, reason);
+}
+
+/// Write a peer's queued messages, highest priority first
+///
+/// Returns the write half and sealer once the queue is drained, so a close
+/// frame can follow the last message.
+async fn write_loop(
+    shared: Arc<Shared>,
+    peer: PeerId,
+    id: u64,
+    queue: Arc<PeerSendQueue>,
+    mut write: OwnedWriteHalf,
+    mut sealer: Sealer,
+) -> Option<(OwnedWriteHalf, Sealer)> {
+    while let Some((stream_type, data)) = queue.pop().await {
+        let len = data.len();
+        let frame = Frame::new(stream_type, data);
+        match shared.write_sealed(&mut write, &mut sealer, frame).await {
+            Ok(()) => shared.traffic.record_sent(peer, stream_type, len),
+            Err(e) => {
+                warn!("Failed to send to peer {}: {}", peer, e);
+                shared.traffic.record_error(peer, stream_type);
+                shared.drop_connection(peer, id, DisconnectReason::Error(e.to_string()));
+                return None;
+            }
+        }
+    }
+    Some((write, sealer))
+}
+
+/// Accept connections until the transport is dropped
+///
+/// Connections arriving while the maximum number of handshakes is running
+/// are dropped, so unverified peers cannot tie up unbounded resources.
+async fn accept_loop(shared: Arc<Shared>, listener: TcpListener) {
+    let handshakes = Arc::new(Semaphore::new(shared.config.max_pending_handshakes));
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                let Ok(permit) = Arc::clone(&handshakes).try_acquire_owned() else {
+                    debug!(
+                        "Rejected TCP connection from {}: too many pending handshakes",
+                        addr
+                    );
+                    continue;
+                };
+                let shared = Arc::clone(&shared);
+                tokio::spawn(async move {
+                    let accepted = shared.accept(stream, addr).await;
+                    drop(permit);
+                    if let Err(e) = accepted {
+                        debug!("Rejected TCP connection from {}: {}", addr, e);
+                    }
+                });
+            }
+            Err(e) => {
+                warn!("Failed to accept TCP connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+/// Gossip transport over TCP
+///
+/// A drop-in alternative to [`crate::AntQuicTransport`] for networks that
+/// block UDP. Given the same keypair it has the same peer ID, and peers are
+/// always identified by their verified ID.
+///
+/// The transport listens on [`TcpTransportConfig::bind_addr`] from the
+/// moment it is created.
+pub struct TcpTransport {
+    shared: Arc<Shared>,
+    inbound: InboundQueues,
+    local_addr: SocketAddr,
+    acceptor: JoinHandle<()>,
+}
+
+impl TcpTransport {
+    /// Create a TCP transport listening on `bind_addr`
+    pub async fn new(bind_addr: SocketAddr) -> Result<Self> {
+        Self::with_config(TcpTransportConfig::new(bind_addr)).await
+    }
+
+    /// Create a TCP transport from a configuration
+    pub async fn with_config(config: TcpTransportConfig) -> Result<Self> {
+        let (public_key, secret_key) = match &config.keypair {
+            Some((public_key, secret_key)) => (
+                MlDsaPublicKey::from_bytes(public_key)
+                    .map_err(|e| anyhow!("Invalid ML-DSA public key: {}", e))?,
+                MlDsaSecretKey::from_bytes(secret_key)
+                    .map_err(|e| anyhow!("Invalid ML-DSA secret key: {}", e))?,
+            ),
+            None => generate_ml_dsa_keypair()
+                .map_err(|e| anyhow!("Failed to generate ML-DSA keypair: {}", e))?,
+        };
+        let identity = LocalIdentity::new(public_key, secret_key);
+
+        let listener = TcpListener::bind(config.bind_addr).await?;
+        let local_addr = listener.local_addr()?;
+        info!(
+            "TCP transport for peer {} listening on {}",
+            identity.peer(),
+            local_addr
+        );
+
+        let (inbound_tx, inbound) = InboundQueues::new(config.channel_capacity);
+        let events = broadcast::channel(EVENT_CHANNEL_CAPACITY).0;
+        let shared = Arc::new(Shared {
+            identity,
+            codec: FrameCodec::new(config.max_frame_size.saturating_add(SEAL_OVERHEAD)),
+            inbound: inbound_tx,
+            limiter: InboundRateLimiter::new(config.rate_limits.clone(), events.clone()),
+            traffic: TrafficStats::default(),
+            events,
+            connections: Mutex::new(HashMap::new()),
+            next_connection: AtomicU64::new(0),
+            config,
+        });
+        let acceptor = tokio::spawn(accept_loop(Arc::clone(&shared), listener));
+
+        Ok(Self {
+            shared,
+            inbound,
+            local_addr,
+            acceptor,
+        })
+    }
+
+    /// Get our peer ID
+    pub fn peer_id(&self) -> PeerId {
+        self.shared.identity.peer()
+    }
+
+    /// Get the address the transport listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Get all connected peers and their addresses
+    pub fn connected_peers(&self) -> Vec<(PeerId, SocketAddr)> {
+        self.shared
+            .connections()
+            .iter()
+            .map(|(peer, conn)| (*peer, conn.addr))
+            .collect()
+    }
+
+    /// Get the number of messages queued for a peer
+    pub fn send_queue_depth(&self, peer: &PeerId) -> usize {
+        self.shared
+            .connections()
+            .get(peer)
+            .map_or(0, |conn| conn.queue.depth())
+    }
+
+    /// Get traffic statistics per peer and per stream type
+    ///
+    /// The RTT of each peer is the one measured during its handshake.
+    pub fn stats(&self) -> TransportStats {
+        let details = self
+            .shared
+            .connections()
+            .iter()
+            .map(|(peer, conn)| {
+                let details = PeerDetails {
+                    addr: Some(conn.addr),
+                    rtt: Some(conn.rtt),
+                    queue_depth: conn.queue.depth(),
+                };
+                (*peer, details)
+            })
+            .collect();
+        self.shared.traffic.snapshot(details)
+    }
+
+    /// Receive the next message on a single stream type
+    pub async fn receive_on(&self, stream_type: StreamType) -> Result<(PeerId, StreamType, Bytes)> {
+        self.inbound
+            .recv(stream_type)
+            .await
+            .ok_or_else(|| anyhow!("Receive channel closed"))
+    }
+
+    /// Gracefully shut down the transport
+    ///
+    /// Stops accepting connections, then flushes the queued messages of every
+    /// peer in parallel and closes each connection with
+    /// [`CloseReason::Shutdown`]. Messages still queued after `timeout` are
+    /// discarded.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<()> {
+        info!("Shutting down TCP transport");
+        self.acceptor.abort();
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        let peers: Vec<PeerId> = self.shared.connections().keys().copied().collect();
+        futures::future::join_all(peers.into_iter().map(|peer| {
+            self.shared
+                .close_peer(peer, CloseReason::Shutdown, deadline)
+        }))
+        .await;
+        Ok(())
+    }
+}
+
+impl Drop for TcpTransport {
+    fn drop(&mut self) {
+        self.acceptor.abort();
+        for (_, conn) in self.shared.connections().drain() {
+            conn.queue.close();
+            conn.writer.abort();
+            conn.reader.abort();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl GossipTransport for TcpTransport {
+    async fn dial(&self, peer: PeerId, addr: SocketAddr) -> Result<()> {
+        if self.shared.connections().contains_key(&peer) {
+            return Ok(());
+        }
+
+        info!("Dialing peer {} at {} over TCP", peer, addr);
+        let (mut stream, mut handshake) = self.shared.connect(addr).await?;
+        let actual = handshake.session.remote.peer();
+        if actual != peer {
+            warn!(
+                "Peer at {} proved identity {} instead of {}",
+                addr, actual, peer
+            );
+            let frame = close_frame(CloseReason::ProtocolViolation);
+            let sealer = &mut handshake.session.sealer;
+            let _ = self.shared.write_sealed(&mut stream, sealer, frame).await;
+            return Err(IdentityError::PeerIdMismatch {
+                claimed: peer,
+                actual,
+            }
+            .into());
+        }
+
+        self.shared.register(stream, addr, handshake, true);
+        Ok(())
+    }
+
+    async fn dial_bootstrap(&self, addr: SocketAddr) -> Result<PeerId> {
+        info!("Dialing bootstrap node at {} over TCP", addr);
+        let (stream, handshake) = self.shared.connect(addr).await?;
+        Ok(self.shared.register(stream, addr, handshake, true))
+    }
+
+    async fn listen(&self, _bind: SocketAddr) -> Result<()> {
+        // The listener is bound when the transport is created
+        info!("TCP transport is listening on {}", self.local_addr);
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.shutdown(self.shared.config.drain_timeout).await
+    }
+
+    async fn disconnect(&self, peer: PeerId, reason: CloseReason) -> Result<()> {
+        info!("Disconnecting peer {}: {}", peer, reason);
+        let deadline = tokio::time::Instant::now() + self.shared.config.drain_timeout;
+        self.shared.close_peer(peer, reason, deadline).await
+    }
+
+    async fn send_to_peer(&self, peer: PeerId, stream_type: StreamType, data: Bytes) -> Result<()> {
+        if data.len() > self.shared.config.max_frame_size {
+            return Err(anyhow!(
+                "Message of {} bytes exceeds the {} byte frame limit",
+                data.len(),
+                self.shared.config.max_frame_size
+            ));
+        }
+
+        let queue = self
+            .shared
+            .connections()
+            .get(&peer)
+            .map(|conn| Arc::clone(&conn.queue))
+            .ok_or_else(|| anyhow!("Not connected to peer {}", peer))?;
+        queue.push(stream_type, data).await
+    }
+
+    async fn receive_message(&self) -> Result<(PeerId, StreamType, Bytes)> {
+        self.inbound
+            .recv_any()
+            .await
+            .ok_or_else(|| anyhow!("Receive channel closed"))
+    }
+
+    fn subscribe_events(&self) -> broadcast::Receiver<TransportEvent> {
+        self.shared.events.subscribe()
+    }
+
+    fn peer_identity(&self, peer: &PeerId) -> Option<VerifiedIdentity> {
+        self.shared
+            .connections()
+            .get(peer)
+            .map(|conn| conn.identity.clone())
+    }
+
+    async fn peer_addr(&self, peer: &PeerId) -> Option<SocketAddr> {
+        self.shared.connections().get(peer).map(|conn| conn.addr)
+    }
+
+    async fn peer_rtt(&self, peer: &PeerId) -> Option<Duration> {
+        self.shared.connections().get(peer).map(|conn| conn.rtt)
+    }
+
+    fn is_encrypted(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::{FrameHeader, FRAME_VERSION};
+    use tokio::io::AsyncReadExt;
+    use tokio::time::timeout;
+
+    async fn transport() -> TcpTransport {
+        let bind = "127.0.0.1:0".parse().expect("valid address");
+        TcpTransport::new(bind).await.expect("transport")
+    }
+
+    async fn next_event(events: &mut broadcast::Receiver<TransportEvent>) -> TransportEvent {
+        timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("event in time")
+            .expect("event")
+    }
+
+    #[tokio::test]
+    async fn test_messages_are_multiplexed_per_stream() {
+        let a = transport().await;
+        let b = transport().await;
+
+        a.dial(b.peer_id(), b.local_addr()).await.expect("dial");
+        let identity = a.peer_identity(&b.peer_id()).expect("verified identity");
+        assert_eq!(PeerId::from_pubkey(identity.public_key()), b.peer_id());
+
+        a.send_to_peer(b.peer_id(), StreamType::Bulk, Bytes::from_static(b"bulk"))
+            .await
+            .expect("send bulk");
+        a.send_to_peer(
+            b.peer_id(),
+            StreamType::Membership,
+            Bytes::from_static(b"ping"),
+        )
+        .await
+        .expect("send membership");
+
+        let (from, stream_type, data) =
+            timeout(Duration::from_secs(5), b.receive_on(StreamType::Bulk))
+                .await
+                .expect("bulk in time")
+                .expect("bulk");
+        assert_eq!((from, stream_type), (a.peer_id(), StreamType::Bulk));
+        assert_eq!(data, Bytes::from_static(b"bulk"));
+
+        let (from, stream_type, data) = timeout(Duration::from_secs(5), b.receive_message())
+            .await
+            .expect("membership in time")
+            .expect("membership");
+        assert_eq!((from, stream_type), (a.peer_id(), StreamType::Membership));
+        assert_eq!(data, Bytes::from_static(b"ping"));
+
+        // The listener verified the dialer and can answer on the same connection
+        assert_eq!(
+            b.peer_identity(&a.peer_id())
+                .map(|identity| identity.peer()),
+            Some(a.peer_id())
+        );
+        b.send_to_peer(a.peer_id(), StreamType::PubSub, Bytes::from_static(b"pong"))
+            .await
+            .expect("reply");
+        let (from, _, data) = timeout(Duration::from_secs(5), a.receive_message())
+            .await
+            .expect("reply in time")
+            .expect("reply");
+        assert_eq!(from, b.peer_id());
+        assert_eq!(data, Bytes::from_static(b"pong"));
+    }
+
+    #[tokio::test]
+    async fn test_dial_rejects_wrong_peer_id() {
+        let a = transport().await;
+        let b = transport().await;
+        let claimed = PeerId::new([7u8; 32]);
+
+        let err = a
+            .dial(claimed, b.local_addr())
+            .await
+            .expect_err("mismatched identity");
+        assert_eq!(
+            err.downcast_ref::<IdentityError>(),
+            Some(&IdentityError::PeerIdMismatch {
+                claimed,
+                actual: b.peer_id(),
+            })
+        );
+        assert!(a.connected_peers().is_empty());
+        assert!(a
+            .send_to_peer(b.peer_id(), StreamType::Membership, Bytes::new())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dial_respects_max_peers() {
+        let bind = "127.0.0.1:0".parse().expect("valid address");
+        let a = TcpTransport::with_config(TcpTransportConfig::new(bind).with_max_peers(1))
+            .await
+            .expect("transport");
+        let b = transport().await;
+        let c = transport().await;
+
+        a.dial(b.peer_id(), b.local_addr()).await.expect("dial");
+        let err = a
+            .dial(c.peer_id(), c.local_addr())
+            .await
+            .expect_err("peer limit");
+        assert!(err.to_string().contains("Peer limit"));
+        assert!(a.dial_bootstrap(c.local_addr()).await.is_err());
+        assert_eq!(a.connected_peers().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_reports_reason_to_both_sides() {
+        let a = transport().await;
+        let b = transport().await;
+        let mut a_events = a.subscribe_events();
+        let mut b_events = b.subscribe_events();
+
+        let peer = a.dial_bootstrap(b.local_addr()).await.expect("dial");
+        assert_eq!(peer, b.peer_id());
+        assert!(matches!(
+            next_event(&mut a_events).await,
+            TransportEvent::PeerConnected { peer, .. } if peer == b.peer_id()
+        ));
+        assert!(matches!(
+            next_event(&mut b_events).await,
+            TransportEvent::PeerConnected { peer, .. } if peer == a.peer_id()
+        ));
+
+        // Queued messages are flushed before the close frame
+        a.send_to_peer(b.peer_id(), StreamType::PubSub, Bytes::from_static(b"last"))
+            .await
+            .expect("send");
+        a.disconnect(b.peer_id(), CloseReason::Evicted)
+            .await
+            .expect("disconnect");
+
+        let (_, _, data) = timeout(Duration::from_secs(5), b.receive_message())
+            .await
+            .expect("message in time")
+            .expect("message");
+        assert_eq!(data, Bytes::from_static(b"last"));
+
+        assert_eq!(
+            next_event(&mut a_events).await,
+            TransportEvent::PeerDisconnected {
+                peer: b.peer_id(),
+                reason: DisconnectReason::LocalDisconnect(CloseReason::Evicted),
+            }
+        );
+        assert_eq!(
+            next_event(&mut b_events).await,
+            TransportEvent::PeerDisconnected {
+                peer: a.peer_id(),
+                reason: DisconnectReason::RemoteDisconnect(CloseReason::Evicted),
+            }
+        );
+        assert!(b.peer_addr(&a.peer_id()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_simultaneous_dials_settle_on_one_connection() {
+        let a = transport().await;
+        let b = transport().await;
+
+        let (dial_a, dial_b) = tokio::join!(
+            a.dial(b.peer_id(), b.local_addr()),
+            b.dial(a.peer_id(), a.local_addr())
+        );
+        dial_a.expect("dial from a");
+        dial_b.expect("dial from b");
+
+        // Give the accepting sides time to settle on the preferred connection
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        a.send_to_peer(
+            b.peer_id(),
+            StreamType::Membership,
+            Bytes::from_static(b"hi"),
+        )
+        .await
+        .expect("send");
+        let (from, _, _) = timeout(Duration::from_secs(5), b.receive_message())
+            .await
+            .expect("message in time")
+            .expect("message");
+        assert_eq!(from, a.peer_id());
+        assert_eq!(a.connected_peers().len(), 1);
+        assert_eq!(b.connected_peers().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pending_handshakes_are_bounded() {
+        let bind = "127.0.0.1:0".parse().expect("valid address");
+        let a =
+            TcpTransport::with_config(TcpTransportConfig::new(bind).with_max_pending_handshakes(1))
+                .await
+                .expect("transport");
+        let b = transport().await;
+
+        // An idle connection holds the only handshake slot
+        let idle = TcpStream::connect(a.local_addr()).await.expect("connect");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(b.dial(a.peer_id(), a.local_addr()).await.is_err());
+
+        drop(idle);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        b.dial(a.peer_id(), a.local_addr())
+            .await
+            .expect("dial once the slot is free");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_handshake_frame_is_refused() {
+        let a = transport().await;
+        let mut stream = TcpStream::connect(a.local_addr()).await.expect("connect");
+
+        // Announce a hello far larger than any handshake message
+        let header = FrameHeader {
+            version: FRAME_VERSION,
+            stream_type: StreamType::Membership,
+            flags: FrameFlags::HELLO,
+            length: u32::try_from(DEFAULT_MAX_FRAME_SIZE).expect("frame size fits"),
+        };
+        stream.write_all(&header.encode()).await.expect("write");
+
+        let mut buf = [0u8; 64];
+        let read = timeout(Duration::from_secs(1), stream.read(&mut buf))
+            .await
+            .expect("listener closes before the handshake timeout");
+        assert!(matches!(read, Ok(0) | Err(_)));
+    }
+
+    #[tokio::test]
+    async fn test_frames_are_sealed_with_their_header() {
+        let a = transport().await;
+        let b = transport().await;
+        assert!(a.is_encrypted());
+
+        let (offer_state, offer) = Initiator::start().expect("offer");
+        let (responder, accept) = Responder::accept(&b.shared.identity, &offer).expect("accept");
+        let (confirm, mut sender) = offer_state
+            .finish(&a.shared.identity, &accept)
+            .expect("finish");
+        let mut receiver = responder.finish(&confirm).expect("confirm");
+
+        let mut wire = Vec::new();
+        let frame = Frame::new(StreamType::PubSub, Bytes::from_static(b"secret"));
+        a.shared
+            .write_sealed(&mut wire, &mut sender.sealer, frame)
+            .await
+            .expect("write");
+        assert!(!wire.windows(6).any(|window| window == b"secret"));
+
+        let mut sealed = b
+            .shared
+            .codec
+            .read_frame(&mut wire.as_slice())
+            .await
+            .expect("read")
+            .expect("frame");
+
+        // Moving the frame to another stream breaks the seal
+        sealed.stream_type = StreamType::Membership;
+        assert!(receiver
+            .opener
+            .open(&frame_aad(&sealed), &sealed.payload)
+            .is_err());
+        sealed.stream_type = StreamType::PubSub;
+        let payload = receiver
+            .opener
+            .open(&frame_aad(&sealed), &sealed.payload)
+            .expect("open");
+        assert_eq!(payload, Bytes::from_static(b"secret"));
+    }
+
+    #[test]
+    fn test_close_reason_roundtrip() {
+        let frame = close_frame(CloseReason::Banned);
+        assert!(frame.flags.contains(FrameFlags::CLOSE));
+        assert_eq!(
+            remote_close_reason(&frame.payload),
+            DisconnectReason::RemoteDisconnect(CloseReason::Banned)
+        );
+        assert!(matches!(
+            remote_close_reason(&[0, 0]),
+            DisconnectReason::RemoteClose(_)
+        ));
+    }
+}